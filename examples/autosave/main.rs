@@ -1,22 +1,17 @@
-use crate::sink::AutosaveSink;
 use std::convert::TryInto;
-use std::fs::File;
-use std::{env, io::BufRead, io::BufReader, io::Read};
-use weasel::event::EventSinkId;
+use std::{env, io::Read};
+use weasel::store::{FileHistoryStore, HistoryStore};
 use weasel::team::TeamId;
 use weasel::{
     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateCreature,
-    CreateTeam, EventReceiver, EventTrigger, FlatVersionedEvent, Server,
+    CreateTeam, EventReceiver, EventTrigger, Server,
 };
 
-mod sink;
-
 // It's not a real game so we can use generic no-op battle rules.
 battle_rules! {}
 
 static TEAM_ID: TeamId<CustomRules> = 0;
 const AUTOSAVE_NAME: &str = "autosave";
-const SINK_ID: EventSinkId = 0;
 
 fn main() {
     print_intro();
@@ -30,7 +25,7 @@ fn main() {
 fn print_intro() {
     println!("Autosave");
     println!();
-    println!("Example to demonstrate how to use an event sink to create autosaves with weasel.");
+    println!("Example to demonstrate how to use a history store to create autosaves with weasel.");
     println!("Create soldiers and exit whenever you want.");
     println!("Next time you launch the game it will resume from the latest progress!");
     println!();
@@ -48,6 +43,7 @@ fn game_loop() {
     loop {
         // Read a char from stdin.
         let input: Option<char> = std::io::stdin()
+            .lock()
             .bytes()
             .next()
             .and_then(|result| result.ok())
@@ -85,48 +81,27 @@ fn create_soldier(server: &mut Server<CustomRules>) {
 
 /// Creates a new server. The battle state will be loaded from the autosave, if found.
 fn create_server() -> Server<CustomRules> {
-    // Create a new server to manage the battle.
-    let battle = Battle::builder(CustomRules::new()).build();
-    let mut server = Server::builder(battle).build();
-    // Read the json stored in a temporary file.
+    // Open the history store kept in a temporary file. The file is created if it doesn't exist.
     let mut path = env::temp_dir();
     path.push(AUTOSAVE_NAME);
-    let file = File::open(path);
-    match file {
-        Ok(file) => {
-            let mut reader = BufReader::new(file);
-            // Deserialize all events, one at a time, because we append them in sequence.
-            loop {
-                let mut buffer = Vec::new();
-                // We use a delimiter to separate the different json objects.
-                let result = reader.read_until(b'#', &mut buffer).unwrap();
-                if result > 0 {
-                    // Remove the delimiter.
-                    buffer.truncate(buffer.len() - 1);
-                    // Replay the event in the server.
-                    let event: FlatVersionedEvent<_> = serde_json::from_slice(&buffer).unwrap();
-                    server.receive(event.into()).unwrap()
-                } else {
-                    // End of file.
-                    break;
-                }
-            }
-            attach_sink(&mut server);
-            // Return the server with the restored autosave.
-            server
-        }
-        Err(_) => {
-            // No autosave, so setup a fresh battle.
-            attach_sink(&mut server);
-            // Create a team where we will put all soldiers.
-            CreateTeam::trigger(&mut server, TEAM_ID).fire().unwrap();
-            server
+    let store = FileHistoryStore::open(path).unwrap();
+    // Read all events saved so far.
+    let events = store.range(store.first_id()..store.len()).unwrap();
+    // Create a new server to manage the battle. Every event will be appended to the store.
+    let battle = Battle::builder(CustomRules::new())
+        .history_store(Box::new(store))
+        .build();
+    let mut server = Server::builder(battle).build();
+    if events.is_empty() {
+        // No autosave, so setup a fresh battle.
+        // Create a team where we will put all soldiers.
+        CreateTeam::trigger(&mut server, TEAM_ID).fire().unwrap();
+    } else {
+        // Replay the events in the server. They are already in the store,
+        // so they won't be saved twice.
+        for event in events {
+            server.receive(event).unwrap();
         }
     }
-}
-
-/// Attaches a sink to the server to dump events into a file.
-fn attach_sink(server: &mut Server<CustomRules>) {
-    let sink = AutosaveSink::new(SINK_ID, AUTOSAVE_NAME);
-    server.client_sinks_mut().add_sink(Box::new(sink)).unwrap();
+    server
 }
@@ -137,7 +137,7 @@ pub(crate) struct InitiativeModel {
 impl InitiativeModel {
     /// Sorts the actors by their initiative score (descending).
     fn sort(&mut self) {
-        self.actors.sort_by(|lhs, rhs| rhs.1.cmp(&lhs.1));
+        self.actors.sort_by_key(|e| std::cmp::Reverse(e.1));
     }
 
     fn insert(&mut self, actor: &dyn Actor<CustomRules>) {
@@ -87,11 +87,11 @@ fn server_game_loop(mut server: TcpServer) {
         }
         if let Some(key) = read_input() {
             match key {
-                '1' | '2' | '3' => {
-                    if play_card(&mut server.game_server, key.to_digit(10).unwrap(), id) {
-                        server_end_turn(&mut server.game_server);
-                        game_status(server.game_server.lock().unwrap().battle(), id);
-                    }
+                '1' | '2' | '3'
+                    if play_card(&mut server.game_server, key.to_digit(10).unwrap(), id) =>
+                {
+                    server_end_turn(&mut server.game_server);
+                    game_status(server.game_server.lock().unwrap().battle(), id);
                 }
                 'h' => print_controls(),
                 'q' => break,
@@ -113,25 +113,25 @@ fn client_game_loop(mut client: TcpClient) {
         }
         if let Some(key) = read_input() {
             match key {
-                '1' | '2' | '3' => {
+                '1' | '2' | '3'
                     if play_card(
                         &mut client.game_client,
                         key.to_digit(10).unwrap(),
                         client.id,
-                    ) {
-                        // Wait for the round completion.
-                        wait(|| {
-                            client
-                                .game_client
-                                .lock()
-                                .unwrap()
-                                .battle()
-                                .rounds()
-                                .completed_rounds()
-                                > round
-                        });
-                        game_status(client.game_client.lock().unwrap().battle(), client.id);
-                    }
+                    ) =>
+                {
+                    // Wait for the round completion.
+                    wait(|| {
+                        client
+                            .game_client
+                            .lock()
+                            .unwrap()
+                            .battle()
+                            .rounds()
+                            .completed_rounds()
+                            > round
+                    });
+                    game_status(client.game_client.lock().unwrap().battle(), client.id);
                 }
                 'h' => print_controls(),
                 'q' => break,
@@ -303,6 +303,7 @@ fn event_callback(
 /// Read a char from stdin.
 fn read_input() -> Option<char> {
     std::io::stdin()
+        .lock()
         .bytes()
         .next()
         .and_then(|result| result.ok())
@@ -202,9 +202,9 @@ impl TcpServer {
             // Find out the id of the newly connected player.
             let mut server = game_server.lock().unwrap();
             let id = if server.client_sinks().sinks().any(|s| s.id() == 1) {
-                2 as u8
+                2_u8
             } else {
-                1 as u8
+                1_u8
             };
             // Register a client sink and share the battle history, from the beginning.
             let sink = Box::new(TcpClientSink::new(id.into(), stream.try_clone().unwrap()));
@@ -317,7 +317,7 @@ impl TcpClient {
                     }
                     // Wait for the ready byte.
                     if size == 3 && buffer[0] == READY_BYTE {
-                        id = Some(buffer[1] as u8);
+                        id = Some(buffer[1]);
                         break;
                     } else if size > 1 && buffer[0] == EVENT_BYTE {
                         let event: FlatVersionedEvent<CustomRules> =
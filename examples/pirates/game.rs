@@ -1,16 +1,15 @@
 use crate::rules::PiratesRules;
 use crate::rules::*;
-use rand::Rng;
 use std::fs::{self, File};
 use std::time::SystemTime;
 use std::{env, io::Read};
 use weasel::creature::CreatureId;
 use weasel::team::TeamId;
 use weasel::{
-    ActivateAbility, AlterStatistics, Battle, BattleController, BattleState, Character,
+    ActivateAbility, AiDriver, AlterStatistics, Battle, BattleController, BattleState, Character,
     CreateCreature, CreateTeam, EndBattle, EndTurn, EntityId, EventKind, EventQueue, EventReceiver,
-    EventTrigger, EventWrapper, FlatVersionedEvent, RemoveCreature, ResetEntropy, Server,
-    StartTurn,
+    EventTrigger, EventWrapper, FlatVersionedEvent, RandomController, RemoveCreature, Replay,
+    ResetEntropy, Server, StartTurn,
 };
 
 // Constants to identify teams.
@@ -22,6 +21,7 @@ static ENEMY_SHIP: CreatureId<PiratesRules> = 1;
 
 pub struct Game {
     server: Server<PiratesRules>,
+    ai: AiDriver<PiratesRules>,
 }
 
 impl Game {
@@ -33,7 +33,11 @@ impl Game {
             .event_callback(Box::new(commentary))
             .build();
         // Create a server to orchestrate the game.
-        let mut server = Server::builder(battle).build();
+        // The AI simulates the outcome of every action it might take, but the commentary
+        // should describe only what really happens.
+        let mut server = Server::builder(battle)
+            .disable_simulation_callback()
+            .build();
         // Reset entropy with a 'random enough' seed.
         let time = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
@@ -65,8 +69,15 @@ impl Game {
         CreateCreature::trigger(&mut server, ENEMY_SHIP, ENEMY_TEAM.to_string(), ())
             .fire()
             .unwrap();
+        // The enemy team is controlled by the computer, which fires a random ability
+        // at the player's ship. See `PiratesAiRules` for the list of possible actions.
+        let mut ai = AiDriver::new(Box::new(PiratesAiRules {}));
+        ai.set_controller(
+            ENEMY_TEAM.to_string(),
+            Box::new(RandomController::new(time.as_secs())),
+        );
         // Return a game object.
-        Self { server }
+        Self { server, ai }
     }
 
     pub fn fire_cannonball(&mut self) {
@@ -110,24 +121,8 @@ impl Game {
         StartTurn::trigger(&mut self.server, EntityId::Creature(ENEMY_SHIP))
             .fire()
             .unwrap();
-        // Fire a random ability.
-        let mut rng = rand::thread_rng();
-        let rng_number = rng.gen_range(0, 2);
-        let ability = if rng_number == 0 {
-            ABILITY_CANNONBALL
-        } else {
-            ABILITY_GRAPESHOT
-        };
-        ActivateAbility::trigger(
-            &mut self.server,
-            EntityId::Creature(ENEMY_SHIP),
-            ability.to_string(),
-        )
-        .activation(EntityId::Creature(PLAYER_SHIP))
-        .fire()
-        .unwrap();
-        // After the ship's attack we just end the enemy turn.
-        EndTurn::trigger(&mut self.server).fire().unwrap();
+        // Let the AI play the turn. The turn is ended automatically.
+        self.ai.play(&mut self.server).unwrap();
     }
 
     /// Saves the battle's history as json in a temporary file.
@@ -153,19 +148,13 @@ impl Game {
 
     /// Restores the battle's history from a json temporary file.
     pub fn load(&mut self) {
-        // Read the json stored in a temporary file.
-        let mut json = String::new();
-        let mut path = env::temp_dir();
-        path.push("savegame");
-        let file = File::open(path);
-        match file {
-            Ok(mut file) => {
-                file.read_to_string(&mut json).unwrap();
-                // Deserialize all events.
-                let events: Vec<FlatVersionedEvent<_>> = serde_json::from_str(&json).unwrap();
+        match read_savegame() {
+            Some(events) => {
                 // Replay all events in a new instance of server.
                 let battle = Battle::builder(PiratesRules::new()).build();
-                self.server = Server::builder(battle).build();
+                self.server = Server::builder(battle)
+                    .disable_simulation_callback()
+                    .build();
                 for event in events {
                     self.server.receive(event.into()).unwrap();
                 }
@@ -174,7 +163,20 @@ impl Game {
                 self.server.set_event_callback(Some(Box::new(commentary)));
                 println!("savegame loaded!");
             }
-            Err(_) => println!("no savegame found!"),
+            None => println!("no savegame found!"),
+        }
+    }
+
+    /// Lets the player step through the events in the savegame.
+    pub fn replay(&self) {
+        match read_savegame() {
+            Some(events) => {
+                let events = events.into_iter().map(|e| e.into()).collect();
+                let mut replay = Replay::new(PiratesRules::new, events);
+                let stdin = std::io::stdin();
+                replay.run(stdin.lock(), std::io::stdout()).unwrap();
+            }
+            None => println!("no savegame found!"),
         }
     }
 
@@ -270,3 +272,14 @@ fn pretty_team_id(id: &TeamId<PiratesRules>) -> &'static str {
         "Enemy"
     }
 }
+
+/// Reads the events stored in the json temporary file, if any.
+fn read_savegame() -> Option<Vec<FlatVersionedEvent<PiratesRules>>> {
+    let mut json = String::new();
+    let mut path = env::temp_dir();
+    path.push("savegame");
+    let mut file = File::open(path).ok()?;
+    file.read_to_string(&mut json).unwrap();
+    // Deserialize all events.
+    Some(serde_json::from_str(&json).unwrap())
+}
@@ -28,6 +28,7 @@ fn print_controls() {
     println!("    h - Display the controls");
     println!("    s - Save the game state");
     println!("    l - Load the savegame");
+    println!("    r - Replay the savegame, step by step");
     println!("    q - Quit");
 }
 
@@ -43,6 +44,7 @@ fn game_loop() {
     loop {
         // Read a char from stdin.
         let input: Option<char> = std::io::stdin()
+            .lock()
             .bytes()
             .next()
             .and_then(|result| result.ok())
@@ -85,6 +87,10 @@ fn game_loop() {
                     }
                     turn_header(&game);
                 }
+                'r' => {
+                    game.replay();
+                    turn_header(&game);
+                }
                 'q' => break,
                 _ => {}
             }
@@ -97,10 +103,9 @@ fn turn_header(game: &Game) {
     let (player_ship_hull, player_ship_crew) = game.player_stats();
     let (enemy_ship_hull, enemy_ship_crew) = game.enemy_stats();
     let stat_to_string = |stat| {
-        let i = (stat as usize + 5 - 1) / 5; // ceiling
-        std::iter::repeat("=")
-            .take(i)
-            .chain(std::iter::repeat(" ").take(20 - i))
+        let i = (stat as usize).div_ceil(5); // ceiling
+        std::iter::repeat_n("=", i)
+            .chain(std::iter::repeat_n(" ", 20 - i))
             .collect::<String>()
     };
     print_separator();
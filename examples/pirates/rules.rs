@@ -2,9 +2,10 @@ use weasel::character::{StatisticId, StatisticsAlteration};
 use weasel::rules::entropy::UniformDistribution;
 use weasel::rules::{ability::SimpleAbility, statistic::SimpleStatistic};
 use weasel::{
-    battle_rules, rules::empty::*, Action, ActorRules, AlterStatistics, ApplyImpact, BattleRules,
-    BattleState, Character, CharacterRules, Conclusion, EntityId, Entropy, EventQueue,
-    EventTrigger, FightRules, Id, ReadMetrics, Team, TeamRules, Transmutation, WriteMetrics,
+    battle_rules, rules::empty::*, Action, ActivateAbility, Actor, ActorRules, AiRules,
+    AlterStatistics, ApplyImpact, Battle, BattleRules, BattleState, Character, CharacterRules,
+    Conclusion, Entity, EntityId, Entropy, EventPrototype, EventQueue, EventTrigger, FightRules,
+    Id, ReadMetrics, Team, TeamRules, Transmutation, WriteMetrics,
 };
 
 // Constants to identify statistics (of ships).
@@ -15,7 +16,7 @@ pub(crate) const ABILITY_CANNONBALL: &str = "cannonballs";
 pub(crate) const ABILITY_GRAPESHOT: &str = "grapeshots";
 
 // Define our custom team rules.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct PiratesTeamRules {}
 
 impl TeamRules<PiratesRules> for PiratesTeamRules {
@@ -43,14 +44,7 @@ impl TeamRules<PiratesRules> for PiratesTeamRules {
         // Get the objective of the team. Which is equal to its enemy id.
         let enemy_id = team.objectives();
         // Now check if the enemy has any creatures left.
-        if state
-            .entities()
-            .team(&enemy_id)
-            .unwrap()
-            .creatures()
-            .count()
-            == 0
-        {
+        if state.entities().team(enemy_id).unwrap().creatures().count() == 0 {
             // We won.
             Some(Conclusion::Victory)
         } else {
@@ -60,7 +54,7 @@ impl TeamRules<PiratesRules> for PiratesTeamRules {
 }
 
 // Define our custom character rules.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct PiratesCharacterRules {}
 
 impl CharacterRules<PiratesRules> for PiratesCharacterRules {
@@ -121,7 +115,7 @@ impl CharacterRules<PiratesRules> for PiratesCharacterRules {
 }
 
 // Define our custom actor rules.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct PiratesActorRules {}
 
 impl ActorRules<PiratesRules> for PiratesActorRules {
@@ -180,7 +174,7 @@ impl ActorRules<PiratesRules> for PiratesActorRules {
 }
 
 // Define our custom fight rules.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct PiratesFightRules {}
 
 impl FightRules<PiratesRules> for PiratesFightRules {
@@ -204,6 +198,34 @@ impl FightRules<PiratesRules> for PiratesFightRules {
     }
 }
 
+// Define the rules used by the computer to control its ships.
+pub struct PiratesAiRules {}
+
+impl AiRules<PiratesRules> for PiratesAiRules {
+    fn actions(
+        &self,
+        battle: &Battle<PiratesRules>,
+        actor: &dyn Actor<PiratesRules>,
+    ) -> Vec<EventPrototype<PiratesRules>> {
+        // A ship can fire any of its abilities at any enemy ship.
+        let mut actions = Vec::new();
+        for ability in actor.abilities() {
+            let enemies = battle
+                .entities()
+                .creatures()
+                .filter(|creature| creature.team_id() != actor.team_id());
+            for enemy in enemies {
+                actions.push(
+                    ActivateAbility::trigger(&mut (), *actor.entity_id(), ability.id().clone())
+                        .activation(*enemy.entity_id())
+                        .prototype(),
+                );
+            }
+        }
+        actions
+    }
+}
+
 // Finally, we use the `battle_rules` macro to quickly create an object that implements
 // the `BattleRules` trait.
 battle_rules! {
@@ -218,8 +218,8 @@ impl Display for Battlefield {
             }
             Self::TwoDimensions(squares) => {
                 // Iterate over the arrays and print entities' ids and traps.
-                for (_, row) in squares.iter().rev().enumerate() {
-                    for (_, col) in row.iter().enumerate() {
+                for row in squares.iter().rev() {
+                    for col in row.iter() {
                         write!(f, "|")?;
                         if let Some(entity) = col.entity {
                             print_creature_id(f, entity)?;
@@ -4,7 +4,7 @@ use weasel::creature::CreatureId;
 use weasel::team::TeamId;
 use weasel::{
     ActivateAbility, Battle, BattleController, CreateCreature, CreateTeam, EndTurn, EntityId,
-    EventKind, EventReceiver, EventTrigger, Server, StartTurn, VersionedEventWrapper,
+    EventKind, EventTrigger, Server, StartTurn,
 };
 
 mod rules;
@@ -46,14 +46,13 @@ fn print_controls() {
 fn game_loop() {
     // Create a server.
     let mut server = create_game();
-    // Create a buffer to keep events for redo.
-    let mut event_buffer = Vec::new();
     println!();
     display_world(&server);
     // Main loop.
     loop {
         // Read a char from stdin.
         let input: Option<char> = std::io::stdin()
+            .lock()
             .bytes()
             .next()
             .and_then(|result| result.ok())
@@ -62,27 +61,27 @@ fn game_loop() {
         if let Some(key) = input {
             match key {
                 'w' => {
-                    walk(&mut server, &mut event_buffer, Direction::Up);
+                    walk(&mut server, Direction::Up);
                     display_world(&server);
                 }
                 's' => {
-                    walk(&mut server, &mut event_buffer, Direction::Down);
+                    walk(&mut server, Direction::Down);
                     display_world(&server);
                 }
                 'd' => {
-                    walk(&mut server, &mut event_buffer, Direction::Right);
+                    walk(&mut server, Direction::Right);
                     display_world(&server);
                 }
                 'a' => {
-                    walk(&mut server, &mut event_buffer, Direction::Left);
+                    walk(&mut server, Direction::Left);
                     display_world(&server);
                 }
                 'u' => {
-                    server = undo(server, &mut event_buffer);
+                    undo(&mut server);
                     display_world(&server);
                 }
                 'r' => {
-                    redo(&mut server, &mut event_buffer);
+                    redo(&mut server);
                     display_world(&server);
                 }
                 'h' => print_controls(),
@@ -98,23 +97,17 @@ fn display_world(server: &Server<CustomRules>) {
     let steps = server
         .battle()
         .history()
-        .events()
-        .iter()
-        .filter(|e| e.kind() == EventKind::ActivateAbility)
+        .query()
+        .kind(EventKind::ActivateAbility)
         .count();
     let battlefield = server.battle().space().model();
     println!("Steps: {}\nBattlefield:\n{}", steps, battlefield);
 }
 
-/// Creates a new server
-fn create_server() -> Server<CustomRules> {
-    let battle = Battle::builder(CustomRules::new()).build();
-    Server::builder(battle).build()
-}
-
 /// Creates a new game: a server with a team and a creature.
 fn create_game() -> Server<CustomRules> {
-    let mut server = create_server();
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut server = Server::builder(battle).build();
     // Create a team and a creature.
     CreateTeam::trigger(&mut server, TEAM_ID).fire().unwrap();
     CreateCreature::trigger(&mut server, CREATURE_ID, TEAM_ID, Square { x: 0, y: 0 })
@@ -124,14 +117,8 @@ fn create_game() -> Server<CustomRules> {
 }
 
 /// Moves the creature on step towards the given direction.
-fn walk(
-    server: &mut Server<CustomRules>,
-    event_buffer: &mut Vec<VersionedEventWrapper<CustomRules>>,
-    direction: Direction,
-) {
-    // Clean the buffered events to invalidate the redo action.
-    event_buffer.clear();
-    // Start a turn.
+fn walk(server: &mut Server<CustomRules>, direction: Direction) {
+    // Start a turn. Firing a new event also invalidates the server's redo buffer.
     StartTurn::trigger(server, ENTITY_ID).fire().unwrap();
     // Activate the 'walk' ability of the creature.
     let result = ActivateAbility::trigger(server, ENTITY_ID, WALK)
@@ -146,85 +133,45 @@ fn walk(
 }
 
 /// Undo the last action.
-fn undo(
-    server: Server<CustomRules>,
-    event_buffer: &mut Vec<VersionedEventWrapper<CustomRules>>,
-) -> Server<CustomRules> {
+fn undo(server: &mut Server<CustomRules>) {
+    let history = server.battle().history();
     // Retrieve the last event of type ActivateAbility.
-    let last_activation_index = server
-        .battle()
-        .history()
-        .events()
-        .iter()
-        .rposition(|e| e.kind() == EventKind::ActivateAbility);
-    match last_activation_index {
-        Some(last_activation_index) => {
-            // We are gonna undo this turn.
-            // First save the current history in a buffer, if it's empty. If it's not, it means
-            // we are already undoing a series of events.
-            if event_buffer.is_empty() {
-                let mut events = server
-                    .battle()
-                    .versioned_events(std::ops::Range {
-                        start: 0,
-                        end: server.battle().history().len() as usize,
-                    })
-                    .collect();
-                event_buffer.append(&mut events);
-            }
-            // Create a completely new server.
-            let mut server = create_server();
-            // Replay history up to the last ActivateAbility before 'last', to skip turns in
-            // which the player did a wrong move.
-            // To nicely wrap the turn we should undo also the StartTurn event.
-            let previous_start_turn_index = &event_buffer[..last_activation_index]
-                .iter()
-                .rposition(|e| e.kind() == EventKind::StartTurn);
-            // We replay all events in the buffer up to the start turn (excluded).
-            // There will always be a StartTurn before an ActivateAbility.
-            for event in event_buffer
-                .iter()
-                .take(previous_start_turn_index.unwrap() as usize)
-            {
-                server.receive(event.clone()).unwrap();
-            }
-            server
-        }
-        None => {
-            // No single action was taken yet. We can't undo anything, so return the same server.
-            server
-        }
+    let last_activation = history.query().kind(EventKind::ActivateAbility).last();
+    if let Some(last_activation) = last_activation {
+        // We are gonna undo this turn.
+        // To nicely wrap the turn we should undo also the StartTurn event.
+        // There will always be a StartTurn in the same turn of an ActivateAbility.
+        let turn = history.turn(last_activation.id()).unwrap();
+        let start_turn = history
+            .query()
+            .kind(EventKind::StartTurn)
+            .turn(turn)
+            .first()
+            .unwrap();
+        // Rewind the battle to the state it had before the start turn.
+        // Events in between, including turns in which the player did a wrong move, are moved
+        // into the server's redo buffer.
+        server.rewind_to(start_turn.id()).unwrap();
     }
+    // No single action was taken yet. We can't undo anything.
 }
 
 /// Redo the last undoed action.
-fn redo(
-    server: &mut Server<CustomRules>,
-    event_buffer: &mut Vec<VersionedEventWrapper<CustomRules>>,
-) {
-    let history_len = server.battle().history().events().len();
-    if event_buffer.len() > history_len {
-        // There are some events to redo.
-        // It's enough to replay the missing events on top of the existing server.
-        // Let's first find the next ActivateAbility.
-        let future_events = &event_buffer[history_len..];
-        let next_activation = future_events
+fn redo(server: &mut Server<CustomRules>) {
+    let future_events = server.redo_buffer();
+    // Let's first find the next ActivateAbility.
+    let next_activation = future_events
+        .iter()
+        .position(|e| e.kind() == EventKind::ActivateAbility);
+    // However, since we want to redo an entire turn, replay up to the EndTurn (included).
+    if let Some(next_activation) = next_activation {
+        // Find the EndTurn immediately after 'next_activation'.
+        let end_turn = future_events[next_activation..]
             .iter()
-            .position(|e| e.kind() == EventKind::ActivateAbility);
-        // However, since we want to redo an entire turn, replay up to the EndTurn (included).
-        if let Some(next_activation) = next_activation {
-            // Find the EndTurn immediately after 'next_activation'.
-            let end_turn = &future_events[next_activation..]
-                .iter()
-                .position(|e| e.kind() == EventKind::EndTurn)
-                .unwrap();
-            // Add 'next_activation' index to get the index of 'end_turn' in 'future_events'.
-            let end_turn = end_turn + next_activation;
-            // Replay events up 'end_turn' (included).
-            for event in &future_events[..=end_turn] {
-                server.receive(event.clone()).unwrap();
-            }
-        }
+            .find(|e| e.kind() == EventKind::EndTurn)
+            .unwrap();
+        // Redo all events up to 'end_turn' (included).
+        server.redo_to(end_turn.id() + 1).unwrap();
     }
     // Nothing to redo.
 }
@@ -162,8 +162,8 @@ impl Battlefield {
 impl Display for Battlefield {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         // Iterate over the arrays and print the entity position.
-        for (_, row) in self.squares.iter().rev().enumerate() {
-            for (_, col) in row.iter().enumerate() {
+        for row in self.squares.iter().rev() {
+            for col in row.iter() {
                 write!(f, "|")?;
                 if *col {
                     write!(f, "X")?;
@@ -37,7 +37,7 @@ impl MakePizza {
     pub(crate) fn trigger<P: EventProcessor<CustomRules>>(
         processor: &mut P,
         name: String,
-    ) -> MakePizzaTrigger<P> {
+    ) -> MakePizzaTrigger<'_, P> {
         MakePizzaTrigger { processor, name }
     }
 }
@@ -100,7 +100,7 @@ impl<R: BattleRules> ActivateAbility<R> {
         processor: &mut P,
         entity_id: EntityId<R>,
         ability_id: AbilityId<R>,
-    ) -> ActivateAbilityTrigger<R, P> {
+    ) -> ActivateAbilityTrigger<'_, R, P> {
         ActivateAbilityTrigger {
             processor,
             entity_id,
@@ -227,6 +227,10 @@ impl<R: BattleRules + 'static> Event<R> for ActivateAbility<R> {
             });
         EventRights::Team(actor.team_id())
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![self.entity_id.clone()]
+    }
 }
 
 /// Trigger to build and fire an `ActivateAbility` event.
@@ -6,7 +6,8 @@ use crate::character::Character;
 use crate::entity::{Entities, EntityId};
 use crate::entropy::Entropy;
 use crate::error::{WeaselError, WeaselResult};
-use crate::event::{Event, EventKind, EventProcessor, EventQueue, EventTrigger};
+use crate::event::{Event, EventKind, EventProcessor, EventPrototype, EventQueue, EventTrigger};
+use crate::fight::Reaction;
 use crate::metric::WriteMetrics;
 use crate::team::TeamId;
 use crate::util::Id;
@@ -90,6 +91,22 @@ pub trait ActorRules<R: BattleRules> {
         Ok(())
     }
 
+    /// Returns all activations with which `actor` might activate `ability`.\
+    /// The ability is guaranteed to be known by the actor.
+    ///
+    /// Activations don't need to be verified, because `Battle::legal_actions` discards
+    /// those refused by `activable`.
+    ///
+    /// The provided implementation returns only an empty activation.
+    fn activations(
+        &self,
+        _state: &BattleState<R>,
+        _actor: &dyn Actor<R>,
+        _ability: &Self::Ability,
+    ) -> Vec<Option<Self::Activation>> {
+        vec![None]
+    }
+
     /// Activates an ability.
     /// `action.ability` is guaranteed to be known by `action.actor`.\
     /// In order to change the state of the world, abilities should insert
@@ -143,6 +160,22 @@ pub trait ActorRules<R: BattleRules> {
         _metrics: &mut WriteMetrics<R>,
     ) {
     }
+
+    /// Inspects an event processed by a server, after it has been verified
+    /// but before it's applied. Invoked before `FightRules::react`, which is skipped
+    /// unless this method returns `Reaction::Proceed`.
+    ///
+    /// See [FightRules::react](../fight/trait.FightRules.html#method.react) for details.
+    ///
+    /// The provided implementation lets all events proceed.
+    fn react(
+        &self,
+        _state: &BattleState<R>,
+        _event: &EventPrototype<R>,
+        _reactions: &mut Option<EventQueue<R>>,
+    ) -> Reaction<R> {
+        Reaction::Proceed
+    }
 }
 
 /// An action is comprised by an actor who activates an ability with a given activation profile.
@@ -298,6 +331,10 @@ impl<R: BattleRules + 'static> Event<R> for AlterAbilities<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![self.id.clone()]
+    }
 }
 
 /// Trigger to build and fire an `AlterAbilities` event.
@@ -480,6 +517,10 @@ impl<R: BattleRules + 'static> Event<R> for RegenerateAbilities<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![self.id.clone()]
+    }
 }
 
 /// Trigger to build and fire a `RegenerateAbilities` event.
@@ -0,0 +1,294 @@
+//! Automatic control of teams.
+
+use crate::actor::Actor;
+use crate::battle::{Battle, BattleController, BattleRules};
+use crate::entity::EntityId;
+use crate::error::WeaselResult;
+use crate::event::{EventProcessor, EventPrototype, EventTrigger};
+use crate::round::{EndTurn, TurnState};
+use crate::server::merge_errors;
+use crate::simulation::{EventSimulator, Simulation};
+use crate::team::TeamId;
+use indexmap::IndexMap;
+#[cfg(feature = "random")]
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "random")]
+use rand_pcg::Lcg64Xsh32;
+
+/// Type of the score given to the outcome of an action. Higher is better.
+pub type Score = i64;
+
+/// Rules to let AI controllers play in place of a player.
+///
+/// These rules tell which actions are available to an actor and how good the
+/// outcome of each action is.
+pub trait AiRules<R: BattleRules> {
+    /// Returns the events that `actor` might fire during its turn.
+    ///
+    /// Invalid events are discarded before being presented to the controller,
+    /// thus there's no need to verify them here.\
+    /// The provided implementation returns `Battle::legal_actions`.
+    fn actions(&self, battle: &Battle<R>, actor: &dyn Actor<R>) -> Vec<EventPrototype<R>>
+    where
+        R: 'static,
+    {
+        battle.legal_actions(actor.entity_id())
+    }
+
+    /// Evaluates the state of the battle after an action, from the point of view
+    /// of the team with id `team_id`.
+    ///
+    /// The provided implementation gives the same score to all outcomes.
+    fn evaluate(&self, _outcome: &Battle<R>, _team_id: &TeamId<R>) -> Score {
+        0
+    }
+}
+
+/// A valid action for an actor, together with its simulated outcome.
+pub struct LegalAction<R: BattleRules> {
+    prototype: EventPrototype<R>,
+    simulation: Simulation<R>,
+    score: Score,
+}
+
+impl<R: BattleRules> LegalAction<R> {
+    /// Returns the prototype of the event to fire in order to perform this action.
+    pub fn prototype(&self) -> &EventPrototype<R> {
+        &self.prototype
+    }
+
+    /// Returns the simulated outcome of this action.
+    pub fn simulation(&self) -> &Simulation<R> {
+        &self.simulation
+    }
+
+    /// Returns the score given to the outcome of this action by `AiRules::evaluate`.
+    pub fn score(&self) -> Score {
+        self.score
+    }
+}
+
+/// A strategy to decide what an AI controlled team does in its turns.
+pub trait Controller<R: BattleRules> {
+    /// Decides the events to fire for an actor of the team with id `team_id`, choosing among
+    /// the actor's legal `actions`.
+    ///
+    /// The returned events are fired in order. The turn ends afterwards.
+    fn play(
+        &mut self,
+        battle: &Battle<R>,
+        team_id: &TeamId<R>,
+        actions: &[LegalAction<R>],
+    ) -> Vec<EventPrototype<R>>;
+}
+
+/// A controller that performs a random legal action.
+///
+/// It uses a seedable pseudo random number generator with deterministic output.
+#[cfg(feature = "random")]
+pub struct RandomController {
+    rng: Lcg64Xsh32,
+}
+
+#[cfg(feature = "random")]
+impl RandomController {
+    /// Creates a new random controller, with the given seed.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Lcg64Xsh32::seed_from_u64(seed),
+        }
+    }
+}
+
+#[cfg(feature = "random")]
+impl<R: BattleRules> Controller<R> for RandomController {
+    fn play(
+        &mut self,
+        _battle: &Battle<R>,
+        _team_id: &TeamId<R>,
+        actions: &[LegalAction<R>],
+    ) -> Vec<EventPrototype<R>> {
+        if actions.is_empty() {
+            return Vec::new();
+        }
+        let index = self.rng.gen_range(0, actions.len());
+        vec![actions[index].prototype().clone()]
+    }
+}
+
+/// A controller that performs the legal action with the highest score.
+///
+/// Among actions with the same score, the first one is chosen.
+#[derive(Default, Clone, Copy)]
+pub struct GreedyController {}
+
+impl<R: BattleRules> Controller<R> for GreedyController {
+    fn play(
+        &mut self,
+        _battle: &Battle<R>,
+        _team_id: &TeamId<R>,
+        actions: &[LegalAction<R>],
+    ) -> Vec<EventPrototype<R>> {
+        let mut best: Option<&LegalAction<R>> = None;
+        for action in actions {
+            match best {
+                Some(best) if best.score() >= action.score() => {}
+                _ => best = Some(action),
+            }
+        }
+        best.map(|action| vec![action.prototype().clone()])
+            .unwrap_or_default()
+    }
+}
+
+/// Plays the turns of actors belonging to AI controlled teams.
+///
+/// Each team can have its own `Controller`, while the `AiRules` are shared among all teams.
+///
+/// The driver doesn't play automatically when a turn starts: call `play` right after
+/// starting a turn. It can't be hooked into the battle's event callback, because
+/// controllers need to simulate actions and fire events through a processor, while callbacks
+/// only see the battle state and can do no more than enqueue derived events.
+///
+/// # Examples
+/// ```
+/// use weasel::{
+///     battle_rules, rules::empty::*, AiDriver, AiRules, Battle, BattleController, BattleRules,
+///     CreateCreature, CreateTeam, EntityId, EventTrigger, GreedyController, Server, StartTurn,
+/// };
+///
+/// battle_rules! {}
+///
+/// struct CustomAiRules {}
+///
+/// impl AiRules<CustomRules> for CustomAiRules {}
+///
+/// let battle = Battle::builder(CustomRules::new()).build();
+/// let mut server = Server::builder(battle).build();
+/// CreateTeam::trigger(&mut server, 1).fire().unwrap();
+/// CreateCreature::trigger(&mut server, 1, 1, ()).fire().unwrap();
+///
+/// let mut ai = AiDriver::new(Box::new(CustomAiRules {}));
+/// ai.set_controller(1, Box::new(GreedyController::default()));
+///
+/// StartTurn::trigger(&mut server, EntityId::Creature(1)).fire().unwrap();
+/// assert!(ai.play(&mut server).unwrap());
+/// assert!(!server.battle().rounds().is_acting(&EntityId::Creature(1)));
+/// ```
+pub struct AiDriver<R: BattleRules> {
+    rules: Box<dyn AiRules<R> + Send>,
+    controllers: IndexMap<TeamId<R>, Box<dyn Controller<R> + Send>>,
+}
+
+impl<R: BattleRules + 'static> AiDriver<R> {
+    /// Creates a new driver, without any AI controlled team.
+    pub fn new(rules: Box<dyn AiRules<R> + Send>) -> Self {
+        Self {
+            rules,
+            controllers: IndexMap::new(),
+        }
+    }
+
+    /// Returns the rules used by this driver.
+    pub fn rules(&self) -> &(dyn AiRules<R> + Send) {
+        &*self.rules
+    }
+
+    /// Lets `controller` play the team with id `team_id`.
+    ///
+    /// Returns the team's previous controller, if any.
+    pub fn set_controller(
+        &mut self,
+        team_id: TeamId<R>,
+        controller: Box<dyn Controller<R> + Send>,
+    ) -> Option<Box<dyn Controller<R> + Send>> {
+        self.controllers.insert(team_id, controller)
+    }
+
+    /// Gives back the control of the team with id `team_id` to its players.
+    ///
+    /// Returns the team's controller, if any.
+    pub fn remove_controller(
+        &mut self,
+        team_id: &TeamId<R>,
+    ) -> Option<Box<dyn Controller<R> + Send>> {
+        self.controllers.shift_remove(team_id)
+    }
+
+    /// Returns true if the team with id `team_id` is AI controlled.
+    pub fn controls(&self, team_id: &TeamId<R>) -> bool {
+        self.controllers.contains_key(team_id)
+    }
+
+    /// Plays the turn in progress in the battle owned by `processor`.
+    ///
+    /// Each actor in the turn belonging to an AI controlled team performs the actions decided
+    /// by its team's controller. The turn is then ended, unless some of the actors are
+    /// controlled by players.
+    ///
+    /// Returns true if at least one actor was played. Errors of the fired events are
+    /// collected and returned after the turn is over.
+    pub fn play<P>(&mut self, processor: &mut P) -> WeaselResult<bool, R>
+    where
+        P: BattleController<R>
+            + EventProcessor<R, ProcessOutput = WeaselResult<(), R>>
+            + EventSimulator<R>,
+    {
+        let actors: Vec<EntityId<R>> = match processor.battle().rounds().state() {
+            TurnState::Started(actors) => actors.iter().cloned().collect(),
+            TurnState::Ready => return Ok(false),
+        };
+        let mut played = false;
+        let mut players_acting = false;
+        let mut errors = Vec::new();
+        for actor_id in &actors {
+            let battle = processor.battle();
+            // Actions of previous actors might have ended the turn or removed this actor.
+            if !battle.rounds().is_acting(actor_id) {
+                continue;
+            }
+            let actor = match battle.entities().actor(actor_id) {
+                Some(actor) => actor,
+                None => continue,
+            };
+            let team_id = actor.team_id().clone();
+            let controller = match self.controllers.get_mut(&team_id) {
+                Some(controller) => controller,
+                None => {
+                    players_acting = true;
+                    continue;
+                }
+            };
+            // Keep only the actions that can be performed, together with their outcome.
+            let candidates = self.rules.actions(battle, actor);
+            let mut actions = Vec::new();
+            for prototype in candidates {
+                if let Ok(simulation) = processor.simulate(prototype.clone()) {
+                    let score = self.rules.evaluate(simulation.outcome(), &team_id);
+                    actions.push(LegalAction {
+                        prototype,
+                        simulation,
+                        score,
+                    });
+                }
+            }
+            let events = controller.play(processor.battle(), &team_id, &actions);
+            for event in events {
+                if let Err(error) = processor.process(event) {
+                    errors.push(error);
+                }
+            }
+            played = true;
+        }
+        if !played {
+            return Ok(false);
+        }
+        // End the turn, unless players still have to act.
+        if !players_acting && processor.battle().rounds().state() != &TurnState::Ready {
+            if let Err(error) = EndTurn::trigger(processor).fire() {
+                errors.push(error);
+            }
+        }
+        merge_errors(errors).map(|_| true)
+    }
+}
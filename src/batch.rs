@@ -0,0 +1,408 @@
+//! Batches of AI played battles, for balance testing.
+
+use crate::ai::AiDriver;
+use crate::battle::{BattleController, BattlePhase, BattleRules};
+use crate::entity::{Entities, EntityId};
+use crate::entropy::{EntropyModel, ResetEntropy, SeedGenerator};
+use crate::error::WeaselError;
+use crate::event::EventTrigger;
+use crate::metric::Metric;
+use crate::round::{EndRound, EndTurn, RoundsCount, RoundsModel, StartTurn, TurnState, TurnsCount};
+use crate::server::Server;
+use crate::space::SpaceModel;
+use crate::team::{Conclusion, TeamId};
+use crate::user::UserMetricId;
+use crate::util::Id;
+use indexmap::IndexMap;
+use std::thread;
+
+/// Plays many complete battles without human players and gathers their outcomes.
+///
+/// Each battle is identified by a seed. The seed is given to the functions creating
+/// the battle and the `AiDriver` playing it, and it can be used to reset the battle's entropy.
+///
+/// Turns are started in round robin order among all actors. A round ends once every actor
+/// had the chance to act.
+/// A battle is over when it ends, when a team reaches a `Conclusion` or after
+/// a maximum number of turns.
+///
+/// Events fired by the AI that are rejected don't stop the battle, but they are counted
+/// in the battle's outcome.
+///
+/// # Examples
+/// ```
+/// use weasel::{
+///     battle_rules, rules::empty::*, AiDriver, AiRules, Battle, BattleRules, BatchSimulator,
+///     CreateCreature, CreateTeam, EventTrigger, GreedyController, Server,
+/// };
+///
+/// battle_rules! {}
+///
+/// struct CustomAiRules {}
+///
+/// impl AiRules<CustomRules> for CustomAiRules {}
+///
+/// fn setup(_seed: u64) -> Server<CustomRules> {
+///     let battle = Battle::builder(CustomRules::new()).build();
+///     let mut server = Server::builder(battle).build();
+///     CreateTeam::trigger(&mut server, 1).fire().unwrap();
+///     CreateCreature::trigger(&mut server, 1, 1, ()).fire().unwrap();
+///     server
+/// }
+///
+/// fn driver(_seed: u64) -> AiDriver<CustomRules> {
+///     let mut ai = AiDriver::new(Box::new(CustomAiRules {}));
+///     ai.set_controller(1, Box::new(GreedyController::default()));
+///     ai
+/// }
+///
+/// let simulator = BatchSimulator::builder(setup, driver).max_turns(10).build();
+/// let report = simulator.run_parallel(4, 2);
+/// assert_eq!(report.battles().len(), 4);
+/// assert_eq!(report.average_turns(), 10.0);
+/// assert_eq!(report.average_rounds(), 10.0);
+/// assert_eq!(report.win_rate(&1), 0.0);
+/// ```
+pub struct BatchSimulator<R: BattleRules> {
+    setup: fn(u64) -> Server<R>,
+    driver: fn(u64) -> AiDriver<R>,
+    seed_generator: Option<SeedGenerator<R>>,
+    max_turns: TurnsCount,
+}
+
+impl<R: BattleRules> Clone for BatchSimulator<R> {
+    fn clone(&self) -> Self {
+        Self {
+            setup: self.setup,
+            driver: self.driver,
+            seed_generator: self.seed_generator,
+            max_turns: self.max_turns,
+        }
+    }
+}
+
+impl<R: BattleRules> BatchSimulator<R> {
+    /// Returns a builder to create a batch simulator.
+    ///
+    /// `setup` creates the server owning a battle ready to be played, while `driver` creates
+    /// the `AiDriver` controlling all its teams. Both receive the seed of the battle.
+    pub fn builder(
+        setup: fn(u64) -> Server<R>,
+        driver: fn(u64) -> AiDriver<R>,
+    ) -> BatchSimulatorBuilder<R> {
+        BatchSimulatorBuilder {
+            simulator: BatchSimulator {
+                setup,
+                driver,
+                seed_generator: None,
+                max_turns: 1000,
+            },
+        }
+    }
+}
+
+impl<R> BatchSimulator<R>
+where
+    R: BattleRules + 'static,
+    R: Clone,
+    R::SR: Clone,
+    R::RR: Clone,
+    R::ER: Clone,
+    Entities<R>: Clone,
+    SpaceModel<R>: Clone,
+    RoundsModel<R>: Clone,
+    EntropyModel<R>: Clone,
+{
+    /// Plays a battle for each of the given seeds, one after the other.
+    pub fn run<I>(&self, seeds: I) -> BatchReport<R>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        BatchReport {
+            battles: seeds.into_iter().map(|seed| self.play(seed)).collect(),
+        }
+    }
+
+    /// Plays `battles` battles, with seeds from zero to `battles` excluded,
+    /// splitting them among `threads` threads.
+    ///
+    /// Battles in the report are sorted by seed.
+    pub fn run_parallel(&self, battles: u64, threads: usize) -> BatchReport<R> {
+        let threads = threads.max(1);
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| {
+                let simulator = self.clone();
+                thread::spawn(move || simulator.run((thread as u64..battles).step_by(threads)))
+            })
+            .collect();
+        let mut battles = Vec::new();
+        for handle in handles {
+            let report = handle.join().expect("batch simulation thread panicked");
+            battles.extend(report.battles);
+        }
+        battles.sort_by_key(|outcome| outcome.seed);
+        BatchReport { battles }
+    }
+
+    /// Plays a complete battle.
+    fn play(&self, seed: u64) -> BattleOutcome<R> {
+        let mut server = (self.setup)(seed);
+        if let Some(seed_generator) = self.seed_generator {
+            // The reset fails only if the battle is already over.
+            let _ = ResetEntropy::trigger(&mut server)
+                .seed(seed_generator(seed))
+                .fire();
+        }
+        let mut driver = (self.driver)(seed);
+        let mut next_actor = 0;
+        let mut errors = 0;
+        for _ in 0..self.max_turns {
+            if is_over(&server) {
+                break;
+            }
+            if server.battle().rounds().state() == &TurnState::Ready {
+                let actors: Vec<EntityId<R>> = server
+                    .battle()
+                    .entities()
+                    .actors()
+                    .map(|actor| actor.entity_id().clone())
+                    .collect();
+                // If none of the actors left in this round can act, start a new round.
+                if !start_turn(&mut server, &actors, &mut next_actor)
+                    && (!end_round(&mut server, &mut next_actor)
+                        || !start_turn(&mut server, &actors, &mut next_actor))
+                {
+                    break;
+                }
+            }
+            if let Err(error) = driver.play(&mut server) {
+                errors += match error {
+                    WeaselError::MultiError(errors) => errors.len(),
+                    _ => 1,
+                };
+            }
+            // Actors without a controller just pass their turn.
+            if server.battle().rounds().state() != &TurnState::Ready
+                && EndTurn::trigger(&mut server).fire().is_err()
+            {
+                break;
+            }
+            // End the round after the last actor's turn.
+            if next_actor >= server.battle().entities().actors().count()
+                && !end_round(&mut server, &mut next_actor)
+            {
+                break;
+            }
+        }
+        BattleOutcome::new(seed, &server, errors)
+    }
+}
+
+/// Starts the turn of the first actor, in round robin order from `next_actor`, that can act.
+///
+/// Returns false if none of them can.
+fn start_turn<R: BattleRules + 'static>(
+    server: &mut Server<R>,
+    actors: &[EntityId<R>],
+    next_actor: &mut usize,
+) -> bool {
+    while let Some(actor_id) = actors.get(*next_actor) {
+        *next_actor += 1;
+        if StartTurn::trigger(server, actor_id.clone()).fire().is_ok() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Ends the current round, so that the next turn starts from the first actor.
+///
+/// Returns false if the round can't be ended.
+fn end_round<R: BattleRules + 'static>(server: &mut Server<R>, next_actor: &mut usize) -> bool {
+    *next_actor = 0;
+    EndRound::trigger(server).fire().is_ok()
+}
+
+/// Returns true if the battle ended or a team reached a conclusion.
+fn is_over<R: BattleRules + 'static>(server: &Server<R>) -> bool {
+    let battle = server.battle();
+    battle.phase() == BattlePhase::Ended
+        || battle
+            .entities()
+            .teams()
+            .any(|team| team.conclusion().is_some())
+}
+
+/// A builder object to create a `BatchSimulator`.
+pub struct BatchSimulatorBuilder<R: BattleRules> {
+    simulator: BatchSimulator<R>,
+}
+
+impl<R: BattleRules> BatchSimulatorBuilder<R> {
+    /// Resets the entropy of each battle, right after its setup, with a seed created
+    /// by `seed_generator` from the battle's seed.
+    pub fn seed_generator(mut self, seed_generator: SeedGenerator<R>) -> Self {
+        self.simulator.seed_generator = Some(seed_generator);
+        self
+    }
+
+    /// Sets the maximum number of turns played in each battle. The default is 1000.
+    pub fn max_turns(mut self, max_turns: TurnsCount) -> Self {
+        self.simulator.max_turns = max_turns;
+        self
+    }
+
+    /// Creates a new batch simulator.
+    pub fn build(self) -> BatchSimulator<R> {
+        self.simulator
+    }
+}
+
+/// The outcome of a single battle played by a `BatchSimulator`.
+pub struct BattleOutcome<R: BattleRules> {
+    seed: u64,
+    conclusions: IndexMap<TeamId<R>, Option<Conclusion>>,
+    completed_rounds: RoundsCount,
+    completed_turns: TurnsCount,
+    metrics: Vec<(UserMetricId<R>, Metric)>,
+    errors: usize,
+}
+
+impl<R: BattleRules + 'static> BattleOutcome<R> {
+    /// Creates the outcome of the battle owned by `server`, in which `errors` events
+    /// fired by the AI were rejected.
+    fn new(seed: u64, server: &Server<R>, errors: usize) -> Self {
+        let battle = server.battle();
+        Self {
+            seed,
+            conclusions: battle
+                .entities()
+                .teams()
+                .map(|team| (team.id().clone(), team.conclusion()))
+                .collect(),
+            completed_rounds: battle.rounds().completed_rounds(),
+            completed_turns: battle.rounds().completed_turns(),
+            metrics: battle
+                .metrics()
+                .user_metrics()
+                .map(|(id, metric)| (id.clone(), metric))
+                .collect(),
+            errors,
+        }
+    }
+}
+
+impl<R: BattleRules> BattleOutcome<R> {
+    /// Returns the seed of the battle.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the conclusion reached by the team with id `team_id`, if any.
+    pub fn conclusion(&self, team_id: &TeamId<R>) -> Option<Conclusion> {
+        self.conclusions.get(team_id).copied().flatten()
+    }
+
+    /// Returns the number of rounds completed in the battle.
+    pub fn completed_rounds(&self) -> RoundsCount {
+        self.completed_rounds
+    }
+
+    /// Returns the number of turns completed in the battle.
+    pub fn completed_turns(&self) -> TurnsCount {
+        self.completed_turns
+    }
+
+    /// Returns the value of the user metric with id `id` at the end of the battle.
+    pub fn metric(&self, id: &UserMetricId<R>) -> Option<Metric> {
+        self.metrics
+            .iter()
+            .find(|(metric_id, _)| metric_id == id)
+            .map(|(_, metric)| *metric)
+    }
+
+    /// Returns the number of events fired by the AI that were rejected during the battle.
+    ///
+    /// A battle with errors might not reflect the intended behavior of the rules or the AI.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+}
+
+/// Aggregated outcomes of the battles played by a `BatchSimulator`.
+pub struct BatchReport<R: BattleRules> {
+    battles: Vec<BattleOutcome<R>>,
+}
+
+impl<R: BattleRules> BatchReport<R> {
+    /// Returns the outcomes of all battles.
+    pub fn battles(&self) -> &[BattleOutcome<R>] {
+        &self.battles
+    }
+
+    /// Returns the number of battles in which the team with id `team_id` reached
+    /// the given conclusion.
+    pub fn conclusions(&self, team_id: &TeamId<R>, conclusion: Conclusion) -> usize {
+        self.battles
+            .iter()
+            .filter(|outcome| outcome.conclusion(team_id) == Some(conclusion))
+            .count()
+    }
+
+    /// Returns the number of battles in which some events fired by the AI were rejected.
+    pub fn battles_with_errors(&self) -> usize {
+        self.battles
+            .iter()
+            .filter(|outcome| outcome.errors > 0)
+            .count()
+    }
+
+    /// Returns the fraction of battles won by the team with id `team_id`.
+    pub fn win_rate(&self, team_id: &TeamId<R>) -> f64 {
+        self.rate(self.conclusions(team_id, Conclusion::Victory))
+    }
+
+    /// Returns the average number of completed rounds.
+    pub fn average_rounds(&self) -> f64 {
+        self.average(|outcome| Some(f64::from(outcome.completed_rounds)))
+    }
+
+    /// Returns the average number of completed turns.
+    pub fn average_turns(&self) -> f64 {
+        self.average(|outcome| Some(f64::from(outcome.completed_turns)))
+    }
+
+    /// Returns the average value of the user metric with id `id`, among the battles
+    /// in which the metric exists.
+    pub fn average_metric(&self, id: &UserMetricId<R>) -> f64 {
+        self.average(|outcome| {
+            outcome.metric(id).map(|metric| match metric {
+                Metric::CounterU64(v) => v as f64,
+                Metric::CounterI64(v) => v as f64,
+                Metric::CounterF64(v) => v,
+            })
+        })
+    }
+
+    /// Returns `count` as a fraction of the number of battles.
+    fn rate(&self, count: usize) -> f64 {
+        if self.battles.is_empty() {
+            0.0
+        } else {
+            count as f64 / self.battles.len() as f64
+        }
+    }
+
+    /// Returns the average of `value` among all battles for which it exists.
+    fn average<F>(&self, value: F) -> f64
+    where
+        F: Fn(&BattleOutcome<R>) -> Option<f64>,
+    {
+        let values: Vec<f64> = self.battles.iter().filter_map(value).collect();
+        if values.is_empty() {
+            0.0
+        } else {
+            values.iter().sum::<f64>() / values.len() as f64
+        }
+    }
+}
@@ -1,20 +1,27 @@
 //! Battle module.
 
+use crate::ability::ActivateAbility;
 use crate::actor::ActorRules;
 use crate::character::CharacterRules;
-use crate::entity::Entities;
-use crate::entropy::{Entropy, EntropyRules};
+#[cfg(feature = "serialization")]
+use crate::checksum::Checksum;
+use crate::entity::{Entities, EntityId};
+use crate::entropy::{Entropy, EntropyModel, EntropyRules};
 use crate::error::{WeaselError, WeaselResult};
 use crate::event::{
-    ClientEventPrototype, Event, EventKind, EventProcessor, EventPrototype, EventQueue,
+    ClientEventPrototype, Event, EventId, EventKind, EventProcessor, EventPrototype, EventQueue,
     EventTrigger, EventWrapper, Prioritized, VersionedEventWrapper,
 };
 use crate::fight::FightRules;
 use crate::history::History;
 use crate::metric::{Metrics, ReadMetrics, WriteMetrics};
-use crate::player::{Rights, RightsHandle, RightsHandleMut};
-use crate::round::{Rounds, RoundsRules};
-use crate::space::{Space, SpaceRules};
+use crate::player::{Rights, RightsHandle, RightsHandleMut, RightsList};
+use crate::round::{EndTurn, Rounds, RoundsModel, RoundsRules};
+#[cfg(feature = "serialization")]
+use crate::schedule::Scheduler;
+use crate::snapshot::{BattleSnapshot, CloneFn};
+use crate::space::{MoveEntity, Space, SpaceModel, SpaceRules};
+use crate::store::HistoryStore;
 use crate::team::{ConcludeObjectives, TeamId, TeamRules};
 use crate::user::UserRules;
 use crate::util::Id;
@@ -44,7 +51,12 @@ pub struct Battle<R: BattleRules> {
     pub(crate) rules: R,
     pub(crate) event_callback: Option<EventCallback<R>>,
     pub(crate) metrics: Metrics<R>,
-    rights: Rights<R>,
+    pub(crate) rights: Rights<R>,
+    /// State of the battle before the first event of the history, if the history
+    /// doesn't start from the beginning.
+    pub(crate) base: Option<BattleSnapshot<R>>,
+    /// Clones the snapshots used to rewind the battle, if any can be taken.
+    pub(crate) clone_snapshot: Option<CloneFn<R>>,
 }
 
 impl<R: BattleRules + 'static> Battle<R> {
@@ -53,6 +65,10 @@ impl<R: BattleRules + 'static> Battle<R> {
         BattleBuilder {
             rules,
             event_callback: None,
+            snapshot: None,
+            base: None,
+            clone_snapshot: None,
+            history_store: None,
         }
     }
 
@@ -61,7 +77,7 @@ impl<R: BattleRules + 'static> Battle<R> {
         if self.phase() == BattlePhase::Ended {
             Err(WeaselError::BattleEnded)
         } else {
-            event.verify(&self)
+            event.verify(self)
         }
     }
 
@@ -73,6 +89,8 @@ impl<R: BattleRules + 'static> Battle<R> {
                 return Err(WeaselError::ConditionUnsatisfied);
             }
         }
+        // Verify origin.
+        self.history.verify_origin(event.origin())?;
         // Verify event.
         self.verify_event(&***event)
     }
@@ -102,6 +120,8 @@ impl<R: BattleRules + 'static> Battle<R> {
                 version.clone(),
             ));
         }
+        // Verify origin.
+        self.history.verify_origin(event.origin())?;
         // Verify event.
         self.verify_event(&***event)
     }
@@ -114,14 +134,17 @@ impl<R: BattleRules + 'static> Battle<R> {
     /// Apply an event to the world.
     /// Takes in a optional `EventQueue`, to eventually store new prototypes derived from `event`.
     pub(crate) fn apply(&mut self, event: &EventWrapper<R>, queue: &mut Option<EventQueue<R>>) {
+        let turns = self.state.rounds.completed_turns();
+        let rounds = self.state.rounds.completed_rounds();
         // Apply the event to the world.
         event.apply(self, queue);
-        // Save into history.
-        self.history.archive(event);
+        // Save into history, along with the turn and round in which the event happened.
+        self.history.archive(event, turns, rounds);
+        self.history.store_event(event, self.rules.version());
         // Check teams' objectives.
         Battle::check_objectives(
             &self.state,
-            &self.rules.team_rules(),
+            self.rules.team_rules(),
             &self.metrics.read_handle(),
             &mut queue.as_mut().map(|queue| Prioritized::new(queue)),
             Checkpoint::EventEnd,
@@ -132,6 +155,110 @@ impl<R: BattleRules + 'static> Battle<R> {
         }
     }
 
+    /// Rewinds the battle to the moment before the event with id `event_id` was applied.
+    ///
+    /// The state is rebuilt from the closest snapshot preceding `event_id`, chosen among
+    /// `checkpoints` and the battle's base, by replaying the events that follow it without
+    /// invoking the event callback. Battles whose history starts from the beginning can be
+    /// rebuilt from their initial state as well.
+    ///
+    /// Returns the discarded events and the players' rights towards teams that
+    /// no longer exist.
+    pub(crate) fn rewind(
+        &mut self,
+        event_id: EventId,
+        checkpoints: &[&BattleSnapshot<R>],
+    ) -> WeaselResult<(Vec<EventWrapper<R>>, RightsList<R>), R> {
+        let history_len = self.history.len();
+        if event_id > history_len {
+            return Err(WeaselError::InvalidEventRange(0..event_id, history_len));
+        }
+        let first_id = self.history.first_id();
+        // Only snapshots whose following events are all in the history can be used.
+        let checkpoint = checkpoints
+            .iter()
+            .copied()
+            .chain(self.base.as_ref())
+            .filter(|snapshot| snapshot.next_id >= first_id && snapshot.next_id <= event_id)
+            .max_by_key(|snapshot| snapshot.next_id)
+            .and_then(|snapshot| self.clone_snapshot.map(|clone| clone(snapshot)));
+        if checkpoint.is_none() && first_id > 0 {
+            return Err(if self.history.evicted().is_empty() {
+                WeaselError::InvalidEventRange(0..first_id, history_len)
+            } else {
+                WeaselError::EvictedEvents(event_id.min(first_id)..first_id, first_id)
+            });
+        }
+        let events = self.history.events();
+        let discarded = events[(event_id - first_id) as usize..].to_vec();
+        let start = checkpoint.as_ref().map_or(0, |snapshot| snapshot.next_id);
+        let replayed = events[(start - first_id) as usize..(event_id - first_id) as usize].to_vec();
+        // Replayed events are already in the store.
+        self.history.truncate_events(start);
+        self.history.truncate_store(event_id);
+        match checkpoint {
+            Some(checkpoint) => {
+                self.restore_state(checkpoint);
+            }
+            None => {
+                // Bring the battle back to its initial state.
+                self.state.entities = Entities::new();
+                self.state.space.regenerate_model(&None);
+                self.state.rounds.reset();
+                self.state.phase = BattlePhase::Started;
+                self.entropy.regenerate_model(&None);
+                self.metrics = Metrics::new();
+            }
+        }
+        // Replay the events between the restored state and `event_id`.
+        let event_callback = self.event_callback.take();
+        for event in &replayed {
+            self.apply(event, &mut None);
+        }
+        self.event_callback = event_callback;
+        // Remove rights towards teams that no longer exist.
+        let entities = &self.state.entities;
+        let rights = self
+            .rights
+            .retain_teams(|team_id| entities.team(team_id).is_some());
+        Ok((discarded, rights))
+    }
+
+    /// Brings the battle to the state saved in `snapshot`.
+    ///
+    /// All events that came after the snapshot are removed from the history. If the snapshot
+    /// is ahead of the history, the latter is replaced by an empty one starting from the snapshot.
+    /// In the same way, the history store is emptied if it ends before the snapshot.
+    pub(crate) fn restore(&mut self, snapshot: BattleSnapshot<R>) {
+        if snapshot.next_id >= self.history.first_id() && snapshot.next_id <= self.history.len() {
+            self.history.truncate(snapshot.next_id);
+        } else {
+            self.history.reset(snapshot.next_id);
+            self.base = self.clone_snapshot.map(|clone| clone(&snapshot));
+        }
+        self.rights = self.restore_state(snapshot);
+    }
+
+    /// Brings the battle's state, except players' rights, to the one saved in `snapshot`.
+    /// Returns the rights saved in the snapshot.
+    ///
+    /// Rights are not part of the timeline, thus rewinds keep them.
+    fn restore_state(&mut self, snapshot: BattleSnapshot<R>) -> Rights<R> {
+        self.state.entities = snapshot.entities;
+        *self.state.space.model_mut() = snapshot.space;
+        self.state.rounds.restore(
+            snapshot.rounds,
+            snapshot.turn_state,
+            snapshot.completed_rounds,
+            snapshot.completed_turns,
+            snapshot.scheduler,
+        );
+        self.state.phase = snapshot.phase;
+        *self.entropy.model_mut() = snapshot.entropy;
+        self.metrics = snapshot.metrics;
+        snapshot.rights
+    }
+
     /// Ends the battle.
     pub(crate) fn end(&mut self) {
         self.state.phase = BattlePhase::Ended;
@@ -198,42 +325,180 @@ impl<R: BattleRules + 'static> Battle<R> {
     }
 
     /// Returns a handle from which metrics can be read.
-    pub fn metrics(&self) -> ReadMetrics<R> {
+    pub fn metrics(&self) -> ReadMetrics<'_, R> {
         self.metrics.read_handle()
     }
 
     /// Returns a handle from which metrics can be modified.
-    pub fn metrics_mut(&mut self) -> WriteMetrics<R> {
+    pub fn metrics_mut(&mut self) -> WriteMetrics<'_, R> {
         self.metrics.write_handle()
     }
 
     /// Returns a handle to access the players' rights to control one or more teams.
-    pub(crate) fn rights(&self) -> RightsHandle<R> {
+    pub(crate) fn rights(&self) -> RightsHandle<'_, R> {
         RightsHandle::new(&self.rights)
     }
 
     /// Returns a mutable handle to manage the players' rights to control one or more teams.
     pub(crate) fn rights_mut<'a>(
         &'a mut self,
-    ) -> RightsHandleMut<R, impl Iterator<Item = &'a TeamId<R>>> {
+    ) -> RightsHandleMut<'a, R, impl Iterator<Item = &'a TeamId<R>>> {
         RightsHandleMut::new(
             &mut self.rights,
             self.state.entities().teams().map(|team| team.id()),
         )
     }
 
+    /// Takes a snapshot of the current state of this battle.
+    ///
+    /// The snapshot can be used to rebuild the battle without replaying the events
+    /// archived so far. See [BattleSnapshot](../snapshot/struct.BattleSnapshot.html).
+    pub fn snapshot(&self) -> BattleSnapshot<R>
+    where
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        BattleSnapshot::new(self)
+    }
+
+    /// Returns the events that the actor with id `actor_id` can fire right now.
+    ///
+    /// The candidates are the activations of the actor's abilities, enumerated by
+    /// `ActorRules::activations`, the movements to the positions listed by
+    /// `SpaceRules::reachable_positions` and the end of the turn.
+    /// Only the candidates that pass verification are returned.
+    ///
+    /// The list is empty if the actor is not acting in the current turn.
+    pub fn legal_actions(&self, actor_id: &EntityId<R>) -> Vec<EventPrototype<R>> {
+        let mut actions = Vec::new();
+        let actor = match self.entities().actor(actor_id) {
+            Some(actor) if self.state.rounds.is_acting(actor_id) => actor,
+            _ => return actions,
+        };
+        // Abilities.
+        let actor_rules = self.rules.actor_rules();
+        for ability in actor.abilities() {
+            for activation in actor_rules.activations(&self.state, actor, ability) {
+                let mut processor = ();
+                let mut trigger = ActivateAbility::trigger(
+                    &mut processor,
+                    actor_id.clone(),
+                    ability.id().clone(),
+                );
+                let prototype = match activation {
+                    Some(activation) => trigger.activation(activation).prototype(),
+                    None => trigger.prototype(),
+                };
+                actions.push(prototype);
+            }
+        }
+        // Movements.
+        let entity = self
+            .entities()
+            .entity(actor_id)
+            .expect("constraint violated: actor is not an entity");
+        for position in self.state.space.reachable_positions(entity) {
+            actions.push(MoveEntity::trigger(&mut (), actor_id.clone(), position).prototype());
+        }
+        // End of turn.
+        actions.push(EndTurn::trigger(&mut ()).prototype());
+        actions.retain(|action| self.verify_prototype(action).is_ok());
+        actions
+    }
+
+    /// Creates an independent copy of this battle's state.
+    ///
+    /// The fork shares nothing with this battle, thus events can be applied to it
+    /// without any effect on the original. Useful for AI lookahead or to preview
+    /// the consequences of an action.\
+    /// The history is not copied: the fork's history is empty and its first event
+    /// will have id `self.history().len()`, as in a battle restored from a snapshot.
+    /// The event callback is not copied either; set a new one on the controller
+    /// owning the fork.
+    pub fn fork(&self) -> Battle<R>
+    where
+        R: Clone,
+        R::SR: Clone,
+        R::RR: Clone,
+        R::ER: Clone,
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        Battle {
+            state: self.state.clone(),
+            entropy: self.entropy.clone(),
+            history: History::starting_from(self.history.len()),
+            rules: self.rules.clone(),
+            event_callback: None,
+            metrics: self.metrics.clone(),
+            rights: self.rights.clone(),
+            base: Some(self.snapshot()),
+            clone_snapshot: Some(BattleSnapshot::clone),
+        }
+    }
+
+    /// Computes a checksum of the current state of this battle, including the entropy model.
+    ///
+    /// Battles that applied the same events with deterministic rules have the same checksum.
+    /// Scheduled events are included, while metrics and players' rights are not.
+    #[cfg(feature = "serialization")]
+    pub fn checksum(&self) -> Checksum
+    where
+        Entities<R>: Serialize,
+        SpaceModel<R>: Serialize,
+        RoundsModel<R>: Serialize,
+        Scheduler<R>: Serialize,
+        EntropyModel<R>: Serialize,
+    {
+        let rounds = &self.state.rounds;
+        crate::checksum::checksum(&(
+            &self.state.entities,
+            self.state.space.model(),
+            rounds.model(),
+            rounds.state(),
+            rounds.scheduler(),
+            rounds.completed_rounds(),
+            rounds.completed_turns(),
+            self.state.phase,
+            self.entropy.model(),
+        ))
+    }
+
     /// Returns an iterator over all history events in a range, versioned.
     ///
-    /// The range must be valid.
+    /// Only the events kept in memory by the history are returned. Events preceding
+    /// `History::first_id()` are skipped; use `read_events` to read them from the history store.
     pub fn versioned_events<'a>(
         &'a self,
         range: Range<usize>,
     ) -> impl Iterator<Item = VersionedEventWrapper<R>> + 'a {
-        self.history().events()[range]
+        let events = self.history().events();
+        let first_id = self.history().first_id() as usize;
+        let start = range.start.saturating_sub(first_id).min(events.len());
+        let end = range
+            .end
+            .saturating_sub(first_id)
+            .min(events.len())
+            .max(start);
+        events[start..end]
             .iter()
             .map(move |e| e.clone().version(self.rules().version().clone()))
     }
 
+    /// Returns all history events with an id inside `range`, versioned.
+    ///
+    /// Events preceding `History::first_id()` are read from the history store, if any.
+    pub fn read_events(
+        &self,
+        range: Range<EventId>,
+    ) -> WeaselResult<Vec<VersionedEventWrapper<R>>, R> {
+        self.history.read(range, self.rules.version())
+    }
+
     /// Checks if one or more teams have completed their objectives and creates events accordingly.
     pub(crate) fn check_objectives<P>(
         state: &BattleState<R>,
@@ -289,6 +554,22 @@ pub struct BattleState<R: BattleRules> {
     pub(crate) phase: BattlePhase,
 }
 
+impl<R: BattleRules> Clone for BattleState<R>
+where
+    Entities<R>: Clone,
+    Space<R>: Clone,
+    Rounds<R>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            entities: self.entities.clone(),
+            space: self.space.clone(),
+            rounds: self.rounds.clone(),
+            phase: self.phase,
+        }
+    }
+}
+
 impl<R: BattleRules> BattleState<R> {
     /// Returns the entities manager for this battle.
     pub fn entities(&self) -> &Entities<R> {
@@ -313,6 +594,7 @@ impl<R: BattleRules> BattleState<R> {
 
 /// All possible phases in which a battle can be.
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub enum BattlePhase {
     /// The battle has started.
     Started,
@@ -398,6 +680,10 @@ pub trait BattleController<R: BattleRules> {
 pub struct BattleBuilder<R: BattleRules> {
     rules: R,
     event_callback: Option<EventCallback<R>>,
+    snapshot: Option<BattleSnapshot<R>>,
+    base: Option<BattleSnapshot<R>>,
+    clone_snapshot: Option<CloneFn<R>>,
+    history_store: Option<Box<dyn HistoryStore<R> + Send>>,
 }
 
 impl<R: BattleRules> BattleBuilder<R> {
@@ -407,8 +693,65 @@ impl<R: BattleRules> BattleBuilder<R> {
         self
     }
 
+    /// Restores the battle's state from a snapshot, instead of starting from an empty battle.
+    ///
+    /// The battle's history will be empty, but it will accept only events whose ids
+    /// follow the snapshot's `next_id()`. The snapshot is kept, so that the battle
+    /// can be rewound up to it.
+    pub fn snapshot(mut self, snapshot: BattleSnapshot<R>) -> Self
+    where
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        self.base = Some(snapshot.clone());
+        self.clone_snapshot = Some(BattleSnapshot::clone);
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Saves every event applied to the battle into `store`, in addition to the history.
+    ///
+    /// Events already in the store are not saved again. This way, a battle can be recovered
+    /// by replaying on it all events read from its own store.
+    pub fn history_store(mut self, store: Box<dyn HistoryStore<R> + Send>) -> Self {
+        self.history_store = Some(store);
+        self
+    }
+
     /// Creates a new battle.
     pub fn build(mut self) -> Battle<R> {
+        let mut history = match &self.snapshot {
+            Some(snapshot) => History::starting_from(snapshot.next_id),
+            None => History::new(),
+        };
+        history.set_store(self.history_store);
+        if let Some(snapshot) = self.snapshot {
+            return Battle {
+                state: BattleState {
+                    entities: snapshot.entities,
+                    space: Space::from_model(snapshot.space, self.rules.space_rules()),
+                    rounds: Rounds::from_model(
+                        snapshot.rounds,
+                        self.rules.rounds_rules(),
+                        snapshot.turn_state,
+                        snapshot.completed_rounds,
+                        snapshot.completed_turns,
+                        snapshot.scheduler,
+                    ),
+                    phase: snapshot.phase,
+                },
+                entropy: Entropy::from_model(snapshot.entropy, self.rules.entropy_rules()),
+                history,
+                rules: self.rules,
+                event_callback: self.event_callback,
+                metrics: snapshot.metrics,
+                rights: snapshot.rights,
+                base: self.base,
+                clone_snapshot: self.clone_snapshot,
+            };
+        }
         Battle {
             state: BattleState {
                 entities: Entities::new(),
@@ -417,11 +760,13 @@ impl<R: BattleRules> BattleBuilder<R> {
                 phase: BattlePhase::Started,
             },
             entropy: Entropy::new(None, self.rules.entropy_rules()),
-            history: History::new(),
+            history,
             rules: self.rules,
             event_callback: self.event_callback,
             metrics: Metrics::new(),
             rights: Rights::new(),
+            base: None,
+            clone_snapshot: self.clone_snapshot,
         }
     }
 }
@@ -451,7 +796,7 @@ pub struct EndBattle<R> {
 
 impl<R: BattleRules> EndBattle<R> {
     /// Returns a trigger for this event.
-    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> EndBattleTrigger<R, P> {
+    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> EndBattleTrigger<'_, R, P> {
         EndBattleTrigger {
             processor,
             _phantom: PhantomData,
@@ -0,0 +1,28 @@
+//! Interactive viewer for battle histories saved as json or in binary format,
+//! such as the files written by a `FileHistoryStore`.
+//!
+//! Usage: `weasel-replay <file>`
+//!
+//! Events are replayed with empty rules, thus the viewer can open only histories recorded
+//! with empty rules, like those of the `autosave` example. Histories recorded with other rules
+//! need a viewer built with those rules through `Replay::view`.
+
+use std::env;
+use std::process;
+use weasel::{battle_rules, rules::empty::*, BattleRules, Replay};
+
+battle_rules! {}
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: weasel-replay <file>");
+            process::exit(2);
+        }
+    };
+    if let Err(error) = Replay::view(CustomRules::new, &path) {
+        eprintln!("can't replay {}: {}", path, error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,205 @@
+//! Compact binary encoding of events.
+//!
+//! This module can encode `FlatEvent`, `FlatVersionedEvent` and `FlatClientEvent` (or any other
+//! serializable type) in a binary format much smaller than json. Integers are stored as varints
+//! and every event is identified by the index of its variant in `FlatEvent`.
+//!
+//! Encoded values can be written into frames, each one prefixed by its length, so that multiple
+//! events can be stored in the same stream. Frames work with any `Read` or `Write` implementor,
+//! such as files, sockets or in-memory buffers.
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+
+/// Maximum length of an encoded value, in bytes.
+pub const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+/// Returns the options of the binary format.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(u64::from(MAX_FRAME_LEN))
+}
+
+/// Converts an encoding error into an I/O error.
+fn invalid_data(error: bincode::Error) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error)
+}
+
+/// Encodes `value` in the compact binary format.
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> io::Result<Vec<u8>> {
+    options().serialize(value).map_err(invalid_data)
+}
+
+/// Decodes a value from `bytes`, previously produced by `encode`.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> io::Result<T> {
+    options().deserialize(bytes).map_err(invalid_data)
+}
+
+/// Encodes `value` and writes it into `writer` as a frame.
+///
+/// A frame is made of the length of the encoded value, as a little endian `u32`,
+/// followed by the encoded value itself.
+pub fn write_frame<W, T>(writer: &mut W, value: &T) -> io::Result<()>
+where
+    W: Write + ?Sized,
+    T: Serialize + ?Sized,
+{
+    let bytes = encode(value)?;
+    // Write the whole frame at once, so that frames written by different sinks
+    // sharing the same stream don't interleave.
+    let mut frame = Vec::with_capacity(bytes.len() + 4);
+    frame.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&bytes);
+    writer.write_all(&frame)
+}
+
+/// Reads a frame from `reader` and decodes its value.
+///
+/// Returns `None` if `reader` has no more data.
+pub fn read_frame<S, T>(reader: &mut S) -> io::Result<Option<T>>
+where
+    S: Read + ?Sized,
+    T: DeserializeOwned,
+{
+    // Read the length prefix. Reaching the end of the stream here is not an error.
+    let mut prefix = [0; 4];
+    let mut read = 0;
+    while read < prefix.len() {
+        match reader.read(&mut prefix[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    let len = u32::from_le_bytes(prefix);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("frame of {} bytes exceeds the maximum length", len),
+        ));
+    }
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    decode(&bytes).map(Some)
+}
+
+/// A streaming decoder that reads frames one at a time.
+///
+/// It's an iterator over the values stored in a stream, for instance the events of
+/// a battle history saved in a file. The iteration stops after the first error.
+///
+/// # Examples
+/// ```
+/// use weasel::binary::{write_frame, FrameReader};
+/// use weasel::{battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateTeam};
+/// use weasel::{EventTrigger, FlatVersionedEvent, Server};
+///
+/// battle_rules! {}
+///
+/// let battle = Battle::builder(CustomRules::new()).build();
+/// let mut server = Server::builder(battle).build();
+/// CreateTeam::trigger(&mut server, 1).fire().unwrap();
+///
+/// // Save the history.
+/// let mut file = Vec::new();
+/// for event in server.battle().versioned_events(0..1) {
+///     let event: FlatVersionedEvent<CustomRules> = event.into();
+///     write_frame(&mut file, &event).unwrap();
+/// }
+///
+/// // Load it back.
+/// let reader = FrameReader::<_, FlatVersionedEvent<CustomRules>>::new(&file[..]);
+/// let events: Result<Vec<_>, _> = reader.collect();
+/// assert_eq!(events.unwrap().len(), 1);
+/// ```
+pub struct FrameReader<S, T> {
+    reader: S,
+    done: bool,
+    _phantom: PhantomData<T>,
+}
+
+impl<S: Read, T: DeserializeOwned> FrameReader<S, T> {
+    /// Creates a new decoder reading from `reader`.
+    pub fn new(reader: S) -> Self {
+        Self {
+            reader,
+            done: false,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Consumes this decoder and returns the underlying reader.
+    pub fn into_inner(self) -> S {
+        self.reader
+    }
+}
+
+impl<S: Read, T: DeserializeOwned> Iterator for FrameReader<S, T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = read_frame(&mut self.reader).transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battle::{BattleRules, EndBattle};
+    use crate::event::{DummyEvent, EventTrigger};
+    use crate::serde::FlatEvent;
+    use crate::{battle_rules, rules::empty::*};
+
+    battle_rules! {}
+
+    #[test]
+    fn stable_tags() {
+        let dummy = FlatEvent::flattened(DummyEvent::<CustomRules>::trigger(&mut ()).event());
+        assert_eq!(encode(&dummy).unwrap(), vec![1]);
+        let end = FlatEvent::flattened(EndBattle::<CustomRules>::trigger(&mut ()).event());
+        assert_eq!(encode(&end).unwrap(), vec![30]);
+    }
+
+    #[test]
+    fn frames() {
+        let mut stream = Vec::new();
+        write_frame(&mut stream, &(1u32, "one")).unwrap();
+        write_frame(&mut stream, &(2u32, "two")).unwrap();
+        let mut reader = &stream[..];
+        assert_eq!(
+            read_frame(&mut reader).unwrap(),
+            Some((1u32, "one".to_string()))
+        );
+        assert_eq!(
+            read_frame(&mut reader).unwrap(),
+            Some((2u32, "two".to_string()))
+        );
+        assert_eq!(read_frame::<_, (u32, String)>(&mut reader).unwrap(), None);
+        // Truncated frames are an error.
+        let mut reader = &stream[..stream.len() - 1];
+        let mut frames = FrameReader::<_, (u32, String)>::new(&mut reader);
+        assert!(frames.next().unwrap().is_ok());
+        assert_eq!(
+            frames.next().unwrap().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+        assert!(frames.next().is_none());
+        // Oversized frames are rejected.
+        let stream = (MAX_FRAME_LEN + 1).to_le_bytes();
+        assert_eq!(
+            read_frame::<_, u32>(&mut &stream[..]).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
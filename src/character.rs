@@ -336,6 +336,10 @@ impl<R: BattleRules + 'static> Event<R> for AlterStatistics<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![self.id.clone()]
+    }
 }
 
 /// Trigger to build and fire an `AlterStatistics` event.
@@ -522,6 +526,10 @@ impl<R: BattleRules + 'static> Event<R> for RegenerateStatistics<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![self.id.clone()]
+    }
 }
 
 /// Trigger to build and fire a `RegenerateStatistics` event.
@@ -0,0 +1,327 @@
+//! Checksums of the battle state.
+
+/// Type of the checksums computed on the state of a battle.
+///
+/// Two battles that applied the same events with the same deterministic rules
+/// have the same checksum. A difference in the checksums of a client and a server means
+/// that their states diverged, usually because of nondeterministic rules.
+pub type Checksum = u64;
+
+#[cfg(feature = "serialization")]
+pub(crate) use self::hasher::checksum;
+
+#[cfg(feature = "serialization")]
+mod hasher {
+    use super::Checksum;
+    use serde::{ser, Serialize};
+    use std::fmt::{self, Display};
+
+    /// Computes a deterministic checksum of `value`, from its serialized representation.
+    ///
+    /// The result doesn't depend on the platform. Values serializing unordered collections,
+    /// such as `HashMap`, won't produce stable checksums.
+    pub(crate) fn checksum<T: Serialize + ?Sized>(value: &T) -> Checksum {
+        let mut hasher = StateHasher::new();
+        value
+            .serialize(&mut hasher)
+            .expect("computing a checksum can't fail");
+        hasher.hash
+    }
+
+    /// A serializer that feeds all data into a 64 bit FNV-1a hash.
+    struct StateHasher {
+        hash: u64,
+    }
+
+    impl StateHasher {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0100_0000_01b3;
+
+        fn new() -> Self {
+            Self {
+                hash: Self::OFFSET_BASIS,
+            }
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.hash ^= u64::from(*byte);
+                self.hash = self.hash.wrapping_mul(Self::PRIME);
+            }
+        }
+
+        /// Writes a tag to distinguish between different kinds of data.
+        fn tag(&mut self, tag: u8) {
+            self.write(&[tag]);
+        }
+
+        fn write_len(&mut self, len: usize) {
+            self.write(&(len as u64).to_le_bytes());
+        }
+    }
+
+    /// Error type for `StateHasher`. It's never produced by the hasher itself.
+    #[derive(Debug)]
+    struct HashError(String);
+
+    impl Display for HashError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl std::error::Error for HashError {}
+
+    impl ser::Error for HashError {
+        fn custom<T: Display>(msg: T) -> Self {
+            Self(msg.to_string())
+        }
+    }
+
+    /// Generates the serialize methods for primitive numeric types.
+    macro_rules! hash_numbers {
+        ($( $method:ident: $ty:ty => $tag:expr ),* $(,)?) => {
+            $(fn $method(self, v: $ty) -> Result<(), HashError> {
+                self.tag($tag);
+                self.write(&v.to_le_bytes());
+                Ok(())
+            })*
+        };
+    }
+
+    impl ser::Serializer for &mut StateHasher {
+        type Ok = ();
+        type Error = HashError;
+        type SerializeSeq = Self;
+        type SerializeTuple = Self;
+        type SerializeTupleStruct = Self;
+        type SerializeTupleVariant = Self;
+        type SerializeMap = Self;
+        type SerializeStruct = Self;
+        type SerializeStructVariant = Self;
+
+        hash_numbers! {
+            serialize_i8: i8 => 1,
+            serialize_i16: i16 => 2,
+            serialize_i32: i32 => 3,
+            serialize_i64: i64 => 4,
+            serialize_i128: i128 => 5,
+            serialize_u8: u8 => 6,
+            serialize_u16: u16 => 7,
+            serialize_u32: u32 => 8,
+            serialize_u64: u64 => 9,
+            serialize_u128: u128 => 10,
+            serialize_f32: f32 => 11,
+            serialize_f64: f64 => 12,
+        }
+
+        fn serialize_bool(self, v: bool) -> Result<(), HashError> {
+            self.tag(13);
+            self.write(&[v as u8]);
+            Ok(())
+        }
+
+        fn serialize_char(self, v: char) -> Result<(), HashError> {
+            self.tag(14);
+            self.write(&(v as u32).to_le_bytes());
+            Ok(())
+        }
+
+        fn serialize_str(self, v: &str) -> Result<(), HashError> {
+            self.tag(15);
+            self.write_len(v.len());
+            self.write(v.as_bytes());
+            Ok(())
+        }
+
+        fn serialize_bytes(self, v: &[u8]) -> Result<(), HashError> {
+            self.tag(16);
+            self.write_len(v.len());
+            self.write(v);
+            Ok(())
+        }
+
+        fn serialize_none(self) -> Result<(), HashError> {
+            self.tag(17);
+            Ok(())
+        }
+
+        fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), HashError> {
+            self.tag(18);
+            value.serialize(self)
+        }
+
+        fn serialize_unit(self) -> Result<(), HashError> {
+            self.tag(19);
+            Ok(())
+        }
+
+        fn serialize_unit_struct(self, _: &'static str) -> Result<(), HashError> {
+            self.serialize_unit()
+        }
+
+        fn serialize_unit_variant(
+            self,
+            _: &'static str,
+            variant_index: u32,
+            _: &'static str,
+        ) -> Result<(), HashError> {
+            self.tag(20);
+            self.write(&variant_index.to_le_bytes());
+            Ok(())
+        }
+
+        fn serialize_newtype_struct<T: Serialize + ?Sized>(
+            self,
+            _: &'static str,
+            value: &T,
+        ) -> Result<(), HashError> {
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_variant<T: Serialize + ?Sized>(
+            self,
+            _: &'static str,
+            variant_index: u32,
+            _: &'static str,
+            value: &T,
+        ) -> Result<(), HashError> {
+            self.tag(21);
+            self.write(&variant_index.to_le_bytes());
+            value.serialize(self)
+        }
+
+        fn serialize_seq(self, _: Option<usize>) -> Result<Self, HashError> {
+            // The length is not always known in advance, thus the end is marked in `end()`.
+            self.tag(22);
+            Ok(self)
+        }
+
+        fn serialize_tuple(self, _: usize) -> Result<Self, HashError> {
+            self.tag(23);
+            Ok(self)
+        }
+
+        fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self, HashError> {
+            self.tag(23);
+            Ok(self)
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _: &'static str,
+            variant_index: u32,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self, HashError> {
+            self.tag(24);
+            self.write(&variant_index.to_le_bytes());
+            Ok(self)
+        }
+
+        fn serialize_map(self, _: Option<usize>) -> Result<Self, HashError> {
+            self.tag(25);
+            Ok(self)
+        }
+
+        fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self, HashError> {
+            self.tag(26);
+            Ok(self)
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _: &'static str,
+            variant_index: u32,
+            _: &'static str,
+            _: usize,
+        ) -> Result<Self, HashError> {
+            self.tag(27);
+            self.write(&variant_index.to_le_bytes());
+            Ok(self)
+        }
+    }
+
+    /// Generates the implementation of a serde compound trait for `StateHasher`.
+    macro_rules! hash_compound {
+        ($trait:ident, $method:ident) => {
+            impl ser::$trait for &mut StateHasher {
+                type Ok = ();
+                type Error = HashError;
+
+                fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), HashError> {
+                    value.serialize(&mut **self)
+                }
+
+                fn end(self) -> Result<(), HashError> {
+                    self.tag(28);
+                    Ok(())
+                }
+            }
+        };
+        ($trait:ident) => {
+            impl ser::$trait for &mut StateHasher {
+                type Ok = ();
+                type Error = HashError;
+
+                fn serialize_field<T: Serialize + ?Sized>(
+                    &mut self,
+                    _: &'static str,
+                    value: &T,
+                ) -> Result<(), HashError> {
+                    value.serialize(&mut **self)
+                }
+
+                fn end(self) -> Result<(), HashError> {
+                    self.tag(28);
+                    Ok(())
+                }
+            }
+        };
+    }
+
+    hash_compound!(SerializeSeq, serialize_element);
+    hash_compound!(SerializeTuple, serialize_element);
+    hash_compound!(SerializeTupleStruct, serialize_field);
+    hash_compound!(SerializeTupleVariant, serialize_field);
+    hash_compound!(SerializeStruct);
+    hash_compound!(SerializeStructVariant);
+
+    impl ser::SerializeMap for &mut StateHasher {
+        type Ok = ();
+        type Error = HashError;
+
+        fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), HashError> {
+            key.serialize(&mut **self)
+        }
+
+        fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), HashError> {
+            value.serialize(&mut **self)
+        }
+
+        fn end(self) -> Result<(), HashError> {
+            self.tag(28);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serialization"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_checksum() {
+        let value = (1u32, "text", vec![Some(2i64), None], [0.5f32; 2]);
+        assert_eq!(checksum(&value), checksum(&value.clone()));
+        assert_ne!(
+            checksum(&value),
+            checksum(&(1u32, "text", vec![Some(2i64)], [0.5f32; 2]))
+        );
+        // Nesting matters.
+        assert_ne!(
+            checksum(&(vec![1u8], vec![2u8])),
+            checksum(&(vec![1u8, 2u8], Vec::<u8>::new()))
+        );
+    }
+}
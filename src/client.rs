@@ -1,12 +1,25 @@
 //! A battle client.
 
 use crate::battle::{Battle, BattleController, BattleRules, EventCallback};
-use crate::error::WeaselResult;
+use crate::checksum::Checksum;
+use crate::entity::Entities;
+use crate::entropy::EntropyModel;
+use crate::error::{WeaselError, WeaselResult};
 use crate::event::{
-    EventProcessor, EventPrototype, EventReceiver, MultiClientSink, MultiClientSinkHandle,
-    MultiClientSinkHandleMut, ServerSink, VersionedEventWrapper,
+    ClientEventPrototype, CorrelationId, EventId, EventKind, EventProcessor, EventPrototype,
+    EventQueue, EventReceiver, MultiClientSink, MultiClientSinkHandle, MultiClientSinkHandleMut,
+    Rejection, ServerSink, VersionedEventWrapper,
 };
 use crate::player::PlayerId;
+use crate::resync::ResyncRequest;
+use crate::round::RoundsModel;
+#[cfg(feature = "serialization")]
+use crate::schedule::Scheduler;
+use crate::simulation::{EventSimulator, Simulation};
+use crate::snapshot::{BattleSnapshot, SnapshotFn};
+use crate::space::SpaceModel;
+#[cfg(feature = "serialization")]
+use serde::Serialize;
 
 /// A client event processor.
 ///
@@ -15,13 +28,27 @@ use crate::player::PlayerId;
 ///
 /// One or more client sinks can be connected to a client. Events received from
 /// the server are propagated to these sinks.
+///
+/// Clients can optionally predict the outcome of their own events, applying them before
+/// the server's confirmation. See `ClientBuilder::enable_prediction`.
+///
+/// Every event sent to the server carries a new correlation id. If the server rejects
+/// the event, it can notify the client with a `Rejection` having the same id.
 pub struct Client<R: BattleRules> {
     battle: Battle<R>,
     server_sink: Box<dyn ServerSink<R> + Send>,
     client_sinks: MultiClientSink<R>,
     player: Option<PlayerId>,
+    prediction: Option<Prediction<R>>,
+    checksum: Option<fn(&Battle<R>) -> Checksum>,
+    last_correlation_id: Option<CorrelationId>,
+    rejection_callback: Option<RejectionCallback>,
+    simulation_callback: bool,
 }
 
+/// Type of callback invoked when the server rejects an event sent by a client.
+pub type RejectionCallback = Box<dyn FnMut(&Rejection) + Send>;
+
 impl<R: BattleRules + 'static> Client<R> {
     /// Returns a client builder.
     pub fn builder(
@@ -32,6 +59,10 @@ impl<R: BattleRules + 'static> Client<R> {
             battle,
             server_sink,
             player: None,
+            prediction: None,
+            checksum: None,
+            rejection_callback: None,
+            simulation_callback: true,
         }
     }
 
@@ -66,6 +97,194 @@ impl<R: BattleRules + 'static> Client<R> {
     pub fn client_sinks_mut(&mut self) -> MultiClientSinkHandleMut<'_, R> {
         MultiClientSinkHandleMut::new(&mut self.client_sinks, &self.battle)
     }
+
+    /// Rewinds the battle to the moment before the event with id `event_id` was applied.
+    ///
+    /// This method should be invoked when the server notifies that it has rewound its battle,
+    /// usually through `ClientSink::rewind`. Client sinks are notified as well.\
+    /// All pending predictions are discarded.
+    ///
+    /// The battle can't be rewound before the snapshot it was restored from, if any.
+    pub fn rewind_to(&mut self, event_id: EventId) -> WeaselResult<(), R> {
+        self.discard_predictions();
+        self.battle.rewind(event_id, &[])?;
+        self.client_sinks.rewind_all(event_id);
+        Ok(())
+    }
+
+    /// Returns a request to resume receiving events from the server, after a disconnection.
+    ///
+    /// The request contains the last event confirmed by the server and, if checksums are
+    /// enabled, the checksum of the battle state at that event. Pending predictions
+    /// are left out. See `Server::resync`.
+    pub fn resync_request(&self) -> ResyncRequest {
+        match &self.prediction {
+            Some(Prediction {
+                confirmed: Some(request),
+                ..
+            }) => *request,
+            _ => resync_request(&self.battle, self.checksum),
+        }
+    }
+
+    /// Replaces the battle state with the one in `snapshot`, usually received from the server
+    /// through `ClientSink::send_snapshot`. Client sinks receive the snapshot as well.\
+    /// All pending predictions are discarded.
+    ///
+    /// The battle's history will contain only the events following the snapshot.
+    pub fn restore(&mut self, snapshot: BattleSnapshot<R>) {
+        if let Some(prediction) = &mut self.prediction {
+            prediction.checkpoint = None;
+            prediction.confirmed = None;
+            prediction.pending.clear();
+        }
+        self.client_sinks.send_snapshot_all(&snapshot);
+        self.battle.restore(snapshot);
+    }
+
+    /// Simulates the outcome of `event`, without changing the battle.
+    ///
+    /// `event` and all its derived events are processed by a scratch server owning a fork
+    /// of the battle, as the real server would do. Nothing is sent to the server, however
+    /// the battle's event callback is invoked for the simulated events as well, unless the client
+    /// was built with `disable_simulation_callback`.
+    ///
+    /// Returns an error if `event` is not applied.
+    pub fn simulate(&mut self, event: EventPrototype<R>) -> WeaselResult<Simulation<R>, R>
+    where
+        R: Clone,
+        R::SR: Clone,
+        R::RR: Clone,
+        R::ER: Clone,
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        Simulation::run(
+            &mut self.battle,
+            event,
+            Some(Battle::snapshot),
+            self.simulation_callback,
+        )
+    }
+
+    /// Returns the correlation id of the last event sent to the server, if any.
+    pub fn last_correlation_id(&self) -> Option<CorrelationId> {
+        self.last_correlation_id
+    }
+
+    /// Returns the callback invoked when the server rejects an event.
+    pub fn rejection_callback(&self) -> &Option<RejectionCallback> {
+        &self.rejection_callback
+    }
+
+    /// Sets a new callback invoked when the server rejects an event.
+    pub fn set_rejection_callback(&mut self, callback: Option<RejectionCallback>) {
+        self.rejection_callback = callback;
+    }
+
+    /// Handles a rejection of one of this client's events, usually received from the server
+    /// through `ClientSink::reject`.
+    ///
+    /// The rejected event is removed from the pending predictions, if present,
+    /// and the rejection callback is invoked.
+    pub fn receive_rejection(&mut self, rejection: &Rejection) {
+        let rejected = |event: &ClientEventPrototype<R>| {
+            event.correlation_id() == Some(rejection.correlation_id())
+        };
+        if self.pending_predictions().iter().any(rejected) {
+            // Predict again the remaining events, without the rejected one.
+            let pending = self.rollback();
+            for prediction in pending.into_iter().filter(|event| !rejected(event)) {
+                self.predict(prediction);
+            }
+        }
+        if let Some(callback) = &mut self.rejection_callback {
+            callback(rejection);
+        }
+    }
+
+    /// Returns true if client-side prediction is enabled.
+    pub fn prediction(&self) -> bool {
+        self.prediction.is_some()
+    }
+
+    /// Returns the events sent to the server whose predicted outcome is not yet confirmed.
+    pub fn pending_predictions(&self) -> &[ClientEventPrototype<R>] {
+        match &self.prediction {
+            Some(prediction) => &prediction.pending,
+            None => &[],
+        }
+    }
+
+    /// Discards all pending predictions and brings the battle back to the last state
+    /// confirmed by the server.
+    ///
+    /// Useful when the server rejected an event sent by this client.
+    pub fn discard_predictions(&mut self) {
+        self.rollback();
+    }
+
+    /// Restores the battle to the last state confirmed by the server.
+    /// Returns the pending predictions.
+    fn rollback(&mut self) -> Vec<ClientEventPrototype<R>> {
+        if let Some(prediction) = &mut self.prediction {
+            if let Some(checkpoint) = prediction.checkpoint.take() {
+                self.battle.restore(checkpoint);
+            }
+            prediction.confirmed = None;
+            std::mem::take(&mut prediction.pending)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Speculatively applies `event` on the battle, if prediction is enabled.
+    /// Invalid events and transactions are ignored.
+    fn predict(&mut self, event: ClientEventPrototype<R>) {
+        // Servers unpack transactions, so they could never be confirmed.
+        if event.kind() == EventKind::Transaction {
+            return;
+        }
+        let prototype = event.clone().prototype();
+        if let Some(prediction) = &mut self.prediction {
+            if self.battle.verify_prototype(&prototype).is_err() {
+                // The server will reject this event as well.
+                return;
+            }
+            // Save the confirmed state before the first prediction.
+            if prediction.checkpoint.is_none() {
+                prediction.checkpoint = Some((prediction.snapshot)(&self.battle));
+                prediction.confirmed = Some(resync_request(&self.battle, self.checksum));
+            }
+            prediction.pending.push(event);
+        } else {
+            return;
+        }
+        // Predictions are not committed, thus they must not reach the history store.
+        let store = self.battle.history.take_store();
+        self.speculate(prototype);
+        self.battle.history.set_store(store);
+    }
+
+    /// Applies a verified prototype and all its derived events.
+    fn speculate(&mut self, prototype: EventPrototype<R>) {
+        let event = self.battle.promote(prototype);
+        let mut event_queue = Some(EventQueue::<R>::new());
+        self.battle.apply(&event, &mut event_queue);
+        if let Some(event_queue) = event_queue {
+            for mut prototype in event_queue {
+                // Set origin id in derived event, only if it wasn't set explicitly.
+                if prototype.origin().is_none() {
+                    prototype.set_origin(Some(event.id()));
+                }
+                if self.battle.verify_prototype(&prototype).is_ok() {
+                    self.speculate(prototype);
+                }
+            }
+        }
+    }
 }
 
 impl<R: BattleRules> BattleController<R> for Client<R> {
@@ -88,29 +307,108 @@ impl<R: BattleRules + 'static> EventProcessor<R> for Client<R> {
     fn process(&mut self, event: EventPrototype<R>) -> Self::ProcessOutput {
         self.battle.verify_prototype(&event)?;
         // Decorate the prototype with additional information.
-        let event = event.client_prototype(self.battle().rules().version().clone(), self.player);
+        let mut event =
+            event.client_prototype(self.battle().rules().version().clone(), self.player);
+        let correlation_id = self.last_correlation_id.map_or(0, |id| id.wrapping_add(1));
+        event.set_correlation_id(Some(correlation_id));
         // Send the event to the server.
-        self.server_sink.send(&event)
+        self.server_sink.send(&event)?;
+        self.last_correlation_id = Some(correlation_id);
+        // Apply the event in advance, if prediction is enabled.
+        self.predict(event);
+        Ok(())
+    }
+}
+
+impl<R: BattleRules + 'static> EventSimulator<R> for Client<R>
+where
+    R: Clone,
+    R::SR: Clone,
+    R::RR: Clone,
+    R::ER: Clone,
+    Entities<R>: Clone,
+    SpaceModel<R>: Clone,
+    RoundsModel<R>: Clone,
+    EntropyModel<R>: Clone,
+{
+    fn simulate(&mut self, event: EventPrototype<R>) -> WeaselResult<Simulation<R>, R> {
+        Client::simulate(self, event)
     }
 }
 
 impl<R: BattleRules + 'static> EventReceiver<R> for Client<R> {
     fn receive(&mut self, event: VersionedEventWrapper<R>) -> WeaselResult<(), R> {
+        // Undo all predictions, since the server has the final word.
+        let mut pending = self.rollback();
         // Verify the event.
-        self.battle.verify_wrapper(&event)?;
-        // Apply the event on the battle.
-        self.battle.apply(&event.wrapper(), &mut None);
-        // Send the event to all client sinks.
-        self.client_sinks.send_all(&event);
-        Ok(())
+        let mut result = self.battle.verify_wrapper(&event);
+        if result.is_ok() {
+            // Check if the event confirms one of the predictions.
+            if let Some(correlation_id) = event.correlation_id() {
+                let confirmed = pending
+                    .iter()
+                    .position(|prediction| prediction.correlation_id() == Some(correlation_id));
+                if let Some(index) = confirmed {
+                    // The server processes events in order, thus it already discarded
+                    // the predictions sent before this one.
+                    pending.drain(..=index);
+                }
+            }
+            // Apply the event on the battle.
+            self.battle.apply(event.wrapper(), &mut None);
+            // Compare the battle state with the server's one.
+            if let (Some(checksum), Some(expected)) = (self.checksum, event.checksum()) {
+                let actual = checksum(&self.battle);
+                if actual != expected {
+                    result = Err(WeaselError::ChecksumMismatch(event.id(), expected, actual));
+                }
+            }
+            // Send the event to all client sinks.
+            self.client_sinks.send_all(&event, &self.battle);
+        }
+        // Predict again the unconfirmed events, on top of the new state.
+        for prediction in pending {
+            self.predict(prediction);
+        }
+        result
     }
 }
 
+/// Returns a resync request for the current state of `battle`.
+fn resync_request<R: BattleRules + 'static>(
+    battle: &Battle<R>,
+    checksum: Option<fn(&Battle<R>) -> Checksum>,
+) -> ResyncRequest {
+    let history = battle.history();
+    let last_event = if history.is_empty() {
+        None
+    } else {
+        Some(history.len() - 1)
+    };
+    ResyncRequest::new(last_event, checksum.map(|checksum| checksum(battle)))
+}
+
+/// Data needed to predict the outcome of events before the server's confirmation.
+struct Prediction<R: BattleRules> {
+    /// Function to take a snapshot of the battle.
+    snapshot: SnapshotFn<R>,
+    /// Snapshot of the last state confirmed by the server, taken before the first prediction.
+    checkpoint: Option<BattleSnapshot<R>>,
+    /// Resync request for the last state confirmed by the server, taken with the checkpoint.
+    confirmed: Option<ResyncRequest>,
+    /// Events sent to the server that are yet to be confirmed.
+    pending: Vec<ClientEventPrototype<R>>,
+}
+
 /// A builder object to create a client.
 pub struct ClientBuilder<R: BattleRules> {
     battle: Battle<R>,
     server_sink: Box<dyn ServerSink<R> + Send>,
     player: Option<PlayerId>,
+    prediction: Option<Prediction<R>>,
+    checksum: Option<fn(&Battle<R>) -> Checksum>,
+    rejection_callback: Option<RejectionCallback>,
+    simulation_callback: bool,
 }
 
 impl<R: BattleRules> ClientBuilder<R> {
@@ -121,6 +419,75 @@ impl<R: BattleRules> ClientBuilder<R> {
         self
     }
 
+    /// Enable client-side prediction on the new client.
+    ///
+    /// Events fired by the client are applied to its battle right after being sent to
+    /// the server, without waiting for a round trip. When an event arrives from the server,
+    /// all predictions are undone and the unconfirmed ones are applied again on top
+    /// of the authoritative state.
+    ///
+    /// Predicted events are appended to the battle's history while they are pending and are
+    /// removed when the client rolls back to the confirmed state. They never reach
+    /// the history store and are not propagated to client sinks.
+    /// Transactions are not predicted.
+    ///
+    /// The battle's event callback is invoked for predicted events as well, since it might fire
+    /// derived events. Pending predictions are applied again after each event received from
+    /// the server and after each rejection, therefore the callback is invoked for the same
+    /// prediction once every time it is applied. The event confirming a prediction
+    /// invokes the callback as usual.
+    ///
+    /// A prediction is confirmed by the server's event carrying the same correlation id.
+    /// Servers deliver correlation ids only to the client that fired the event, therefore they
+    /// must receive events through `Server::process_client_from`, or with authentication.
+    /// Predictions sent before a confirmed one are discarded, since the server didn't apply them.
+    pub fn enable_prediction(mut self) -> Self
+    where
+        R: 'static,
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        self.prediction = Some(Prediction {
+            snapshot: Battle::snapshot,
+            checkpoint: None,
+            confirmed: None,
+            pending: Vec::new(),
+        });
+        self
+    }
+
+    /// Enable verification of the checksums attached by the server to events.
+    ///
+    /// `Client::receive` returns `WeaselError::ChecksumMismatch` if the state of the battle
+    /// differs from the server's one after applying an event. The event is applied nonetheless.
+    #[cfg(feature = "serialization")]
+    pub fn enable_checksum(mut self) -> Self
+    where
+        R: 'static,
+        Entities<R>: Serialize,
+        SpaceModel<R>: Serialize,
+        RoundsModel<R>: Serialize,
+        Scheduler<R>: Serialize,
+        EntropyModel<R>: Serialize,
+    {
+        self.checksum = Some(Battle::checksum);
+        self
+    }
+
+    /// Set a callback invoked when the server rejects an event sent by the new client.
+    pub fn rejection_callback(mut self, callback: RejectionCallback) -> Self {
+        self.rejection_callback = Some(callback);
+        self
+    }
+
+    /// Don't invoke the battle's event callback for the events of simulations.
+    pub fn disable_simulation_callback(mut self) -> Self {
+        self.simulation_callback = false;
+        self
+    }
+
     /// Creates a new client.
     pub fn build(self) -> Client<R> {
         Client {
@@ -128,6 +495,11 @@ impl<R: BattleRules> ClientBuilder<R> {
             server_sink: self.server_sink,
             client_sinks: MultiClientSink::new(),
             player: self.player,
+            prediction: self.prediction,
+            checksum: self.checksum,
+            last_correlation_id: None,
+            rejection_callback: self.rejection_callback,
+            simulation_callback: self.simulation_callback,
         }
     }
 }
@@ -39,15 +39,90 @@ type Abilities<R> = IndexMap<
 ///
 /// Creatures can activate abilities during their turn, occupy a spatial position,
 /// suffer status effects and are characterized by their statistics.
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct Creature<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "EntityId<R>: Serialize",
+            deserialize = "EntityId<R>: Deserialize<'de>"
+        ))
+    )]
     id: EntityId<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "TeamId<R>: Serialize",
+            deserialize = "TeamId<R>: Deserialize<'de>"
+        ))
+    )]
     team_id: TeamId<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "Position<R>: Serialize",
+            deserialize = "Position<R>: Deserialize<'de>"
+        ))
+    )]
     position: Position<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "Statistic<R>: Serialize",
+                deserialize = "Statistic<R>: Deserialize<'de>"
+            )
+        )
+    )]
     statistics: Statistics<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "AppliedStatus<R>: Serialize",
+                deserialize = "AppliedStatus<R>: Deserialize<'de>"
+            )
+        )
+    )]
     statuses: Statuses<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "Ability<R>: Serialize",
+                deserialize = "Ability<R>: Deserialize<'de>"
+            )
+        )
+    )]
     abilities: Abilities<R>,
 }
 
+impl<R: BattleRules> Clone for Creature<R>
+where
+    Statistic<R>: Clone,
+    AppliedStatus<R>: Clone,
+    Ability<R>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            team_id: self.team_id.clone(),
+            position: self.position.clone(),
+            statistics: self.statistics.clone(),
+            statuses: self.statuses.clone(),
+            abilities: self.abilities.clone(),
+        }
+    }
+}
+
 impl<R: BattleRules> Creature<R> {
     pub(crate) fn set_team_id(&mut self, id: TeamId<R>) {
         self.team_id = id;
@@ -309,7 +384,7 @@ impl<R: BattleRules + 'static> Event<R> for CreateCreature<R> {
         battle
             .rules()
             .team_rules()
-            .allow_new_entity(&battle.state, &team, EntityAddition::CreatureSpawn)
+            .allow_new_entity(&battle.state, team, EntityAddition::CreatureSpawn)
             .map_err(|err| {
                 WeaselError::NewCreatureUnaccepted(self.team_id.clone(), Box::new(err))
             })?;
@@ -396,6 +471,14 @@ impl<R: BattleRules + 'static> Event<R> for CreateCreature<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![EntityId::Creature(self.id.clone())]
+    }
+
+    fn teams(&self) -> Vec<TeamId<R>> {
+        vec![self.team_id.clone()]
+    }
 }
 
 /// Trigger to build and fire a `CreateCreature` event.
@@ -519,7 +602,7 @@ impl<R: BattleRules> ConvertCreature<R> {
         processor: &mut P,
         creature_id: CreatureId<R>,
         team_id: TeamId<R>,
-    ) -> ConvertCreatureTrigger<R, P> {
+    ) -> ConvertCreatureTrigger<'_, R, P> {
         ConvertCreatureTrigger {
             processor,
             creature_id,
@@ -580,8 +663,8 @@ impl<R: BattleRules + 'static> Event<R> for ConvertCreature<R> {
             .team_rules()
             .allow_new_entity(
                 &battle.state,
-                &team,
-                EntityAddition::CreatureConversion(&creature),
+                team,
+                EntityAddition::CreatureConversion(creature),
             )
             .map_err(|err| {
                 WeaselError::ConvertedCreatureUnaccepted(
@@ -611,6 +694,14 @@ impl<R: BattleRules + 'static> Event<R> for ConvertCreature<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![EntityId::Creature(self.creature_id.clone())]
+    }
+
+    fn teams(&self) -> Vec<TeamId<R>> {
+        vec![self.team_id.clone()]
+    }
 }
 
 /// Trigger to build and fire a `ConvertCreature` event.
@@ -687,7 +778,7 @@ impl<R: BattleRules> RemoveCreature<R> {
     pub fn trigger<P: EventProcessor<R>>(
         processor: &mut P,
         id: CreatureId<R>,
-    ) -> RemoveCreatureTrigger<R, P> {
+    ) -> RemoveCreatureTrigger<'_, R, P> {
         RemoveCreatureTrigger { processor, id }
     }
 
@@ -740,7 +831,7 @@ impl<R: BattleRules + 'static> Event<R> for RemoveCreature<R> {
                 // Check teams' objectives.
                 Battle::check_objectives(
                     &battle.state,
-                    &battle.rules.team_rules(),
+                    battle.rules.team_rules(),
                     &battle.metrics.read_handle(),
                     event_queue,
                     Checkpoint::TurnEnd,
@@ -789,6 +880,10 @@ impl<R: BattleRules + 'static> Event<R> for RemoveCreature<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![EntityId::Creature(self.id.clone())]
+    }
 }
 
 /// Trigger to build and fire a `RemoveCreature` event.
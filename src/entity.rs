@@ -184,13 +184,73 @@ pub(crate) fn transmute_entity<R, P>(
 }
 
 /// Data structure to manage ownership of teams and entities.
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct Entities<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "Team<R>: Serialize",
+                deserialize = "Team<R>: Deserialize<'de>"
+            )
+        )
+    )]
     teams: IndexMap<TeamId<R>, Team<R>>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "Creature<R>: Serialize",
+                deserialize = "Creature<R>: Deserialize<'de>"
+            )
+        )
+    )]
     creatures: IndexMap<CreatureId<R>, Creature<R>>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "Object<R>: Serialize",
+                deserialize = "Object<R>: Deserialize<'de>"
+            )
+        )
+    )]
     objects: IndexMap<ObjectId<R>, Object<R>>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "RelationshipPair<R>: Serialize",
+                deserialize = "RelationshipPair<R>: Deserialize<'de>"
+            )
+        )
+    )]
     relations: IndexMap<RelationshipPair<R>, Relation>,
 }
 
+impl<R: BattleRules> Clone for Entities<R>
+where
+    Team<R>: Clone,
+    Creature<R>: Clone,
+    Object<R>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            teams: self.teams.clone(),
+            creatures: self.creatures.clone(),
+            objects: self.objects.clone(),
+            relations: self.relations.clone(),
+        }
+    }
+}
+
 impl<R: BattleRules> Entities<R> {
     pub(crate) fn new() -> Self {
         Self {
@@ -402,13 +462,12 @@ impl<R: BattleRules> Entities<R> {
             .iter()
             .filter(move |&(k, _)| k.first == *id || k.second == *id)
             .filter(|&(_, v)| *v == Relation::Ally)
-            .map(|(k, _)| k.values())
-            .flatten()
+            .flat_map(|(k, _)| k.values())
             .filter(move |v| v != id)
     }
 
     /// Returns all allied teams of a team.
-    pub fn allies<'a>(&'a self, id: &'a TeamId<R>) -> impl Iterator<Item = &Team<R>> + 'a {
+    pub fn allies<'a>(&'a self, id: &'a TeamId<R>) -> impl Iterator<Item = &'a Team<R>> + 'a {
         self.allies_id(id).map(move |id| self.team(&id).unwrap())
     }
 
@@ -418,13 +477,12 @@ impl<R: BattleRules> Entities<R> {
             .iter()
             .filter(move |&(k, _)| k.first == *id || k.second == *id)
             .filter(|&(_, v)| *v == Relation::Enemy)
-            .map(|(k, _)| k.values())
-            .flatten()
+            .flat_map(|(k, _)| k.values())
             .filter(move |v| v != id)
     }
 
     /// Returns all enemy teams of a team.
-    pub fn enemies<'a>(&'a self, id: &'a TeamId<R>) -> impl Iterator<Item = &Team<R>> + 'a {
+    pub fn enemies<'a>(&'a self, id: &'a TeamId<R>) -> impl Iterator<Item = &'a Team<R>> + 'a {
         self.enemies_id(id).map(move |id| self.team(&id).unwrap())
     }
 
@@ -567,7 +625,7 @@ impl<R: BattleRules> RemoveEntity<R> {
     pub fn trigger<P: EventProcessor<R>>(
         processor: &mut P,
         id: EntityId<R>,
-    ) -> RemoveEntityTrigger<R, P> {
+    ) -> RemoveEntityTrigger<'_, R, P> {
         RemoveEntityTrigger { processor, id }
     }
 }
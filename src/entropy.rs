@@ -16,6 +16,19 @@ pub struct Entropy<R: BattleRules> {
     rules: R::ER,
 }
 
+impl<R: BattleRules> Clone for Entropy<R>
+where
+    EntropyModel<R>: Clone,
+    R::ER: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            model: self.model.clone(),
+            rules: self.rules.clone(),
+        }
+    }
+}
+
 impl<R: BattleRules> Entropy<R> {
     /// Creates a new entropy object.
     pub(crate) fn new(seed: Option<EntropySeed<R>>, rules: R::ER) -> Self {
@@ -25,6 +38,11 @@ impl<R: BattleRules> Entropy<R> {
         }
     }
 
+    /// Creates an entropy object from an existing model.
+    pub(crate) fn from_model(model: EntropyModel<R>, rules: R::ER) -> Self {
+        Self { model, rules }
+    }
+
     /// See [generate](EntropyRules::generate).
     pub fn generate(&mut self, low: EntropyOutput<R>, high: EntropyOutput<R>) -> EntropyOutput<R> {
         match low.partial_cmp(&high) {
@@ -97,6 +115,9 @@ pub trait EntropyRules {
 /// It is used to bootstrap the `EntropyModel` for a game.
 pub type EntropySeed<R> = <<R as BattleRules>::ER as EntropyRules>::EntropySeed;
 
+/// Function to create an entropy seed from a number.
+pub type SeedGenerator<R> = fn(u64) -> EntropySeed<R>;
+
 /// Type to store all information about the entropy in the game.
 ///
 /// The entropy model is the source of randomness (or the lack thereof) for a battle.\
@@ -140,7 +161,7 @@ pub struct ResetEntropy<R: BattleRules> {
 
 impl<R: BattleRules> ResetEntropy<R> {
     /// Returns a trigger for this event.
-    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> ResetEntropyTrigger<R, P> {
+    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> ResetEntropyTrigger<'_, R, P> {
         ResetEntropyTrigger {
             processor,
             seed: None,
@@ -2,6 +2,7 @@
 
 use crate::ability::AbilityId;
 use crate::battle::{BattleRules, Version};
+use crate::checksum::Checksum;
 use crate::creature::CreatureId;
 use crate::entity::EntityId;
 use crate::event::{DefaultOutput, Event, EventId, EventSinkId};
@@ -11,6 +12,8 @@ use crate::player::PlayerId;
 use crate::space::Position;
 use crate::status::StatusId;
 use crate::team::TeamId;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
 use std::error;
 use std::ops::Range;
 use std::result::Result;
@@ -33,8 +36,16 @@ pub type WeaselErrorType<R> = WeaselError<
 /// Alias for a `Result` returning a `WeaselError`.
 pub type WeaselResult<T, R> = Result<T, WeaselErrorType<R>>;
 
+/// Type of the numeric codes identifying each kind of `WeaselError`.
+pub type ErrorCode = u16;
+
 /// Error type for all kind of errors generated by weasel.
+///
+/// Every variant has a stable numeric code, returned by `code()`.\
+/// With the `serialization` feature errors can be serialized, once their events
+/// have been flattened. See `FlatWeaselError`.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub enum WeaselError<V, TI, EI, CI, OI, PI, AI, SI, MI, E> {
     /// A generic error.
     GenericError,
@@ -64,6 +75,8 @@ pub enum WeaselError<V, TI, EI, CI, OI, PI, AI, SI, MI, E> {
     EntityNotFound(EI),
     /// The event id is not contiguous.
     NonContiguousEventId(EventId, EventId),
+    /// The battle state differs from the server's one after applying an event.
+    ChecksumMismatch(EventId, Checksum, Checksum),
     /// A turn is already in progress.
     TurnInProgress,
     /// No turn is in progress.
@@ -124,6 +137,24 @@ pub enum WeaselError<V, TI, EI, CI, OI, PI, AI, SI, MI, E> {
     UserError(String),
     /// A generic event sink error.
     EventSinkError(String),
+    /// Transactions are not enabled on the server.
+    TransactionsDisabled,
+    /// The event range includes events evicted from the history, which now starts
+    /// from the given event id.
+    EvictedEvents(Range<EventId>, EventId),
+    /// A generic history store error.
+    HistoryStoreError(String),
+    /// The event was cancelled by the rules' reaction to it.
+    EventCancelled,
+    /// The event's origin is not a previous event.
+    InvalidOrigin(EventId, EventId),
+    /// The event range can't be sent again to client sinks, because the server
+    /// filters events by visibility.
+    FilteredEventRange(Range<EventId>),
+    /// The event prototype has a condition, which can't be serialized.
+    ConditionNotSerializable,
+    /// A transaction can't contain other transactions.
+    NestedTransaction,
 }
 
 impl<V, TI, EI, CI, OI, PI, AI, SI, MI, E> fmt::Display
@@ -175,6 +206,11 @@ where
             NonContiguousEventId(id, expected) => {
                 write!(f, "event has id {:?}, expected {:?}", id, expected)
             }
+            ChecksumMismatch(id, expected, actual) => write!(
+                f,
+                "battle state diverged after event {:?}: checksum is {:x}, expected {:x}",
+                id, actual, expected
+            ),
             TurnInProgress => write!(f, "a turn is already in progress"),
             NoTurnInProgress => write!(f, "no turn is in progress"),
             ActorNotEligible(id) => write!(f, "actor {:?} is not eligible to start a new turn", id),
@@ -244,6 +280,28 @@ where
             }
             UserError(msg) => write!(f, "user error: {}", msg),
             EventSinkError(msg) => write!(f, "sink error: {}", msg),
+            TransactionsDisabled => write!(f, "transactions are not enabled"),
+            EvictedEvents(range, first_id) => write!(
+                f,
+                "event range {:?} was evicted from the history, which starts from event {}",
+                range, first_id
+            ),
+            HistoryStoreError(msg) => write!(f, "history store error: {}", msg),
+            EventCancelled => write!(f, "the event was cancelled by a reaction"),
+            InvalidOrigin(origin, id) => write!(
+                f,
+                "event {:?} can't originate from the later event {:?}",
+                id, origin
+            ),
+            FilteredEventRange(range) => write!(
+                f,
+                "event range {:?} can't be sent again when events are filtered by visibility",
+                range
+            ),
+            ConditionNotSerializable => {
+                write!(f, "the condition of an event prototype can't be serialized")
+            }
+            NestedTransaction => write!(f, "a transaction can't contain other transactions"),
         }
     }
 }
@@ -268,6 +326,168 @@ where
 }
 
 impl<V, TI, EI, CI, OI, PI, AI, SI, MI, E> WeaselError<V, TI, EI, CI, OI, PI, AI, SI, MI, E> {
+    /// Returns the numeric code of this kind of error.
+    ///
+    /// Codes never change between versions of weasel, thus they can be used to identify errors
+    /// in remote peers, logs or localized messages.
+    ///
+    /// # Examples
+    /// ```
+    /// use weasel::{
+    ///     battle_rules, error::WeaselErrorType, rules::empty::*, BattleRules, WeaselError,
+    /// };
+    ///
+    /// battle_rules! {}
+    /// let error: WeaselErrorType<CustomRules> = WeaselError::TurnInProgress;
+    /// assert_eq!(error.code(), 15);
+    /// ```
+    pub fn code(&self) -> ErrorCode {
+        use WeaselError::*;
+        match self {
+            GenericError => 0,
+            DuplicatedCreature(..) => 1,
+            DuplicatedObject(..) => 2,
+            DuplicatedTeam(..) => 3,
+            TeamNotFound(..) => 4,
+            CreatureNotFound(..) => 5,
+            ObjectNotFound(..) => 6,
+            NewCreatureUnaccepted(..) => 7,
+            ConvertedCreatureUnaccepted(..) => 8,
+            InvalidCreatureConversion(..) => 9,
+            TeamNotEmpty(..) => 10,
+            PositionError(..) => 11,
+            EntityNotFound(..) => 12,
+            NonContiguousEventId(..) => 13,
+            ChecksumMismatch(..) => 14,
+            TurnInProgress => 15,
+            NoTurnInProgress => 16,
+            ActorNotEligible(..) => 17,
+            ActorNotReady(..) => 18,
+            AbilityNotKnown(..) => 19,
+            AbilityNotActivable(..) => 20,
+            StatusNotPresent(..) => 21,
+            EmptyEventProcessor => 22,
+            NotACharacter(..) => 23,
+            NotAnActor(..) => 24,
+            NotACreature(..) => 25,
+            NotAnObject(..) => 26,
+            KinshipRelation => 27,
+            SelfRelation => 28,
+            IncompatibleVersions(..) => 29,
+            BattleEnded => 30,
+            WrongMetricType(..) => 31,
+            ConditionUnsatisfied => 32,
+            DuplicatedEventSink(..) => 33,
+            InvalidEventRange(..) => 34,
+            EventSinkNotFound(..) => 35,
+            AuthenticationError(..) => 36,
+            MissingAuthentication => 37,
+            ServerOnlyEvent => 38,
+            UserEventPackingError(..) => 39,
+            UserEventUnpackingError(..) => 40,
+            InvalidEvent(..) => 41,
+            MultiError(..) => 42,
+            UserError(..) => 43,
+            EventSinkError(..) => 44,
+            TransactionsDisabled => 45,
+            EvictedEvents(..) => 46,
+            HistoryStoreError(..) => 47,
+            EventCancelled => 48,
+            InvalidOrigin(..) => 49,
+            FilteredEventRange(..) => 50,
+            ConditionNotSerializable => 51,
+            NestedTransaction => 52,
+        }
+    }
+
+    /// Transforms the events contained in this error, including those in inner errors,
+    /// with the function `op`.
+    pub fn map_event<E2, F>(self, mut op: F) -> WeaselError<V, TI, EI, CI, OI, PI, AI, SI, MI, E2>
+    where
+        F: FnMut(E) -> E2,
+    {
+        self.map_event_with(&mut op)
+    }
+
+    fn map_event_with<E2, F>(self, op: &mut F) -> WeaselError<V, TI, EI, CI, OI, PI, AI, SI, MI, E2>
+    where
+        F: FnMut(E) -> E2,
+    {
+        use WeaselError::*;
+        match self {
+            GenericError => GenericError,
+            DuplicatedCreature(id) => DuplicatedCreature(id),
+            DuplicatedObject(id) => DuplicatedObject(id),
+            DuplicatedTeam(id) => DuplicatedTeam(id),
+            TeamNotFound(id) => TeamNotFound(id),
+            CreatureNotFound(id) => CreatureNotFound(id),
+            ObjectNotFound(id) => ObjectNotFound(id),
+            NewCreatureUnaccepted(id, error) => {
+                NewCreatureUnaccepted(id, Box::new(error.map_event_with(op)))
+            }
+            ConvertedCreatureUnaccepted(team_id, creature_id, error) => {
+                ConvertedCreatureUnaccepted(
+                    team_id,
+                    creature_id,
+                    Box::new(error.map_event_with(op)),
+                )
+            }
+            InvalidCreatureConversion(team_id, creature_id) => {
+                InvalidCreatureConversion(team_id, creature_id)
+            }
+            TeamNotEmpty(id) => TeamNotEmpty(id),
+            PositionError(source, destination, error) => {
+                PositionError(source, destination, Box::new(error.map_event_with(op)))
+            }
+            EntityNotFound(id) => EntityNotFound(id),
+            NonContiguousEventId(id, expected) => NonContiguousEventId(id, expected),
+            ChecksumMismatch(id, expected, actual) => ChecksumMismatch(id, expected, actual),
+            TurnInProgress => TurnInProgress,
+            NoTurnInProgress => NoTurnInProgress,
+            ActorNotEligible(id) => ActorNotEligible(id),
+            ActorNotReady(id) => ActorNotReady(id),
+            AbilityNotKnown(actor_id, ability_id) => AbilityNotKnown(actor_id, ability_id),
+            AbilityNotActivable(actor_id, ability_id, error) => {
+                AbilityNotActivable(actor_id, ability_id, Box::new(error.map_event_with(op)))
+            }
+            StatusNotPresent(character_id, status_id) => StatusNotPresent(character_id, status_id),
+            EmptyEventProcessor => EmptyEventProcessor,
+            NotACharacter(id) => NotACharacter(id),
+            NotAnActor(id) => NotAnActor(id),
+            NotACreature(id) => NotACreature(id),
+            NotAnObject(id) => NotAnObject(id),
+            KinshipRelation => KinshipRelation,
+            SelfRelation => SelfRelation,
+            IncompatibleVersions(client, server) => IncompatibleVersions(client, server),
+            BattleEnded => BattleEnded,
+            WrongMetricType(id) => WrongMetricType(id),
+            ConditionUnsatisfied => ConditionUnsatisfied,
+            DuplicatedEventSink(id) => DuplicatedEventSink(id),
+            InvalidEventRange(range, history_len) => InvalidEventRange(range, history_len),
+            EventSinkNotFound(id) => EventSinkNotFound(id),
+            AuthenticationError(player, team) => AuthenticationError(player, team),
+            MissingAuthentication => MissingAuthentication,
+            ServerOnlyEvent => ServerOnlyEvent,
+            UserEventPackingError(event, error) => UserEventPackingError(op(event), error),
+            UserEventUnpackingError(error) => UserEventUnpackingError(error),
+            InvalidEvent(event, error) => {
+                let event = op(event);
+                InvalidEvent(event, Box::new(error.map_event_with(op)))
+            }
+            MultiError(v) => MultiError(v.into_iter().map(|err| err.map_event_with(op)).collect()),
+            UserError(msg) => UserError(msg),
+            EventSinkError(msg) => EventSinkError(msg),
+            TransactionsDisabled => TransactionsDisabled,
+            EvictedEvents(range, first_id) => EvictedEvents(range, first_id),
+            HistoryStoreError(msg) => HistoryStoreError(msg),
+            EventCancelled => EventCancelled,
+            InvalidOrigin(origin, id) => InvalidOrigin(origin, id),
+            FilteredEventRange(range) => FilteredEventRange(range),
+            ConditionNotSerializable => ConditionNotSerializable,
+            NestedTransaction => NestedTransaction,
+        }
+    }
+
     /// Unfolds an error, return the inner one in case the original is an `InvalidEvent`.
     /// If not, it returns the original.\
     /// In the case of `MultiError`, unfolds all contained errors.
@@ -387,7 +607,7 @@ where
 mod tests {
     use super::*;
     use crate::battle::BattleRules;
-    use crate::event::{DummyEvent, EventTrigger};
+    use crate::event::{DummyEvent, EventKind, EventTrigger};
     use crate::{battle_rules, rules::empty::*};
 
     #[test]
@@ -414,13 +634,7 @@ mod tests {
         battle_rules! {}
         let mut processor = ();
         let trigger = DummyEvent::trigger(&mut (processor));
-        let filter_fn = |err: &WeaselErrorType<_>| {
-            if let WeaselError::EmptyEventProcessor = err {
-                false
-            } else {
-                true
-            }
-        };
+        let filter_fn = |err: &WeaselErrorType<_>| !matches!(err, WeaselError::EmptyEventProcessor);
         // Test a direct filtering.
         let error_to_filter: WeaselErrorType<CustomRules> =
             WeaselError::InvalidEvent(trigger.event(), Box::new(WeaselError::EmptyEventProcessor));
@@ -442,4 +656,61 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    #[allow(clippy::let_unit_value)]
+    fn codes() {
+        battle_rules! {}
+        let mut processor = ();
+        let trigger = DummyEvent::trigger(&mut processor);
+        // Codes must never change.
+        let error: WeaselErrorType<CustomRules> = WeaselError::GenericError;
+        assert_eq!(error.code(), 0);
+        let error: WeaselErrorType<CustomRules> = WeaselError::TurnInProgress;
+        assert_eq!(error.code(), 15);
+        let error: WeaselErrorType<CustomRules> =
+            WeaselError::InvalidEvent(trigger.event(), Box::new(WeaselError::TurnInProgress));
+        assert_eq!(error.code(), 41);
+        let error: WeaselErrorType<CustomRules> = WeaselError::EventSinkError(String::new());
+        assert_eq!(error.code(), 44);
+        let error: WeaselErrorType<CustomRules> = WeaselError::TransactionsDisabled;
+        assert_eq!(error.code(), 45);
+        let error: WeaselErrorType<CustomRules> = WeaselError::EvictedEvents(0..1, 1);
+        assert_eq!(error.code(), 46);
+        let error: WeaselErrorType<CustomRules> = WeaselError::HistoryStoreError(String::new());
+        assert_eq!(error.code(), 47);
+        let error: WeaselErrorType<CustomRules> = WeaselError::EventCancelled;
+        assert_eq!(error.code(), 48);
+        let error: WeaselErrorType<CustomRules> = WeaselError::InvalidOrigin(1, 1);
+        assert_eq!(error.code(), 49);
+        let error: WeaselErrorType<CustomRules> = WeaselError::FilteredEventRange(0..1);
+        assert_eq!(error.code(), 50);
+        let error: WeaselErrorType<CustomRules> = WeaselError::ConditionNotSerializable;
+        assert_eq!(error.code(), 51);
+        let error: WeaselErrorType<CustomRules> = WeaselError::NestedTransaction;
+        assert_eq!(error.code(), 52);
+    }
+
+    #[test]
+    #[allow(clippy::let_unit_value)]
+    fn map_event() {
+        battle_rules! {}
+        let mut processor = ();
+        let trigger = DummyEvent::trigger(&mut processor);
+        let error: WeaselErrorType<CustomRules> = WeaselError::MultiError(vec![
+            WeaselError::InvalidEvent(trigger.event(), Box::new(WeaselError::TurnInProgress)),
+            WeaselError::UserEventPackingError(trigger.event(), "error".to_string()),
+        ]);
+        let error = error.map_event(|event| event.kind());
+        assert_eq!(
+            error,
+            WeaselError::MultiError(vec![
+                WeaselError::InvalidEvent(
+                    EventKind::DummyEvent,
+                    Box::new(WeaselError::TurnInProgress)
+                ),
+                WeaselError::UserEventPackingError(EventKind::DummyEvent, "error".to_string()),
+            ])
+        );
+    }
 }
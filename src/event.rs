@@ -1,14 +1,19 @@
 //! Event module.
 
 use crate::battle::{Battle, BattleRules, BattleState, Version};
-use crate::error::{WeaselError, WeaselResult};
+use crate::checksum::Checksum;
+use crate::entity::EntityId;
+use crate::error::{ErrorCode, WeaselError, WeaselErrorType, WeaselResult};
 use crate::player::PlayerId;
-use crate::team::TeamId;
+use crate::snapshot::BattleSnapshot;
+use crate::team::{TeamId, TeamRules};
 use crate::user::UserEventId;
 use log::error;
 #[cfg(feature = "serialization")]
 use serde::{Deserialize, Serialize};
 use std::any::Any;
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::fmt::{Debug, Formatter, Result};
 use std::marker::PhantomData;
 use std::ops::{Deref, Range};
@@ -16,10 +21,34 @@ use std::ops::{Deref, Range};
 /// Type for the id of events.
 pub type EventId = u32;
 
+/// Type for the id that a client attaches to its events, to correlate them with
+/// the server's response.
+pub type CorrelationId = u64;
+
+/// The client that fired an event, to whom the event's correlation id is delivered.
+pub(crate) struct Correlation {
+    pub(crate) id: CorrelationId,
+    /// Sink through which the event was received, if known.
+    pub(crate) sink: Option<EventSinkId>,
+    /// Player who fired the event.
+    pub(crate) player: Option<PlayerId>,
+}
+
+impl Correlation {
+    /// Returns true if `sink` leads to the client that fired the event.
+    fn matches<R: BattleRules>(&self, sink: &(dyn ClientSink<R> + Send)) -> bool {
+        match self.sink {
+            Some(id) => sink.id() == id,
+            None => self.player.is_some() && sink.player() == self.player,
+        }
+    }
+}
+
 /// Enum to represent all different kinds of events.
 // Internal note: remember to update the event debug and serialization tests in tests/event.rs
 // each time a new event is added to weasel.
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub enum EventKind {
     /// Dummy event doing nothing.
     DummyEvent,
@@ -81,6 +110,10 @@ pub enum EventKind {
     ResetSpace,
     /// End the battle.
     EndBattle,
+    /// Schedule an event to be fired at a later time.
+    ScheduleEvent,
+    /// Apply a group of events atomically.
+    Transaction,
     /// A user defined event with an unique id.
     UserEvent(UserEventId),
 }
@@ -153,6 +186,22 @@ pub trait Event<R: BattleRules>: Debug {
     fn rights<'a>(&'a self, _battle: &'a Battle<R>) -> EventRights<'a, R> {
         EventRights::Server
     }
+
+    /// Returns the ids of the entities involved in this event.
+    /// They are used to index the event in the battle's `History`.
+    ///
+    /// The provided implementation returns an empty vector.
+    fn entities(&self) -> Vec<EntityId<R>> {
+        Vec::new()
+    }
+
+    /// Returns the ids of the teams involved in this event.
+    /// They are used to index the event in the battle's `History`.
+    ///
+    /// The provided implementation returns an empty vector.
+    fn teams(&self) -> Vec<TeamId<R>> {
+        Vec::new()
+    }
 }
 
 impl<R: BattleRules> Clone for Box<dyn Event<R> + Send> {
@@ -224,21 +273,48 @@ impl<R: BattleRules> Deref for EventWrapper<R> {
 }
 
 /// Decorates an `EventWrapper` with the battle rules version.
+///
+/// It can also carry the checksum of the battle state computed by the server
+/// right after the event was applied.
 pub struct VersionedEventWrapper<R: BattleRules> {
     pub(crate) wrapper: EventWrapper<R>,
     pub(crate) version: Version<R>,
+    pub(crate) checksum: Option<Checksum>,
+    pub(crate) correlation_id: Option<CorrelationId>,
 }
 
 impl<R: BattleRules> Clone for VersionedEventWrapper<R> {
     fn clone(&self) -> Self {
-        Self::new(self.wrapper.clone(), self.version.clone())
+        Self {
+            wrapper: self.wrapper.clone(),
+            version: self.version.clone(),
+            checksum: self.checksum,
+            correlation_id: self.correlation_id,
+        }
     }
 }
 
 impl<R: BattleRules> VersionedEventWrapper<R> {
     /// Creates a new VersionedEventWrapper.
     pub(crate) fn new(wrapper: EventWrapper<R>, version: Version<R>) -> Self {
-        Self { wrapper, version }
+        Self {
+            wrapper,
+            version,
+            checksum: None,
+            correlation_id: None,
+        }
+    }
+
+    /// Returns the checksum of the battle state after this event, if the server provided it.
+    pub fn checksum(&self) -> Option<Checksum> {
+        self.checksum
+    }
+
+    /// Returns the correlation id of the client event from which this event was created.
+    ///
+    /// Servers set it only in the copy of the event sent to the client that fired it.
+    pub fn correlation_id(&self) -> Option<CorrelationId> {
+        self.correlation_id
     }
 
     /// Returns the `EventWrapper` contained in this object.
@@ -262,7 +338,7 @@ impl<R: BattleRules> Deref for VersionedEventWrapper<R> {
 
 /// Function that tells if an event prototype met its additional conditions
 /// in order to be applied.
-pub type Condition<R> = std::rc::Rc<dyn Fn(&BattleState<R>) -> bool>;
+pub type Condition<R> = std::sync::Arc<dyn Fn(&BattleState<R>) -> bool + Send + Sync>;
 
 /// A prototype for tentative events that are not yet verified.
 pub struct EventPrototype<R: BattleRules> {
@@ -353,6 +429,8 @@ pub struct ClientEventPrototype<R: BattleRules> {
     pub(crate) version: Version<R>,
     /// Id of the player who fired this event.
     player: Option<PlayerId>,
+    /// Id to correlate this event with the server's response.
+    correlation_id: Option<CorrelationId>,
 }
 
 impl<R: BattleRules> ClientEventPrototype<R> {
@@ -368,6 +446,7 @@ impl<R: BattleRules> ClientEventPrototype<R> {
             event,
             version,
             player,
+            correlation_id: None,
         }
     }
 
@@ -408,6 +487,18 @@ impl<R: BattleRules> ClientEventPrototype<R> {
     pub fn player(&self) -> Option<PlayerId> {
         self.player
     }
+
+    /// Returns the id used to correlate this event with the server's response.
+    pub fn correlation_id(&self) -> Option<CorrelationId> {
+        self.correlation_id
+    }
+
+    /// Sets the id used to correlate this event with the server's response.
+    ///
+    /// Clients assign a new id to every event they send.
+    pub fn set_correlation_id(&mut self, correlation_id: Option<CorrelationId>) {
+        self.correlation_id = correlation_id;
+    }
 }
 
 impl<R: BattleRules> Deref for ClientEventPrototype<R> {
@@ -425,6 +516,7 @@ impl<R: BattleRules> Clone for ClientEventPrototype<R> {
             event: self.event.clone(),
             version: self.version.clone(),
             player: self.player,
+            correlation_id: self.correlation_id,
         }
     }
 }
@@ -522,7 +614,7 @@ pub struct DummyEvent<R> {
 
 impl<R: BattleRules> DummyEvent<R> {
     /// Returns a trigger for this event.
-    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> DummyEventTrigger<R, P> {
+    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> DummyEventTrigger<'_, R, P> {
         DummyEventTrigger {
             processor,
             _phantom: PhantomData,
@@ -751,7 +843,7 @@ where
 ///
 /// let result = Conditional::new(
 ///     DummyEvent::trigger(&mut server),
-///     std::rc::Rc::new(|state: &BattleState<CustomRules>| {
+///     std::sync::Arc::new(|state: &BattleState<CustomRules>| {
 ///         state
 ///             .entities()
 ///             .teams()
@@ -832,6 +924,88 @@ pub trait EventSink {
 pub trait ClientSink<R: BattleRules>: EventSink {
     /// Sends an already accepted event to a remote or local client.
     fn send(&mut self, event: &VersionedEventWrapper<R>) -> WeaselResult<(), R>;
+
+    /// Returns the player to whom this sink sends events, if any.
+    /// Servers use it to decide the visibility of each event through `TeamRules`.
+    ///
+    /// The provided implementation returns `None`.
+    fn player(&self) -> Option<PlayerId> {
+        None
+    }
+
+    /// Notifies a remote or local client that the battle has been rewound.
+    /// All events having an id equal to or greater than `event_id` were discarded.
+    ///
+    /// The provided implementation does nothing. Clients that aren't told about the rewind
+    /// can be brought back in sync with `Server::resync`.
+    fn rewind(&mut self, _event_id: EventId) -> WeaselResult<(), R> {
+        Ok(())
+    }
+
+    /// Sends a snapshot of the battle to a remote or local client, that should restore it.
+    /// Events sent afterwards will follow the snapshot.
+    ///
+    /// The provided implementation returns an error.
+    fn send_snapshot(&mut self, snapshot: &BattleSnapshot<R>) -> WeaselResult<(), R> {
+        Err(WeaselError::EventSinkError(format!(
+            "sink {} can't receive a snapshot up to event {}",
+            self.id(),
+            snapshot.next_id()
+        )))
+    }
+
+    /// Notifies a remote or local client that the server rejected one of its events.
+    ///
+    /// The provided implementation ignores the rejection.
+    fn reject(&mut self, _rejection: &Rejection) -> WeaselResult<(), R> {
+        Ok(())
+    }
+}
+
+/// Notification sent by a server to a client, when one of the client's events is rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Rejection {
+    correlation_id: CorrelationId,
+    code: ErrorCode,
+    reason: String,
+}
+
+impl Rejection {
+    /// Creates a new rejection for the event with the given `correlation_id`.
+    ///
+    /// `code` and `reason` describe the error that caused the rejection.
+    pub fn new(correlation_id: CorrelationId, code: ErrorCode, reason: String) -> Self {
+        Self {
+            correlation_id,
+            code,
+            reason,
+        }
+    }
+
+    /// Creates a new rejection for the event with the given `correlation_id`,
+    /// caused by `error`.
+    pub fn from_error<R: BattleRules>(
+        correlation_id: CorrelationId,
+        error: &WeaselErrorType<R>,
+    ) -> Self {
+        Self::new(correlation_id, error.code(), error.to_string())
+    }
+
+    /// Returns the correlation id of the rejected event.
+    pub fn correlation_id(&self) -> CorrelationId {
+        self.correlation_id
+    }
+
+    /// Returns the code of the error that caused the rejection. See `WeaselError::code`.
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    /// Returns a description of why the event was rejected.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
 }
 
 /// An output sink to dump tentative events to a server.
@@ -840,14 +1014,62 @@ pub trait ServerSink<R: BattleRules>: EventSink {
     fn send(&mut self, event: &ClientEventPrototype<R>) -> WeaselResult<(), R>;
 }
 
+/// Describes how an event is shown to a player.
+///
+/// Events that are not fully visible are still sent, so that the ids of the events
+/// received by each client remain contiguous.\
+/// Clients don't know what was hidden, so see `TeamRules::event_visibility` for the
+/// constraints on events depending on those not fully visible.
+pub enum Visibility<R: BattleRules> {
+    /// The event is sent as it is.
+    Full,
+    /// The event is replaced by another one, for instance a copy without secret information.\
+    /// The replacement keeps the id and the origin of the original event.
+    Redacted(Box<dyn Event<R> + Send>),
+    /// The event is replaced by a `DummyEvent`.
+    Hidden,
+}
+
 /// A data structure to contain multiple client sinks.
 pub(crate) struct MultiClientSink<R: BattleRules> {
     sinks: Vec<Box<dyn ClientSink<R> + Send>>,
+    filter: bool,
+    /// Sinks that received an event not fully visible, whose clients diverged
+    /// from the battle state.
+    diverged: HashSet<EventSinkId>,
 }
 
 impl<R: BattleRules> MultiClientSink<R> {
     pub(crate) fn new() -> Self {
-        Self { sinks: Vec::new() }
+        Self {
+            sinks: Vec::new(),
+            filter: false,
+            diverged: HashSet::new(),
+        }
+    }
+
+    /// Creates a `MultiClientSink` that applies `TeamRules::event_visibility`
+    /// to all events, before sending them.
+    pub(crate) fn with_visibility() -> Self {
+        Self {
+            sinks: Vec::new(),
+            filter: true,
+            diverged: HashSet::new(),
+        }
+    }
+}
+
+impl<R: BattleRules + 'static> MultiClientSink<R> {
+    /// Checks if the events in `range` can be sent again to a sink.
+    ///
+    /// The visibility of each event depends on the battle state at the moment it was
+    /// broadcast, thus past events can't be filtered again.
+    pub(crate) fn verify_range(&self, range: &Range<EventId>) -> WeaselResult<(), R> {
+        if self.filter && !range.is_empty() {
+            Err(WeaselError::FilteredEventRange(range.clone()))
+        } else {
+            Ok(())
+        }
     }
 
     /// Adds a new sink.
@@ -856,6 +1078,8 @@ impl<R: BattleRules> MultiClientSink<R> {
         if self.sinks.iter().any(|e| e.id() == sink.id()) {
             Err(WeaselError::DuplicatedEventSink(sink.id()))
         } else {
+            // Forget about any previous sink with the same id.
+            self.diverged.remove(&sink.id());
             self.sinks.push(sink);
             Ok(())
         }
@@ -863,15 +1087,17 @@ impl<R: BattleRules> MultiClientSink<R> {
 
     /// Sends all `events` to an existing sink.
     /// Returns an error if sending the events failed or the sink doesn't exist.
-    fn send<I>(&mut self, id: EventSinkId, events: I) -> WeaselResult<(), R>
+    fn send<I>(&mut self, id: EventSinkId, events: I, battle: &Battle<R>) -> WeaselResult<(), R>
     where
         I: Iterator<Item = VersionedEventWrapper<R>>,
     {
         let index = self.sinks.iter().position(|e| e.id() == id);
         if let Some(index) = index {
+            let filter = self.filter;
             // Send events.
             for event in events {
                 let sink = &mut self.sinks[index];
+                let event = visible_event(filter, &event, &**sink, battle, &mut self.diverged);
                 let result = sink.send(&event);
                 if result.is_err() {
                     sink.on_disconnect();
@@ -885,6 +1111,23 @@ impl<R: BattleRules> MultiClientSink<R> {
         }
     }
 
+    /// Sends a rejection to the sink with the given `id`.
+    /// The sink is disconnected if it returns an error.
+    pub(crate) fn reject(&mut self, id: EventSinkId, rejection: &Rejection) -> WeaselResult<(), R> {
+        let index = self.sinks.iter().position(|e| e.id() == id);
+        if let Some(index) = index {
+            let sink = &mut self.sinks[index];
+            let result = sink.reject(rejection);
+            if result.is_err() {
+                sink.on_disconnect();
+                self.sinks.remove(index);
+            }
+            result
+        } else {
+            Err(WeaselError::EventSinkNotFound(id))
+        }
+    }
+
     /// Removes the sink with the given `id`, if it exists.
     fn remove(&mut self, id: EventSinkId) {
         let index = self.sinks.iter().position(|e| e.id() == id);
@@ -896,15 +1139,58 @@ impl<R: BattleRules> MultiClientSink<R> {
     /// Sends an event to all sinks.
     /// If a sink returns an error, its on_disconnect() fn will be invoked
     /// and the sink is disconnected from the server.
-    pub(crate) fn send_all(&mut self, event: &VersionedEventWrapper<R>) {
+    pub(crate) fn send_all(&mut self, event: &VersionedEventWrapper<R>, battle: &Battle<R>) {
+        self.send_all_correlated(event, battle, None);
+    }
+
+    /// Sends an event to all sinks, as in `send_all`.
+    /// The sink leading to the client that fired the event receives its correlation id.
+    pub(crate) fn send_all_correlated(
+        &mut self,
+        event: &VersionedEventWrapper<R>,
+        battle: &Battle<R>,
+        correlation: Option<&Correlation>,
+    ) {
+        let filter = self.filter;
+        let mut diverged = std::mem::take(&mut self.diverged);
+        self.broadcast(|sink| {
+            let mut event = visible_event(filter, event, &**sink, battle, &mut diverged);
+            if let Some(correlation) = correlation.filter(|c| c.matches(&**sink)) {
+                event.to_mut().correlation_id = Some(correlation.id);
+            }
+            sink.send(&event)
+        });
+        self.diverged = diverged;
+    }
+
+    /// Notifies all sinks that the battle has been rewound to `event_id`.
+    /// Sinks returning an error are disconnected, as in `send_all`.
+    pub(crate) fn rewind_all(&mut self, event_id: EventId) {
+        self.broadcast(|sink| sink.rewind(event_id));
+    }
+
+    /// Sends a snapshot to all sinks.
+    /// Sinks returning an error are disconnected, as in `send_all`.
+    pub(crate) fn send_snapshot_all(&mut self, snapshot: &BattleSnapshot<R>) {
+        self.broadcast(|sink| sink.send_snapshot(snapshot));
+        // Clients are back in sync with the battle state.
+        self.diverged.clear();
+    }
+
+    /// Invokes `f` on every sink and disconnects those for which it returned an error.
+    fn broadcast<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Box<dyn ClientSink<R> + Send>) -> WeaselResult<(), R>,
+    {
         let mut failed_sinks_index = Vec::new();
         for (i, sink) in self.sinks.iter_mut().enumerate() {
-            sink.send(event).unwrap_or_else(|err| {
+            f(sink).unwrap_or_else(|err| {
                 error!("{:?}", err);
                 failed_sinks_index.push(i)
             });
         }
-        for i in failed_sinks_index {
+        // Remove from the back, so that the other indices stay valid.
+        for i in failed_sinks_index.into_iter().rev() {
             self.sinks[i].on_disconnect();
             self.sinks.remove(i);
         }
@@ -915,6 +1201,46 @@ impl<R: BattleRules> MultiClientSink<R> {
     }
 }
 
+/// Returns the version of `event` that can be seen by the player behind `sink`.
+///
+/// Sinks receiving an event not fully visible are added to `diverged`. Events sent to them
+/// don't carry a checksum, since their clients can't reach the server's state anymore.
+fn visible_event<'a, R: BattleRules + 'static>(
+    filter: bool,
+    event: &'a VersionedEventWrapper<R>,
+    sink: &(dyn ClientSink<R> + Send),
+    battle: &Battle<R>,
+    diverged: &mut HashSet<EventSinkId>,
+) -> Cow<'a, VersionedEventWrapper<R>> {
+    if !filter {
+        return Cow::Borrowed(event);
+    }
+    let visibility = battle.rules().team_rules().event_visibility(
+        &battle.state,
+        event.wrapper(),
+        sink.player(),
+        &battle.rights(),
+    );
+    let replacement: Box<dyn Event<R> + Send> = match visibility {
+        Visibility::Full => {
+            let mut event = Cow::Borrowed(event);
+            if diverged.contains(&sink.id()) && event.checksum.is_some() {
+                event.to_mut().checksum = None;
+            }
+            return event;
+        }
+        Visibility::Redacted(replacement) => replacement,
+        Visibility::Hidden => Box::new(DummyEvent {
+            _phantom: PhantomData,
+        }),
+    };
+    diverged.insert(sink.id());
+    Cow::Owned(VersionedEventWrapper::new(
+        EventWrapper::new(event.id(), event.origin(), replacement),
+        event.version().clone(),
+    ))
+}
+
 /// A structure to access client sinks.
 pub struct MultiClientSinkHandle<'a, R>
 where
@@ -964,7 +1290,9 @@ where
     /// Adds a new sink and shares the battle history with it,
     /// starting from the event having `event_id` up to the most recent event.
     ///
-    /// Sinks must have unique ids.
+    /// Sinks must have unique ids.\
+    /// Returns `WeaselError::FilteredEventRange` if events are filtered by visibility
+    /// and the range is not empty.
     pub fn add_sink_from(
         &mut self,
         sink: Box<dyn ClientSink<R> + Send>,
@@ -982,26 +1310,58 @@ where
     /// Adds a new sink and shares a portion of the battle history with it.
     /// More precisely, only the events inside `range` will be sent to the sink.
     ///
-    /// Sinks must have unique ids.
+    /// Sinks must have unique ids.\
+    /// Returns `WeaselError::FilteredEventRange` if events are filtered by visibility
+    /// and the range is not empty.
     pub fn add_sink_range(
         &mut self,
         sink: Box<dyn ClientSink<R> + Send>,
         range: Range<EventId>,
     ) -> WeaselResult<(), R> {
-        let range = normalize_range(range, self.battle.history().len())?;
+        self.sinks.verify_range(&range)?;
+        // Get all versioned events from history.
+        let events = self.battle.read_events(range)?;
+        self.add_sink_events(sink, events)
+    }
+
+    /// Replaces the sink having the same id of `sink`, if any, with `sink` and sends
+    /// the events inside `range` to it.
+    ///
+    /// The existing sink is kept if the events can't be read.
+    pub(crate) fn replace_sink_range(
+        &mut self,
+        sink: Box<dyn ClientSink<R> + Send>,
+        range: Range<EventId>,
+    ) -> WeaselResult<(), R> {
+        self.sinks.verify_range(&range)?;
+        // Get all versioned events from history, before removing the existing sink.
+        let events = self.battle.read_events(range)?;
+        self.sinks.remove(sink.id());
+        self.add_sink_events(sink, events)
+    }
+
+    /// Adds a new sink and sends `events` to it.
+    fn add_sink_events(
+        &mut self,
+        sink: Box<dyn ClientSink<R> + Send>,
+        events: Vec<VersionedEventWrapper<R>>,
+    ) -> WeaselResult<(), R> {
         // Add the new sink.
         let sink_id = sink.id();
         self.sinks.add(sink)?;
-        // Get all versioned events from history and send them.
-        self.sinks
-            .send(sink_id, self.battle.versioned_events(range))
+        // Send the events.
+        self.sinks.send(sink_id, events.into_iter(), self.battle)
     }
 
     /// Sends a range of events from the battle history to the sink with the given id.
+    ///
+    /// Returns `WeaselError::FilteredEventRange` if events are filtered by visibility
+    /// and the range is not empty.
     pub fn send_range(&mut self, id: EventSinkId, range: Range<EventId>) -> WeaselResult<(), R> {
-        let range = normalize_range(range, self.battle.history().len())?;
+        self.sinks.verify_range(&range)?;
         // Get all versioned events from history and send them.
-        self.sinks.send(id, self.battle.versioned_events(range))
+        let events = self.battle.read_events(range)?;
+        self.sinks.send(id, events.into_iter(), self.battle)
     }
 
     /// Removes the sink with the given id.
@@ -1015,23 +1375,10 @@ where
     }
 }
 
-/// Converts a range of `EventId` into a range of `usize`.
-fn normalize_range<R: BattleRules>(
-    range: Range<EventId>,
-    history_len: EventId,
-) -> WeaselResult<Range<usize>, R> {
-    if range.start > range.end || range.end > history_len {
-        return Err(WeaselError::InvalidEventRange(range, history_len));
-    }
-    let range: Range<usize> = Range {
-        start: range.start as usize,
-        end: range.end as usize,
-    };
-    Ok(range)
-}
-
 /// Decorator for event triggers to manually set the origin of an event.
 ///
+/// The origin must be the id of an event already in the battle's history.
+///
 /// # Examples
 /// ```
 /// use weasel::{
@@ -1043,11 +1390,12 @@ fn normalize_range<R: BattleRules>(
 ///
 /// let battle = Battle::builder(CustomRules::new()).build();
 /// let mut server = Server::builder(battle).build();
+/// DummyEvent::trigger(&mut server).fire().unwrap();
 ///
-/// Originated::new(DummyEvent::trigger(&mut server), 42)
+/// Originated::new(DummyEvent::trigger(&mut server), 0)
 ///     .fire()
 ///     .unwrap();
-/// assert_eq!(server.battle().history().events()[0].origin(), Some(42));
+/// assert_eq!(server.battle().history().events()[1].origin(), Some(0));
 /// ```
 pub struct Originated<'a, R, T, P>
 where
@@ -1143,6 +1491,7 @@ mod tests {
             }
         }
 
+        let battle = Battle::builder(CustomRules::new()).build();
         // Check add.
         let mut multi = MultiClientSink::new();
         assert_eq!(multi.add(Box::new(Sink { id: 0, ok: true })).err(), None);
@@ -1160,23 +1509,30 @@ mod tests {
         // Check send_all.
         assert_eq!(multi.add(Box::new(Sink { id: 0, ok: true })).err(), None);
         assert_eq!(multi.add(Box::new(Sink { id: 1, ok: false })).err(), None);
-        assert_eq!(multi.sinks.len(), 2);
+        assert_eq!(multi.add(Box::new(Sink { id: 2, ok: true })).err(), None);
+        assert_eq!(multi.add(Box::new(Sink { id: 3, ok: false })).err(), None);
+        assert_eq!(multi.sinks.len(), 4);
         let event = DummyEvent::<CustomRules>::trigger(&mut ())
             .prototype()
             .promote(0)
             .version(0);
-        multi.send_all(&event);
+        multi.send_all(&event, &battle);
+        assert_eq!(
+            multi.sinks().map(|sink| sink.id()).collect::<Vec<_>>(),
+            [0, 2]
+        );
+        multi.remove(2);
         assert_eq!(multi.sinks.len(), 1);
         // Check send.
-        assert_eq!(multi.send(0, once(event.clone())).err(), None);
+        assert_eq!(multi.send(0, once(event.clone()), &battle).err(), None);
         assert_eq!(
-            multi.send(2, once(event.clone())).err(),
+            multi.send(2, once(event.clone()), &battle).err(),
             Some(WeaselError::EventSinkNotFound(2))
         );
         assert_eq!(multi.add(Box::new(Sink { id: 1, ok: false })).err(), None);
         assert_eq!(multi.sinks.len(), 2);
         assert_eq!(
-            multi.send(1, once(event)).err(),
+            multi.send(1, once(event), &battle).err(),
             Some(WeaselError::EventSinkError("broken".to_string()))
         );
         assert_eq!(multi.sinks.len(), 1);
@@ -1188,7 +1544,7 @@ mod tests {
         let mut processor = ();
         let event = Conditional::new(
             DummyEvent::trigger(&mut processor),
-            std::rc::Rc::new(|_: &BattleState<CustomRules>| true),
+            std::sync::Arc::new(|_: &BattleState<CustomRules>| true),
         );
         let event = Originated::new(event, 0);
         let prototype = event.prototype();
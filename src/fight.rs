@@ -4,7 +4,9 @@ use crate::battle::{Battle, BattleRules, BattleState};
 use crate::character::Character;
 use crate::entropy::Entropy;
 use crate::error::WeaselResult;
-use crate::event::{Event, EventKind, EventProcessor, EventQueue, EventTrigger, LinkedQueue};
+use crate::event::{
+    Event, EventKind, EventProcessor, EventPrototype, EventQueue, EventTrigger, LinkedQueue,
+};
 use crate::metric::WriteMetrics;
 use crate::status::{Application, AppliedStatus};
 #[cfg(feature = "serialization")]
@@ -95,6 +97,39 @@ pub trait FightRules<R: BattleRules> {
         _metrics: &mut WriteMetrics<R>,
     ) {
     }
+
+    /// Inspects an event processed by a server, after it has been verified
+    /// but before it's applied. Useful to implement counterattacks, parries and
+    /// any other effect that must resolve before the event itself.
+    ///
+    /// Events pushed into `reactions` are processed right away, before `event`.
+    /// Reactions inherit the origin of `event`, unless they have one already.
+    /// The returned `Reaction` decides what happens to `event` afterwards.\
+    /// This method is invoked for reactions as well, thus take care to not react endlessly.
+    ///
+    /// Only servers invoke this method. Reactions are stored in the history as any other event,
+    /// so that clients can reproduce their outcome.\
+    /// A cancelled event is reported as an `EventCancelled` error.
+    ///
+    /// The provided implementation lets all events proceed.
+    fn react(
+        &self,
+        _state: &BattleState<R>,
+        _event: &EventPrototype<R>,
+        _reactions: &mut Option<EventQueue<R>>,
+    ) -> Reaction<R> {
+        Reaction::Proceed
+    }
+}
+
+/// Outcome of `ActorRules::react` and `FightRules::react` for an event that is about to be applied.
+pub enum Reaction<R: BattleRules> {
+    /// The event is applied, if it's still valid after the reactions.
+    Proceed,
+    /// The event is replaced by another one, keeping the same origin.
+    Replace(Box<dyn Event<R> + Send>),
+    /// The event is discarded.
+    Cancel,
 }
 
 /// Impacts encapsulate information about which creatures or areas are affected
@@ -1,62 +1,789 @@
 //! History of events.
 
-use crate::battle::BattleRules;
-use crate::error::{WeaselError, WeaselResult};
+use crate::battle::{BattleRules, Version};
+use crate::entity::EntityId;
+use crate::error::{WeaselError, WeaselErrorType, WeaselResult};
 use crate::event::EventId;
-use crate::event::EventWrapper;
+use crate::event::{EventKind, EventWrapper, VersionedEventWrapper};
+use crate::round::{RoundsCount, TurnsCount};
+use crate::store::HistoryStore;
+use crate::team::TeamId;
+use log::error;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
+use std::fmt::Write;
+use std::hash::Hash;
+use std::ops::Range;
 
 /// History is the place where all events are kept, in a way such that they
 /// construct a single, consistent timeline.
+///
+/// A history created from a `BattleSnapshot` doesn't contain the events that precede the
+/// snapshot. Nonetheless, event ids continue the original timeline.
+///
+/// The oldest events are evicted from the history of servers with compaction enabled,
+/// see `ServerBuilder::compact_history`.
+///
+/// Events are indexed by kind, involved entities and teams, origin, turn and round.
+/// Use `query` to find them.
+///
+/// Events are kept in memory. If the battle has a `HistoryStore`, every event is also
+/// appended to the store, from which evicted events can still be read.
 pub struct History<R: BattleRules> {
     events: Vec<EventWrapper<R>>,
+    first_id: EventId,
+    /// Id of the first event ever stored in this history, including evicted ones.
+    start_id: EventId,
+    /// Completed turns at the time each event was applied.
+    turns: Vec<TurnsCount>,
+    /// Completed rounds at the time each event was applied.
+    rounds: Vec<RoundsCount>,
+    kinds: HashMap<EventKind, Vec<EventId>>,
+    entities: HashMap<EntityId<R>, Vec<EventId>>,
+    teams: HashMap<TeamId<R>, Vec<EventId>>,
+    origins: HashMap<EventId, Vec<EventId>>,
+    store: Option<Box<dyn HistoryStore<R> + Send>>,
+    /// Description of the first failure of the store, after which it's no longer updated.
+    store_error: Option<String>,
+}
+
+impl<R: BattleRules> Clone for History<R> {
+    fn clone(&self) -> Self {
+        Self {
+            events: self.events.clone(),
+            first_id: self.first_id,
+            start_id: self.start_id,
+            turns: self.turns.clone(),
+            rounds: self.rounds.clone(),
+            kinds: self.kinds.clone(),
+            entities: self.entities.clone(),
+            teams: self.teams.clone(),
+            origins: self.origins.clone(),
+            store: None,
+            store_error: None,
+        }
+    }
 }
 
 impl<R: BattleRules> History<R> {
     /// Creates a new History.
     pub(crate) fn new() -> Self {
-        Self { events: Vec::new() }
+        Self::starting_from(0)
+    }
+
+    /// Creates a new History whose first event will have the given id.
+    pub(crate) fn starting_from(first_id: EventId) -> Self {
+        Self {
+            events: Vec::new(),
+            first_id,
+            start_id: first_id,
+            turns: Vec::new(),
+            rounds: Vec::new(),
+            kinds: HashMap::new(),
+            entities: HashMap::new(),
+            teams: HashMap::new(),
+            origins: HashMap::new(),
+            store: None,
+            store_error: None,
+        }
     }
 
     /// Returns all events inside this timeline.
+    ///
+    /// The first event in the slice has id equal to `first_id()`.
     pub fn events(&self) -> &[EventWrapper<R>] {
         &self.events
     }
 
+    /// Returns the id of the first event stored in this history.
+    ///
+    /// It's always zero, unless the battle was restored from a snapshot
+    /// or some events were evicted.
+    pub fn first_id(&self) -> EventId {
+        self.first_id
+    }
+
+    /// Returns the range of ids of the events evicted from this history.
+    ///
+    /// The range is empty if no events were evicted.
+    pub fn evicted(&self) -> Range<EventId> {
+        self.start_id..self.first_id
+    }
+
+    /// Returns the store in which events are saved, if any.
+    pub fn store(&self) -> Option<&(dyn HistoryStore<R> + Send)> {
+        self.store.as_deref()
+    }
+
+    /// Replaces the store in which events are saved.
+    pub(crate) fn set_store(&mut self, store: Option<Box<dyn HistoryStore<R> + Send>>) {
+        self.store = store;
+    }
+
+    /// Detaches the store from this history and returns it.
+    pub(crate) fn take_store(&mut self) -> Option<Box<dyn HistoryStore<R> + Send>> {
+        self.store.take()
+    }
+
+    /// Saves `event` into the store, unless the store already contains it.
+    ///
+    /// Failures must not prevent the event from being applied. Instead, the store is
+    /// no longer updated and reading events returns an error from then on.
+    pub(crate) fn store_event(&mut self, event: &EventWrapper<R>, version: &Version<R>) {
+        if self.store_error.is_some() {
+            return;
+        }
+        let result = match &mut self.store {
+            Some(store) if store.is_empty() || event.id() >= store.len() => {
+                store.append(&event.clone().version(version.clone()))
+            }
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            self.poison_store(err);
+        }
+    }
+
+    /// Removes from the store all events with an id equal to or greater than `len`.
+    ///
+    /// A store ending before `len` is emptied instead, because the next events
+    /// couldn't be appended to it without leaving a gap.
+    pub(crate) fn truncate_store(&mut self, len: EventId) {
+        if self.store_error.is_some() {
+            return;
+        }
+        let result = match &mut self.store {
+            Some(store) if store.len() < len => store.truncate(store.first_id()),
+            Some(store) => store.truncate(len),
+            None => Ok(()),
+        };
+        if let Err(err) = result {
+            self.poison_store(err);
+        }
+    }
+
+    /// Stops updating the store after `err`.
+    fn poison_store(&mut self, err: WeaselErrorType<R>) {
+        error!("{:?}", err);
+        self.store_error = Some(match err {
+            WeaselError::HistoryStoreError(msg) => msg,
+            err => err.to_string(),
+        });
+    }
+
+    /// Removes all events and lets the next one have id `first_id`.
+    ///
+    /// The store is kept, as long as it can be continued from `first_id`.
+    /// Otherwise it's emptied.
+    pub(crate) fn reset(&mut self, first_id: EventId) {
+        let store = self.store.take();
+        let store_error = self.store_error.take();
+        *self = Self::starting_from(first_id);
+        self.store = store;
+        self.store_error = store_error;
+        self.truncate_store(first_id);
+    }
+
+    /// Returns whether the event with the given id, which precedes `first_id()`,
+    /// can be read from the store.
+    pub(crate) fn in_store(&self, id: EventId) -> bool {
+        matches!(&self.store, Some(store) if store.first_id() <= id && store.len() >= self.first_id)
+    }
+
+    /// Returns all events with an id inside `range`, versioned.
+    ///
+    /// Events preceding `first_id()` are read from the store.
+    /// Returns an error if the store failed to save an event.
+    pub(crate) fn read(
+        &self,
+        range: Range<EventId>,
+        version: &Version<R>,
+    ) -> WeaselResult<Vec<VersionedEventWrapper<R>>, R> {
+        if let Some(msg) = &self.store_error {
+            return Err(WeaselError::HistoryStoreError(msg.clone()));
+        }
+        if range.start > range.end || range.end > self.len() {
+            return Err(WeaselError::InvalidEventRange(range, self.len()));
+        }
+        let mut events = Vec::new();
+        if range.start < self.first_id {
+            if self.in_store(range.start) {
+                let end = range.end.min(self.first_id);
+                // `in_store` guarantees that the store is present.
+                events = self.store.as_ref().unwrap().range(range.start..end)?;
+            } else if self.evicted().contains(&range.start) {
+                return Err(WeaselError::EvictedEvents(range, self.first_id));
+            } else {
+                return Err(WeaselError::InvalidEventRange(range, self.len()));
+            }
+        }
+        let start = range.start.max(self.first_id);
+        events.extend(
+            self.slice(start..range.end)
+                .iter()
+                .map(|event| event.clone().version(version.clone())),
+        );
+        Ok(events)
+    }
+
     /// Stores a new event in the history logs.
-    pub(crate) fn archive(&mut self, event: &EventWrapper<R>) {
-        assert_eq!(event.id() as usize, self.events.len());
+    ///
+    /// `turns` and `rounds` are the number of turns and rounds completed
+    /// when the event was applied.
+    pub(crate) fn archive(
+        &mut self,
+        event: &EventWrapper<R>,
+        turns: TurnsCount,
+        rounds: RoundsCount,
+    ) {
+        let id = event.id();
+        assert_eq!(id, self.next_id());
+        index(&mut self.kinds, event.kind(), id);
+        for entity_id in event.entities() {
+            index(&mut self.entities, entity_id, id);
+        }
+        for team_id in event.teams() {
+            index(&mut self.teams, team_id, id);
+        }
+        if let Some(origin) = event.origin() {
+            index(&mut self.origins, origin, id);
+        }
+        self.turns.push(turns);
+        self.rounds.push(rounds);
         self.events.push(event.clone());
     }
 
+    /// Removes all events with an id equal to or greater than `len`, also from the store.
+    pub(crate) fn truncate(&mut self, len: EventId) {
+        self.truncate_events(len);
+        self.truncate_store(len);
+    }
+
+    /// Removes all events with an id equal to or greater than `len`.
+    pub(crate) fn truncate_events(&mut self, len: EventId) {
+        assert!(len >= self.first_id);
+        let stored = (len - self.first_id) as usize;
+        self.events.truncate(stored);
+        self.turns.truncate(stored);
+        self.rounds.truncate(stored);
+        truncate_index(&mut self.kinds, len);
+        truncate_index(&mut self.entities, len);
+        truncate_index(&mut self.teams, len);
+        truncate_index(&mut self.origins, len);
+    }
+
+    /// Removes all events with an id lower than `id` and returns them.
+    ///
+    /// Ids of the remaining events don't change.
+    pub(crate) fn evict(&mut self, id: EventId) -> Vec<EventWrapper<R>> {
+        assert!(id >= self.first_id && id <= self.len());
+        let count = (id - self.first_id) as usize;
+        self.turns.drain(..count);
+        self.rounds.drain(..count);
+        evict_index(&mut self.kinds, id);
+        evict_index(&mut self.entities, id);
+        evict_index(&mut self.teams, id);
+        evict_index(&mut self.origins, id);
+        self.first_id = id;
+        self.events.drain(..count).collect()
+    }
+
     /// Verifies if an event has an id compatible with the current timeline.
     /// Timeline only accepts monotonically increasing ids with no gaps.
+    /// Events can only originate from previous events.
     pub(crate) fn verify_event(&self, event: &EventWrapper<R>) -> WeaselResult<(), R> {
-        if event.id() as usize != self.events.len() {
+        if event.id() != self.next_id() {
             return Err(WeaselError::NonContiguousEventId(
                 event.id(),
-                self.events.len().try_into().unwrap(),
+                self.next_id(),
             ));
         }
-        Ok(())
+        self.verify_origin(event.origin())
+    }
+
+    /// Verifies that `origin` is the id of an event already in the timeline.
+    pub(crate) fn verify_origin(&self, origin: Option<EventId>) -> WeaselResult<(), R> {
+        match origin {
+            Some(origin) if origin >= self.next_id() => {
+                Err(WeaselError::InvalidOrigin(origin, self.next_id()))
+            }
+            _ => Ok(()),
+        }
     }
 
     /// Returns the id for the next event.
     pub(crate) fn next_id(&self) -> EventId {
-        self.events.len().try_into().unwrap()
+        self.len()
     }
 
-    /// Returns the number of events in this history.
+    /// Returns the number of events in this timeline,
+    /// including those preceding `first_id()`.
     pub fn len(&self) -> EventId {
-        self.events.len().try_into().unwrap()
+        let stored: EventId = self.events.len().try_into().unwrap();
+        self.first_id + stored
     }
 
     /// Returns whether this history is empty.
     pub fn is_empty(&self) -> bool {
-        self.events.is_empty()
+        self.len() == 0
+    }
+
+    /// Returns the event with the given id, if it's stored in this history.
+    pub fn event(&self, id: EventId) -> Option<&EventWrapper<R>> {
+        self.position(id).map(|position| &self.events[position])
+    }
+
+    /// Returns the chain of events from which the event with the given id originated,
+    /// starting from its direct origin.
+    ///
+    /// The chain stops at the first origin not stored in this history, or at the first
+    /// event already in the chain.
+    pub fn ancestors(&self, id: EventId) -> Vec<&EventWrapper<R>> {
+        let mut ancestors = Vec::new();
+        let mut visited = HashSet::new();
+        visited.insert(id);
+        let mut origin = self.event(id).and_then(|event| event.origin());
+        while let Some(event) = origin.and_then(|id| self.event(id)) {
+            if !visited.insert(event.id()) {
+                break;
+            }
+            ancestors.push(event);
+            origin = event.origin();
+        }
+        ancestors
+    }
+
+    /// Returns all events originated, directly or indirectly, from the event
+    /// with the given id, ordered by id.
+    pub fn descendants(&self, id: EventId) -> Vec<&EventWrapper<R>> {
+        let mut descendants = Vec::new();
+        let mut visited = HashSet::new();
+        visited.insert(id);
+        let mut causes = vec![id];
+        while let Some(cause) = causes.pop() {
+            if let Some(ids) = self.origins.get(&cause) {
+                for &id in ids {
+                    // Skip events already found, in case origins form a cycle.
+                    if visited.insert(id) {
+                        causes.push(id);
+                        descendants.push(id);
+                    }
+                }
+            }
+        }
+        descendants.sort_unstable();
+        descendants
+            .into_iter()
+            .filter_map(|id| self.event(id))
+            .collect()
+    }
+
+    /// Returns the number of turns completed when the event with the given id was applied,
+    /// if the event is stored in this history.
+    ///
+    /// This is the index of the turn to which the event belongs.
+    pub fn turn(&self, id: EventId) -> Option<TurnsCount> {
+        self.position(id).map(|position| self.turns[position])
+    }
+
+    /// Returns the number of rounds completed when the event with the given id was applied,
+    /// if the event is stored in this history.
+    ///
+    /// This is the index of the round to which the event belongs.
+    pub fn round(&self, id: EventId) -> Option<RoundsCount> {
+        self.position(id).map(|position| self.rounds[position])
+    }
+
+    /// Returns the events belonging to the turn with index `turn`, that is those
+    /// applied when exactly `turn` turns were completed.
+    ///
+    /// Turns are numbered from zero. A turn includes the events coming after the end
+    /// of the previous turn, up to and including its own `EndTurn`.
+    ///
+    /// # Examples
+    /// ```
+    /// use weasel::{
+    ///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateCreature,
+    ///     CreateTeam, EndTurn, EntityId, EventKind, EventTrigger, Server, StartTurn,
+    /// };
+    ///
+    /// battle_rules! {}
+    ///
+    /// let battle = Battle::builder(CustomRules::new()).build();
+    /// let mut server = Server::builder(battle).build();
+    /// CreateTeam::trigger(&mut server, 1).fire().unwrap();
+    /// CreateCreature::trigger(&mut server, 1, 1, ()).fire().unwrap();
+    /// for _ in 0..2 {
+    ///     StartTurn::trigger(&mut server, EntityId::Creature(1)).fire().unwrap();
+    ///     EndTurn::trigger(&mut server).fire().unwrap();
+    /// }
+    ///
+    /// let turn = server.battle().history().events_in_turn(1);
+    /// assert_eq!(turn.len(), 2);
+    /// assert_eq!(turn[0].kind(), EventKind::StartTurn);
+    /// assert_eq!(turn[1].kind(), EventKind::EndTurn);
+    /// ```
+    pub fn events_in_turn(&self, turn: TurnsCount) -> &[EventWrapper<R>] {
+        self.slice(self.count_range(&self.turns, &turn))
+    }
+
+    /// Returns the events belonging to the round with index `round`, that is those
+    /// applied when exactly `round` rounds were completed.
+    ///
+    /// Rounds are numbered from zero. A round includes the events coming after the end
+    /// of the previous round, up to and including its own `EndRound`.
+    pub fn events_in_round(&self, round: RoundsCount) -> &[EventWrapper<R>] {
+        self.slice(self.count_range(&self.rounds, &round))
+    }
+
+    /// Returns a query to find the events in this history matching a set of filters.
+    ///
+    /// # Examples
+    /// ```
+    /// use weasel::{
+    ///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateCreature,
+    ///     CreateTeam, EntityId, EventKind, EventTrigger, Server,
+    /// };
+    ///
+    /// battle_rules! {}
+    ///
+    /// let battle = Battle::builder(CustomRules::new()).build();
+    /// let mut server = Server::builder(battle).build();
+    /// CreateTeam::trigger(&mut server, 1).fire().unwrap();
+    /// CreateCreature::trigger(&mut server, 1, 1, ()).fire().unwrap();
+    /// CreateCreature::trigger(&mut server, 2, 1, ()).fire().unwrap();
+    ///
+    /// let history = server.battle().history();
+    /// assert_eq!(history.query().kind(EventKind::CreateCreature).count(), 2);
+    /// assert_eq!(history.query().team(1).count(), 3);
+    /// let event = history.query().entity(EntityId::Creature(2)).last().unwrap();
+    /// assert_eq!(event.id(), 2);
+    /// ```
+    pub fn query(&self) -> HistoryQuery<'_, R> {
+        HistoryQuery {
+            history: self,
+            kind: None,
+            entity: None,
+            team: None,
+            origin: None,
+            turn: None,
+            round: None,
+        }
+    }
+
+    /// Returns the position in `events` of the event with the given id.
+    fn position(&self, id: EventId) -> Option<usize> {
+        if id >= self.first_id && id < self.len() {
+            Some((id - self.first_id) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the stored events with an id inside `ids`.
+    fn slice(&self, ids: Range<EventId>) -> &[EventWrapper<R>] {
+        &self.events[(ids.start - self.first_id) as usize..(ids.end - self.first_id) as usize]
+    }
+
+    /// Returns the range of ids of the events applied while `count` had the given value.
+    ///
+    /// `counts` contains a non decreasing count for each stored event.
+    fn count_range<T: Ord>(&self, counts: &[T], count: &T) -> Range<EventId> {
+        let start = counts.partition_point(|c| c < count) as EventId;
+        let end = counts.partition_point(|c| c <= count) as EventId;
+        self.first_id + start..self.first_id + end
+    }
+
+    /// Returns the causality graph of the event with the given id, made of
+    /// its ancestors, the event itself and its descendants.
+    ///
+    /// The graph is empty if the event is not stored in this history.
+    pub fn causality(&self, id: EventId) -> CausalityGraph {
+        let mut events = self.ancestors(id);
+        events.reverse();
+        events.extend(self.event(id));
+        events.extend(self.descendants(id));
+        CausalityGraph::new(events)
+    }
+
+    /// Returns the causality graph of all events stored in this history.
+    pub fn causality_graph(&self) -> CausalityGraph {
+        CausalityGraph::new(self.events.iter().collect())
+    }
+}
+
+/// Adds the event with id `id` to the events indexed under `key`.
+fn index<K: Hash + Eq>(index: &mut HashMap<K, Vec<EventId>>, key: K, id: EventId) {
+    let ids = index.entry(key).or_default();
+    // An event might list the same key more than once.
+    if ids.last() != Some(&id) {
+        ids.push(id);
+    }
+}
+
+/// Removes all events with an id equal to or greater than `len` from `index`.
+fn truncate_index<K: Hash + Eq>(index: &mut HashMap<K, Vec<EventId>>, len: EventId) {
+    for ids in index.values_mut() {
+        ids.truncate(ids.partition_point(|id| *id < len));
+    }
+    index.retain(|_, ids| !ids.is_empty());
+}
+
+/// Removes all events with an id lower than `id` from `index`.
+fn evict_index<K: Hash + Eq>(index: &mut HashMap<K, Vec<EventId>>, id: EventId) {
+    for ids in index.values_mut() {
+        ids.drain(..ids.partition_point(|other| *other < id));
+    }
+    index.retain(|_, ids| !ids.is_empty());
+}
+
+/// A query to find the events in a `History` matching all the given filters.
+///
+/// Events are looked up through the history's indexes, thus queries stay fast
+/// even on long histories.
+pub struct HistoryQuery<'a, R: BattleRules> {
+    history: &'a History<R>,
+    kind: Option<EventKind>,
+    entity: Option<EntityId<R>>,
+    team: Option<TeamId<R>>,
+    origin: Option<EventId>,
+    turn: Option<TurnsCount>,
+    round: Option<RoundsCount>,
+}
+
+impl<'a, R: BattleRules> HistoryQuery<'a, R> {
+    /// Keeps only the events of the given kind.
+    pub fn kind(mut self, kind: EventKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Keeps only the events involving the entity with the given id.
+    ///
+    /// Involved entities are those returned by `Event::entities`.
+    pub fn entity(mut self, entity_id: EntityId<R>) -> Self {
+        self.entity = Some(entity_id);
+        self
+    }
+
+    /// Keeps only the events involving the team with the given id.
+    ///
+    /// Involved teams are those returned by `Event::teams`.
+    pub fn team(mut self, team_id: TeamId<R>) -> Self {
+        self.team = Some(team_id);
+        self
+    }
+
+    /// Keeps only the events directly originated from the event with the given id.
+    pub fn origin(mut self, origin: EventId) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Keeps only the events applied when exactly `turn` turns were completed.
+    pub fn turn(mut self, turn: TurnsCount) -> Self {
+        self.turn = Some(turn);
+        self
+    }
+
+    /// Keeps only the events applied when exactly `round` rounds were completed.
+    pub fn round(mut self, round: RoundsCount) -> Self {
+        self.round = Some(round);
+        self
+    }
+
+    /// Returns all matching events, ordered by id.
+    pub fn events(&self) -> Vec<&'a EventWrapper<R>> {
+        let history = self.history;
+        self.ids()
+            .into_iter()
+            .filter_map(|id| history.event(id))
+            .collect()
+    }
+
+    /// Returns the matching event with the lowest id.
+    pub fn first(&self) -> Option<&'a EventWrapper<R>> {
+        self.ids().first().and_then(|id| self.history.event(*id))
+    }
+
+    /// Returns the matching event with the highest id.
+    pub fn last(&self) -> Option<&'a EventWrapper<R>> {
+        self.ids().last().and_then(|id| self.history.event(*id))
+    }
+
+    /// Returns the number of matching events.
+    pub fn count(&self) -> usize {
+        self.ids().len()
+    }
+
+    /// Returns the ids of all matching events, ordered by id.
+    fn ids(&self) -> Vec<EventId> {
+        let history = self.history;
+        // Restrict the search to the events of the requested turn and round.
+        let mut range = history.first_id..history.len();
+        if let Some(turn) = &self.turn {
+            range = intersect(range, history.count_range(&history.turns, turn));
+        }
+        if let Some(round) = &self.round {
+            range = intersect(range, history.count_range(&history.rounds, round));
+        }
+        // Gather the lists of indexed events for all other filters.
+        let mut lists: Vec<&[EventId]> = Vec::new();
+        if let Some(kind) = &self.kind {
+            lists.push(lookup(&history.kinds, kind));
+        }
+        if let Some(entity_id) = &self.entity {
+            lists.push(lookup(&history.entities, entity_id));
+        }
+        if let Some(team_id) = &self.team {
+            lists.push(lookup(&history.teams, team_id));
+        }
+        if let Some(origin) = &self.origin {
+            lists.push(lookup(&history.origins, origin));
+        }
+        // Iterate over the shortest list and check that each id belongs to the others.
+        lists.sort_by_key(|list| list.len());
+        match lists.split_first() {
+            Some((shortest, others)) => {
+                let start = shortest.partition_point(|id| *id < range.start);
+                let end = shortest.partition_point(|id| *id < range.end);
+                shortest[start..end]
+                    .iter()
+                    .filter(|id| others.iter().all(|list| list.binary_search(id).is_ok()))
+                    .copied()
+                    .collect()
+            }
+            None => range.collect(),
+        }
     }
 }
 
+/// Returns the events indexed under `key`.
+fn lookup<'a, K: Hash + Eq>(index: &'a HashMap<K, Vec<EventId>>, key: &K) -> &'a [EventId] {
+    index.get(key).map_or(&[], |ids| ids.as_slice())
+}
+
+/// Returns the intersection of two ranges.
+fn intersect(a: Range<EventId>, b: Range<EventId>) -> Range<EventId> {
+    let start = a.start.max(b.start);
+    start..a.end.min(b.end).max(start)
+}
+
+/// An event inside a `CausalityGraph`.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct CausalityNode {
+    /// Id of the event.
+    pub id: EventId,
+    /// Id of the event from which this one originated, if any.
+    pub origin: Option<EventId>,
+    /// Kind of the event.
+    pub kind: EventKind,
+    /// Debug description of the event.
+    pub description: String,
+}
+
+/// A graph of events, linked to the events from which they originated.
+///
+/// It can be exported to Graphviz DOT with `to_dot`. With the `serialization` feature
+/// it can be serialized in any serde format, such as json.
+///
+/// # Examples
+/// ```
+/// use weasel::{
+///     battle_rules, event::DummyEvent, event::Originated, rules::empty::*, Battle,
+///     BattleController, BattleRules, EventTrigger, Server,
+/// };
+///
+/// battle_rules! {}
+///
+/// let battle = Battle::builder(CustomRules::new()).build();
+/// let mut server = Server::builder(battle).build();
+/// DummyEvent::trigger(&mut server).fire().unwrap();
+/// Originated::new(DummyEvent::trigger(&mut server), 0)
+///     .fire()
+///     .unwrap();
+///
+/// let graph = server.battle().history().causality(1);
+/// assert_eq!(graph.nodes().len(), 2);
+/// assert!(graph.to_dot().contains("0 -> 1;"));
+/// ```
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct CausalityGraph {
+    nodes: Vec<CausalityNode>,
+}
+
+impl CausalityGraph {
+    /// Creates a graph with the given events, ordered by id.
+    fn new<R: BattleRules>(events: Vec<&EventWrapper<R>>) -> Self {
+        Self {
+            nodes: events
+                .into_iter()
+                .map(|event| CausalityNode {
+                    id: event.id(),
+                    origin: event.origin(),
+                    kind: event.kind(),
+                    description: format!("{:?}", event.event()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns all events in this graph, ordered by id.
+    pub fn nodes(&self) -> &[CausalityNode] {
+        &self.nodes
+    }
+
+    /// Returns all links in this graph, as pairs of (origin, derived event) ids.
+    ///
+    /// Origins not inside the graph are omitted.
+    pub fn edges(&self) -> impl Iterator<Item = (EventId, EventId)> + '_ {
+        self.nodes.iter().filter_map(move |node| match node.origin {
+            Some(origin) if self.contains(origin) => Some((origin, node.id)),
+            _ => None,
+        })
+    }
+
+    /// Returns whether the event with the given id is in this graph.
+    pub fn contains(&self, id: EventId) -> bool {
+        self.nodes.binary_search_by_key(&id, |node| node.id).is_ok()
+    }
+
+    /// Exports this graph in the Graphviz DOT language.
+    ///
+    /// Each node is labelled with the event's id and kind, while its full description
+    /// is shown as tooltip.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph causality {\n");
+        for node in &self.nodes {
+            writeln!(
+                dot,
+                "    {} [label=\"{}: {:?}\", tooltip=\"{}\"];",
+                node.id,
+                node.id,
+                node.kind,
+                escape(&node.description)
+            )
+            .unwrap();
+        }
+        for (origin, id) in self.edges() {
+            writeln!(dot, "    {} -> {};", origin, id).unwrap();
+        }
+        dot.push('}');
+        dot
+    }
+}
+
+/// Escapes a string to be used inside a quoted DOT identifier.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,7 +797,7 @@ mod tests {
         let mut try_archive = |id| -> WeaselResult<(), _> {
             let event = EventWrapper::new(id, None, DummyEvent::trigger(&mut ()).event());
             history.verify_event(&event)?;
-            history.archive(&event);
+            history.archive(&event, 0, 0);
             Ok(())
         };
         assert!(try_archive(3).is_err());
@@ -80,4 +807,20 @@ mod tests {
         assert!(try_archive(1).is_err());
         assert!(try_archive(0).is_err());
     }
+
+    #[test]
+    fn starting_from() {
+        battle_rules! {}
+        let mut history = History::<CustomRules>::starting_from(5);
+        assert_eq!(history.len(), 5);
+        assert!(history.events().is_empty());
+        let event = EventWrapper::new(0, None, DummyEvent::trigger(&mut ()).event());
+        assert!(history.verify_event(&event).is_err());
+        let event = EventWrapper::new(5, None, DummyEvent::trigger(&mut ()).event());
+        assert!(history.verify_event(&event).is_ok());
+        history.archive(&event, 0, 0);
+        assert_eq!(history.len(), 6);
+        assert_eq!(history.first_id(), 5);
+        assert_eq!(history.events().len(), 1);
+    }
 }
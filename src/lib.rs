@@ -56,9 +56,16 @@
 //! - Player managed teams.
 //! - Team objectives and diplomacy.
 //! - Division of the battle into turns and rounds.
+//! - Events scheduled to happen in future turns or rounds.
 //! - Rules to govern the game subdivided into orthogonal traits.
 //! - Fully serializable battle history.
+//! - Snapshots of the battle state.
 //! - Cause-effect relationship between events.
+//! - Atomic transactions of events.
+//! - Simulation of the outcome of events.
+//! - AI controllers to play teams automatically, including a Monte Carlo tree search.
+//! - Batch simulations of AI played battles, for balance testing.
+//! - Step by step replays of saved histories.
 //! - Server side verification of clients' events.
 //! - Player permissions and authorization.
 //! - Versioning for battle rules.
@@ -119,22 +126,40 @@
 //! The following optional features are available:
 //!
 //! - `random`: enables built-in entropy rules that use a pseudorandom number generator.
-//! - `serialization`: enables serialization and deserialization of events.
+//! - `serialization`: enables serialization and deserialization of events and state checksums.
+//! - `binary`: enables a compact binary encoding of serialized events and sinks to transport
+//!   them over any byte stream. Implies `serialization`.
+//! - `replay`: enables loading json or binary histories into a `Replay` and the `weasel-replay`
+//!   binary, an interactive viewer for saved histories. Implies `binary`.
 
 pub mod ability;
 pub use crate::ability::ActivateAbility;
 
+pub mod ai;
+#[cfg(feature = "random")]
+pub use crate::ai::RandomController;
+pub use crate::ai::{AiDriver, AiRules, Controller, GreedyController, LegalAction};
+
 pub mod actor;
 pub use crate::actor::{Action, Actor, ActorRules, AlterAbilities, RegenerateAbilities};
 
+pub mod batch;
+pub use crate::batch::{BatchReport, BatchSimulator, BattleOutcome};
+
 pub mod battle;
 pub use crate::battle::{
     Battle, BattleController, BattleRules, BattleState, EndBattle, EventCallback, Version,
 };
 
+#[cfg(feature = "binary")]
+pub mod binary;
+
 pub mod character;
 pub use crate::character::{AlterStatistics, Character, CharacterRules, RegenerateStatistics};
 
+pub mod checksum;
+pub use crate::checksum::Checksum;
+
 pub mod client;
 pub use crate::client::Client;
 
@@ -152,16 +177,21 @@ pub use crate::error::{WeaselError, WeaselResult};
 
 pub mod event;
 pub use crate::event::{
-    ClientEventPrototype, Event, EventId, EventKind, EventProcessor, EventPrototype, EventQueue,
-    EventReceiver, EventRights, EventServer, EventTrigger, EventWrapper, LinkedQueue,
-    VersionedEventWrapper,
+    ClientEventPrototype, CorrelationId, Event, EventId, EventKind, EventProcessor, EventPrototype,
+    EventQueue, EventReceiver, EventRights, EventServer, EventTrigger, EventWrapper, LinkedQueue,
+    Rejection, VersionedEventWrapper, Visibility,
 };
 
 pub mod fight;
-pub use crate::fight::{ApplyImpact, FightRules};
+pub use crate::fight::{ApplyImpact, FightRules, Reaction};
 
 pub mod history;
-pub use crate::history::History;
+pub use crate::history::{CausalityGraph, History, HistoryQuery};
+
+#[cfg(feature = "random")]
+pub mod mcts;
+#[cfg(feature = "random")]
+pub use crate::mcts::{MctsBuilder, MctsController};
 
 pub mod metric;
 pub use crate::metric::{Metric, MetricId, ReadMetrics, SystemMetricId, WriteMetrics};
@@ -177,15 +207,30 @@ pub use crate::round::{
     EndRound, EndTurn, EnvironmentTurn, ResetRounds, Rounds, RoundsRules, StartTurn,
 };
 
+pub mod replay;
+pub use crate::replay::Replay;
+
+pub mod resync;
+pub use crate::resync::{Resync, ResyncRequest};
+
 pub mod rules;
 
 #[cfg(feature = "serialization")]
 pub mod serde;
 #[cfg(feature = "serialization")]
-pub use crate::serde::{FlatClientEvent, FlatEvent, FlatVersionedEvent};
+pub use crate::serde::{FlatClientEvent, FlatEvent, FlatVersionedEvent, FlatWeaselError};
+
+pub mod schedule;
+pub use crate::schedule::{ScheduleEvent, Scheduler, Timing};
 
 pub mod server;
-pub use crate::server::Server;
+pub use crate::server::{HistoryOffload, Server};
+
+pub mod simulation;
+pub use crate::simulation::{Change, EventSimulator, Simulation};
+
+pub mod snapshot;
+pub use crate::snapshot::BattleSnapshot;
 
 pub mod space;
 pub use crate::space::{AlterSpace, MoveEntity, PositionClaim, ResetSpace, Space, SpaceRules};
@@ -193,12 +238,23 @@ pub use crate::space::{AlterSpace, MoveEntity, PositionClaim, ResetSpace, Space,
 pub mod status;
 pub use crate::status::{AlterStatuses, Application, AppliedStatus, ClearStatus, InflictStatus};
 
+pub mod store;
+#[cfg(feature = "binary")]
+pub use crate::store::FileHistoryStore;
+pub use crate::store::{HistoryStore, MemoryHistoryStore};
+
 pub mod team;
 pub use crate::team::{
     ConcludeObjectives, Conclusion, CreateTeam, EntityAddition, Relation, RemoveTeam,
     ResetObjectives, SetRelations, Team, TeamRules,
 };
 
+pub mod transaction;
+pub use crate::transaction::Transaction;
+
+#[cfg(feature = "binary")]
+pub mod transport;
+
 pub mod user;
 #[cfg(feature = "serialization")]
 pub use crate::user::UserEventPacker;
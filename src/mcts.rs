@@ -0,0 +1,418 @@
+//! Monte Carlo tree search.
+
+use crate::ai::{Controller, LegalAction};
+use crate::battle::{Battle, BattleController, BattlePhase, BattleRules};
+use crate::entity::Entities;
+use crate::entropy::{EntropyModel, SeedGenerator};
+use crate::event::{EventKind, EventProcessor, EventPrototype, EventTrigger};
+use crate::round::{RoundsModel, StartTurn, TurnState};
+use crate::server::Server;
+use crate::space::SpaceModel;
+use crate::team::{Conclusion, TeamId};
+use indexmap::IndexMap;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Lcg64Xsh32;
+use std::time::{Duration, Instant};
+
+/// Function to estimate how favorable a battle is for the team with the given id.
+///
+/// The result should be between 0 (certain defeat) and 1 (certain victory).
+pub type Heuristic<R> = fn(&Battle<R>, &TeamId<R>) -> f64;
+
+/// A candidate action, together with the team performing it.
+type Candidate<R> = (EventPrototype<R>, TeamId<R>);
+
+/// A node in the search tree.
+struct Node<R: BattleRules> {
+    /// The action leading to this node from its parent.
+    action: Option<Candidate<R>>,
+    children: Vec<usize>,
+    /// Actions not yet explored from this node.
+    untried: Vec<Candidate<R>>,
+    visits: u32,
+    /// Sum of the rewards obtained by the team performing `action`.
+    reward: f64,
+}
+
+impl<R: BattleRules> Node<R> {
+    fn new(action: Option<Candidate<R>>, untried: Vec<Candidate<R>>) -> Self {
+        Self {
+            action,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            reward: 0.0,
+        }
+    }
+}
+
+/// A controller that chooses actions with a Monte Carlo tree search.
+///
+/// Each iteration of the search plays a sequence of legal events on a fork of the battle,
+/// followed by random events up to a maximum depth. The outcome is scored with the teams'
+/// `Conclusion` or, if the battle is not over, with a user supplied heuristic.\
+/// All teams are assumed to pick the actions that are best for themselves.
+///
+/// The search works with any rules, as long as they and their models are cloneable.
+/// Actions are enumerated with `Battle::legal_actions`. By default, the entropy of the battle
+/// is rerolled at each iteration, so that the search doesn't rely on random outcomes
+/// it can't know in advance.
+pub struct MctsController<R: BattleRules> {
+    rng: Lcg64Xsh32,
+    iterations: u32,
+    time_limit: Option<Duration>,
+    exploration: f64,
+    max_depth: u32,
+    heuristic: Option<Heuristic<R>>,
+    seed_generator: Option<SeedGenerator<R>>,
+}
+
+impl<R: BattleRules> MctsController<R> {
+    /// Returns a builder to create a controller, whose random choices are
+    /// made with the given seed.
+    ///
+    /// `seed_generator` creates the seeds used to reroll the entropy at each iteration.
+    pub fn builder(seed: u64, seed_generator: SeedGenerator<R>) -> MctsBuilder<R> {
+        MctsBuilder {
+            seed,
+            iterations: 1000,
+            time_limit: None,
+            exploration: std::f64::consts::SQRT_2,
+            max_depth: 100,
+            heuristic: None,
+            seed_generator: Some(seed_generator),
+        }
+    }
+}
+
+impl<R> MctsController<R>
+where
+    R: BattleRules + Clone + 'static,
+    R::SR: Clone,
+    R::RR: Clone,
+    R::ER: Clone,
+    Entities<R>: Clone,
+    SpaceModel<R>: Clone,
+    RoundsModel<R>: Clone,
+    EntropyModel<R>: Clone,
+{
+    /// Returns the best action for the team with id `team_id` in `battle`.
+    ///
+    /// The candidates are the actions of the team's actors, or the start of a turn for
+    /// one of them when no turn is in progress.\
+    /// Returns `None` if the team can't do anything.
+    pub fn search(&mut self, battle: &Battle<R>, team_id: &TeamId<R>) -> Option<EventPrototype<R>> {
+        let candidates = candidates(battle)
+            .into_iter()
+            .filter(|(_, candidate_team)| candidate_team == team_id)
+            .collect();
+        self.search_among(battle, team_id, candidates)
+    }
+
+    /// Runs the search, considering only `candidates` as first action.
+    fn search_among(
+        &mut self,
+        battle: &Battle<R>,
+        team_id: &TeamId<R>,
+        candidates: Vec<Candidate<R>>,
+    ) -> Option<EventPrototype<R>> {
+        if candidates.len() <= 1 {
+            return candidates
+                .into_iter()
+                .next()
+                .map(|(prototype, _)| prototype);
+        }
+        let start = Instant::now();
+        let mut tree = vec![Node::new(None, candidates)];
+        for _ in 0..self.iterations {
+            if let Some(limit) = self.time_limit {
+                if start.elapsed() >= limit {
+                    break;
+                }
+            }
+            let mut server = Server::builder(self.scratch(battle)).build();
+            let path = self.descend(&mut tree, &mut server, team_id);
+            self.rollout(&mut server, team_id);
+            // Propagate the outcome to all nodes in the path.
+            let mut rewards = IndexMap::new();
+            for index in path {
+                let node = &mut tree[index];
+                node.visits += 1;
+                if let Some((_, node_team)) = &node.action {
+                    let reward = *rewards
+                        .entry(node_team.clone())
+                        .or_insert_with(|| self.evaluate(server.battle(), node_team));
+                    node.reward += reward;
+                }
+            }
+        }
+        // Choose the most visited action. Among actions with the same visits, the first one.
+        let mut best: Option<&Node<R>> = None;
+        for &child in &tree[0].children {
+            let child = &tree[child];
+            match best {
+                Some(best) if best.visits >= child.visits => {}
+                _ => best = Some(child),
+            }
+        }
+        best.and_then(|node| node.action.as_ref())
+            .map(|(prototype, _)| prototype.clone())
+    }
+
+    /// Creates a fork of `battle` to play an iteration of the search, with new entropy.
+    fn scratch(&mut self, battle: &Battle<R>) -> Battle<R> {
+        let mut fork = battle.fork();
+        if let Some(seed_generator) = self.seed_generator {
+            let seed = seed_generator(self.rng.gen());
+            fork.entropy.regenerate_model(&Some(seed));
+        }
+        fork
+    }
+
+    /// Walks down the tree from the root, applying each node's action to the battle owned
+    /// by `server`, until a new node is added or a leaf is reached.
+    ///
+    /// Returns the indices of the visited nodes.
+    fn descend(
+        &mut self,
+        tree: &mut Vec<Node<R>>,
+        server: &mut Server<R>,
+        team_id: &TeamId<R>,
+    ) -> Vec<usize> {
+        let mut node = 0;
+        let mut path = vec![node];
+        loop {
+            // Expand the node with one of the unexplored actions.
+            if !tree[node].untried.is_empty() {
+                let index = self.rng.gen_range(0, tree[node].untried.len());
+                let candidate = tree[node].untried.swap_remove(index);
+                // Actions might be invalid when the entropy is different.
+                // Keep them for the next iterations, which play with a new entropy.
+                if server.process(candidate.0.clone()).is_err() {
+                    let untried = &mut tree[node].untried;
+                    untried.push(candidate);
+                    let last = untried.len() - 1;
+                    untried.swap(index, last);
+                    return path;
+                }
+                let untried = if is_over(server.battle(), team_id) {
+                    Vec::new()
+                } else {
+                    candidates(server.battle())
+                };
+                let child = tree.len();
+                tree.push(Node::new(Some(candidate), untried));
+                tree[node].children.push(child);
+                path.push(child);
+                return path;
+            }
+            // Otherwise continue with the most promising child.
+            let child = match self.select(tree, node) {
+                Some(child) => child,
+                None => return path,
+            };
+            let prototype = tree[child]
+                .action
+                .as_ref()
+                .map(|(prototype, _)| prototype.clone())
+                .expect("constraint violated: child node without action");
+            if server.process(prototype).is_err() {
+                return path;
+            }
+            path.push(child);
+            node = child;
+        }
+    }
+
+    /// Returns the child of `node` with the highest upper confidence bound.
+    fn select(&self, tree: &[Node<R>], node: usize) -> Option<usize> {
+        let parent_visits = f64::from(tree[node].visits.max(1));
+        let mut best: Option<(usize, f64)> = None;
+        for &child in &tree[node].children {
+            let visits = f64::from(tree[child].visits.max(1));
+            let bound = tree[child].reward / visits
+                + self.exploration * (parent_visits.ln() / visits).sqrt();
+            match best {
+                Some((_, best_bound)) if best_bound >= bound => {}
+                _ => best = Some((child, bound)),
+            }
+        }
+        best.map(|(child, _)| child)
+    }
+
+    /// Plays random actions until the battle is over or the maximum depth is reached.
+    fn rollout(&mut self, server: &mut Server<R>, team_id: &TeamId<R>) {
+        for _ in 0..self.max_depth {
+            if is_over(server.battle(), team_id) {
+                return;
+            }
+            let mut candidates = candidates(server.battle());
+            if candidates.is_empty() {
+                return;
+            }
+            let index = self.rng.gen_range(0, candidates.len());
+            let (prototype, _) = candidates.swap_remove(index);
+            if server.process(prototype).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Scores `battle` from the point of view of the team with id `team_id`.
+    fn evaluate(&self, battle: &Battle<R>, team_id: &TeamId<R>) -> f64 {
+        match battle
+            .entities()
+            .team(team_id)
+            .and_then(|team| team.conclusion())
+        {
+            Some(Conclusion::Victory) => 1.0,
+            Some(Conclusion::Defeat) => 0.0,
+            None => match self.heuristic {
+                Some(heuristic) => heuristic(battle, team_id).clamp(0.0, 1.0),
+                None => 0.5,
+            },
+        }
+    }
+}
+
+impl<R> Controller<R> for MctsController<R>
+where
+    R: BattleRules + Clone + 'static,
+    R::SR: Clone,
+    R::RR: Clone,
+    R::ER: Clone,
+    Entities<R>: Clone,
+    SpaceModel<R>: Clone,
+    RoundsModel<R>: Clone,
+    EntropyModel<R>: Clone,
+{
+    fn play(
+        &mut self,
+        battle: &Battle<R>,
+        team_id: &TeamId<R>,
+        actions: &[LegalAction<R>],
+    ) -> Vec<EventPrototype<R>> {
+        let candidates = actions
+            .iter()
+            .map(|action| (action.prototype().clone(), team_id.clone()))
+            .collect();
+        self.search_among(battle, team_id, candidates)
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Returns true if the battle ended or the team with id `team_id` reached a conclusion.
+fn is_over<R: BattleRules + 'static>(battle: &Battle<R>, team_id: &TeamId<R>) -> bool {
+    battle.phase() == BattlePhase::Ended
+        || battle
+            .entities()
+            .team(team_id)
+            .and_then(|team| team.conclusion())
+            .is_some()
+}
+
+/// Returns all actions that can be performed in `battle`, together with the team
+/// performing them.
+fn candidates<R: BattleRules + 'static>(battle: &Battle<R>) -> Vec<Candidate<R>> {
+    let mut candidates = Vec::new();
+    match battle.rounds().state() {
+        TurnState::Started(actors) => {
+            // All actors share the same end of turn.
+            let mut end_turn = false;
+            for actor_id in actors {
+                let team_id = match battle.entities().actor(actor_id) {
+                    Some(actor) => actor.team_id().clone(),
+                    None => continue,
+                };
+                for action in battle.legal_actions(actor_id) {
+                    if action.kind() == EventKind::EndTurn {
+                        if end_turn {
+                            continue;
+                        }
+                        end_turn = true;
+                    }
+                    candidates.push((action, team_id.clone()));
+                }
+            }
+        }
+        TurnState::Ready => {
+            for actor in battle.entities().actors() {
+                let prototype = StartTurn::trigger(&mut (), actor.entity_id().clone()).prototype();
+                if battle.verify_prototype(&prototype).is_ok() {
+                    candidates.push((prototype, actor.team_id().clone()));
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// A builder object to create a `MctsController`.
+pub struct MctsBuilder<R: BattleRules> {
+    seed: u64,
+    iterations: u32,
+    time_limit: Option<Duration>,
+    exploration: f64,
+    max_depth: u32,
+    heuristic: Option<Heuristic<R>>,
+    seed_generator: Option<SeedGenerator<R>>,
+}
+
+impl<R: BattleRules> MctsBuilder<R> {
+    /// Sets the maximum number of iterations of each search. The default is 1000.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Stops each search after `time_limit`, even if not all iterations are done.
+    pub fn time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    /// Sets the exploration constant of the search. Higher values favor less visited actions.
+    /// The default is the square root of two.
+    pub fn exploration(mut self, exploration: f64) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    /// Sets the maximum number of random events played at the end of each iteration.
+    /// The default is 100.
+    pub fn max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Scores battles that are not over with `heuristic`.
+    ///
+    /// Without a heuristic, battles that are not over are scored as a draw.
+    pub fn heuristic(mut self, heuristic: Heuristic<R>) -> Self {
+        self.heuristic = Some(heuristic);
+        self
+    }
+
+    /// Keeps the entropy of the battle in all iterations, instead of rerolling it.
+    ///
+    /// All iterations will see the same random outcomes. Useful when the entropy is
+    /// deterministic or known to the players.
+    pub fn fixed_entropy(mut self) -> Self {
+        self.seed_generator = None;
+        self
+    }
+
+    /// Creates a new controller.
+    pub fn build(self) -> MctsController<R> {
+        MctsController {
+            rng: Lcg64Xsh32::seed_from_u64(self.seed),
+            iterations: self.iterations,
+            time_limit: self.time_limit,
+            exploration: self.exploration,
+            max_depth: self.max_depth,
+            heuristic: self.heuristic,
+            seed_generator: self.seed_generator,
+        }
+    }
+}
@@ -3,14 +3,35 @@
 use crate::battle::BattleRules;
 use crate::error::{WeaselError, WeaselResult};
 use crate::user::{UserMetricId, UserRules};
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::hash::Hash;
 
 /// Manages all metrics in a battle.
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub(crate) struct Metrics<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "MetricIdType<R>: Serialize",
+                deserialize = "MetricIdType<R>: Deserialize<'de>"
+            )
+        )
+    )]
     map: HashMap<MetricIdType<R>, Metric>,
 }
 
+impl<R: BattleRules> Clone for Metrics<R> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
 impl<R: BattleRules> Metrics<R> {
     pub(crate) fn new() -> Self {
         Self {
@@ -19,12 +40,12 @@ impl<R: BattleRules> Metrics<R> {
     }
 
     /// Returns a handle to read metrics.
-    pub(crate) fn read_handle(&self) -> ReadMetrics<R> {
+    pub(crate) fn read_handle(&self) -> ReadMetrics<'_, R> {
         ReadMetrics { metrics: self }
     }
 
     /// Returns a handle to write metrics.
-    pub(crate) fn write_handle(&mut self) -> WriteMetrics<R> {
+    pub(crate) fn write_handle(&mut self) -> WriteMetrics<'_, R> {
         WriteMetrics { metrics: self }
     }
 }
@@ -37,6 +58,7 @@ pub type MetricIdType<R> = MetricId<<<R as BattleRules>::UR as UserRules<R>>::Us
 
 /// An id to uniquely identify metrics.
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub enum MetricId<T> {
     /// System metric.
     System(SystemMetricId),
@@ -46,6 +68,7 @@ pub enum MetricId<T> {
 
 /// A metric is a compact measurement of some quantity.
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub enum Metric {
     /// A 64 bit unsigned counter.
     CounterU64(u64),
@@ -130,6 +153,14 @@ impl<'a, R: BattleRules> ReadMetrics<'a, R> {
     pub fn user_f64(&self, id: UserMetricId<R>) -> Option<f64> {
         get_metric!(self.metrics.map, id, User, CounterF64)
     }
+
+    /// Returns an iterator over all user counters, in no particular order.
+    pub fn user_metrics(&self) -> impl Iterator<Item = (&UserMetricId<R>, Metric)> {
+        self.metrics.map.iter().filter_map(|(id, metric)| match id {
+            MetricId::User(id) => Some((id, *metric)),
+            MetricId::System(_) => None,
+        })
+    }
 }
 
 /// Handle to write metrics.
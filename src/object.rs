@@ -31,13 +31,66 @@ type Statuses<R> =
 /// Objects possess a position and a set of statistics, but they can't start a turn
 /// nor activate abilities. They can be target of status effects.\
 /// Objects aren't part of any team.
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct Object<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "EntityId<R>: Serialize",
+            deserialize = "EntityId<R>: Deserialize<'de>"
+        ))
+    )]
     id: EntityId<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "Position<R>: Serialize",
+            deserialize = "Position<R>: Deserialize<'de>"
+        ))
+    )]
     position: Position<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "Statistic<R>: Serialize",
+                deserialize = "Statistic<R>: Deserialize<'de>"
+            )
+        )
+    )]
     statistics: Statistics<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            with = "crate::serde::map_as_pairs",
+            bound(
+                serialize = "AppliedStatus<R>: Serialize",
+                deserialize = "AppliedStatus<R>: Deserialize<'de>"
+            )
+        )
+    )]
     statuses: Statuses<R>,
 }
 
+impl<R: BattleRules> Clone for Object<R>
+where
+    Statistic<R>: Clone,
+    AppliedStatus<R>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            position: self.position.clone(),
+            statistics: self.statistics.clone(),
+            statuses: self.statuses.clone(),
+        }
+    }
+}
+
 impl<R: BattleRules> Id for Object<R> {
     type Id = ObjectId<R>;
 
@@ -283,6 +336,10 @@ impl<R: BattleRules + 'static> Event<R> for CreateObject<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![EntityId::Object(self.id.clone())]
+    }
 }
 
 /// Trigger to build and fire a `CreateObject` event.
@@ -373,7 +430,7 @@ impl<R: BattleRules> RemoveObject<R> {
     pub fn trigger<P: EventProcessor<R>>(
         processor: &mut P,
         id: ObjectId<R>,
-    ) -> RemoveObjectTrigger<R, P> {
+    ) -> RemoveObjectTrigger<'_, R, P> {
         RemoveObjectTrigger { processor, id }
     }
 
@@ -441,6 +498,10 @@ impl<R: BattleRules + 'static> Event<R> for RemoveObject<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![EntityId::Object(self.id.clone())]
+    }
 }
 
 /// Trigger to build and fire a `RemoveObject` event.
@@ -3,6 +3,8 @@
 use crate::battle::BattleRules;
 use crate::error::{WeaselError, WeaselResult};
 use crate::team::TeamId;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
 
 /// Type to uniquely identify players.
 ///
@@ -13,11 +15,30 @@ use crate::team::TeamId;
 /// the server itself when an new event is received from a secure socket.
 pub type PlayerId = u64;
 
+/// A list of rights, each one allowing a player to control a team.
+pub(crate) type RightsList<R> = Vec<(PlayerId, TeamId<R>)>;
+
 /// Manages players' rights to initiate events on behalf of a given team.
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub(crate) struct Rights<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "TeamId<R>: Serialize",
+            deserialize = "TeamId<R>: Deserialize<'de>"
+        ))
+    )]
     data: Vec<(PlayerId, Vec<TeamId<R>>)>,
 }
 
+impl<R: BattleRules> Clone for Rights<R> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+        }
+    }
+}
+
 impl<R: BattleRules> Rights<R> {
     pub(crate) fn new() -> Self {
         Self { data: Vec::new() }
@@ -50,6 +71,44 @@ impl<R: BattleRules> Rights<R> {
         self.cleanup_players();
     }
 
+    /// Keeps only the rights towards teams for which `f` returns true.
+    ///
+    /// Returns the removed rights.
+    pub(crate) fn retain_teams<F>(&mut self, mut f: F) -> RightsList<R>
+    where
+        F: FnMut(&TeamId<R>) -> bool,
+    {
+        let mut removed = Vec::new();
+        for (player, rights) in &mut self.data {
+            rights.retain(|team| {
+                let keep = f(team);
+                if !keep {
+                    removed.push((*player, team.clone()));
+                }
+                keep
+            });
+        }
+        self.cleanup_players();
+        removed
+    }
+
+    /// Gives back the `removed` rights towards teams for which `f` returns true.
+    ///
+    /// Restored rights are taken out of `removed`.
+    pub(crate) fn restore_teams<F>(&mut self, removed: &mut RightsList<R>, mut f: F)
+    where
+        F: FnMut(&TeamId<R>) -> bool,
+    {
+        removed.retain(|(player, team)| {
+            if f(team) {
+                self.add(*player, team);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
     /// Removes all stored rights.
     fn clear(&mut self) {
         self.data.clear();
@@ -201,22 +260,22 @@ mod tests {
         // Add rights for team 1 to player 2.
         rights.add(PLAYER_2_ID, &TEAM_1_ID);
         assert_eq!(rights.data.len(), 2);
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_1_ID), true);
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_2_ID), true);
-        assert_eq!(rights.check(PLAYER_2_ID, &TEAM_1_ID), true);
-        assert_eq!(rights.check(PLAYER_2_ID, &TEAM_2_ID), false);
+        assert!(rights.check(PLAYER_1_ID, &TEAM_1_ID));
+        assert!(rights.check(PLAYER_1_ID, &TEAM_2_ID));
+        assert!(rights.check(PLAYER_2_ID, &TEAM_1_ID));
+        assert!(!rights.check(PLAYER_2_ID, &TEAM_2_ID));
         // Remove rights for team 2 to player 1.
         rights.remove(PLAYER_1_ID, &TEAM_2_ID);
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_2_ID), false);
+        assert!(!rights.check(PLAYER_1_ID, &TEAM_2_ID));
         // Remove rights for team 1 to player 2.
         rights.remove(PLAYER_2_ID, &TEAM_1_ID);
-        assert_eq!(rights.check(PLAYER_2_ID, &TEAM_1_ID), false);
+        assert!(!rights.check(PLAYER_2_ID, &TEAM_1_ID));
         assert_eq!(rights.data.len(), 1);
         // Clear.
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_1_ID), true);
+        assert!(rights.check(PLAYER_1_ID, &TEAM_1_ID));
         rights.clear();
         assert_eq!(rights.data.len(), 0);
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_1_ID), false);
+        assert!(!rights.check(PLAYER_1_ID, &TEAM_1_ID));
     }
 
     #[test]
@@ -225,16 +284,16 @@ mod tests {
         // Add rights for team 1 to player 1 and player 2.
         rights.add(PLAYER_1_ID, &TEAM_1_ID);
         rights.add(PLAYER_2_ID, &TEAM_1_ID);
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_1_ID), true);
-        assert_eq!(rights.check(PLAYER_2_ID, &TEAM_1_ID), true);
+        assert!(rights.check(PLAYER_1_ID, &TEAM_1_ID));
+        assert!(rights.check(PLAYER_2_ID, &TEAM_1_ID));
         // Add rights for team 2 to player 1.
         rights.add(PLAYER_1_ID, &TEAM_2_ID);
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_2_ID), true);
+        assert!(rights.check(PLAYER_1_ID, &TEAM_2_ID));
         assert_eq!(rights.data.len(), 2);
         // Remove team 1.
         rights.remove_team(&TEAM_1_ID);
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_1_ID), false);
-        assert_eq!(rights.check(PLAYER_2_ID, &TEAM_1_ID), false);
+        assert!(!rights.check(PLAYER_1_ID, &TEAM_1_ID));
+        assert!(!rights.check(PLAYER_2_ID, &TEAM_1_ID));
         assert_eq!(rights.data.len(), 1);
     }
 
@@ -244,12 +303,12 @@ mod tests {
         // Add rights for team 1 to player 1 and player 2.
         rights.add(PLAYER_1_ID, &TEAM_1_ID);
         rights.add(PLAYER_2_ID, &TEAM_1_ID);
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_1_ID), true);
-        assert_eq!(rights.check(PLAYER_2_ID, &TEAM_1_ID), true);
+        assert!(rights.check(PLAYER_1_ID, &TEAM_1_ID));
+        assert!(rights.check(PLAYER_2_ID, &TEAM_1_ID));
         assert_eq!(rights.data.len(), 2);
         // Remove player 1.
         rights.remove_player(PLAYER_1_ID);
-        assert_eq!(rights.check(PLAYER_1_ID, &TEAM_1_ID), false);
+        assert!(!rights.check(PLAYER_1_ID, &TEAM_1_ID));
         assert_eq!(rights.data.len(), 1);
     }
 
@@ -0,0 +1,344 @@
+//! Step by step inspection of saved battle histories.
+
+use crate::battle::{Battle, BattleController, BattleRules};
+#[cfg(feature = "replay")]
+use crate::binary;
+use crate::entity::Entities;
+use crate::entropy::EntropyModel;
+use crate::error::WeaselResult;
+use crate::event::{EventReceiver, EventWrapper, VersionedEventWrapper};
+use crate::round::{RoundsModel, TurnState};
+#[cfg(feature = "replay")]
+use crate::serde::FlatVersionedEvent;
+use crate::server::Server;
+use crate::snapshot::{BattleSnapshot, SnapshotFn};
+use crate::space::SpaceModel;
+use crate::util::Id;
+#[cfg(feature = "replay")]
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, Write};
+#[cfg(feature = "replay")]
+use std::path::Path;
+
+/// A battle history that can be played forwards and backwards, one event at a time.
+///
+/// Events are applied to a new battle created with the given rules, which must be
+/// the same rules used when the history was recorded.
+///
+/// A snapshot of the battle is kept every `CHECKPOINT_INTERVAL` events. Moving backward
+/// restores the closest preceding snapshot and applies only the events that follow it.
+///
+/// The `Display` implementation describes the last applied event, its chain of origins
+/// and a summary of entities, teams and turn state.
+///
+/// # Examples
+/// ```
+/// use weasel::{
+///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateTeam,
+///     EventTrigger, Replay, Server,
+/// };
+///
+/// battle_rules! {}
+///
+/// let battle = Battle::builder(CustomRules::new()).build();
+/// let mut server = Server::builder(battle).build();
+/// CreateTeam::trigger(&mut server, 1).fire().unwrap();
+/// CreateTeam::trigger(&mut server, 2).fire().unwrap();
+/// let events = server.battle().versioned_events(0..2).collect();
+///
+/// let mut replay = Replay::new(CustomRules::new, events);
+/// assert!(replay.forward().unwrap());
+/// assert_eq!(replay.battle().entities().teams().count(), 1);
+/// assert!(replay.backward().unwrap());
+/// assert_eq!(replay.battle().entities().teams().count(), 0);
+/// ```
+pub struct Replay<R: BattleRules> {
+    rules: fn() -> R,
+    events: Vec<VersionedEventWrapper<R>>,
+    server: Server<R>,
+    snapshot: SnapshotFn<R>,
+    /// Snapshots taken after every `CHECKPOINT_INTERVAL` events, in order.
+    checkpoints: Vec<BattleSnapshot<R>>,
+}
+
+/// Number of events between two consecutive snapshots kept by a `Replay`.
+pub const CHECKPOINT_INTERVAL: usize = 64;
+
+impl<R: BattleRules + 'static> Replay<R> {
+    /// Creates a new replay of `events`, positioned before the first event.
+    ///
+    /// `rules` is invoked to create the rules each time the battle is rebuilt.
+    pub fn new(rules: fn() -> R, events: Vec<VersionedEventWrapper<R>>) -> Self
+    where
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        Self {
+            rules,
+            events,
+            server: Self::server(rules, None),
+            snapshot: Battle::snapshot,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Creates a server for a new battle, optionally restored from `snapshot`.
+    fn server(rules: fn() -> R, snapshot: Option<BattleSnapshot<R>>) -> Server<R> {
+        let mut battle = Battle::builder(rules()).build();
+        if let Some(snapshot) = snapshot {
+            battle.restore(snapshot);
+        }
+        Server::builder(battle).build()
+    }
+
+    /// Returns all events in this replay.
+    pub fn events(&self) -> &[VersionedEventWrapper<R>] {
+        &self.events
+    }
+
+    /// Returns the number of events applied so far.
+    pub fn position(&self) -> usize {
+        self.server.battle().history().len() as usize
+    }
+
+    /// Returns the battle, with all events up to the current position applied.
+    pub fn battle(&self) -> &Battle<R> {
+        self.server.battle()
+    }
+
+    /// Returns the last applied event, if any.
+    pub fn current(&self) -> Option<&EventWrapper<R>> {
+        self.applied(self.position().checked_sub(1)?)
+    }
+
+    /// Returns the applied event at `index`, if any.
+    fn applied(&self, index: usize) -> Option<&EventWrapper<R>> {
+        if index < self.position() {
+            self.events.get(index).map(|event| event.wrapper())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the events from which the last applied event originated,
+    /// starting from its direct origin.
+    pub fn origins(&self) -> Vec<&EventWrapper<R>> {
+        // Walk the replay's events, since the battle's history may start from a checkpoint.
+        let mut origins = Vec::new();
+        let mut visited = HashSet::new();
+        let mut origin = self.current().and_then(|event| {
+            visited.insert(event.id());
+            event.origin()
+        });
+        while let Some(event) = origin.and_then(|id| self.applied(id as usize)) {
+            if !visited.insert(event.id()) {
+                break;
+            }
+            origins.push(event);
+            origin = event.origin();
+        }
+        origins
+    }
+
+    /// Applies the next event.
+    ///
+    /// Returns false if there are no more events.
+    pub fn forward(&mut self) -> WeaselResult<bool, R> {
+        match self.events.get(self.position()) {
+            Some(event) => {
+                self.server.receive(event.clone())?;
+                if self.checkpoints.len() < self.position() / CHECKPOINT_INTERVAL {
+                    self.checkpoints.push((self.snapshot)(self.server.battle()));
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Undoes the last applied event, by replaying the previous events
+    /// from the closest checkpoint.
+    ///
+    /// Returns false if no events were applied.
+    pub fn backward(&mut self) -> WeaselResult<bool, R> {
+        match self.position() {
+            0 => Ok(false),
+            position => {
+                self.seek(position - 1)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Moves to `position`, so that exactly `position` events are applied.
+    ///
+    /// Positions past the last event move to the end of the replay.
+    pub fn seek(&mut self, position: usize) -> WeaselResult<(), R> {
+        if position < self.position() {
+            // Restore the closest checkpoint not past `position`. Later checkpoints
+            // are dropped and taken again when moving forward.
+            self.checkpoints
+                .truncate((position / CHECKPOINT_INTERVAL).min(self.checkpoints.len()));
+            let checkpoint = self.checkpoints.pop();
+            self.server = Self::server(self.rules, checkpoint);
+            if self.position() > 0 {
+                self.checkpoints.push((self.snapshot)(self.server.battle()));
+            }
+        }
+        while self.position() < position && self.forward()? {}
+        Ok(())
+    }
+
+    /// Runs an interactive viewer, reading commands from `input` and writing
+    /// descriptions of the battle into `output`.
+    ///
+    /// Commands are `n` (next event), `p` (previous event), `g <position>`
+    /// (go to position), `h` (help) and `q` (quit). An empty line repeats the last command.
+    pub fn run<I, O>(&mut self, input: I, mut output: O) -> io::Result<()>
+    where
+        I: BufRead,
+        O: Write,
+    {
+        writeln!(output, "{} events loaded", self.events.len())?;
+        writeln!(output, "{}", HELP)?;
+        let mut last = String::from("n");
+        for line in input.lines() {
+            let line = line?;
+            let command = match line.trim() {
+                "" => last.clone(),
+                command => command.to_string(),
+            };
+            let mut words = command.split_whitespace();
+            let result = match words.next() {
+                Some("n") => self.forward().map(|_| ()),
+                Some("p") => self.backward().map(|_| ()),
+                Some("g") => match words.next().and_then(|word| word.parse().ok()) {
+                    Some(position) => self.seek(position),
+                    None => {
+                        writeln!(output, "usage: g <position>")?;
+                        continue;
+                    }
+                },
+                Some("q") => return Ok(()),
+                _ => {
+                    writeln!(output, "{}", HELP)?;
+                    continue;
+                }
+            };
+            if let Err(error) = result {
+                writeln!(output, "error: {:?}", error)?;
+            }
+            writeln!(output, "{}", self)?;
+            last = command;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "replay")]
+impl<R> Replay<R>
+where
+    R: BattleRules + 'static,
+    FlatVersionedEvent<R>: DeserializeOwned,
+    Entities<R>: Clone,
+    SpaceModel<R>: Clone,
+    RoundsModel<R>: Clone,
+    EntropyModel<R>: Clone,
+{
+    /// Creates a replay from a json history, made of an array of `FlatVersionedEvent`
+    /// as the one saved by the `pirates` example.
+    pub fn from_json(rules: fn() -> R, json: &str) -> serde_json::Result<Self> {
+        let events: Vec<FlatVersionedEvent<R>> = serde_json::from_str(json)?;
+        Ok(Self::new(
+            rules,
+            events.into_iter().map(|event| event.into()).collect(),
+        ))
+    }
+
+    /// Creates a replay from a binary history, made of a sequence of `FlatVersionedEvent`
+    /// each one in its own frame, as the one written by a `FileHistoryStore`.
+    pub fn from_binary(rules: fn() -> R, mut bytes: &[u8]) -> io::Result<Self> {
+        let mut events = Vec::new();
+        while let Some(event) = binary::read_frame::<_, FlatVersionedEvent<R>>(&mut bytes)? {
+            events.push(event.into());
+        }
+        Ok(Self::new(rules, events))
+    }
+
+    /// Creates a replay from a history stored in the file at `path`,
+    /// either in json or in binary format.
+    ///
+    /// See [from_json](#method.from_json) and [from_binary](#method.from_binary)
+    /// for the supported formats.
+    pub fn load<P: AsRef<Path>>(rules: fn() -> R, path: P) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        // Json histories are arrays. Binary ones might look alike, if the length
+        // of the first frame happens to start with a bracket.
+        match std::str::from_utf8(&bytes) {
+            Ok(json) if json.trim_start().starts_with('[') => Self::from_json(rules, json)
+                .or_else(|error| Self::from_binary(rules, &bytes).map_err(|_| error.into())),
+            _ => Self::from_binary(rules, &bytes),
+        }
+    }
+
+    /// Loads the history stored in the file at `path` and runs the interactive viewer
+    /// on the standard input and output.
+    ///
+    /// Games can use it to build a viewer for the histories recorded with their own rules.
+    pub fn view<P: AsRef<Path>>(rules: fn() -> R, path: P) -> io::Result<()> {
+        let mut replay = Self::load(rules, path)?;
+        let stdin = io::stdin();
+        replay.run(stdin.lock(), io::stdout())
+    }
+}
+
+const HELP: &str = "commands: n - next, p - previous, g <position> - go to, q - quit";
+
+impl<R: BattleRules + 'static> Display for Replay<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "position {}/{}", self.position(), self.events.len())?;
+        match self.current() {
+            Some(event) => {
+                writeln!(f, "event {}: {:?}", event.id(), event.event())?;
+                for origin in self.origins() {
+                    writeln!(f, "  from event {}: {:?}", origin.id(), origin.event())?;
+                }
+            }
+            None => writeln!(f, "no events applied")?,
+        }
+        let battle = self.battle();
+        writeln!(f, "phase: {:?}", battle.phase())?;
+        for team in battle.entities().teams() {
+            writeln!(
+                f,
+                "team {:?}: conclusion {:?}, creatures {:?}",
+                team.id(),
+                team.conclusion(),
+                team.creatures().collect::<Vec<_>>()
+            )?;
+        }
+        for entity in battle.entities().entities() {
+            writeln!(
+                f,
+                "entity {:?}: position {:?}",
+                entity.entity_id(),
+                entity.position()
+            )?;
+        }
+        let rounds = battle.rounds();
+        match rounds.state() {
+            TurnState::Ready => write!(f, "turn: ready")?,
+            TurnState::Started(actors) => write!(f, "turn: started, actors {:?}", actors)?,
+        }
+        write!(
+            f,
+            " (completed turns {}, rounds {})",
+            rounds.completed_turns(),
+            rounds.completed_rounds()
+        )
+    }
+}
@@ -0,0 +1,56 @@
+//! Resynchronization of clients after a disconnection.
+
+use crate::checksum::Checksum;
+use crate::event::EventId;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Request sent by a client to resume receiving events from a server.
+///
+/// It contains the id of the last event applied by the client and,
+/// optionally, the checksum of the client's battle state.\
+/// Servers handle it with `Server::resync`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ResyncRequest {
+    last_event: Option<EventId>,
+    checksum: Option<Checksum>,
+}
+
+impl ResyncRequest {
+    /// Creates a new resync request.
+    ///
+    /// `last_event` should be `None` if the client didn't apply any event.
+    pub fn new(last_event: Option<EventId>, checksum: Option<Checksum>) -> Self {
+        Self {
+            last_event,
+            checksum,
+        }
+    }
+
+    /// Returns the id of the last event applied by the client.
+    pub fn last_event(&self) -> Option<EventId> {
+        self.last_event
+    }
+
+    /// Returns the checksum of the client's battle state.
+    pub fn checksum(&self) -> Option<Checksum> {
+        self.checksum
+    }
+
+    /// Returns the id of the first event missing in the client.
+    pub(crate) fn next_event(&self) -> EventId {
+        self.last_event.map_or(0, |id| id + 1)
+    }
+}
+
+/// Describes how a server brought a client up to date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resync {
+    /// The events in the range have been sent to the client.
+    Replay(Range<EventId>),
+    /// A snapshot of the battle has been sent to the client.
+    /// The first event following the snapshot has the given id.
+    Snapshot(EventId),
+}
@@ -7,6 +7,7 @@ use crate::entropy::Entropy;
 use crate::error::{WeaselError, WeaselResult};
 use crate::event::{Event, EventKind, EventProcessor, EventQueue, EventRights, EventTrigger};
 use crate::metric::WriteMetrics;
+use crate::schedule::Scheduler;
 use crate::space::Space;
 use crate::status::update_statuses;
 use indexmap::IndexSet;
@@ -30,6 +31,24 @@ pub struct Rounds<R: BattleRules> {
     rules: R::RR,
     rounds: RoundsCount,
     turns: TurnsCount,
+    scheduler: Scheduler<R>,
+}
+
+impl<R: BattleRules> Clone for Rounds<R>
+where
+    RoundsModel<R>: Clone,
+    R::RR: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            model: self.model.clone(),
+            rules: self.rules.clone(),
+            rounds: self.rounds,
+            turns: self.turns,
+            scheduler: self.scheduler.clone(),
+        }
+    }
 }
 
 impl<R: BattleRules> Rounds<R> {
@@ -40,6 +59,26 @@ impl<R: BattleRules> Rounds<R> {
             rules,
             rounds: 0,
             turns: 0,
+            scheduler: Scheduler::new(),
+        }
+    }
+
+    /// Creates a rounds object from an existing model, turn state and scheduler.
+    pub(crate) fn from_model(
+        model: RoundsModel<R>,
+        rules: R::RR,
+        state: TurnStateType<R>,
+        rounds: RoundsCount,
+        turns: TurnsCount,
+        scheduler: Scheduler<R>,
+    ) -> Self {
+        Self {
+            state,
+            model,
+            rules,
+            rounds,
+            turns,
+            scheduler,
         }
     }
 
@@ -105,6 +144,16 @@ impl<R: BattleRules> Rounds<R> {
         self.turns += 1;
     }
 
+    /// Returns the scheduler containing the events to be fired in future turns or rounds.
+    pub fn scheduler(&self) -> &Scheduler<R> {
+        &self.scheduler
+    }
+
+    /// Returns a mutable reference to the scheduler.
+    pub(crate) fn scheduler_mut(&mut self) -> &mut Scheduler<R> {
+        &mut self.scheduler
+    }
+
     /// Called when a new actor is added to the battle.
     pub(crate) fn on_actor_added(
         &mut self,
@@ -125,6 +174,7 @@ impl<R: BattleRules> Rounds<R> {
     ) {
         self.rules
             .on_actor_removed(&mut self.model, actor, entropy, metrics);
+        self.scheduler.purge(actor.entity_id());
     }
 
     /// Invoked when a turn ends.
@@ -144,6 +194,31 @@ impl<R: BattleRules> Rounds<R> {
     pub(crate) fn regenerate_model(&mut self, seed: &Option<RoundsSeed<R>>) {
         self.model = self.rules.generate_model(seed)
     }
+
+    /// Replaces this rounds' model, turn state and scheduler with the given ones.
+    pub(crate) fn restore(
+        &mut self,
+        model: RoundsModel<R>,
+        state: TurnStateType<R>,
+        rounds: RoundsCount,
+        turns: TurnsCount,
+        scheduler: Scheduler<R>,
+    ) {
+        self.model = model;
+        self.state = state;
+        self.rounds = rounds;
+        self.turns = turns;
+        self.scheduler = scheduler;
+    }
+
+    /// Brings this rounds manager back to its initial state.
+    pub(crate) fn reset(&mut self) {
+        self.state = TurnState::Ready;
+        self.regenerate_model(&None);
+        self.rounds = 0;
+        self.turns = 0;
+        self.scheduler.clear();
+    }
 }
 
 /// `TurnState` alias parameterized on the `BattleRules` R.
@@ -151,6 +226,7 @@ pub type TurnStateType<R> = TurnState<EntityId<R>>;
 
 /// State machine to manage the turns' state.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub enum TurnState<EI>
 where
     EI: Debug + Hash + Eq,
@@ -315,7 +391,7 @@ impl<R: BattleRules> StartTurn<R> {
     pub fn trigger<P: EventProcessor<R>>(
         processor: &mut P,
         id: EntityId<R>,
-    ) -> StartTurnTrigger<R, P> {
+    ) -> StartTurnTrigger<'_, R, P> {
         StartTurnTrigger {
             processor,
             ids: vec![id],
@@ -324,7 +400,7 @@ impl<R: BattleRules> StartTurn<R> {
 
     /// Returns a trigger for this event, to start a turn with a list of actors.\
     /// Duplicated ids will be dropped during the event's processing.
-    pub fn trigger_with_actors<P, I>(processor: &mut P, ids: I) -> StartTurnTrigger<R, P>
+    pub fn trigger_with_actors<P, I>(processor: &mut P, ids: I) -> StartTurnTrigger<'_, R, P>
     where
         P: EventProcessor<R>,
         I: IntoIterator<Item = EntityId<R>>,
@@ -367,7 +443,7 @@ impl<R: BattleRules + 'static> Event<R> for StartTurn<R> {
                 return Err(WeaselError::NotAnActor(id.clone()));
             }
             // Verify if entity exists.
-            if let Some(actor) = battle.entities().actor(&id) {
+            if let Some(actor) = battle.entities().actor(id) {
                 // Verify if actor is eligible.
                 if !battle.rounds().eligible(actor) {
                     return Err(WeaselError::ActorNotEligible(id.clone()));
@@ -415,6 +491,8 @@ impl<R: BattleRules + 'static> Event<R> for StartTurn<R> {
             // Update all statuses afflicting the actor.
             update_statuses(id, battle, event_queue)
                 .unwrap_or_else(|err| panic!("constraint violated: {:?}", err));
+            // Fire the events scheduled for the start of the actor's turn.
+            battle.state.rounds.scheduler.on_turn_start(id, event_queue);
         }
     }
 
@@ -442,6 +520,10 @@ impl<R: BattleRules + 'static> Event<R> for StartTurn<R> {
         }
         EventRights::Teams(teams)
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        self.ids.clone()
+    }
 }
 
 /// Trigger to build and fire a `StartTurn` event.
@@ -507,7 +589,7 @@ pub struct EndTurn<R> {
 
 impl<R: BattleRules> EndTurn<R> {
     /// Returns a trigger for this event.
-    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> EndTurnTrigger<R, P> {
+    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> EndTurnTrigger<'_, R, P> {
         EndTurnTrigger {
             processor,
             _phantom: PhantomData,
@@ -572,16 +654,25 @@ impl<R: BattleRules + 'static> Event<R> for EndTurn<R> {
             // Check teams' objectives.
             Battle::check_objectives(
                 &battle.state,
-                &battle.rules.team_rules(),
+                battle.rules.team_rules(),
                 &battle.metrics.read_handle(),
                 event_queue,
                 Checkpoint::TurnEnd,
             );
+            // Fire the events scheduled for the end of the actor's turn.
+            battle
+                .state
+                .rounds
+                .scheduler
+                .on_turn_end(&actor_id, event_queue);
         }
         // Set the turn state.
         battle.state.rounds.set_state(TurnState::Ready);
         // Increase the turns counter.
-        battle.rounds_mut().increase_completed_turns();
+        let rounds = battle.rounds_mut();
+        rounds.increase_completed_turns();
+        let turns = rounds.completed_turns();
+        rounds.scheduler.on_turns_completed(turns, event_queue);
     }
 
     fn kind(&self) -> EventKind {
@@ -680,7 +771,7 @@ pub struct ResetRounds<R: BattleRules> {
 
 impl<R: BattleRules> ResetRounds<R> {
     /// Returns a trigger for this event.
-    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> ResetRoundsTrigger<R, P> {
+    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> ResetRoundsTrigger<'_, R, P> {
         ResetRoundsTrigger {
             processor,
             seed: None,
@@ -801,7 +892,7 @@ pub struct EnvironmentTurn<R> {
 
 impl<R: BattleRules> EnvironmentTurn<R> {
     /// Returns a trigger for this event.
-    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> EnvironmentTurnTrigger<R, P> {
+    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> EnvironmentTurnTrigger<'_, R, P> {
         EnvironmentTurnTrigger {
             processor,
             _phantom: PhantomData,
@@ -845,7 +936,10 @@ impl<R: BattleRules + 'static> Event<R> for EnvironmentTurn<R> {
                 .unwrap_or_else(|err| panic!("constraint violated: {:?}", err));
         }
         // The turn started and ended, atomically.
-        battle.rounds_mut().increase_completed_turns();
+        let rounds = battle.rounds_mut();
+        rounds.increase_completed_turns();
+        let turns = rounds.completed_turns();
+        rounds.scheduler.on_turns_completed(turns, event_queue);
     }
 
     fn kind(&self) -> EventKind {
@@ -922,7 +1016,7 @@ pub struct EndRound<R> {
 
 impl<R: BattleRules> EndRound<R> {
     /// Returns a trigger for this event.
-    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> EndRoundTrigger<R, P> {
+    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> EndRoundTrigger<'_, R, P> {
         EndRoundTrigger {
             processor,
             _phantom: PhantomData,
@@ -953,8 +1047,11 @@ impl<R: BattleRules + 'static> Event<R> for EndRound<R> {
         Ok(())
     }
 
-    fn apply(&self, battle: &mut Battle<R>, _: &mut Option<EventQueue<R>>) {
-        battle.rounds_mut().increase_completed_rounds();
+    fn apply(&self, battle: &mut Battle<R>, event_queue: &mut Option<EventQueue<R>>) {
+        let rounds = battle.rounds_mut();
+        rounds.increase_completed_rounds();
+        let completed = rounds.completed_rounds();
+        rounds.scheduler.on_rounds_completed(completed, event_queue);
     }
 
     fn kind(&self) -> EventKind {
@@ -15,7 +15,7 @@ use crate::util::Id;
 use serde::{Deserialize, Serialize};
 
 /// An empty statistic.
-#[derive(Hash, Eq, PartialEq, Debug)]
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug)]
 #[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct EmptyStat {
     /// The id of this statistic.
@@ -36,7 +36,7 @@ pub type EmptyAbility = EmptyStat;
 pub type EmptyStatus = EmptyStat;
 
 /// Minimalistic implementation of team rules, doing no-op for everything.
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct EmptyTeamRules {}
 
 impl<R: BattleRules> TeamRules<R> for EmptyTeamRules {
@@ -46,7 +46,7 @@ impl<R: BattleRules> TeamRules<R> for EmptyTeamRules {
 }
 
 /// Minimalistic implementation of character rules, doing no-op for everything.
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct EmptyCharacterRules {}
 
 impl<R: BattleRules> CharacterRules<R> for EmptyCharacterRules {
@@ -60,7 +60,7 @@ impl<R: BattleRules> CharacterRules<R> for EmptyCharacterRules {
 }
 
 /// Minimalistic implementation of actor rules, doing no-op for everything.
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct EmptyActorRules {}
 
 impl<R: BattleRules> ActorRules<R> for EmptyActorRules {
@@ -71,7 +71,7 @@ impl<R: BattleRules> ActorRules<R> for EmptyActorRules {
 }
 
 /// Minimalistic implementation of space rules, doing no-op for everything.
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct EmptySpaceRules {}
 
 impl<R: BattleRules> SpaceRules<R> for EmptySpaceRules {
@@ -84,7 +84,7 @@ impl<R: BattleRules> SpaceRules<R> for EmptySpaceRules {
 }
 
 /// Minimalistic implementation of rounds rules, doing no-op for everything.
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct EmptyRoundsRules {}
 
 impl<R: BattleRules> RoundsRules<R> for EmptyRoundsRules {
@@ -95,7 +95,7 @@ impl<R: BattleRules> RoundsRules<R> for EmptyRoundsRules {
 }
 
 /// Minimalistic implementation of fight rules, doing no-op for everything.
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct EmptyFightRules {}
 
 impl<R: BattleRules> FightRules<R> for EmptyFightRules {
@@ -104,7 +104,7 @@ impl<R: BattleRules> FightRules<R> for EmptyFightRules {
 }
 
 /// Minimalistic implementation of user rules, doing no-op for everything.
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct EmptyUserRules {}
 
 impl<R: BattleRules> UserRules<R> for EmptyUserRules {
@@ -45,6 +45,34 @@ macro_rules! battle_rules {
             }
         }
 
+        // Rules are cloneable only if all their parts are.
+        // Higher-ranked bounds postpone the check until the rules are cloned.
+        impl Clone for CustomRules
+        where
+            for<'a> $ty: Clone,
+            for<'a> $cy: Clone,
+            for<'a> $ay: Clone,
+            for<'a> $fy: Clone,
+            for<'a> $uy: Clone,
+            for<'a> $sy: Clone,
+            for<'a> $ry: Clone,
+            for<'a> $ey: Clone,
+        {
+            fn clone(&self) -> Self {
+                Self {
+                    team_rules: self.team_rules.clone(),
+                    character_rules: self.character_rules.clone(),
+                    actor_rules: self.actor_rules.clone(),
+                    fight_rules: self.fight_rules.clone(),
+                    user_rules: self.user_rules.clone(),
+                    space_rules: self.space_rules.clone(),
+                    rounds_rules: self.rounds_rules.clone(),
+                    entropy_rules: self.entropy_rules.clone(),
+                    version: self.version,
+                }
+            }
+        }
+
         impl BattleRules for CustomRules {
             type TR = $ty;
             type CR = $cy;
@@ -0,0 +1,473 @@
+//! Events scheduled to happen at a later time.
+
+use crate::battle::{Battle, BattleRules};
+use crate::entity::EntityId;
+use crate::error::{WeaselError, WeaselResult};
+use crate::event::{
+    Event, EventKind, EventProcessor, EventPrototype, EventQueue, EventRights, EventTrigger,
+};
+use crate::round::{RoundsCount, TurnsCount};
+#[cfg(feature = "serialization")]
+use crate::serde::FlatEvent;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt::{Debug, Formatter, Result};
+use std::hash::Hash;
+
+/// The moment at which a scheduled event fires.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub enum Timing<EI>
+where
+    EI: Debug + Hash + Eq,
+{
+    /// At the start of the next turn of the given actor.
+    TurnStart(EI),
+    /// At the end of the next turn of the given actor.
+    TurnEnd(EI),
+    /// After the given number of turns have been completed.
+    ///
+    /// Environment turns are counted as well.
+    Turns(TurnsCount),
+    /// After the given number of rounds have been completed.
+    ///
+    /// `Rounds(1)` fires at the end of the current round.
+    Rounds(RoundsCount),
+}
+
+/// `Timing` alias parameterized on the `BattleRules` R.
+pub type TimingType<R> = Timing<EntityId<R>>;
+
+/// An event waiting inside a `Scheduler`.
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ScheduledEvent<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            bound(
+                serialize = "R: 'static, FlatEvent<R>: Serialize",
+                deserialize = "R: 'static, FlatEvent<R>: Deserialize<'de>"
+            ),
+            with = "crate::serde::prototype"
+        )
+    )]
+    prototype: EventPrototype<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "TimingType<R>: Serialize",
+            deserialize = "TimingType<R>: Deserialize<'de>"
+        ))
+    )]
+    timing: TimingType<R>,
+
+    rounds: RoundsCount,
+
+    turns: TurnsCount,
+}
+
+impl<R: BattleRules> ScheduledEvent<R> {
+    /// Returns the prototype of the event that will be fired.
+    pub fn prototype(&self) -> &EventPrototype<R> {
+        &self.prototype
+    }
+
+    /// Returns when the event will be fired.
+    pub fn timing(&self) -> &TimingType<R> {
+        &self.timing
+    }
+
+    /// Returns the number of rounds completed when the event was scheduled.
+    pub fn scheduled_at_round(&self) -> RoundsCount {
+        self.rounds
+    }
+
+    /// Returns the number of turns completed when the event was scheduled.
+    pub fn scheduled_at_turn(&self) -> TurnsCount {
+        self.turns
+    }
+}
+
+impl<R: BattleRules> Debug for ScheduledEvent<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "ScheduledEvent {{ event: {:?}, origin: {:?}, timing: {:?}, rounds: {:?}, turns: {:?} }}",
+            self.prototype.event(),
+            self.prototype.origin(),
+            self.timing,
+            self.rounds,
+            self.turns
+        )
+    }
+}
+
+impl<R: BattleRules> Clone for ScheduledEvent<R> {
+    fn clone(&self) -> Self {
+        Self {
+            prototype: self.prototype.clone(),
+            timing: self.timing.clone(),
+            rounds: self.rounds,
+            turns: self.turns,
+        }
+    }
+}
+
+/// Stores events to be fired at a later time.
+///
+/// The scheduler is owned by `Rounds` and it's part of the battle state. Events are added
+/// to it through `ScheduleEvent`. When their time comes, scheduled events are fired as
+/// derived events of the event that caused the turn or round to change. This way the
+/// history remains the only source of truth.
+///
+/// Scheduled events are verified only when fired, with their origin.
+/// Events scheduled at the start or end of an actor's turn are discarded
+/// when the actor is removed.
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Scheduler<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "ScheduledEvent<R>: Serialize",
+            deserialize = "ScheduledEvent<R>: Deserialize<'de>"
+        ))
+    )]
+    events: Vec<ScheduledEvent<R>>,
+}
+
+impl<R: BattleRules> Scheduler<R> {
+    /// Creates an empty scheduler.
+    pub(crate) fn new() -> Self {
+        Self { events: Vec::new() }
+    }
+
+    /// Returns an iterator over all scheduled events, in the order they were scheduled.
+    pub fn events(&self) -> impl Iterator<Item = &ScheduledEvent<R>> {
+        self.events.iter()
+    }
+
+    /// Returns the number of scheduled events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns true if there are no scheduled events.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Adds an event to the scheduler.
+    pub(crate) fn schedule(
+        &mut self,
+        prototype: EventPrototype<R>,
+        timing: TimingType<R>,
+        rounds: RoundsCount,
+        turns: TurnsCount,
+    ) {
+        self.events.push(ScheduledEvent {
+            prototype,
+            timing,
+            rounds,
+            turns,
+        });
+    }
+
+    /// Removes all scheduled events.
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Discards the events scheduled for the start or end of `actor`'s turns.
+    pub(crate) fn purge(&mut self, actor: &EntityId<R>) {
+        self.events.retain(|event| match &event.timing {
+            Timing::TurnStart(id) | Timing::TurnEnd(id) => id != actor,
+            Timing::Turns(_) | Timing::Rounds(_) => true,
+        });
+    }
+
+    /// Fires the events scheduled for the start of `actor`'s turn.
+    pub(crate) fn on_turn_start(
+        &mut self,
+        actor: &EntityId<R>,
+        event_queue: &mut Option<EventQueue<R>>,
+    ) {
+        self.fire(
+            |event| matches!(&event.timing, Timing::TurnStart(id) if id == actor),
+            event_queue,
+        );
+    }
+
+    /// Fires the events scheduled for the end of `actor`'s turn.
+    pub(crate) fn on_turn_end(
+        &mut self,
+        actor: &EntityId<R>,
+        event_queue: &mut Option<EventQueue<R>>,
+    ) {
+        self.fire(
+            |event| matches!(&event.timing, Timing::TurnEnd(id) if id == actor),
+            event_queue,
+        );
+    }
+
+    /// Fires the events waiting for a number of turns, now that `turns` have been completed.
+    pub(crate) fn on_turns_completed(
+        &mut self,
+        turns: TurnsCount,
+        event_queue: &mut Option<EventQueue<R>>,
+    ) {
+        self.fire(
+            |event| {
+                matches!(event.timing,
+                    Timing::Turns(count) if turns.saturating_sub(event.turns) >= count)
+            },
+            event_queue,
+        );
+    }
+
+    /// Fires the events waiting for a number of rounds, now that `rounds` have been completed.
+    pub(crate) fn on_rounds_completed(
+        &mut self,
+        rounds: RoundsCount,
+        event_queue: &mut Option<EventQueue<R>>,
+    ) {
+        self.fire(
+            |event| {
+                matches!(event.timing,
+                    Timing::Rounds(count) if rounds.saturating_sub(event.rounds) >= count)
+            },
+            event_queue,
+        );
+    }
+
+    /// Removes all events satisfying `predicate` and fires them, in the order they
+    /// were scheduled.
+    fn fire<F>(&mut self, predicate: F, event_queue: &mut Option<EventQueue<R>>)
+    where
+        F: Fn(&ScheduledEvent<R>) -> bool,
+    {
+        let mut due = Vec::new();
+        let mut i = 0;
+        while i < self.events.len() {
+            if predicate(&self.events[i]) {
+                due.push(self.events.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        Self::enqueue(due, event_queue);
+    }
+
+    /// Pushes `events` into the event queue, if there's one.
+    ///
+    /// Without a queue the events are simply dropped, because they are already part
+    /// of the history received from the server.
+    fn enqueue(events: Vec<ScheduledEvent<R>>, event_queue: &mut Option<EventQueue<R>>) {
+        if let Some(event_queue) = event_queue {
+            event_queue.extend(events.into_iter().map(|scheduled| scheduled.prototype));
+        }
+    }
+}
+
+impl<R: BattleRules> Debug for Scheduler<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Scheduler {{ events: {:?} }}", self.events)
+    }
+}
+
+impl<R: BattleRules> Clone for Scheduler<R> {
+    fn clone(&self) -> Self {
+        Self {
+            events: self.events.clone(),
+        }
+    }
+}
+
+/// Event to schedule another event at a later time.
+///
+/// The scheduled prototype is stored in the `Scheduler` owned by `Rounds` and it's fired
+/// when the given timing is met, as a consequence of the event that started or ended
+/// the turn or round. The prototype keeps its origin, if it has one.
+///
+/// Prototypes having a condition are rejected with `WeaselError::ConditionNotSerializable`,
+/// since the condition couldn't be saved along with the event and the battle state.
+///
+/// Only the server or a player with the rights required by the scheduled event
+/// can fire this event.
+///
+/// # Examples
+/// ```
+/// use weasel::{
+///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, EndRound,
+///     EventKind, EventTrigger, ResetSpace, ScheduleEvent, Server, Timing,
+/// };
+///
+/// battle_rules! {}
+///
+/// let battle = Battle::builder(CustomRules::new()).build();
+/// let mut server = Server::builder(battle).build();
+///
+/// let prototype = ResetSpace::trigger(&mut ()).prototype();
+/// ScheduleEvent::trigger(&mut server, prototype, Timing::Rounds(2))
+///     .fire()
+///     .unwrap();
+/// assert_eq!(server.battle().rounds().scheduler().len(), 1);
+///
+/// EndRound::trigger(&mut server).fire().unwrap();
+/// EndRound::trigger(&mut server).fire().unwrap();
+/// assert!(server.battle().rounds().scheduler().is_empty());
+/// assert_eq!(
+///     server.battle().history().events().iter().last().unwrap().kind(),
+///     EventKind::ResetSpace
+/// );
+/// ```
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct ScheduleEvent<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            bound(
+                serialize = "R: 'static, FlatEvent<R>: Serialize",
+                deserialize = "R: 'static, FlatEvent<R>: Deserialize<'de>"
+            ),
+            with = "crate::serde::prototype"
+        )
+    )]
+    prototype: EventPrototype<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "TimingType<R>: Serialize",
+            deserialize = "TimingType<R>: Deserialize<'de>"
+        ))
+    )]
+    timing: TimingType<R>,
+}
+
+impl<R: BattleRules> ScheduleEvent<R> {
+    /// Returns a trigger for this event.
+    pub fn trigger<P: EventProcessor<R>>(
+        processor: &mut P,
+        prototype: EventPrototype<R>,
+        timing: TimingType<R>,
+    ) -> ScheduleEventTrigger<'_, R, P> {
+        ScheduleEventTrigger {
+            processor,
+            prototype,
+            timing,
+        }
+    }
+
+    /// Returns the prototype of the event to be scheduled.
+    pub fn prototype(&self) -> &EventPrototype<R> {
+        &self.prototype
+    }
+
+    /// Returns when the event will be fired.
+    pub fn timing(&self) -> &TimingType<R> {
+        &self.timing
+    }
+}
+
+impl<R: BattleRules> Debug for ScheduleEvent<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "ScheduleEvent {{ event: {:?}, origin: {:?}, timing: {:?} }}",
+            self.prototype.event(),
+            self.prototype.origin(),
+            self.timing
+        )
+    }
+}
+
+impl<R: BattleRules> Clone for ScheduleEvent<R> {
+    fn clone(&self) -> Self {
+        Self {
+            prototype: self.prototype.clone(),
+            timing: self.timing.clone(),
+        }
+    }
+}
+
+impl<R: BattleRules + 'static> Event<R> for ScheduleEvent<R> {
+    fn verify(&self, battle: &Battle<R>) -> WeaselResult<(), R> {
+        // Verify that the prototype can be serialized.
+        if self.prototype.condition().is_some() {
+            return Err(WeaselError::ConditionNotSerializable);
+        }
+        match &self.timing {
+            Timing::TurnStart(id) | Timing::TurnEnd(id) => {
+                // Verify if entity is an actor.
+                if !id.is_actor() {
+                    return Err(WeaselError::NotAnActor(id.clone()));
+                }
+                // Verify if entity exists.
+                if battle.entities().actor(id).is_none() {
+                    return Err(WeaselError::EntityNotFound(id.clone()));
+                }
+                Ok(())
+            }
+            Timing::Turns(_) | Timing::Rounds(_) => Ok(()),
+        }
+    }
+
+    fn apply(&self, battle: &mut Battle<R>, _: &mut Option<EventQueue<R>>) {
+        let rounds = battle.state.rounds.completed_rounds();
+        let turns = battle.state.rounds.completed_turns();
+        battle.state.rounds.scheduler_mut().schedule(
+            self.prototype.clone(),
+            self.timing.clone(),
+            rounds,
+            turns,
+        );
+    }
+
+    fn kind(&self) -> EventKind {
+        EventKind::ScheduleEvent
+    }
+
+    fn box_clone(&self) -> Box<dyn Event<R> + Send> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn rights<'a>(&'a self, battle: &'a Battle<R>) -> EventRights<'a, R> {
+        // Scheduled events are fired by the server, so require their rights now.
+        self.prototype.event().rights(battle)
+    }
+}
+
+/// Trigger to build and fire a `ScheduleEvent` event.
+pub struct ScheduleEventTrigger<'a, R, P>
+where
+    R: BattleRules,
+    P: EventProcessor<R>,
+{
+    processor: &'a mut P,
+    prototype: EventPrototype<R>,
+    timing: TimingType<R>,
+}
+
+impl<'a, R, P> EventTrigger<'a, R, P> for ScheduleEventTrigger<'a, R, P>
+where
+    R: BattleRules + 'static,
+    P: EventProcessor<R>,
+{
+    fn processor(&'a mut self) -> &'a mut P {
+        self.processor
+    }
+
+    /// Returns a `ScheduleEvent` event.
+    fn event(&self) -> Box<dyn Event<R> + Send> {
+        Box::new(ScheduleEvent {
+            prototype: self.prototype.clone(),
+            timing: self.timing.clone(),
+        })
+    }
+}
@@ -1,22 +1,30 @@
 //! Module to handle serialization and deserialization.
 
-use crate::ability::ActivateAbility;
+use crate::ability::{AbilityId, ActivateAbility};
 use crate::actor::{AlterAbilities, RegenerateAbilities};
 use crate::battle::{BattleRules, EndBattle, Version};
 use crate::character::{AlterStatistics, RegenerateStatistics};
-use crate::creature::{ConvertCreature, CreateCreature, RemoveCreature};
+use crate::checksum::Checksum;
+use crate::creature::{ConvertCreature, CreateCreature, CreatureId, RemoveCreature};
+use crate::entity::EntityId;
 use crate::entropy::ResetEntropy;
+use crate::error::{WeaselError, WeaselErrorType};
 use crate::event::{
-    ClientEventPrototype, DummyEvent, Event, EventId, EventKind, EventWrapper,
+    ClientEventPrototype, CorrelationId, DummyEvent, Event, EventId, EventKind, EventWrapper,
     VersionedEventWrapper,
 };
 use crate::fight::ApplyImpact;
-use crate::object::{CreateObject, RemoveObject};
+use crate::metric::MetricIdType;
+use crate::object::{CreateObject, ObjectId, RemoveObject};
 use crate::player::PlayerId;
 use crate::round::{EndRound, EndTurn, EnvironmentTurn, ResetRounds, StartTurn};
-use crate::space::{AlterSpace, MoveEntity, ResetSpace};
-use crate::status::{AlterStatuses, ClearStatus, InflictStatus};
-use crate::team::{ConcludeObjectives, CreateTeam, RemoveTeam, ResetObjectives, SetRelations};
+use crate::schedule::{ScheduleEvent, TimingType};
+use crate::space::{AlterSpace, MoveEntity, Position, ResetSpace};
+use crate::status::{AlterStatuses, ClearStatus, InflictStatus, StatusId};
+use crate::team::{
+    ConcludeObjectives, CreateTeam, RemoveTeam, ResetObjectives, SetRelations, TeamId,
+};
+use crate::transaction::Transaction;
 use crate::user::{UserEventPackage, UserEventPacker};
 use serde::{Deserialize, Serialize};
 
@@ -77,20 +85,23 @@ macro_rules! flat_event_flattened {
 macro_rules! flat_event {
     ($( $x:ident, $ser:expr, $de:expr ),* $(,)?) => {
         /// An enum representation of event trait objects.
+        ///
+        /// The index of each variant is a stable tag for the event's kind, used by
+        /// compact binary encodings. New variants are always appended at the end.
         #[derive(Serialize, Deserialize)]
         pub enum FlatEvent<R: BattleRules> {
-            $(#[allow(missing_docs)]
-            #[serde(bound(
-                serialize = $ser,
-                deserialize = $de
-            ))]
-            $x($x<R>),)*
             #[allow(missing_docs)]
             #[serde(bound(
                 serialize = "UserEventPackage<R>: Serialize",
                 deserialize = "UserEventPackage<R>: Deserialize<'de>"
             ))]
             UserEventPackage(UserEventPackage<R>),
+            $(#[allow(missing_docs)]
+            #[serde(bound(
+                serialize = $ser,
+                deserialize = $de
+            ))]
+            $x($x<R>),)*
         }
 
         impl<R: BattleRules + 'static> FlatEvent<R> {
@@ -132,6 +143,10 @@ flat_event! {
     ResetRounds, "ResetRounds<R>: Serialize", "ResetRounds<R>: Deserialize<'de>",
     ResetSpace, "ResetSpace<R>: Serialize", "ResetSpace<R>: Deserialize<'de>",
     EndBattle, "EndBattle<R>: Serialize", "EndBattle<R>: Deserialize<'de>",
+    // The bounds of events containing other events can't mention the events themselves,
+    // because they contain a `FlatEvent`.
+    ScheduleEvent, "R: 'static, TimingType<R>: Serialize", "R: 'static, TimingType<R>: Deserialize<'de>",
+    Transaction, "R: 'static", "R: 'static",
 }
 
 /// A versioned event wrapper containing a flattened event.
@@ -152,6 +167,12 @@ pub struct FlatVersionedEvent<R: BattleRules> {
         deserialize = "Version<R>: Deserialize<'de>"
     ))]
     version: Version<R>,
+
+    #[serde(default)]
+    checksum: Option<Checksum>,
+
+    #[serde(default)]
+    correlation_id: Option<CorrelationId>,
 }
 
 impl<R: BattleRules> FlatVersionedEvent<R> {
@@ -174,6 +195,16 @@ impl<R: BattleRules> FlatVersionedEvent<R> {
     pub fn version(&self) -> &Version<R> {
         &self.version
     }
+
+    /// Returns the checksum of the battle state after this event, if any.
+    pub fn checksum(&self) -> Option<Checksum> {
+        self.checksum
+    }
+
+    /// Returns the correlation id of the client event from which this event was created, if any.
+    pub fn correlation_id(&self) -> Option<CorrelationId> {
+        self.correlation_id
+    }
 }
 
 impl<R: BattleRules + 'static> From<VersionedEventWrapper<R>> for FlatVersionedEvent<R> {
@@ -183,16 +214,21 @@ impl<R: BattleRules + 'static> From<VersionedEventWrapper<R>> for FlatVersionedE
             origin: event.wrapper().origin(),
             event: FlatEvent::flattened(event.wrapper.event),
             version: event.version,
+            checksum: event.checksum,
+            correlation_id: event.correlation_id,
         }
     }
 }
 
 impl<R: BattleRules + 'static> From<FlatVersionedEvent<R>> for VersionedEventWrapper<R> {
     fn from(event: FlatVersionedEvent<R>) -> Self {
-        Self::new(
+        let mut wrapper = Self::new(
             EventWrapper::new(event.id, event.origin, event.event.boxed()),
             event.version,
-        )
+        );
+        wrapper.checksum = event.checksum;
+        wrapper.correlation_id = event.correlation_id;
+        wrapper
     }
 }
 
@@ -215,6 +251,9 @@ pub struct FlatClientEvent<R: BattleRules> {
     version: Version<R>,
 
     player: Option<PlayerId>,
+
+    #[serde(default)]
+    correlation_id: Option<CorrelationId>,
 }
 
 impl<R: BattleRules> FlatClientEvent<R> {
@@ -237,27 +276,203 @@ impl<R: BattleRules> FlatClientEvent<R> {
     pub fn player(&self) -> Option<PlayerId> {
         self.player
     }
+
+    /// Returns the id used to correlate this event with the server's response.
+    pub fn correlation_id(&self) -> Option<CorrelationId> {
+        self.correlation_id
+    }
 }
 
 impl<R: BattleRules + 'static> From<ClientEventPrototype<R>> for FlatClientEvent<R> {
     fn from(event: ClientEventPrototype<R>) -> Self {
         let player = event.player();
+        let correlation_id = event.correlation_id();
         Self {
             origin: event.origin(),
             event: FlatEvent::flattened(event.event),
             version: event.version,
             player,
+            correlation_id,
         }
     }
 }
 
 impl<R: BattleRules + 'static> From<FlatClientEvent<R>> for ClientEventPrototype<R> {
     fn from(event: FlatClientEvent<R>) -> Self {
-        Self::new(
+        let mut prototype = Self::new(
             event.origin,
             event.event.boxed(),
             event.version,
             event.player,
-        )
+        );
+        prototype.set_correlation_id(event.correlation_id);
+        prototype
+    }
+}
+
+/// A `WeaselError` whose events are flattened.
+/// Use this type to serialize/deserialize a `WeaselError`.
+///
+/// # Examples
+/// ```
+/// use weasel::{battle_rules, rules::empty::*, BattleRules, FlatWeaselError, WeaselError};
+/// use weasel::{error::WeaselErrorType, event::DummyEvent, EventTrigger};
+///
+/// battle_rules! {}
+///
+/// let error: WeaselErrorType<CustomRules> = WeaselError::InvalidEvent(
+///     DummyEvent::trigger(&mut ()).event(),
+///     Box::new(WeaselError::TurnInProgress),
+/// );
+/// let flat: FlatWeaselError<CustomRules> = error.clone().into();
+/// let json = serde_json::to_string(&flat).unwrap();
+/// let flat: FlatWeaselError<CustomRules> = serde_json::from_str(&json).unwrap();
+/// assert_eq!(flat.boxed(), error);
+/// ```
+pub type FlatWeaselError<R> = WeaselError<
+    Version<R>,
+    TeamId<R>,
+    EntityId<R>,
+    CreatureId<R>,
+    ObjectId<R>,
+    Position<R>,
+    AbilityId<R>,
+    StatusId<R>,
+    MetricIdType<R>,
+    FlatEvent<R>,
+>;
+
+impl<R: BattleRules + 'static> From<WeaselErrorType<R>> for FlatWeaselError<R> {
+    fn from(error: WeaselErrorType<R>) -> Self {
+        error.map_event(FlatEvent::flattened)
+    }
+}
+
+impl<R: BattleRules + 'static> FlatWeaselError<R> {
+    /// Transforms this error into a `WeaselError` containing boxed events.
+    // A `From` implementation would prevent type inference of errors in the whole crate.
+    pub fn boxed(self) -> WeaselErrorType<R> {
+        self.map_event(FlatEvent::boxed)
+    }
+}
+
+/// (De)serializes a map as a sequence of key-value pairs.
+///
+/// Formats such as JSON accept only strings as map keys, while ids in weasel
+/// can be of any type.
+pub(crate) mod map_as_pairs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::iter::FromIterator;
+
+    pub(crate) fn serialize<'a, M, K, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        S: Serializer,
+    {
+        serializer.collect_seq(map)
+    }
+
+    pub(crate) fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        M: FromIterator<(K, V)>,
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+/// (De)serializes an event prototype as its origin and a `FlatEvent`.
+///
+/// It allows to store prototypes inside events or inside the battle state.
+/// The prototype's condition can't be serialized, thus serializing a conditional
+/// prototype returns an error.
+pub(crate) mod prototype {
+    use super::FlatEvent;
+    use crate::battle::BattleRules;
+    use crate::event::{EventId, EventPrototype};
+    use serde::ser::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct FlatPrototype<R: BattleRules> {
+        origin: Option<EventId>,
+        #[serde(bound(
+            serialize = "FlatEvent<R>: Serialize",
+            deserialize = "FlatEvent<R>: Deserialize<'de>"
+        ))]
+        event: FlatEvent<R>,
+    }
+
+    pub(crate) fn serialize<R, S>(
+        prototype: &EventPrototype<R>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        R: BattleRules + 'static,
+        FlatEvent<R>: Serialize,
+        S: Serializer,
+    {
+        if prototype.condition().is_some() {
+            return Err(S::Error::custom(
+                "the condition of an event prototype can't be serialized",
+            ));
+        }
+        FlatPrototype {
+            origin: prototype.origin(),
+            event: FlatEvent::flattened(prototype.event().clone()),
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, R, D>(deserializer: D) -> Result<EventPrototype<R>, D::Error>
+    where
+        R: BattleRules + 'static,
+        FlatEvent<R>: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let flat = FlatPrototype::deserialize(deserializer)?;
+        let mut prototype = EventPrototype::new(flat.event.boxed());
+        prototype.set_origin(flat.origin);
+        Ok(prototype)
+    }
+}
+
+/// (De)serializes a vector of boxed events as a sequence of `FlatEvent`.
+pub(crate) mod boxed_events {
+    use super::FlatEvent;
+    use crate::battle::BattleRules;
+    use crate::event::Event;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<R, S>(
+        events: &[Box<dyn Event<R> + Send>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        R: BattleRules + 'static,
+        FlatEvent<R>: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(events.iter().cloned().map(FlatEvent::flattened))
+    }
+
+    pub(crate) fn deserialize<'de, R, D>(
+        deserializer: D,
+    ) -> Result<Vec<Box<dyn Event<R> + Send>>, D::Error>
+    where
+        R: BattleRules + 'static,
+        FlatEvent<R>: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<FlatEvent<R>>::deserialize(deserializer)?
+            .into_iter()
+            .map(FlatEvent::boxed)
+            .collect())
     }
 }
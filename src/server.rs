@@ -1,14 +1,38 @@
 //! A battle server.
 
+use crate::actor::ActorRules;
 use crate::battle::{Battle, BattleController, BattleRules, EventCallback};
-use crate::error::{WeaselError, WeaselResult};
+use crate::checksum::Checksum;
+use crate::entity::Entities;
+use crate::entropy::EntropyModel;
+use crate::error::{WeaselError, WeaselErrorType, WeaselResult};
 use crate::event::{
-    ClientEventPrototype, EventProcessor, EventPrototype, EventQueue, EventReceiver, EventRights,
-    EventServer, EventWrapper, MultiClientSink, MultiClientSinkHandle, MultiClientSinkHandleMut,
+    ClientEventPrototype, ClientSink, Correlation, Event, EventId, EventProcessor, EventPrototype,
+    EventQueue, EventReceiver, EventRights, EventServer, EventSinkId, EventWrapper,
+    MultiClientSink, MultiClientSinkHandle, MultiClientSinkHandleMut, Rejection,
     VersionedEventWrapper,
 };
-use crate::player::{PlayerId, RightsHandle, RightsHandleMut};
+use crate::fight::{FightRules, Reaction};
+use crate::player::{PlayerId, RightsHandle, RightsHandleMut, RightsList};
+use crate::resync::{Resync, ResyncRequest};
+use crate::round::RoundsModel;
+#[cfg(feature = "serialization")]
+use crate::schedule::Scheduler;
+use crate::simulation::{EventSimulator, Simulation};
+use crate::snapshot::{BattleSnapshot, SnapshotFn};
+use crate::space::SpaceModel;
+use crate::store::HistoryStore;
 use crate::team::TeamId;
+use crate::transaction::Transaction;
+use log::error;
+#[cfg(feature = "serialization")]
+use serde::Serialize;
+
+/// Type of the function receiving the events evicted from the history of a server.
+///
+/// Events are passed in order, decorated with the rules' version and, if enabled,
+/// with their checksums.
+pub type HistoryOffload<R> = Box<dyn FnMut(Vec<VersionedEventWrapper<R>>) + Send>;
 
 /// The server is the main object used to orchestrate a battle.
 ///
@@ -17,10 +41,90 @@ use crate::team::TeamId;
 /// Exactly one server is required in order to start a game.
 ///
 /// One or more client sinks can be connected to a server, to receive verified events.
+/// If visibility is enabled through `ServerBuilder::enable_visibility`, each sink receives
+/// only what its player is allowed to see, according to `TeamRules::event_visibility`.
 pub struct Server<R: BattleRules> {
     pub(crate) battle: Battle<R>,
     client_sinks: MultiClientSink<R>,
     authentication: bool,
+    redo_buffer: Vec<EventWrapper<R>>,
+    /// Rights towards teams removed by rewinds, given back when the teams are redone.
+    redo_rights: RightsList<R>,
+    checksum: Option<fn(&Battle<R>) -> Checksum>,
+    checksums: Checksums,
+    snapshot: Option<SnapshotFn<R>>,
+    max_resync_gap: EventId,
+    transactions: Option<SnapshotFn<R>>,
+    compaction: Option<Compaction<R>>,
+    simulation_callback: bool,
+}
+
+/// Checksums of the battle state computed after each event, indexed by event id.
+#[derive(Default)]
+struct Checksums {
+    /// Id of the event to which the first checksum belongs.
+    first_id: EventId,
+    checksums: Vec<Checksum>,
+}
+
+impl Checksums {
+    /// Stores the checksum of the state after the event with id `id`.
+    ///
+    /// Checksums must be contiguous: a gap discards all previous checksums.
+    fn push(&mut self, id: EventId, checksum: Checksum) {
+        if self.checksums.is_empty() || id != self.end() {
+            self.first_id = id;
+            self.checksums.clear();
+        }
+        self.checksums.push(checksum);
+    }
+
+    /// Returns the checksum of the state after the event with id `id`, if known.
+    fn get(&self, id: EventId) -> Option<Checksum> {
+        id.checked_sub(self.first_id)
+            .and_then(|index| self.checksums.get(index as usize))
+            .copied()
+    }
+
+    /// Removes the checksums of all events with an id equal to or greater than `len`.
+    fn truncate(&mut self, len: EventId) {
+        self.checksums
+            .truncate(len.saturating_sub(self.first_id) as usize);
+    }
+
+    /// Removes the checksums of all events with an id lower than `id`.
+    fn evict(&mut self, id: EventId) {
+        let count = (id.saturating_sub(self.first_id) as usize).min(self.checksums.len());
+        self.checksums.drain(..count);
+        self.first_id = self.first_id.max(id);
+    }
+
+    /// Returns the id following the one of the last event having a checksum.
+    fn end(&self) -> EventId {
+        self.first_id + self.checksums.len() as EventId
+    }
+}
+
+/// State of the history compaction of a server.
+struct Compaction<R: BattleRules> {
+    horizon: EventId,
+    snapshot: SnapshotFn<R>,
+    offload: Option<HistoryOffload<R>>,
+    /// State of the battle before the first event in the history.
+    base: Option<BattleSnapshot<R>>,
+    /// The most recent checkpoint. Events preceding it are evicted at the next compaction.
+    latest: Option<BattleSnapshot<R>>,
+}
+
+/// Parts of a server detached while trying the members of a transaction.
+struct Detached<R: BattleRules> {
+    client_sinks: MultiClientSink<R>,
+    store: Option<Box<dyn HistoryStore<R> + Send>>,
+    event_callback: Option<EventCallback<R>>,
+    checksum: Option<fn(&Battle<R>) -> Checksum>,
+    compaction: Option<Compaction<R>>,
+    redo_buffer: Vec<EventWrapper<R>>,
+    redo_rights: RightsList<R>,
 }
 
 impl<R: BattleRules + 'static> Server<R> {
@@ -29,6 +133,14 @@ impl<R: BattleRules + 'static> Server<R> {
         ServerBuilder {
             battle,
             authentication: false,
+            visibility: false,
+            checksum: None,
+            snapshot: None,
+            max_resync_gap: 0,
+            transactions: None,
+            compaction: None,
+            offload: None,
+            simulation_callback: true,
         }
     }
 
@@ -38,12 +150,14 @@ impl<R: BattleRules + 'static> Server<R> {
     }
 
     /// Returns a handle to access the players' rights to control one or more teams.
-    pub fn rights(&self) -> RightsHandle<R> {
+    pub fn rights(&self) -> RightsHandle<'_, R> {
         self.battle.rights()
     }
 
     /// Returns a mutable handle to manage the players' rights to control one or more teams.
-    pub fn rights_mut<'a>(&'a mut self) -> RightsHandleMut<R, impl Iterator<Item = &'a TeamId<R>>> {
+    pub fn rights_mut<'a>(
+        &'a mut self,
+    ) -> RightsHandleMut<'a, R, impl Iterator<Item = &'a TeamId<R>>> {
         self.battle.rights_mut()
     }
 
@@ -57,34 +171,687 @@ impl<R: BattleRules + 'static> Server<R> {
         MultiClientSinkHandleMut::new(&mut self.client_sinks, &self.battle)
     }
 
+    /// Rewinds the battle to the moment before the event with id `event_id` was applied.
+    ///
+    /// All events starting from `event_id` are removed from the history and moved into
+    /// the redo buffer. Client sinks are notified with `ClientSink::rewind`.\
+    /// The battle's state is rebuilt from the closest preceding snapshot, either the one the battle
+    /// was restored from or a checkpoint of the history compaction, by replaying the events
+    /// that follow it. Battles whose history starts from the beginning are rebuilt
+    /// from their initial state instead. Events preceding the first snapshot can't be rewound.
+    ///
+    /// # Examples
+    /// ```
+    /// use weasel::{
+    ///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateTeam,
+    ///     EventTrigger, Server,
+    /// };
+    ///
+    /// battle_rules! {}
+    ///
+    /// let battle = Battle::builder(CustomRules::new()).build();
+    /// let mut server = Server::builder(battle).build();
+    /// CreateTeam::trigger(&mut server, 1).fire().unwrap();
+    /// CreateTeam::trigger(&mut server, 2).fire().unwrap();
+    ///
+    /// server.rewind_to(1).unwrap();
+    /// assert_eq!(server.battle().entities().teams().count(), 1);
+    /// assert_eq!(server.redo_buffer().len(), 1);
+    ///
+    /// server.redo_to(2).unwrap();
+    /// assert_eq!(server.battle().entities().teams().count(), 2);
+    /// ```
+    pub fn rewind_to(&mut self, event_id: EventId) -> WeaselResult<(), R> {
+        let checkpoints: Vec<_> = self
+            .compaction
+            .iter()
+            .flat_map(|compaction| compaction.base.iter().chain(compaction.latest.iter()))
+            .collect();
+        let (mut discarded, mut rights) = self.battle.rewind(event_id, &checkpoints)?;
+        // Checkpoints of the discarded events are no longer valid.
+        if let Some(compaction) = &mut self.compaction {
+            if matches!(&compaction.latest, Some(latest) if latest.next_id() > event_id) {
+                compaction.latest = None;
+            }
+        }
+        // Events discarded now come before those discarded by previous rewinds.
+        discarded.append(&mut self.redo_buffer);
+        self.redo_buffer = discarded;
+        self.redo_rights.append(&mut rights);
+        self.checksums.truncate(event_id);
+        // Notify the clients.
+        self.client_sinks.rewind_all(event_id);
+        Ok(())
+    }
+
+    /// Applies again the events in the redo buffer, up to `event_id` (excluded).
+    ///
+    /// The redo buffer is cleared as soon as a new event is processed or received.
+    pub fn redo_to(&mut self, event_id: EventId) -> WeaselResult<(), R> {
+        let history_len = self.battle.history().len();
+        let buffer_end = history_len + self.redo_buffer.len() as EventId;
+        if event_id < history_len || event_id > buffer_end {
+            return Err(WeaselError::InvalidEventRange(
+                history_len..event_id,
+                buffer_end,
+            ));
+        }
+        let count = (event_id - history_len) as usize;
+        let events: Vec<_> = self.redo_buffer.drain(..count).collect();
+        for event in events {
+            self.battle.apply(&event, &mut None);
+            // Give back the rights towards the teams created again.
+            let entities = &self.battle.state.entities;
+            self.battle
+                .rights
+                .restore_teams(&mut self.redo_rights, |team_id| {
+                    entities.team(team_id).is_some()
+                });
+            let event = self.versioned(event);
+            self.client_sinks.send_all(&event, &self.battle);
+            self.compact();
+        }
+        Ok(())
+    }
+
+    /// Reconnects a client sink, sending to it only what the client is missing.
+    ///
+    /// `request` contains the last event applied by the client and, optionally, the checksum
+    /// of its state. Any existing sink with the same id is replaced by `sink`.
+    ///
+    /// The missing events are replayed to the sink, unless snapshots are enabled through
+    /// `ServerBuilder::enable_snapshot_resync` and one of the following is true:
+    /// - the missing events are more than the maximum gap.
+    /// - the missing events are no longer in the history.
+    /// - the client's checksum differs from the one computed by the server.
+    /// - events are filtered by visibility, since they can't be replayed.
+    ///
+    /// In these cases the sink receives a snapshot with `ClientSink::send_snapshot`.
+    /// Keep in mind that snapshots contain the entire battle state,
+    /// regardless of `TeamRules::event_visibility`.
+    ///
+    /// Returns an error if the client can't be resynchronized.
+    pub fn resync(
+        &mut self,
+        mut sink: Box<dyn ClientSink<R> + Send>,
+        request: &ResyncRequest,
+    ) -> WeaselResult<Resync, R> {
+        let history = self.battle.history();
+        let (first_id, len) = (history.first_id(), history.len());
+        let start = request.next_event();
+        // Check if the missing events can be replayed.
+        // Events preceding the history can still be read from the history store.
+        let mut error = None;
+        if start < first_id && !history.in_store(start) {
+            if history.evicted().contains(&start) {
+                error = Some(WeaselError::EvictedEvents(start..len, first_id));
+            } else {
+                error = Some(WeaselError::InvalidEventRange(start..len, len));
+            }
+        } else if start > len {
+            error = Some(WeaselError::InvalidEventRange(start..len, len));
+        } else if start > 0 {
+            let expected = self.checksums.get(start - 1);
+            if let (Some(actual), Some(expected)) = (request.checksum(), expected) {
+                if actual != expected {
+                    error = Some(WeaselError::ChecksumMismatch(start - 1, expected, actual));
+                }
+            }
+        }
+        if error.is_none() {
+            error = self.client_sinks.verify_range(&(start..len)).err();
+        }
+        if let Some(snapshot) = self.snapshot {
+            if error.is_some() || len - start > self.max_resync_gap {
+                // Send a snapshot instead of the missing events.
+                let snapshot = snapshot(&self.battle);
+                let next_id = snapshot.next_id();
+                if let Err(error) = sink.send_snapshot(&snapshot) {
+                    sink.on_disconnect();
+                    return Err(error);
+                }
+                // Replace the stale sink.
+                self.client_sinks_mut().remove_sink(sink.id());
+                self.client_sinks_mut().add_sink(sink)?;
+                return Ok(Resync::Snapshot(next_id));
+            }
+        } else if let Some(error) = error {
+            return Err(error);
+        }
+        // Replace the stale sink.
+        self.client_sinks_mut()
+            .replace_sink_range(sink, start..len)?;
+        Ok(Resync::Replay(start..len))
+    }
+
+    /// Processes a client event prototype received through the client sink with id `sink`.
+    ///
+    /// Works like `EventServer::process_client`, but if the event is rejected and has
+    /// a correlation id, the reason is also sent to the sink with `ClientSink::reject`.
+    /// The sink is disconnected if it fails to receive the rejection.
+    ///
+    /// Errors of derived events or reactions don't cause a rejection
+    /// if the event itself was applied.
+    pub fn process_client_from(
+        &mut self,
+        sink: EventSinkId,
+        event: ClientEventPrototype<R>,
+    ) -> WeaselResult<(), R> {
+        let correlation_id = event.correlation_id();
+        let mut errors = Vec::new();
+        let applied = self.process_client_event(event, Some(sink), &mut errors);
+        let result = merge_errors(errors);
+        if let (Err(error), Some(correlation_id), false) = (&result, correlation_id, applied) {
+            let rejection = Rejection::from_error(correlation_id, error);
+            // The original error is more relevant than a failure to notify the client.
+            if let Err(err) = self.client_sinks.reject(sink, &rejection) {
+                error!("{:?}", err);
+            }
+        }
+        result
+    }
+
+    /// Processes a client event prototype, received through the client sink `sink` if known.
+    /// Errors are collected into `errors`.
+    ///
+    /// Returns true if the event was applied, even if some of its derived events failed.
+    fn process_client_event(
+        &mut self,
+        event: ClientEventPrototype<R>,
+        sink: Option<EventSinkId>,
+        errors: &mut Vec<WeaselErrorType<R>>,
+    ) -> bool {
+        // Verify this event.
+        if let Err(error) = self.battle.verify_client(&event) {
+            errors.push(error);
+            return false;
+        }
+        let player = event.player();
+        let correlation = event
+            .correlation_id()
+            .map(|id| Correlation { id, sink, player });
+        let event = event.prototype();
+        // Members of transactions are checked one by one.
+        if let Some(transaction) = event.as_any().downcast_ref::<Transaction<R>>() {
+            let check = |server: &Self, member: &(dyn Event<R> + Send)| {
+                server.check_client_rights(member, player)
+            };
+            return self.process_transaction(transaction, event.origin(), check, errors);
+        }
+        // Verify event's rights.
+        if let Err(error) = self.check_client_rights(&**event, player) {
+            errors.push(error);
+            return false;
+        }
+        // React to the event and apply it.
+        self.resolve(event, correlation, errors)
+    }
+
+    /// Simulates the outcome of `event`, without changing the battle.
+    ///
+    /// `event` and all its derived events are processed by a scratch server owning a fork
+    /// of the battle. Client sinks, the redo buffer and the history store are not involved.
+    /// The battle's event callback is invoked for the simulated events as well, since it might
+    /// derive new events from them, unless the server was built with
+    /// `disable_simulation_callback`.
+    ///
+    /// Returns an error if `event` is not applied. Errors of derived events are ignored,
+    /// as they would be when processing `event`.
+    pub fn simulate(&mut self, event: EventPrototype<R>) -> WeaselResult<Simulation<R>, R>
+    where
+        R: Clone,
+        R::SR: Clone,
+        R::RR: Clone,
+        R::ER: Clone,
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        Simulation::run(
+            &mut self.battle,
+            event,
+            self.transactions,
+            self.simulation_callback,
+        )
+    }
+
+    /// Creates a server without client sinks for a scratch battle.
+    /// Transactions are processed only if `transactions` is present.
+    pub(crate) fn scratch(battle: Battle<R>, transactions: Option<SnapshotFn<R>>) -> Self {
+        let mut server = Server::builder(battle).build();
+        server.transactions = transactions;
+        server
+    }
+
+    /// Verifies `event`, gives the rules a chance to react to it and applies it.
+    /// Errors are collected into `errors`.
+    ///
+    /// Returns true if the event, or its replacement, was applied.
+    pub(crate) fn process_prototype(
+        &mut self,
+        event: EventPrototype<R>,
+        errors: &mut Vec<WeaselErrorType<R>>,
+    ) -> bool {
+        if let Err(error) = self.battle.verify_prototype(&event) {
+            errors.push(WeaselError::InvalidEvent(
+                event.event().clone(),
+                error.into(),
+            ));
+            return false;
+        }
+        if let Some(transaction) = event.as_any().downcast_ref::<Transaction<R>>() {
+            self.process_transaction(transaction, event.origin(), |_, _| Ok(()), errors)
+        } else {
+            // React to the event and apply it.
+            self.resolve(event, None, errors)
+        }
+    }
+
+    /// Decorates `event` with the rules' version and, if enabled,
+    /// with the checksum of the current battle state.
+    fn versioned(&mut self, event: EventWrapper<R>) -> VersionedEventWrapper<R> {
+        let mut event = event.version(self.battle.rules().version().clone());
+        event.checksum = self.record_checksum();
+        event
+    }
+
+    /// Computes and stores the checksum of the current battle state, if enabled.
+    fn record_checksum(&mut self) -> Option<Checksum> {
+        let checksum = self.checksum.map(|checksum| checksum(&self.battle));
+        if let Some(checksum) = checksum {
+            // The last event in the history is the one just applied.
+            self.checksums
+                .push(self.battle.history().len() - 1, checksum);
+        }
+        checksum
+    }
+
+    /// Returns the events that were discarded by `rewind_to` and can be redone.
+    pub fn redo_buffer(&self) -> &[EventWrapper<R>] {
+        &self.redo_buffer
+    }
+
+    /// Returns the state of the battle right before the first event in the history,
+    /// if some events were evicted by the history compaction.
+    ///
+    /// The checkpoint together with the history contains everything needed to rebuild the battle.
+    pub fn checkpoint(&self) -> Option<&BattleSnapshot<R>> {
+        self.compaction
+            .as_ref()
+            .and_then(|compaction| compaction.base.as_ref())
+    }
+
+    /// Saves a checkpoint every `horizon` events, if compaction is enabled.
+    /// Each time, events preceding the previous checkpoint are evicted from the history.
+    fn compact(&mut self) {
+        let compaction = match &mut self.compaction {
+            Some(compaction) => compaction,
+            None => return,
+        };
+        let history = self.battle.history();
+        let checkpoint_id = compaction
+            .latest
+            .as_ref()
+            .map_or(history.first_id(), |latest| latest.next_id());
+        if history.len() - checkpoint_id < compaction.horizon {
+            return;
+        }
+        if let Some(latest) = compaction.latest.take() {
+            let evicted = self.battle.history.evict(latest.next_id());
+            if let Some(offload) = &mut compaction.offload {
+                let version = self.battle.rules().version();
+                let checksums = &self.checksums;
+                let events = evicted
+                    .into_iter()
+                    .map(|event| {
+                        let checksum = checksums.get(event.id());
+                        let mut event = event.version(version.clone());
+                        event.checksum = checksum;
+                        event
+                    })
+                    .collect();
+                offload(events);
+            }
+            self.checksums.evict(latest.next_id());
+            compaction.base = Some(latest);
+        }
+        compaction.latest = Some((compaction.snapshot)(&self.battle));
+    }
+
+    /// Clears the redo buffer, together with the rights waiting to be given back.
+    fn clear_redo_buffer(&mut self) {
+        self.redo_buffer.clear();
+        self.redo_rights.clear();
+    }
+
     /// Applies an event. The event must be valid.
-    fn apply_event(&mut self, event: EventWrapper<R>) -> WeaselResult<(), R> {
+    /// Errors of its derived events are collected into `errors`.
+    ///
+    /// `correlation` identifies the client that fired the event, if any.
+    fn apply_event(
+        &mut self,
+        event: EventWrapper<R>,
+        correlation: Option<Correlation>,
+        errors: &mut Vec<WeaselErrorType<R>>,
+    ) {
+        let id = event.id();
+        let event_queue = self.commit_event(event, correlation);
+        // Recursively process derived events.
+        if let Some(event_queue) = event_queue {
+            self.process_queue(event_queue, Some(id), errors);
+        }
+    }
+
+    /// Applies an event and sends it to all client sinks. The event must be valid.
+    ///
+    /// Returns the prototypes derived from the event, which are not processed.
+    fn commit_event(
+        &mut self,
+        event: EventWrapper<R>,
+        correlation: Option<Correlation>,
+    ) -> Option<EventQueue<R>> {
+        // The timeline changed, events in the redo buffer are no longer valid.
+        self.clear_redo_buffer();
         let mut event_queue = Some(EventQueue::<R>::new());
         // Apply the event on the battle.
         self.battle.apply(&event, &mut event_queue);
         // Send the event to all client sinks.
+        let versioned = self.versioned(event);
         self.client_sinks
-            .send_all(&event.clone().version(self.battle.rules().version().clone()));
-        // Recursively process derived events.
-        let mut errors = Vec::new();
-        if let Some(event_queue) = event_queue {
-            for mut prototype in event_queue {
-                // Set origin id in derived event, only if it wasn't set explicitly.
-                if prototype.origin().is_none() {
-                    prototype.set_origin(Some(event.id()));
-                }
-                let result = self.process(prototype);
-                if let Err(error) = result {
-                    errors.push(error);
+            .send_all_correlated(&versioned, &self.battle, correlation.as_ref());
+        self.compact();
+        event_queue
+    }
+
+    /// Gives the actor and fight rules a chance to react to a verified event,
+    /// then applies the event.
+    /// Errors are collected into `errors`.
+    ///
+    /// Returns true if the event, or its replacement, was applied.
+    fn resolve(
+        &mut self,
+        event: EventPrototype<R>,
+        correlation: Option<Correlation>,
+        errors: &mut Vec<WeaselErrorType<R>>,
+    ) -> bool {
+        match self.react(event, errors) {
+            Ok(event) => {
+                let event = self.battle.promote(event);
+                self.apply_event(event, correlation, errors);
+                true
+            }
+            Err(error) => {
+                errors.push(error);
+                false
+            }
+        }
+    }
+
+    /// Gives the actor and fight rules a chance to react to a verified event.
+    /// Reactions are processed right away and their errors are collected into `errors`.
+    ///
+    /// Returns the event to apply, which might be a replacement of `event`,
+    /// or the error that prevents its application.
+    fn react(
+        &mut self,
+        event: EventPrototype<R>,
+        errors: &mut Vec<WeaselErrorType<R>>,
+    ) -> WeaselResult<EventPrototype<R>, R> {
+        let mut reactions = Some(EventQueue::<R>::new());
+        let rules = self.battle.rules();
+        let reaction = match rules
+            .actor_rules()
+            .react(&self.battle.state, &event, &mut reactions)
+        {
+            Reaction::Proceed => {
+                rules
+                    .fight_rules()
+                    .react(&self.battle.state, &event, &mut reactions)
+            }
+            reaction => reaction,
+        };
+        // Process the reactions first.
+        let mut verified = true;
+        if let Some(reactions) = reactions {
+            if !reactions.is_empty() {
+                self.process_queue(reactions, event.origin(), errors);
+                // The reactions might have invalidated the event.
+                verified = false;
+            }
+        }
+        let event = match reaction {
+            Reaction::Proceed => event,
+            Reaction::Replace(replacement) => {
+                let mut prototype = EventPrototype::new(replacement);
+                prototype.set_origin(event.origin());
+                verified = false;
+                prototype
+            }
+            Reaction::Cancel => {
+                return Err(WeaselError::InvalidEvent(
+                    event.event().clone(),
+                    Box::new(WeaselError::EventCancelled),
+                ));
+            }
+        };
+        if !verified {
+            if let Err(error) = self.battle.verify_prototype(&event) {
+                return Err(WeaselError::InvalidEvent(
+                    event.event().clone(),
+                    error.into(),
+                ));
+            }
+        }
+        Ok(event)
+    }
+
+    /// Applies all members of `transaction`, or none of them.
+    /// Errors are collected into `errors`.
+    ///
+    /// Members are first tried with client sinks, the event callback, the history store,
+    /// checksums and compaction detached, then applied again for real only if all of
+    /// them succeeded. The events applied for real are stored and sent to the client sinks
+    /// once the last member is applied, while the battle is rolled back if a member fails.
+    ///
+    /// `check` is invoked on each verified member, before the rules react to it.
+    ///
+    /// Returns true if the transaction was committed.
+    fn process_transaction<F>(
+        &mut self,
+        transaction: &Transaction<R>,
+        origin: Option<EventId>,
+        check: F,
+        errors: &mut Vec<WeaselErrorType<R>>,
+    ) -> bool
+    where
+        F: Fn(&Self, &(dyn Event<R> + Send)) -> WeaselResult<(), R>,
+    {
+        let snapshot = match self.transactions {
+            Some(snapshot) => snapshot,
+            None => {
+                errors.push(WeaselError::TransactionsDisabled);
+                return false;
+            }
+        };
+        // Save the state before the transaction, to roll back at the end of the trial.
+        let checkpoint = snapshot(&self.battle);
+        // Try the members without side effects, since they might be rolled back.
+        let detached = self.detach();
+        let result = self.apply_members(transaction, origin, &check, &mut Vec::new());
+        self.battle.restore(checkpoint);
+        self.attach(detached);
+        if let Err(error) = result {
+            errors.push(error);
+            return false;
+        }
+        // Apply the members for real, postponing their derived events. Events fired by the
+        // callback in reaction to a member's reactions might make a later member fail, so
+        // nothing leaves the server until all of them are applied.
+        let checkpoint = snapshot(&self.battle);
+        let start = self.battle.history().len();
+        let mut detached = self.detach();
+        self.battle.event_callback = detached.event_callback.take();
+        self.checksum = detached.checksum.take();
+        let result = self.apply_members(transaction, origin, &check, errors);
+        detached.event_callback = self.battle.event_callback.take();
+        detached.checksum = self.checksum.take();
+        match result {
+            Ok(applied) => {
+                self.attach(detached);
+                self.publish(start);
+                for (id, event_queue) in applied {
+                    if let Some(event_queue) = event_queue {
+                        self.process_queue(event_queue, Some(id), errors);
+                    }
                 }
+                true
+            }
+            Err(error) => {
+                self.battle.restore(checkpoint);
+                self.checksums.truncate(start);
+                self.attach(detached);
+                errors.push(error);
+                false
+            }
+        }
+    }
+
+    /// Stores and sends to all client sinks the events applied from `start` onward,
+    /// then compacts the history.
+    fn publish(&mut self, start: EventId) {
+        let end = self.battle.history().len();
+        if start < end {
+            // The timeline changed, events in the redo buffer are no longer valid.
+            self.clear_redo_buffer();
+        }
+        let version = self.battle.rules().version().clone();
+        for id in start..end {
+            let event = match self.battle.history().event(id) {
+                Some(event) => event.clone(),
+                None => continue,
+            };
+            self.battle.history.store_event(&event, &version);
+            let mut versioned = event.version(version.clone());
+            versioned.checksum = self.checksums.get(id);
+            self.client_sinks
+                .send_all_correlated(&versioned, &self.battle, None);
+        }
+        self.compact();
+    }
+
+    /// Verifies, reacts to and applies the members of `transaction` in order.
+    /// Errors of the reactions are collected into `errors`.
+    ///
+    /// Returns the id and the derived events of each applied member,
+    /// or the error of the first member that couldn't be applied.
+    #[allow(clippy::type_complexity)]
+    fn apply_members<F>(
+        &mut self,
+        transaction: &Transaction<R>,
+        origin: Option<EventId>,
+        check: &F,
+        errors: &mut Vec<WeaselErrorType<R>>,
+    ) -> WeaselResult<Vec<(EventId, Option<EventQueue<R>>)>, R>
+    where
+        F: Fn(&Self, &(dyn Event<R> + Send)) -> WeaselResult<(), R>,
+    {
+        let mut applied = Vec::new();
+        for event in transaction.events() {
+            let mut prototype = EventPrototype::new(event.clone());
+            prototype.set_origin(origin);
+            self.battle
+                .verify_prototype(&prototype)
+                .and_then(|_| check(self, &**event))
+                .map_err(|error| WeaselError::InvalidEvent(event.clone(), error.into()))?;
+            let prototype = self.react(prototype, errors)?;
+            let event = self.battle.promote(prototype);
+            let id = event.id();
+            applied.push((id, self.commit_event(event, None)));
+        }
+        Ok(applied)
+    }
+
+    /// Detaches everything that must not observe events that will be rolled back.
+    fn detach(&mut self) -> Detached<R> {
+        Detached {
+            client_sinks: std::mem::replace(&mut self.client_sinks, MultiClientSink::new()),
+            store: self.battle.history.take_store(),
+            event_callback: self.battle.event_callback.take(),
+            checksum: self.checksum.take(),
+            compaction: self.compaction.take(),
+            redo_buffer: std::mem::take(&mut self.redo_buffer),
+            redo_rights: std::mem::take(&mut self.redo_rights),
+        }
+    }
+
+    /// Attaches again what was detached by `detach`.
+    fn attach(&mut self, detached: Detached<R>) {
+        self.client_sinks = detached.client_sinks;
+        self.battle.history.set_store(detached.store);
+        self.battle.event_callback = detached.event_callback;
+        self.checksum = detached.checksum;
+        self.compaction = detached.compaction;
+        self.redo_buffer = detached.redo_buffer;
+        self.redo_rights = detached.redo_rights;
+    }
+
+    /// Processes all prototypes in `event_queue`. Errors are collected into `errors`.
+    ///
+    /// `origin` is set in the prototypes which don't have an origin already.
+    fn process_queue(
+        &mut self,
+        event_queue: EventQueue<R>,
+        origin: Option<EventId>,
+        errors: &mut Vec<WeaselErrorType<R>>,
+    ) {
+        for mut prototype in event_queue {
+            // Set origin id in derived event, only if it wasn't set explicitly.
+            if prototype.origin().is_none() {
+                prototype.set_origin(origin);
+            }
+            let result = self.process(prototype);
+            if let Err(error) = result {
+                errors.push(error);
             }
         }
-        // If there is an error, return it.
-        // In the case of multiple errors, wrap them into a multi error.
-        match errors.len() {
-            1 => Err(errors.swap_remove(0)),
-            x if x > 1 => Err(WeaselError::MultiError(errors)),
-            _ => Ok(()),
+    }
+
+    /// Checks if `player` can fire `event` from a client.
+    fn check_client_rights(
+        &self,
+        event: &(dyn Event<R> + Send),
+        player: Option<PlayerId>,
+    ) -> WeaselResult<(), R> {
+        match event.rights(&self.battle) {
+            EventRights::Server => Err(WeaselError::ServerOnlyEvent),
+            EventRights::Team(team_id) => {
+                if self.authentication {
+                    if let Some(player) = player {
+                        // Player id is present. Check if it matches the event's rights.
+                        self.check_rights(player, team_id)?;
+                    } else {
+                        // No player id present.
+                        return Err(WeaselError::MissingAuthentication);
+                    }
+                }
+                Ok(())
+            }
+            EventRights::Teams(teams_ids) => {
+                if self.authentication {
+                    if let Some(player) = player {
+                        // Player id is present. Check if it matches the event's rights.
+                        for team_id in teams_ids {
+                            self.check_rights(player, team_id)?;
+                        }
+                    } else {
+                        // No player id present.
+                        return Err(WeaselError::MissingAuthentication);
+                    }
+                }
+                Ok(())
+            }
+            EventRights::None => Ok(()),
         }
     }
 
@@ -101,6 +868,18 @@ impl<R: BattleRules + 'static> Server<R> {
     }
 }
 
+/// Returns the error in `errors`, if there's only one.
+/// In the case of multiple errors, wraps them into a multi error.
+pub(crate) fn merge_errors<R: BattleRules>(
+    mut errors: Vec<WeaselErrorType<R>>,
+) -> WeaselResult<(), R> {
+    match errors.len() {
+        1 => Err(errors.swap_remove(0)),
+        x if x > 1 => Err(WeaselError::MultiError(errors)),
+        _ => Ok(()),
+    }
+}
+
 impl<R: BattleRules> BattleController<R> for Server<R> {
     fn battle(&self) -> &Battle<R> {
         &self.battle
@@ -119,56 +898,33 @@ impl<R: BattleRules + 'static> EventProcessor<R> for Server<R> {
     type ProcessOutput = WeaselResult<(), R>;
 
     fn process(&mut self, event: EventPrototype<R>) -> Self::ProcessOutput {
-        // Verify this event.
-        self.battle
-            .verify_prototype(&event)
-            .map_err(|e| WeaselError::InvalidEvent(event.event().clone(), e.into()))?;
-        // Promote verified event.
-        let event = self.battle.promote(event);
-        // Apply it.
-        self.apply_event(event)
+        let mut errors = Vec::new();
+        self.process_prototype(event, &mut errors);
+        merge_errors(errors)
+    }
+}
+
+impl<R: BattleRules + 'static> EventSimulator<R> for Server<R>
+where
+    R: Clone,
+    R::SR: Clone,
+    R::RR: Clone,
+    R::ER: Clone,
+    Entities<R>: Clone,
+    SpaceModel<R>: Clone,
+    RoundsModel<R>: Clone,
+    EntropyModel<R>: Clone,
+{
+    fn simulate(&mut self, event: EventPrototype<R>) -> WeaselResult<Simulation<R>, R> {
+        Server::simulate(self, event)
     }
 }
 
 impl<R: BattleRules + 'static> EventServer<R> for Server<R> {
     fn process_client(&mut self, event: ClientEventPrototype<R>) -> WeaselResult<(), R> {
-        // Verify this event.
-        self.battle.verify_client(&event)?;
-        // Verify event's rights.
-        match event.rights(&self.battle) {
-            EventRights::Server => {
-                return Err(WeaselError::ServerOnlyEvent);
-            }
-            EventRights::Team(team_id) => {
-                if self.authentication {
-                    if let Some(player) = event.player() {
-                        // Player id is present. Check if it matches the event's rights.
-                        self.check_rights(player, team_id)?;
-                    } else {
-                        // No player id present.
-                        return Err(WeaselError::MissingAuthentication);
-                    }
-                }
-            }
-            EventRights::Teams(teams_ids) => {
-                if self.authentication {
-                    if let Some(player) = event.player() {
-                        // Player id is present. Check if it matches the event's rights.
-                        for team_id in teams_ids {
-                            self.check_rights(player, team_id)?;
-                        }
-                    } else {
-                        // No player id present.
-                        return Err(WeaselError::MissingAuthentication);
-                    }
-                }
-            }
-            EventRights::None => {}
-        }
-        // Promote verified event.
-        let event = self.battle.promote(event.prototype());
-        // Apply it.
-        self.apply_event(event)
+        let mut errors = Vec::new();
+        self.process_client_event(event, None, &mut errors);
+        merge_errors(errors)
     }
 }
 
@@ -176,10 +932,14 @@ impl<R: BattleRules + 'static> EventReceiver<R> for Server<R> {
     fn receive(&mut self, event: VersionedEventWrapper<R>) -> WeaselResult<(), R> {
         // Verify the event.
         self.battle.verify_wrapper(&event)?;
+        // The timeline changed, events in the redo buffer are no longer valid.
+        self.clear_redo_buffer();
         // Apply the event on the battle.
-        self.battle.apply(&event.wrapper(), &mut None);
+        self.battle.apply(event.wrapper(), &mut None);
+        self.record_checksum();
         // Send the event to all client sinks.
-        self.client_sinks.send_all(&event);
+        self.client_sinks.send_all(&event, &self.battle);
+        self.compact();
         Ok(())
     }
 }
@@ -188,6 +948,14 @@ impl<R: BattleRules + 'static> EventReceiver<R> for Server<R> {
 pub struct ServerBuilder<R: BattleRules> {
     battle: Battle<R>,
     authentication: bool,
+    visibility: bool,
+    checksum: Option<fn(&Battle<R>) -> Checksum>,
+    snapshot: Option<SnapshotFn<R>>,
+    max_resync_gap: EventId,
+    transactions: Option<SnapshotFn<R>>,
+    compaction: Option<(EventId, SnapshotFn<R>)>,
+    offload: Option<HistoryOffload<R>>,
+    simulation_callback: bool,
 }
 
 impl<R: BattleRules> ServerBuilder<R> {
@@ -198,12 +966,159 @@ impl<R: BattleRules> ServerBuilder<R> {
         self
     }
 
+    /// Filter the events sent to each client sink with `TeamRules::event_visibility`,
+    /// according to the player returned by `ClientSink::player`.
+    ///
+    /// The visibility of an event is decided with the battle state at the moment the event
+    /// is broadcast. Past events can't be filtered again, thus `add_sink_from`, `add_sink_range`
+    /// and `send_range` return `WeaselError::FilteredEventRange` for non empty ranges, and
+    /// `Server::resync` can only bring clients up to date with snapshots.
+    pub fn enable_visibility(mut self) -> Self {
+        self.visibility = true;
+        self
+    }
+
+    /// Attach the checksum of the battle state to every event sent to client sinks.
+    ///
+    /// Clients with checksums enabled will detect when their state diverges from the server's one.
+    ///
+    /// Events hidden or redacted by `TeamRules::event_visibility` don't carry a checksum.
+    /// Neither do the events sent to a sink after it received one of them, because
+    /// its client can't reach the server's state anymore. Checksums are sent again to the
+    /// sink after it receives a snapshot.
+    #[cfg(feature = "serialization")]
+    pub fn enable_checksum(mut self) -> Self
+    where
+        R: 'static,
+        Entities<R>: Serialize,
+        SpaceModel<R>: Serialize,
+        RoundsModel<R>: Serialize,
+        Scheduler<R>: Serialize,
+        EntropyModel<R>: Serialize,
+    {
+        self.checksum = Some(Battle::checksum);
+        self
+    }
+
+    /// Let `Server::resync` send a snapshot of the battle to clients that are missing
+    /// more than `max_gap` events, or whose state can't be brought up to date with a replay.
+    pub fn enable_snapshot_resync(mut self, max_gap: EventId) -> Self
+    where
+        R: 'static,
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        self.snapshot = Some(Battle::snapshot);
+        self.max_resync_gap = max_gap;
+        self
+    }
+
+    /// Allow the server to process `Transaction` events.
+    ///
+    /// The state of the battle is saved before each transaction, in order to restore it
+    /// if any member of the transaction is invalid. The event callback and the history store
+    /// only observe the members of committed transactions.
+    pub fn enable_transactions(mut self) -> Self
+    where
+        R: 'static,
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        self.transactions = Some(Battle::snapshot);
+        self
+    }
+
+    /// Keep the memory used by the battle history bounded, by evicting the oldest events.
+    ///
+    /// Every `horizon` events the server saves a checkpoint of the battle state. When a new
+    /// checkpoint is saved, events preceding the previous one are evicted. Thus the history
+    /// keeps up to `2 * horizon` events and `Server::checkpoint` contains the state right
+    /// before its first event.
+    ///
+    /// Event ids are not affected. Evicted events can't be sent to client sinks anymore.
+    ///
+    /// # Examples
+    /// ```
+    /// use weasel::{
+    ///     battle_rules, event::DummyEvent, rules::empty::*, Battle, BattleController,
+    ///     BattleRules, EventTrigger, Server,
+    /// };
+    ///
+    /// battle_rules! {}
+    ///
+    /// let battle = Battle::builder(CustomRules::new()).build();
+    /// let mut server = Server::builder(battle).compact_history(10).build();
+    /// for _ in 0..25 {
+    ///     DummyEvent::trigger(&mut server).fire().unwrap();
+    /// }
+    ///
+    /// let history = server.battle().history();
+    /// assert_eq!(history.len(), 25);
+    /// assert_eq!(history.evicted(), 0..10);
+    /// assert_eq!(history.events().len(), 15);
+    /// assert_eq!(server.checkpoint().unwrap().next_id(), 10);
+    /// ```
+    pub fn compact_history(mut self, horizon: EventId) -> Self
+    where
+        R: 'static,
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        self.compaction = Some((horizon.max(1), Battle::snapshot));
+        self.battle.clone_snapshot = Some(BattleSnapshot::clone);
+        self
+    }
+
+    /// Pass the events evicted by the history compaction to `offload`,
+    /// for instance to store them in a file.
+    ///
+    /// It has no effect unless compaction is enabled through `compact_history`.
+    pub fn offload_history(mut self, offload: HistoryOffload<R>) -> Self {
+        self.offload = Some(offload);
+        self
+    }
+
+    /// Don't invoke the battle's event callback for the events of simulations.
+    ///
+    /// Useful when the callback has side effects, such as printing to screen,
+    /// that must happen only for events really applied to the battle.
+    pub fn disable_simulation_callback(mut self) -> Self {
+        self.simulation_callback = false;
+        self
+    }
+
     /// Creates a new server.
     pub fn build(self) -> Server<R> {
+        let offload = self.offload;
         Server {
             battle: self.battle,
-            client_sinks: MultiClientSink::new(),
+            client_sinks: if self.visibility {
+                MultiClientSink::with_visibility()
+            } else {
+                MultiClientSink::new()
+            },
             authentication: self.authentication,
+            redo_buffer: Vec::new(),
+            redo_rights: Vec::new(),
+            checksum: self.checksum,
+            checksums: Checksums::default(),
+            snapshot: self.snapshot,
+            max_resync_gap: self.max_resync_gap,
+            transactions: self.transactions,
+            compaction: self.compaction.map(|(horizon, snapshot)| Compaction {
+                horizon,
+                snapshot,
+                offload,
+                base: None,
+                latest: None,
+            }),
+            simulation_callback: self.simulation_callback,
         }
     }
 }
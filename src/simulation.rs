@@ -0,0 +1,181 @@
+//! Simulations of the outcome of events.
+
+use crate::battle::{Battle, BattleRules};
+use crate::character::Character;
+use crate::entity::{Entities, EntityId};
+use crate::entropy::EntropyModel;
+use crate::error::WeaselResult;
+use crate::event::{EventId, EventPrototype, EventWrapper};
+use crate::round::RoundsModel;
+use crate::server::{merge_errors, Server};
+use crate::snapshot::SnapshotFn;
+use crate::space::SpaceModel;
+use crate::team::{Team, TeamId};
+
+/// The outcome of an event applied to a fork of the battle.
+///
+/// Simulations are created with `Server::simulate` or `Client::simulate`.
+/// The real battle is left untouched.
+///
+/// # Examples
+/// ```
+/// use weasel::{
+///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateTeam,
+///     EventKind, EventTrigger, Id, Server,
+/// };
+///
+/// battle_rules! {}
+///
+/// let battle = Battle::builder(CustomRules::new()).build();
+/// let mut server = Server::builder(battle).build();
+///
+/// let simulation = server
+///     .simulate(CreateTeam::trigger(&mut (), 1).prototype())
+///     .unwrap();
+/// assert_eq!(simulation.events()[0].kind(), EventKind::CreateTeam);
+/// let changes = simulation.teams();
+/// assert!(changes[0].before().is_none());
+/// assert_eq!(changes[0].after().unwrap().id(), &1);
+/// assert_eq!(server.battle().entities().teams().count(), 0);
+/// ```
+pub struct Simulation<R: BattleRules> {
+    /// Entities before the simulation.
+    before: Entities<R>,
+    /// The fork on which the simulation took place.
+    outcome: Battle<R>,
+    /// Id of the simulated event.
+    start: EventId,
+}
+
+impl<R: BattleRules + 'static> Simulation<R> {
+    /// Simulates `event` on a scratch server owning a fork of `battle`.
+    /// Transactions are simulated only if `transactions` is present.
+    ///
+    /// If `callback` is true, the battle's event callback is lent to the fork
+    /// for the duration of the simulation.
+    pub(crate) fn run(
+        battle: &mut Battle<R>,
+        event: EventPrototype<R>,
+        transactions: Option<SnapshotFn<R>>,
+        callback: bool,
+    ) -> WeaselResult<Self, R>
+    where
+        R: Clone,
+        R::SR: Clone,
+        R::RR: Clone,
+        R::ER: Clone,
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        let before = battle.entities().clone();
+        let start = battle.history().len();
+        let mut fork = battle.fork();
+        if callback {
+            fork.event_callback = battle.event_callback.take();
+        }
+        let mut server = Server::scratch(fork, transactions);
+        let mut errors = Vec::new();
+        let applied = server.process_prototype(event, &mut errors);
+        if callback {
+            battle.event_callback = server.battle.event_callback.take();
+        }
+        // Only failures of the simulated event matter, not those of its derived events.
+        if !applied {
+            merge_errors(errors)?;
+        }
+        Ok(Self {
+            before,
+            outcome: server.battle,
+            start,
+        })
+    }
+
+    /// Returns the simulated event followed by all its derived events,
+    /// with the ids they would have in the battle.
+    pub fn events(&self) -> &[EventWrapper<R>] {
+        let history = self.outcome.history();
+        &history.events()[(self.start - history.first_id()) as usize..]
+    }
+
+    /// Returns the changes to the characters involved in the simulated events,
+    /// in the order in which they were first involved.
+    ///
+    /// Characters created and removed during the simulation are omitted.
+    pub fn characters(&self) -> Vec<Change<'_, dyn Character<R> + '_>> {
+        let mut ids: Vec<EntityId<R>> = Vec::new();
+        for event in self.events() {
+            for id in event.entities() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.iter()
+            .map(|id| Change {
+                before: self.before.character(id),
+                after: self.outcome.entities().character(id),
+            })
+            .filter(Change::exists)
+            .collect()
+    }
+
+    /// Returns the changes to the teams involved in the simulated events,
+    /// in the order in which they were first involved.
+    ///
+    /// Teams created and removed during the simulation are omitted.
+    pub fn teams(&self) -> Vec<Change<'_, Team<R>>> {
+        let mut ids: Vec<TeamId<R>> = Vec::new();
+        for event in self.events() {
+            for id in event.teams() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.iter()
+            .map(|id| Change {
+                before: self.before.team(id),
+                after: self.outcome.entities().team(id),
+            })
+            .filter(Change::exists)
+            .collect()
+    }
+
+    /// Returns the fork of the battle after the simulated events.
+    ///
+    /// Useful to evaluate the whole state of the battle, rather than what changed.
+    pub fn outcome(&self) -> &Battle<R> {
+        &self.outcome
+    }
+}
+
+/// The state of an object before and after a simulation.
+pub struct Change<'a, T: ?Sized> {
+    before: Option<&'a T>,
+    after: Option<&'a T>,
+}
+
+impl<'a, T: ?Sized> Change<'a, T> {
+    /// Returns the object before the simulation, or `None` if it was created by the simulation.
+    pub fn before(&self) -> Option<&'a T> {
+        self.before
+    }
+
+    /// Returns the object after the simulation, or `None` if it was removed by the simulation.
+    pub fn after(&self) -> Option<&'a T> {
+        self.after
+    }
+
+    /// Returns true if the object exists before or after the simulation.
+    fn exists(&self) -> bool {
+        self.before.is_some() || self.after.is_some()
+    }
+}
+
+/// A trait for objects that can simulate the outcome of events.
+pub trait EventSimulator<R: BattleRules> {
+    /// Simulates the outcome of `event`, without changing the battle.
+    fn simulate(&mut self, event: EventPrototype<R>) -> WeaselResult<Simulation<R>, R>;
+}
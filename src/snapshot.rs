@@ -0,0 +1,212 @@
+//! Snapshots of a battle's state.
+
+use crate::battle::{Battle, BattlePhase, BattleRules};
+use crate::entity::Entities;
+use crate::entropy::EntropyModel;
+use crate::event::EventId;
+#[cfg(feature = "serialization")]
+use crate::metric::MetricIdType;
+use crate::metric::Metrics;
+use crate::player::Rights;
+use crate::round::{RoundsCount, RoundsModel, TurnStateType, TurnsCount};
+use crate::schedule::Scheduler;
+use crate::space::SpaceModel;
+#[cfg(feature = "serialization")]
+use crate::team::TeamId;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
+
+/// Function to take a snapshot of a battle.
+///
+/// It allows to store `Battle::snapshot` without carrying around its trait bounds.
+pub(crate) type SnapshotFn<R> = fn(&Battle<R>) -> BattleSnapshot<R>;
+
+/// Function to clone a snapshot.
+///
+/// It allows to clone a `BattleSnapshot` without carrying around its trait bounds.
+pub(crate) type CloneFn<R> = fn(&BattleSnapshot<R>) -> BattleSnapshot<R>;
+
+/// A copy of the complete state of a battle, taken after a given event.
+///
+/// A snapshot contains entities, the space, rounds and entropy models, scheduled events,
+/// metrics and players' rights. It can be used to rebuild a battle without replaying its entire history;
+/// only the events that follow the snapshot need to be applied.
+///
+/// Rules are not part of the snapshot and must be provided when restoring it.
+///
+/// # Examples
+/// ```
+/// use weasel::{
+///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateTeam,
+///     EventReceiver, EventTrigger, Server,
+/// };
+///
+/// battle_rules! {}
+///
+/// let battle = Battle::builder(CustomRules::new()).build();
+/// let mut server = Server::builder(battle).build();
+/// CreateTeam::trigger(&mut server, 1).fire().unwrap();
+///
+/// // Take a snapshot and then create another team.
+/// let snapshot = server.battle().snapshot();
+/// CreateTeam::trigger(&mut server, 2).fire().unwrap();
+///
+/// // Restore the snapshot and replay the events that came after it.
+/// let battle = Battle::builder(CustomRules::new()).snapshot(snapshot).build();
+/// let mut restored = Server::builder(battle).build();
+/// for event in server.battle().versioned_events(1..2) {
+///     restored.receive(event).unwrap();
+/// }
+/// assert_eq!(restored.battle().entities().teams().count(), 2);
+/// assert_eq!(restored.battle().history().len(), 2);
+/// ```
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct BattleSnapshot<R: BattleRules> {
+    pub(crate) next_id: EventId,
+
+    pub(crate) phase: BattlePhase,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "Entities<R>: Serialize",
+            deserialize = "Entities<R>: Deserialize<'de>"
+        ))
+    )]
+    pub(crate) entities: Entities<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "SpaceModel<R>: Serialize",
+            deserialize = "SpaceModel<R>: Deserialize<'de>"
+        ))
+    )]
+    pub(crate) space: SpaceModel<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "RoundsModel<R>: Serialize",
+            deserialize = "RoundsModel<R>: Deserialize<'de>"
+        ))
+    )]
+    pub(crate) rounds: RoundsModel<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "TurnStateType<R>: Serialize",
+            deserialize = "TurnStateType<R>: Deserialize<'de>"
+        ))
+    )]
+    pub(crate) turn_state: TurnStateType<R>,
+
+    pub(crate) completed_rounds: RoundsCount,
+
+    pub(crate) completed_turns: TurnsCount,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "Scheduler<R>: Serialize",
+            deserialize = "Scheduler<R>: Deserialize<'de>"
+        ))
+    )]
+    pub(crate) scheduler: Scheduler<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "EntropyModel<R>: Serialize",
+            deserialize = "EntropyModel<R>: Deserialize<'de>"
+        ))
+    )]
+    pub(crate) entropy: EntropyModel<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "MetricIdType<R>: Serialize",
+            deserialize = "MetricIdType<R>: Deserialize<'de>"
+        ))
+    )]
+    pub(crate) metrics: Metrics<R>,
+
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "TeamId<R>: Serialize",
+            deserialize = "TeamId<R>: Deserialize<'de>"
+        ))
+    )]
+    pub(crate) rights: Rights<R>,
+}
+
+impl<R: BattleRules> BattleSnapshot<R> {
+    /// Creates a snapshot of the current state of `battle`.
+    pub(crate) fn new(battle: &Battle<R>) -> Self
+    where
+        Entities<R>: Clone,
+        SpaceModel<R>: Clone,
+        RoundsModel<R>: Clone,
+        EntropyModel<R>: Clone,
+    {
+        Self {
+            next_id: battle.history.len(),
+            phase: battle.state.phase,
+            entities: battle.state.entities.clone(),
+            space: battle.state.space.model().clone(),
+            rounds: battle.state.rounds.model().clone(),
+            turn_state: battle.state.rounds.state().clone(),
+            completed_rounds: battle.state.rounds.completed_rounds(),
+            completed_turns: battle.state.rounds.completed_turns(),
+            scheduler: battle.state.rounds.scheduler().clone(),
+            entropy: battle.entropy.model().clone(),
+            metrics: battle.metrics.clone(),
+            rights: battle.rights.clone(),
+        }
+    }
+
+    /// Returns the id of the first event not included in this snapshot.
+    ///
+    /// A battle restored from this snapshot will accept only events starting from this id.
+    pub fn next_id(&self) -> EventId {
+        self.next_id
+    }
+
+    /// Returns the phase of the battle at the moment of the snapshot.
+    pub fn phase(&self) -> BattlePhase {
+        self.phase
+    }
+
+    /// Returns the entities stored in this snapshot.
+    pub fn entities(&self) -> &Entities<R> {
+        &self.entities
+    }
+}
+
+impl<R: BattleRules> Clone for BattleSnapshot<R>
+where
+    Entities<R>: Clone,
+    SpaceModel<R>: Clone,
+    RoundsModel<R>: Clone,
+    EntropyModel<R>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            next_id: self.next_id,
+            phase: self.phase,
+            entities: self.entities.clone(),
+            space: self.space.clone(),
+            rounds: self.rounds.clone(),
+            turn_state: self.turn_state.clone(),
+            completed_rounds: self.completed_rounds,
+            completed_turns: self.completed_turns,
+            scheduler: self.scheduler.clone(),
+            entropy: self.entropy.clone(),
+            metrics: self.metrics.clone(),
+            rights: self.rights.clone(),
+        }
+    }
+}
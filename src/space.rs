@@ -18,6 +18,19 @@ pub struct Space<R: BattleRules> {
     rules: R::SR,
 }
 
+impl<R: BattleRules> Clone for Space<R>
+where
+    SpaceModel<R>: Clone,
+    R::SR: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            model: self.model.clone(),
+            rules: self.rules.clone(),
+        }
+    }
+}
+
 impl<R: BattleRules> Space<R> {
     /// Creates a new space object.
     pub(crate) fn new(seed: Option<SpaceSeed<R>>, rules: R::SR) -> Self {
@@ -27,6 +40,16 @@ impl<R: BattleRules> Space<R> {
         }
     }
 
+    /// Creates a space object from an existing model.
+    pub(crate) fn from_model(model: SpaceModel<R>, rules: R::SR) -> Self {
+        Self { model, rules }
+    }
+
+    /// Regenerates this space's model starting from the given seed.
+    pub(crate) fn regenerate_model(&mut self, seed: &Option<SpaceSeed<R>>) {
+        self.model = self.rules.generate_model(seed)
+    }
+
     /// See [check_move](trait.SpaceRules.html#method.check_move).
     pub(crate) fn check_move<'a>(
         &self,
@@ -36,6 +59,11 @@ impl<R: BattleRules> Space<R> {
         self.rules.check_move(&self.model, claim, position)
     }
 
+    /// See [reachable_positions](trait.SpaceRules.html#method.reachable_positions).
+    pub(crate) fn reachable_positions(&self, entity: &dyn Entity<R>) -> Vec<Position<R>> {
+        self.rules.reachable_positions(&self.model, entity)
+    }
+
     /// See [move_entity](trait.SpaceRules.html#method.move_entity).
     pub(crate) fn move_entity<'a>(
         &mut self,
@@ -115,6 +143,20 @@ pub trait SpaceRules<R: BattleRules> {
         Ok(())
     }
 
+    /// Returns the positions that `entity` might reach with a movement.
+    ///
+    /// Positions don't need to be verified, because `Battle::legal_actions` discards
+    /// those refused by `check_move`.
+    ///
+    /// The provided implementation returns no positions.
+    fn reachable_positions(
+        &self,
+        _model: &Self::SpaceModel,
+        _entity: &dyn Entity<R>,
+    ) -> Vec<Self::Position> {
+        Vec::new()
+    }
+
     /// Moves an entity into a new position.
     ///
     /// Position's correctness will be validated beforehand with `check_move`,
@@ -352,6 +394,10 @@ impl<R: BattleRules + 'static> Event<R> for MoveEntity<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![self.id.clone()]
+    }
 }
 
 /// Trigger to build and fire a `DummyEvent` event.
@@ -417,7 +463,7 @@ pub struct ResetSpace<R: BattleRules> {
 
 impl<R: BattleRules> ResetSpace<R> {
     /// Returns a trigger for this event.
-    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> ResetSpaceTrigger<R, P> {
+    pub fn trigger<P: EventProcessor<R>>(processor: &mut P) -> ResetSpaceTrigger<'_, R, P> {
         ResetSpaceTrigger {
             processor,
             seed: None,
@@ -560,7 +606,7 @@ impl<R: BattleRules> AlterSpace<R> {
     pub fn trigger<P: EventProcessor<R>>(
         processor: &mut P,
         alteration: SpaceAlteration<R>,
-    ) -> AlterSpaceTrigger<R, P> {
+    ) -> AlterSpaceTrigger<'_, R, P> {
         AlterSpaceTrigger {
             processor,
             alteration,
@@ -34,8 +34,16 @@ pub type StatusesAlteration<R> = <<R as BattleRules>::CR as CharacterRules<R>>::
 pub type StatusDuration = EventId;
 
 /// Stores a `Status` and additional information about it.
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct AppliedStatus<R: BattleRules> {
     /// The status.
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "Status<R>: Serialize",
+            deserialize = "Status<R>: Deserialize<'de>"
+        ))
+    )]
     status: Status<R>,
     /// An optional link to the origin event.
     origin: Option<EventId>,
@@ -43,6 +51,19 @@ pub struct AppliedStatus<R: BattleRules> {
     duration: StatusDuration,
 }
 
+impl<R: BattleRules> Clone for AppliedStatus<R>
+where
+    Status<R>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            status: self.status.clone(),
+            origin: self.origin,
+            duration: self.duration,
+        }
+    }
+}
+
 impl<R: BattleRules> AppliedStatus<R> {
     /// Creates a new `AppliedStatus` without any origin.
     pub fn new(status: Status<R>) -> Self {
@@ -354,6 +375,10 @@ impl<R: BattleRules + 'static> Event<R> for InflictStatus<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![self.entity_id.clone()]
+    }
 }
 
 /// Trigger to build and fire an `InflictStatus` event.
@@ -560,6 +585,10 @@ impl<R: BattleRules + 'static> Event<R> for ClearStatus<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![self.entity_id.clone()]
+    }
 }
 
 /// Trigger to build and fire a `ClearStatus` event.
@@ -719,6 +748,10 @@ impl<R: BattleRules + 'static> Event<R> for AlterStatuses<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        vec![self.id.clone()]
+    }
 }
 
 /// Trigger to build and fire an `AlterStatuses` event.
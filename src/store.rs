@@ -0,0 +1,324 @@
+//! Storage backends for battle histories.
+//!
+//! A `History` keeps its events in memory. A `HistoryStore` attached to the battle
+//! additionally receives every applied event, so that the timeline can be read back
+//! even after the oldest events were evicted from memory, or after a crash.
+
+use crate::battle::BattleRules;
+#[cfg(feature = "binary")]
+use crate::binary;
+#[cfg(feature = "binary")]
+use crate::error::WeaselErrorType;
+use crate::error::{WeaselError, WeaselResult};
+use crate::event::{EventId, VersionedEventWrapper};
+#[cfg(feature = "binary")]
+use crate::serde::FlatVersionedEvent;
+#[cfg(feature = "binary")]
+use serde::{de::DeserializeOwned, Serialize};
+use std::convert::TryInto;
+#[cfg(feature = "binary")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "binary")]
+use std::io::{self, BufReader, Seek, SeekFrom};
+#[cfg(feature = "binary")]
+use std::marker::PhantomData;
+use std::ops::Range;
+#[cfg(feature = "binary")]
+use std::path::Path;
+
+/// A storage for a contiguous sequence of versioned events.
+///
+/// Stores are attached to a battle with `BattleBuilder::history_store`. From then on,
+/// each event applied to the battle is appended to the store. Speculative events, such as
+/// the predictions of clients, are not.
+///
+/// If the store fails to save an event, it's no longer updated and `Battle::read_events`
+/// returns the error.
+pub trait HistoryStore<R: BattleRules> {
+    /// Appends `event` at the end of the store.
+    ///
+    /// The event's id must be equal to `len()`, unless the store is empty.
+    fn append(&mut self, event: &VersionedEventWrapper<R>) -> WeaselResult<(), R>;
+
+    /// Returns all events whose id is inside `range`.
+    fn range(&self, range: Range<EventId>) -> WeaselResult<Vec<VersionedEventWrapper<R>>, R>;
+
+    /// Returns the id of the first event in the store.
+    fn first_id(&self) -> EventId;
+
+    /// Returns the id following the one of the last event in the store.
+    fn len(&self) -> EventId;
+
+    /// Returns whether this store doesn't contain any event.
+    fn is_empty(&self) -> bool {
+        self.len() == self.first_id()
+    }
+
+    /// Removes all events with an id equal to or greater than `len`.
+    fn truncate(&mut self, len: EventId) -> WeaselResult<(), R>;
+}
+
+/// Checks that `event` can be appended to `store`.
+fn verify_append<R, S>(store: &S, event: &VersionedEventWrapper<R>) -> WeaselResult<(), R>
+where
+    R: BattleRules,
+    S: HistoryStore<R> + ?Sized,
+{
+    if !store.is_empty() && event.id() != store.len() {
+        return Err(WeaselError::NonContiguousEventId(event.id(), store.len()));
+    }
+    Ok(())
+}
+
+/// Checks that `range` lies inside `store` and returns its position relative to the first event.
+fn verify_range<R, S>(store: &S, range: &Range<EventId>) -> WeaselResult<Range<usize>, R>
+where
+    R: BattleRules,
+    S: HistoryStore<R> + ?Sized,
+{
+    if range.start > range.end || range.start < store.first_id() || range.end > store.len() {
+        return Err(WeaselError::InvalidEventRange(range.clone(), store.len()));
+    }
+    let first_id = store.first_id();
+    Ok((range.start - first_id) as usize..(range.end - first_id) as usize)
+}
+
+/// A store keeping events in memory.
+///
+/// # Examples
+/// ```
+/// use weasel::store::{HistoryStore, MemoryHistoryStore};
+/// use weasel::{
+///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateTeam,
+///     EventTrigger, Server,
+/// };
+///
+/// battle_rules! {}
+///
+/// let battle = Battle::builder(CustomRules::new())
+///     .history_store(Box::new(MemoryHistoryStore::new()))
+///     .build();
+/// let mut server = Server::builder(battle).build();
+/// CreateTeam::trigger(&mut server, 1).fire().unwrap();
+///
+/// let store = server.battle().history().store().unwrap();
+/// assert_eq!(store.len(), 1);
+/// assert_eq!(store.range(0..1).unwrap()[0].id(), 0);
+/// ```
+pub struct MemoryHistoryStore<R: BattleRules> {
+    events: Vec<VersionedEventWrapper<R>>,
+    first_id: EventId,
+}
+
+impl<R: BattleRules> MemoryHistoryStore<R> {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            first_id: 0,
+        }
+    }
+
+    /// Returns all events in this store.
+    pub fn events(&self) -> &[VersionedEventWrapper<R>] {
+        &self.events
+    }
+}
+
+impl<R: BattleRules> Default for MemoryHistoryStore<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: BattleRules> HistoryStore<R> for MemoryHistoryStore<R> {
+    fn append(&mut self, event: &VersionedEventWrapper<R>) -> WeaselResult<(), R> {
+        verify_append(self, event)?;
+        if self.is_empty() {
+            self.first_id = event.id();
+        }
+        self.events.push(event.clone());
+        Ok(())
+    }
+
+    fn range(&self, range: Range<EventId>) -> WeaselResult<Vec<VersionedEventWrapper<R>>, R> {
+        let range = verify_range(self, &range)?;
+        Ok(self.events[range].to_vec())
+    }
+
+    fn first_id(&self) -> EventId {
+        self.first_id
+    }
+
+    fn len(&self) -> EventId {
+        let stored: EventId = self.events.len().try_into().unwrap();
+        self.first_id + stored
+    }
+
+    fn truncate(&mut self, len: EventId) -> WeaselResult<(), R> {
+        self.events
+            .truncate(len.saturating_sub(self.first_id) as usize);
+        Ok(())
+    }
+}
+
+/// An append-only store that streams events to a file.
+///
+/// Each event is written as a `FlatVersionedEvent`, in its own frame of the `binary` format,
+/// as soon as it's applied. Opening an existing file restores all events it contains,
+/// which makes it possible to recover a battle after a crash: replay the stored events
+/// on a new battle having the same store.
+///
+/// # Examples
+/// ```
+/// use weasel::store::{FileHistoryStore, HistoryStore};
+/// use weasel::{
+///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateTeam,
+///     EventReceiver, EventTrigger, Server,
+/// };
+///
+/// battle_rules! {}
+///
+/// let path = std::env::temp_dir().join("weasel_file_history_store_doc");
+/// # let _ = std::fs::remove_file(&path);
+/// let store = FileHistoryStore::open(&path).unwrap();
+/// let battle = Battle::builder(CustomRules::new())
+///     .history_store(Box::new(store))
+///     .build();
+/// let mut server = Server::builder(battle).build();
+/// CreateTeam::trigger(&mut server, 1).fire().unwrap();
+/// drop(server);
+///
+/// // Recover the battle from the file.
+/// let store = FileHistoryStore::<CustomRules>::open(&path).unwrap();
+/// let events = store.range(0..store.len()).unwrap();
+/// let battle = Battle::builder(CustomRules::new())
+///     .history_store(Box::new(store))
+///     .build();
+/// let mut server = Server::builder(battle).build();
+/// for event in events {
+///     server.receive(event).unwrap();
+/// }
+/// assert_eq!(server.battle().entities().teams().count(), 1);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[cfg(feature = "binary")]
+pub struct FileHistoryStore<R: BattleRules> {
+    file: File,
+    first_id: EventId,
+    /// Position in the file of each stored event.
+    offsets: Vec<u64>,
+    /// Position in the file right after the last stored event.
+    end: u64,
+    _phantom: PhantomData<R>,
+}
+
+#[cfg(feature = "binary")]
+impl<R> FileHistoryStore<R>
+where
+    R: BattleRules + 'static,
+    FlatVersionedEvent<R>: Serialize + DeserializeOwned,
+{
+    /// Opens the store saved in the file at `path`, creating the file if it doesn't exist.
+    ///
+    /// An incomplete last event, left for instance by a crash in the middle of a write,
+    /// is discarded and the file is truncated after the last valid event.
+    /// Any other failure to read the file, including corrupted events, is returned as an error.
+    pub fn open<P: AsRef<Path>>(path: P) -> WeaselResult<Self, R> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(store_error)?;
+        let mut store = Self {
+            file,
+            first_id: 0,
+            offsets: Vec::new(),
+            end: 0,
+            _phantom: PhantomData,
+        };
+        let mut reader = BufReader::new(&store.file);
+        loop {
+            let event = match binary::read_frame::<_, FlatVersionedEvent<R>>(&mut reader) {
+                Ok(Some(event)) => event,
+                Ok(None) => break,
+                // The last frame was not written completely.
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(store_error(error)),
+            };
+            if store.offsets.is_empty() {
+                store.first_id = event.id();
+            } else if event.id() != store.len() {
+                return Err(WeaselError::NonContiguousEventId(event.id(), store.len()));
+            }
+            store.offsets.push(store.end);
+            store.end = reader.stream_position().map_err(store_error)?;
+        }
+        store.file.set_len(store.end).map_err(store_error)?;
+        Ok(store)
+    }
+}
+
+#[cfg(feature = "binary")]
+impl<R> HistoryStore<R> for FileHistoryStore<R>
+where
+    R: BattleRules + 'static,
+    FlatVersionedEvent<R>: Serialize + DeserializeOwned,
+{
+    fn append(&mut self, event: &VersionedEventWrapper<R>) -> WeaselResult<(), R> {
+        verify_append(self, event)?;
+        let flat: FlatVersionedEvent<R> = event.clone().into();
+        binary::write_frame(&mut self.file, &flat).map_err(store_error)?;
+        if self.is_empty() {
+            self.first_id = event.id();
+        }
+        self.offsets.push(self.end);
+        self.end = self.file.metadata().map_err(store_error)?.len();
+        Ok(())
+    }
+
+    fn range(&self, range: Range<EventId>) -> WeaselResult<Vec<VersionedEventWrapper<R>>, R> {
+        let range = verify_range(self, &range)?;
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(self.offsets[range.start]))
+            .map_err(store_error)?;
+        let mut reader = BufReader::new(file);
+        let mut events = Vec::with_capacity(range.len());
+        for _ in range {
+            let event: FlatVersionedEvent<R> = binary::read_frame(&mut reader)
+                .map_err(store_error)?
+                .ok_or_else(|| store_error(io::ErrorKind::UnexpectedEof.into()))?;
+            events.push(event.into());
+        }
+        Ok(events)
+    }
+
+    fn first_id(&self) -> EventId {
+        self.first_id
+    }
+
+    fn len(&self) -> EventId {
+        let stored: EventId = self.offsets.len().try_into().unwrap();
+        self.first_id + stored
+    }
+
+    fn truncate(&mut self, len: EventId) -> WeaselResult<(), R> {
+        if len >= self.len() {
+            return Ok(());
+        }
+        let stored = len.saturating_sub(self.first_id) as usize;
+        self.end = self.offsets[stored];
+        self.offsets.truncate(stored);
+        self.file.set_len(self.end).map_err(store_error)
+    }
+}
+
+/// Converts an I/O error into a `WeaselError`.
+#[cfg(feature = "binary")]
+fn store_error<R: BattleRules>(error: io::Error) -> WeaselErrorType<R> {
+    WeaselError::HistoryStoreError(error.to_string())
+}
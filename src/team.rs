@@ -3,9 +3,12 @@
 use crate::battle::{Battle, BattleRules, BattleState};
 use crate::creature::{Creature, CreatureId};
 use crate::error::{WeaselError, WeaselResult};
-use crate::event::{Event, EventKind, EventProcessor, EventQueue, EventTrigger};
+use crate::event::{
+    Event, EventKind, EventProcessor, EventQueue, EventTrigger, EventWrapper, Visibility,
+};
 use crate::metric::system::*;
 use crate::metric::ReadMetrics;
+use crate::player::{PlayerId, RightsHandle};
 use crate::util::Id;
 #[cfg(feature = "serialization")]
 use serde::{Deserialize, Serialize};
@@ -17,17 +20,53 @@ use std::{any::Any, iter};
 ///
 /// A team represents the unit of control of a player. Teams must achieve their objectives in
 /// order to win the battle.
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub struct Team<R: BattleRules> {
     /// The id of this team.
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "TeamId<R>: Serialize",
+            deserialize = "TeamId<R>: Deserialize<'de>"
+        ))
+    )]
     id: TeamId<R>,
     /// Ids of all creatures which are currently part of this team.
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "CreatureId<R>: Serialize",
+            deserialize = "CreatureId<R>: Deserialize<'de>"
+        ))
+    )]
     creatures: Vec<CreatureId<R>>,
     /// `Conclusion`, if any, reached by this team.
     conclusion: Option<Conclusion>,
     /// Team objectives.
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "Objectives<R>: Serialize",
+            deserialize = "Objectives<R>: Deserialize<'de>"
+        ))
+    )]
     objectives: Objectives<R>,
 }
 
+impl<R: BattleRules> Clone for Team<R>
+where
+    Objectives<R>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            creatures: self.creatures.clone(),
+            conclusion: self.conclusion,
+            objectives: self.objectives.clone(),
+        }
+    }
+}
+
 impl<R: BattleRules> Team<R> {
     /// Returns an iterator over creatures.
     pub fn creatures(&self) -> impl Iterator<Item = &CreatureId<R>> {
@@ -141,6 +180,30 @@ pub trait TeamRules<R: BattleRules> {
     ) -> Option<Conclusion> {
         None
     }
+
+    /// Decides how much of `event` can be seen by `player`.
+    /// This check is done by servers before sending an event to a client sink,
+    /// if visibility is enabled through `ServerBuilder::enable_visibility`.
+    ///
+    /// `player` is the one returned by `ClientSink::player`, while `rights` tells which teams
+    /// are controlled by each player. `state` is the current state of the battle.
+    ///
+    /// Clients apply what they receive, thus their battle diverges from the server's one
+    /// as soon as an event is not fully visible. Every later event depending on a hidden or
+    /// redacted one must be hidden or redacted as well, otherwise clients fail to verify it.
+    /// For instance, hiding the creation of a creature requires hiding also its turns,
+    /// movements and any other event involving it.
+    ///
+    /// The provided implementation makes every event fully visible.
+    fn event_visibility(
+        &self,
+        _state: &BattleState<R>,
+        _event: &EventWrapper<R>,
+        _player: Option<PlayerId>,
+        _rights: &RightsHandle<'_, R>,
+    ) -> Visibility<R> {
+        Visibility::Full
+    }
 }
 
 /// Type to drive the generation of the objectives for a given team.
@@ -282,7 +345,7 @@ impl<R: BattleRules + 'static> Event<R> for CreateTeam<R> {
                     return Err(WeaselError::KinshipRelation);
                 }
                 // Teams in the relations list must exist.
-                if battle.entities().team(&team_id).is_none() {
+                if battle.entities().team(team_id).is_none() {
                     return Err(WeaselError::TeamNotFound(team_id.clone()));
                 }
             }
@@ -347,6 +410,14 @@ impl<R: BattleRules + 'static> Event<R> for CreateTeam<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn teams(&self) -> Vec<TeamId<R>> {
+        let mut teams = vec![self.id.clone()];
+        if let Some(relations) = &self.relations {
+            teams.extend(relations.iter().map(|(team_id, _)| team_id.clone()));
+        }
+        teams
+    }
 }
 
 /// Trigger to build and fire a `CreateTeam` event.
@@ -417,12 +488,35 @@ pub enum Relation {
 }
 
 /// A pair of two teams that are part of a relationship.
-#[derive(Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 pub(crate) struct RelationshipPair<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "TeamId<R>: Serialize",
+            deserialize = "TeamId<R>: Deserialize<'de>"
+        ))
+    )]
     pub(crate) first: TeamId<R>,
+    #[cfg_attr(
+        feature = "serialization",
+        serde(bound(
+            serialize = "TeamId<R>: Serialize",
+            deserialize = "TeamId<R>: Deserialize<'de>"
+        ))
+    )]
     pub(crate) second: TeamId<R>,
 }
 
+impl<R: BattleRules> Clone for RelationshipPair<R> {
+    fn clone(&self) -> Self {
+        Self {
+            first: self.first.clone(),
+            second: self.second.clone(),
+        }
+    }
+}
+
 impl<R: BattleRules> Debug for RelationshipPair<R> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(
@@ -582,6 +676,13 @@ impl<R: BattleRules + 'static> Event<R> for SetRelations<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn teams(&self) -> Vec<TeamId<R>> {
+        self.relations
+            .iter()
+            .flat_map(|(first, second, _)| vec![first.clone(), second.clone()])
+            .collect()
+    }
 }
 
 /// Trigger to build and fire a `SetRelations` event.
@@ -725,6 +826,10 @@ impl<R: BattleRules + 'static> Event<R> for ConcludeObjectives<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn teams(&self) -> Vec<TeamId<R>> {
+        vec![self.id.clone()]
+    }
 }
 
 /// Trigger to build and fire a `ConcludeObjectives` event.
@@ -809,7 +914,7 @@ impl<R: BattleRules> ResetObjectives<R> {
     pub fn trigger<P: EventProcessor<R>>(
         processor: &mut P,
         id: TeamId<R>,
-    ) -> ResetObjectivesTrigger<R, P> {
+    ) -> ResetObjectivesTrigger<'_, R, P> {
         ResetObjectivesTrigger {
             processor,
             id,
@@ -879,6 +984,10 @@ impl<R: BattleRules + 'static> Event<R> for ResetObjectives<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn teams(&self) -> Vec<TeamId<R>> {
+        vec![self.id.clone()]
+    }
 }
 
 /// Trigger to build and fire a `ResetObjectives` event.
@@ -960,7 +1069,7 @@ impl<R: BattleRules> RemoveTeam<R> {
     pub fn trigger<P: EventProcessor<R>>(
         processor: &mut P,
         id: TeamId<R>,
-    ) -> RemoveTeamTrigger<R, P> {
+    ) -> RemoveTeamTrigger<'_, R, P> {
         RemoveTeamTrigger { processor, id }
     }
 
@@ -1020,6 +1129,10 @@ impl<R: BattleRules + 'static> Event<R> for RemoveTeam<R> {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn teams(&self) -> Vec<TeamId<R>> {
+        vec![self.id.clone()]
+    }
 }
 
 /// Trigger to build and fire a `RemoveTeam` event.
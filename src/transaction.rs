@@ -0,0 +1,182 @@
+//! Atomic batches of events.
+
+use crate::battle::{Battle, BattleRules};
+use crate::entity::EntityId;
+use crate::error::{WeaselError, WeaselResult};
+use crate::event::{Event, EventKind, EventProcessor, EventQueue, EventTrigger};
+#[cfg(feature = "serialization")]
+use crate::serde::FlatEvent;
+use crate::team::TeamId;
+#[cfg(feature = "serialization")]
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt::{Debug, Formatter, Result};
+
+/// Event to apply a group of events atomically.
+///
+/// Servers unpack a transaction into its members, which are verified, reacted to by the rules
+/// and applied one after the other. If any member is invalid or cancelled, the battle is brought
+/// back to the state before the transaction and none of the members, nor their reactions,
+/// is kept. Otherwise all members are stored in the history, each one preceded by its reactions,
+/// while their derived events are processed after the last member.
+///
+/// Keep in mind that:
+/// - servers must enable transactions with `ServerBuilder::enable_transactions`.
+/// - the members of a transaction fired by a client must satisfy the rights of the client's
+///   player, as if they were fired separately.
+/// - `ActorRules::react` and `FightRules::react` are invoked for each member,
+///   as if it were fired separately.
+/// - transactions can't contain other transactions.
+/// - the battle's event callback is invoked only for the members of transactions whose trial
+///   succeeded. Events it fires in response to reactions are processed right away, thus they
+///   can still make a member fail and roll back the transaction.
+/// - member ids aren't contiguous if the rules react to members.
+/// - clients don't predict the outcome of transactions.
+///
+/// # Examples
+/// ```
+/// use weasel::{
+///     battle_rules, rules::empty::*, Battle, BattleController, BattleRules, CreateTeam,
+///     EventTrigger, Server, Transaction,
+/// };
+///
+/// battle_rules! {}
+///
+/// let battle = Battle::builder(CustomRules::new()).build();
+/// let mut server = Server::builder(battle).enable_transactions().build();
+///
+/// // The second team can't be created, thus the first one isn't created either.
+/// let events = vec![
+///     CreateTeam::trigger(&mut (), 1).event(),
+///     CreateTeam::trigger(&mut (), 1).event(),
+/// ];
+/// assert!(Transaction::trigger(&mut server, events).fire().is_err());
+/// assert_eq!(server.battle().entities().teams().count(), 0);
+///
+/// let events = vec![
+///     CreateTeam::trigger(&mut (), 1).event(),
+///     CreateTeam::trigger(&mut (), 2).event(),
+/// ];
+/// assert!(Transaction::trigger(&mut server, events).fire().is_ok());
+/// assert_eq!(server.battle().entities().teams().count(), 2);
+/// assert_eq!(server.battle().history().len(), 2);
+/// ```
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+pub struct Transaction<R: BattleRules> {
+    #[cfg_attr(
+        feature = "serialization",
+        serde(
+            bound(
+                serialize = "R: 'static, FlatEvent<R>: Serialize",
+                deserialize = "R: 'static, FlatEvent<R>: Deserialize<'de>"
+            ),
+            with = "crate::serde::boxed_events"
+        )
+    )]
+    events: Vec<Box<dyn Event<R> + Send>>,
+}
+
+impl<R: BattleRules> Transaction<R> {
+    /// Returns a trigger for this event.
+    pub fn trigger<P: EventProcessor<R>>(
+        processor: &mut P,
+        events: Vec<Box<dyn Event<R> + Send>>,
+    ) -> TransactionTrigger<'_, R, P> {
+        TransactionTrigger { processor, events }
+    }
+
+    /// Returns the members of this transaction.
+    pub fn events(&self) -> &[Box<dyn Event<R> + Send>] {
+        &self.events
+    }
+}
+
+impl<R: BattleRules> Debug for Transaction<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Transaction {{ events: {:?} }}", self.events)
+    }
+}
+
+impl<R: BattleRules> Clone for Transaction<R> {
+    fn clone(&self) -> Self {
+        Self {
+            events: self.events.clone(),
+        }
+    }
+}
+
+impl<R: BattleRules + 'static> Event<R> for Transaction<R> {
+    fn verify(&self, _battle: &Battle<R>) -> WeaselResult<(), R> {
+        // Members can be verified only while the transaction is being processed,
+        // because each one depends on the outcome of those preceding it.
+        if self
+            .events
+            .iter()
+            .any(|event| event.kind() == EventKind::Transaction)
+        {
+            Err(WeaselError::NestedTransaction)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn apply(&self, battle: &mut Battle<R>, event_queue: &mut Option<EventQueue<R>>) {
+        // Servers never store transactions in the history. Apply the valid members in order,
+        // for battles receiving a transaction nonetheless.
+        for event in &self.events {
+            if battle.verify_event(&**event).is_ok() {
+                event.apply(battle, event_queue);
+            }
+        }
+    }
+
+    fn kind(&self) -> EventKind {
+        EventKind::Transaction
+    }
+
+    fn box_clone(&self) -> Box<dyn Event<R> + Send> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn entities(&self) -> Vec<EntityId<R>> {
+        self.events
+            .iter()
+            .flat_map(|event| event.entities())
+            .collect()
+    }
+
+    fn teams(&self) -> Vec<TeamId<R>> {
+        self.events.iter().flat_map(|event| event.teams()).collect()
+    }
+}
+
+/// Trigger to build and fire a `Transaction` event.
+pub struct TransactionTrigger<'a, R, P>
+where
+    R: BattleRules,
+    P: EventProcessor<R>,
+{
+    processor: &'a mut P,
+    events: Vec<Box<dyn Event<R> + Send>>,
+}
+
+impl<'a, R, P> EventTrigger<'a, R, P> for TransactionTrigger<'a, R, P>
+where
+    R: BattleRules + 'static,
+    P: EventProcessor<R>,
+{
+    fn processor(&'a mut self) -> &'a mut P {
+        self.processor
+    }
+
+    /// Returns a `Transaction` event.
+    fn event(&self) -> Box<dyn Event<R> + Send> {
+        Box::new(Transaction {
+            events: self.events.clone(),
+        })
+    }
+}
@@ -0,0 +1,319 @@
+//! Transport of events over byte streams.
+//!
+//! This module contains a `ServerSink` and a `ClientSink` that write messages into any
+//! `Write` implementor, such as a tcp stream, and a `Pump` that reads messages from the
+//! other end of the stream and feeds them into a server or a client.
+//!
+//! Messages are encoded with the compact binary format of the `binary` module,
+//! one message for each frame.
+
+use crate::battle::BattleRules;
+use crate::binary::{self, FrameReader};
+use crate::error::{WeaselError, WeaselErrorType, WeaselResult};
+use crate::event::{
+    ClientEventPrototype, ClientSink, EventId, EventReceiver, EventServer, EventSink, EventSinkId,
+    Rejection, ServerSink, VersionedEventWrapper,
+};
+use crate::player::PlayerId;
+use crate::resync::ResyncRequest;
+use crate::serde::{FlatClientEvent, FlatVersionedEvent};
+use crate::server::Server;
+use crate::snapshot::BattleSnapshot;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+/// Messages exchanged between servers and clients.
+#[derive(Serialize, Deserialize)]
+pub enum Message<R: BattleRules> {
+    /// Sent by a client right after connecting to a server.
+    Handshake(Handshake),
+    /// An event prototype sent by a client to a server.
+    #[serde(bound(
+        serialize = "FlatClientEvent<R>: Serialize",
+        deserialize = "FlatClientEvent<R>: Deserialize<'de>"
+    ))]
+    ClientEvent(FlatClientEvent<R>),
+    /// An event accepted by a server, sent to clients.
+    #[serde(bound(
+        serialize = "FlatVersionedEvent<R>: Serialize",
+        deserialize = "FlatVersionedEvent<R>: Deserialize<'de>"
+    ))]
+    Event(FlatVersionedEvent<R>),
+    /// The server rewound its battle to the moment before the given event was applied.
+    Rewind(EventId),
+    /// A `BattleSnapshot` sent by a server, encoded with `binary::encode`.
+    ///
+    /// Use `binary::decode` to retrieve the snapshot.
+    Snapshot(Vec<u8>),
+    /// Description of an error occurred on the other side.
+    Error(String),
+    /// The other side is closing the connection.
+    Bye,
+    /// The server rejected an event sent by the client.
+    Rejection(Rejection),
+}
+
+/// First message sent by a client to a server.
+///
+/// It tells the server the player associated to the client and which events
+/// the client is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    player: Option<PlayerId>,
+    resync: ResyncRequest,
+}
+
+impl Handshake {
+    /// Creates a new handshake.
+    pub fn new(player: Option<PlayerId>, resync: ResyncRequest) -> Self {
+        Self { player, resync }
+    }
+
+    /// Returns the player associated to the client.
+    pub fn player(&self) -> Option<PlayerId> {
+        self.player
+    }
+
+    /// Returns the request to bring the client up to date. See `Server::resync`.
+    pub fn resync(&self) -> &ResyncRequest {
+        &self.resync
+    }
+}
+
+/// Writes `message` as a single frame into `writer`.
+pub fn write_message<R, W>(writer: &mut W, message: &Message<R>) -> io::Result<()>
+where
+    R: BattleRules + 'static,
+    W: Write + ?Sized,
+{
+    binary::write_frame(writer, message)?;
+    writer.flush()
+}
+
+/// Writes `message` into `writer`, converting I/O errors into `WeaselError`.
+fn send_message<R, W>(id: EventSinkId, writer: &mut W, message: &Message<R>) -> WeaselResult<(), R>
+where
+    R: BattleRules + 'static,
+    W: Write + ?Sized,
+{
+    write_message(writer, message)
+        .map_err(|err| WeaselError::EventSinkError(format!("sink {}: {}", id, err)))
+}
+
+/// A `ServerSink` writing client events into a stream.
+pub struct StreamServerSink<R, W> {
+    id: EventSinkId,
+    writer: W,
+    _phantom: PhantomData<R>,
+}
+
+impl<R: BattleRules + 'static, W: Write> StreamServerSink<R, W> {
+    /// Creates a new sink with the given id, writing into `writer`.
+    pub fn new(id: EventSinkId, writer: W) -> Self {
+        Self {
+            id,
+            writer,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Sends a handshake to the server.
+    pub fn handshake(&mut self, handshake: Handshake) -> WeaselResult<(), R> {
+        send_message(self.id, &mut self.writer, &Message::Handshake(handshake))
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+}
+
+impl<R: BattleRules + 'static, W: Write> EventSink for StreamServerSink<R, W> {
+    fn id(&self) -> EventSinkId {
+        self.id
+    }
+
+    fn on_disconnect(&mut self) {
+        // The stream might be already broken.
+        let _ = write_message(&mut self.writer, &Message::<R>::Bye);
+    }
+}
+
+impl<R: BattleRules + 'static, W: Write> ServerSink<R> for StreamServerSink<R, W> {
+    fn send(&mut self, event: &ClientEventPrototype<R>) -> WeaselResult<(), R> {
+        let message = Message::ClientEvent(event.clone().into());
+        send_message(self.id, &mut self.writer, &message)
+    }
+}
+
+/// Function to encode a snapshot.
+type EncodeFn<R> = fn(&BattleSnapshot<R>) -> io::Result<Vec<u8>>;
+
+/// A `ClientSink` writing events into a stream.
+pub struct StreamClientSink<R: BattleRules, W> {
+    id: EventSinkId,
+    player: Option<PlayerId>,
+    writer: W,
+    encode_snapshot: Option<EncodeFn<R>>,
+}
+
+impl<R: BattleRules + 'static, W: Write> StreamClientSink<R, W> {
+    /// Creates a new sink with the given id, writing into `writer`.
+    ///
+    /// `player` is the player associated to the remote client, if any.
+    pub fn new(id: EventSinkId, player: Option<PlayerId>, writer: W) -> Self {
+        Self {
+            id,
+            player,
+            writer,
+            encode_snapshot: None,
+        }
+    }
+
+    /// Allows this sink to send snapshots of the battle, for instance during a resync.
+    ///
+    /// Without this option `ClientSink::send_snapshot` returns an error.
+    pub fn enable_snapshots(mut self) -> Self
+    where
+        BattleSnapshot<R>: Serialize,
+    {
+        self.encode_snapshot = Some(binary::encode::<BattleSnapshot<R>>);
+        self
+    }
+
+    /// Sends an error message to the client.
+    pub fn send_error(&mut self, error: &WeaselErrorType<R>) -> WeaselResult<(), R> {
+        let message = Message::Error(error.to_string());
+        send_message(self.id, &mut self.writer, &message)
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+}
+
+impl<R: BattleRules + 'static, W: Write> EventSink for StreamClientSink<R, W> {
+    fn id(&self) -> EventSinkId {
+        self.id
+    }
+
+    fn on_disconnect(&mut self) {
+        // The stream might be already broken.
+        let _ = write_message(&mut self.writer, &Message::<R>::Bye);
+    }
+}
+
+impl<R: BattleRules + 'static, W: Write> ClientSink<R> for StreamClientSink<R, W> {
+    fn send(&mut self, event: &VersionedEventWrapper<R>) -> WeaselResult<(), R> {
+        let message = Message::Event(event.clone().into());
+        send_message(self.id, &mut self.writer, &message)
+    }
+
+    fn player(&self) -> Option<PlayerId> {
+        self.player
+    }
+
+    fn rewind(&mut self, event_id: EventId) -> WeaselResult<(), R> {
+        send_message(self.id, &mut self.writer, &Message::Rewind(event_id))
+    }
+
+    fn send_snapshot(&mut self, snapshot: &BattleSnapshot<R>) -> WeaselResult<(), R> {
+        let encode = self.encode_snapshot.ok_or_else(|| {
+            WeaselError::EventSinkError(format!("sink {} can't send snapshots", self.id))
+        })?;
+        let bytes = encode(snapshot)
+            .map_err(|err| WeaselError::EventSinkError(format!("sink {}: {}", self.id, err)))?;
+        send_message(self.id, &mut self.writer, &Message::Snapshot(bytes))
+    }
+
+    fn reject(&mut self, rejection: &Rejection) -> WeaselResult<(), R> {
+        send_message(
+            self.id,
+            &mut self.writer,
+            &Message::Rejection(rejection.clone()),
+        )
+    }
+}
+
+/// Outcome of pumping a message.
+pub enum Pumped<R: BattleRules> {
+    /// An event was fed into the server or the client, with the given result.
+    Processed(WeaselResult<(), R>),
+    /// A message that must be handled by the caller, such as a handshake or a rewind.
+    Unhandled(Message<R>),
+    /// The stream ended or the other side closed the connection.
+    Closed,
+}
+
+/// Reads messages from a stream and feeds them into a server or a client.
+///
+/// The pump doesn't do any I/O on its own initiative. Call `pump_server` or `pump_client`
+/// in a loop, for instance in a thread dedicated to the connection.
+pub struct Pump<R: BattleRules, S> {
+    messages: FrameReader<S, Message<R>>,
+}
+
+impl<R: BattleRules + 'static, S: Read> Pump<R, S> {
+    /// Creates a new pump reading from `reader`.
+    pub fn new(reader: S) -> Self {
+        Self {
+            messages: FrameReader::new(reader),
+        }
+    }
+
+    /// Reads the next message.
+    ///
+    /// Returns `None` if the stream ended or the other side sent `Message::Bye`.
+    pub fn next_message(&mut self) -> io::Result<Option<Message<R>>> {
+        match self.messages.next().transpose()? {
+            Some(Message::Bye) | None => Ok(None),
+            Some(message) => Ok(Some(message)),
+        }
+    }
+
+    /// Reads the next message and, if it's a client event, feeds it into `server`.
+    pub fn pump_server<P: EventServer<R>>(&mut self, server: &mut P) -> io::Result<Pumped<R>> {
+        Ok(match self.next_message()? {
+            Some(Message::ClientEvent(event)) => {
+                Pumped::Processed(server.process_client(event.into()))
+            }
+            Some(message) => Pumped::Unhandled(message),
+            None => Pumped::Closed,
+        })
+    }
+
+    /// Reads the next message and, if it's a client event, feeds it into `server`
+    /// with `Server::process_client_from`.
+    ///
+    /// `sink` is the id of the client sink connected to the other side of the stream.
+    /// If the event is rejected, the client is notified through this sink.
+    pub fn pump_server_from(
+        &mut self,
+        sink: EventSinkId,
+        server: &mut Server<R>,
+    ) -> io::Result<Pumped<R>> {
+        Ok(match self.next_message()? {
+            Some(Message::ClientEvent(event)) => {
+                Pumped::Processed(server.process_client_from(sink, event.into()))
+            }
+            Some(message) => Pumped::Unhandled(message),
+            None => Pumped::Closed,
+        })
+    }
+
+    /// Reads the next message and, if it's an event, feeds it into `client`.
+    pub fn pump_client<P: EventReceiver<R>>(&mut self, client: &mut P) -> io::Result<Pumped<R>> {
+        Ok(match self.next_message()? {
+            Some(Message::Event(event)) => Pumped::Processed(client.receive(event.into())),
+            Some(message) => Pumped::Unhandled(message),
+            None => Pumped::Closed,
+        })
+    }
+
+    /// Consumes this pump and returns the underlying reader.
+    pub fn into_inner(self) -> S {
+        self.messages.into_inner()
+    }
+}
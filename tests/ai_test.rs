@@ -0,0 +1,190 @@
+use std::sync::{Arc, Mutex};
+use weasel::ability::ActivateAbility;
+use weasel::actor::{Action, Actor, ActorRules};
+use weasel::ai::{AiDriver, AiRules, Controller, GreedyController, LegalAction, Score};
+use weasel::battle::{Battle, BattleController, BattleRules, BattleState};
+use weasel::battle_rules_with_actor;
+use weasel::entity::EntityId;
+use weasel::entropy::Entropy;
+use weasel::event::{DummyEvent, EventKind, EventPrototype, EventQueue, EventTrigger};
+use weasel::metric::WriteMetrics;
+use weasel::rules::empty::EmptyAbility;
+use weasel::team::{CreateTeam, TeamId};
+use weasel::Server;
+use weasel::{battle_rules, rules::empty::*};
+
+const TEAM_1_ID: u32 = 1;
+const TEAM_2_ID: u32 = 2;
+const TEAM_NEW_ID: u32 = 99;
+const CREATURE_1_ID: u32 = 1;
+const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
+const ABILITY_DUMMY: u32 = 1;
+const ABILITY_TEAM: u32 = 2;
+const ABILITY_ERR: u32 = 99;
+
+#[derive(Default, Clone)]
+pub struct CustomActorRules {}
+
+impl ActorRules<CustomRules> for CustomActorRules {
+    type Ability = EmptyAbility;
+    type AbilitiesSeed = ();
+    type Activation = ();
+    type AbilitiesAlteration = ();
+
+    fn generate_abilities(
+        &self,
+        _: &Option<Self::AbilitiesSeed>,
+        _entropy: &mut Entropy<CustomRules>,
+        _metrics: &mut WriteMetrics<CustomRules>,
+    ) -> Box<dyn Iterator<Item = Self::Ability>> {
+        let v = vec![
+            EmptyAbility { id: ABILITY_DUMMY },
+            EmptyAbility { id: ABILITY_TEAM },
+        ];
+        Box::new(v.into_iter())
+    }
+
+    fn activate(
+        &self,
+        _state: &BattleState<CustomRules>,
+        action: Action<CustomRules>,
+        mut event_queue: &mut Option<EventQueue<CustomRules>>,
+        _entropy: &mut Entropy<CustomRules>,
+        _metrics: &mut WriteMetrics<CustomRules>,
+    ) {
+        if action.ability.id == ABILITY_TEAM {
+            CreateTeam::trigger(&mut event_queue, TEAM_NEW_ID).fire();
+        } else {
+            DummyEvent::trigger(&mut event_queue).fire();
+        }
+    }
+}
+
+battle_rules_with_actor! { CustomActorRules }
+
+/// AI rules that prefer battles with more teams.
+struct CustomAiRules {}
+
+impl AiRules<CustomRules> for CustomAiRules {
+    fn actions(
+        &self,
+        _battle: &Battle<CustomRules>,
+        actor: &dyn Actor<CustomRules>,
+    ) -> Vec<EventPrototype<CustomRules>> {
+        let actor_id = *actor.entity_id();
+        vec![
+            ActivateAbility::trigger(&mut (), actor_id, ABILITY_ERR).prototype(),
+            ActivateAbility::trigger(&mut (), actor_id, ABILITY_DUMMY).prototype(),
+            ActivateAbility::trigger(&mut (), actor_id, ABILITY_TEAM).prototype(),
+        ]
+    }
+
+    fn evaluate(&self, outcome: &Battle<CustomRules>, _: &TeamId<CustomRules>) -> Score {
+        outcome.entities().teams().count() as Score
+    }
+}
+
+/// A controller that records the legal actions it receives and does nothing.
+struct RecordingController {
+    scores: Arc<Mutex<Vec<Score>>>,
+}
+
+impl Controller<CustomRules> for RecordingController {
+    fn play(
+        &mut self,
+        _battle: &Battle<CustomRules>,
+        _team_id: &TeamId<CustomRules>,
+        actions: &[LegalAction<CustomRules>],
+    ) -> Vec<EventPrototype<CustomRules>> {
+        *self.scores.lock().unwrap() = actions.iter().map(|action| action.score()).collect();
+        Vec::new()
+    }
+}
+
+/// Creates a server with two teams and a creature in the first team.
+fn init() -> Server<CustomRules> {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::team(&mut server, TEAM_2_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    server
+}
+
+#[test]
+fn legal_actions() {
+    let mut server = init();
+    let scores = Arc::new(Mutex::new(Vec::new()));
+    let mut ai = AiDriver::new(Box::new(CustomAiRules {}));
+    ai.set_controller(
+        TEAM_1_ID,
+        Box::new(RecordingController {
+            scores: scores.clone(),
+        }),
+    );
+    assert!(ai.controls(&TEAM_1_ID));
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    assert_eq!(ai.play(&mut server).err(), None);
+    // Invalid actions are discarded, the others are scored by their outcome.
+    assert_eq!(*scores.lock().unwrap(), vec![2, 3]);
+    // The simulations didn't change the battle and the turn is over.
+    let events = server.battle().history().events();
+    assert_eq!(events.len(), 5);
+    assert_eq!(events[3].kind(), EventKind::StartTurn);
+    assert_eq!(events[4].kind(), EventKind::EndTurn);
+}
+
+#[test]
+fn greedy() {
+    let mut server = init();
+    let mut ai = AiDriver::new(Box::new(CustomAiRules {}));
+    ai.set_controller(TEAM_1_ID, Box::new(GreedyController::default()));
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    assert_eq!(ai.play(&mut server).err(), None);
+    // The action with the best outcome was performed.
+    assert!(server.battle().entities().team(&TEAM_NEW_ID).is_some());
+    let events = server.battle().history().events();
+    assert_eq!(events[4].kind(), EventKind::ActivateAbility);
+    assert_eq!(events[5].kind(), EventKind::CreateTeam);
+    assert_eq!(events[6].kind(), EventKind::EndTurn);
+}
+
+#[test]
+fn player_controlled() {
+    let mut server = init();
+    let mut ai = AiDriver::new(Box::new(CustomAiRules {}));
+    ai.set_controller(TEAM_2_ID, Box::new(GreedyController::default()));
+    // Nothing to do without a turn in progress.
+    assert_eq!(ai.play(&mut server).err(), None);
+    assert_eq!(ai.play(&mut server).ok(), Some(false));
+    // Actors of other teams are left to their players.
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    assert_eq!(ai.play(&mut server).ok(), Some(false));
+    assert!(server.battle().rounds().is_acting(&ENTITY_1_ID));
+    // Teams can be handed over to the AI and back.
+    ai.set_controller(TEAM_1_ID, Box::new(GreedyController::default()));
+    assert!(ai.remove_controller(&TEAM_1_ID).is_some());
+    assert!(!ai.controls(&TEAM_1_ID));
+    assert_eq!(ai.play(&mut server).ok(), Some(false));
+}
+
+#[cfg(feature = "random")]
+#[test]
+fn random() {
+    use weasel::ai::RandomController;
+
+    let seed = 1_204_678_643_940_597_513;
+    let mut chosen = Vec::new();
+    for _ in 0..2 {
+        let mut server = init();
+        let mut ai = AiDriver::new(Box::new(CustomAiRules {}));
+        ai.set_controller(TEAM_1_ID, Box::new(RandomController::new(seed)));
+        util::start_turn(&mut server, &ENTITY_1_ID);
+        assert_eq!(ai.play(&mut server).ok(), Some(true));
+        // A legal action is always performed.
+        let events = server.battle().history().events();
+        assert_eq!(events[4].kind(), EventKind::ActivateAbility);
+        chosen.push(events[5].kind());
+    }
+    // The same seed gives the same choices.
+    assert_eq!(chosen[0], chosen[1]);
+}
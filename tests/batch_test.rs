@@ -0,0 +1,204 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use weasel::actor::{Action, ActorRules};
+use weasel::ai::{AiDriver, AiRules, Controller, GreedyController, LegalAction};
+use weasel::batch::BatchSimulator;
+use weasel::battle::{Battle, BattleRules, BattleState};
+use weasel::battle_rules_with_actor;
+use weasel::creature::CreateCreature;
+use weasel::entropy::Entropy;
+use weasel::event::{EventPrototype, EventQueue, EventTrigger};
+use weasel::metric::WriteMetrics;
+use weasel::round::EndRound;
+use weasel::rules::empty::EmptyAbility;
+use weasel::server::Server;
+use weasel::team::{ConcludeObjectives, Conclusion, TeamId};
+use weasel::{battle_rules, rules::empty::*};
+
+const TEAM_1_ID: u32 = 1;
+const TEAM_2_ID: u32 = 2;
+const CREATURE_1_ID: u32 = 1;
+const CREATURE_2_ID: u32 = 2;
+const ABILITY_WIN: u32 = 1;
+const ABILITY_LOSE: u32 = 2;
+const METRIC_ID: u16 = 1;
+const BATTLES: u64 = 10;
+const MAX_TURNS: u32 = 8;
+
+#[derive(Default, Clone)]
+pub struct CustomActorRules {}
+
+impl ActorRules<CustomRules> for CustomActorRules {
+    type Ability = EmptyAbility;
+    type AbilitiesSeed = u32;
+    type Activation = ();
+    type AbilitiesAlteration = ();
+
+    fn generate_abilities(
+        &self,
+        seed: &Option<Self::AbilitiesSeed>,
+        _entropy: &mut Entropy<CustomRules>,
+        _metrics: &mut WriteMetrics<CustomRules>,
+    ) -> Box<dyn Iterator<Item = Self::Ability>> {
+        let v: Vec<_> = seed.iter().map(|id| EmptyAbility { id: *id }).collect();
+        Box::new(v.into_iter())
+    }
+
+    fn activate(
+        &self,
+        _state: &BattleState<CustomRules>,
+        action: Action<CustomRules>,
+        mut event_queue: &mut Option<EventQueue<CustomRules>>,
+        _entropy: &mut Entropy<CustomRules>,
+        metrics: &mut WriteMetrics<CustomRules>,
+    ) {
+        metrics.add_user_u64(METRIC_ID, 1).unwrap();
+        let conclusion = if action.ability.id == ABILITY_WIN {
+            Conclusion::Victory
+        } else {
+            Conclusion::Defeat
+        };
+        ConcludeObjectives::trigger(&mut event_queue, TEAM_1_ID, conclusion).fire();
+    }
+}
+
+battle_rules_with_actor! { CustomActorRules }
+
+struct CustomAiRules {}
+
+impl AiRules<CustomRules> for CustomAiRules {}
+
+/// Creates a battle in which the first creature wins with even seeds and loses with odd ones.
+fn setup(seed: u64) -> Server<CustomRules> {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    let ability = match seed % 2 {
+        0 => ABILITY_WIN,
+        _ => ABILITY_LOSE,
+    };
+    assert_eq!(
+        CreateCreature::trigger(&mut server, CREATURE_1_ID, TEAM_1_ID, ())
+            .abilities_seed(ability)
+            .fire()
+            .err(),
+        None
+    );
+    server
+}
+
+/// Creates a battle with two teams of creatures without abilities.
+fn setup_endless(_: u64) -> Server<CustomRules> {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::team(&mut server, TEAM_2_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    util::creature(&mut server, CREATURE_2_ID, TEAM_2_ID, ());
+    server
+}
+
+fn driver(_: u64) -> AiDriver<CustomRules> {
+    let mut ai = AiDriver::new(Box::new(CustomAiRules {}));
+    ai.set_controller(TEAM_1_ID, Box::new(GreedyController::default()));
+    ai
+}
+
+#[test]
+fn aggregation() {
+    let simulator = BatchSimulator::builder(setup, driver).build();
+    let report = simulator.run(0..BATTLES);
+    assert_eq!(report.battles().len(), BATTLES as usize);
+    assert_eq!(report.battles()[0].seed(), 0);
+    assert_eq!(
+        report.battles()[0].conclusion(&TEAM_1_ID),
+        Some(Conclusion::Victory)
+    );
+    assert_eq!(
+        report.battles()[1].conclusion(&TEAM_1_ID),
+        Some(Conclusion::Defeat)
+    );
+    assert_eq!(report.conclusions(&TEAM_1_ID, Conclusion::Defeat), 5);
+    assert_eq!(report.win_rate(&TEAM_1_ID), 0.5);
+    assert_eq!(report.win_rate(&TEAM_2_ID), 0.0);
+    // Battles are over after the first turn, in which the ability is activated.
+    // The only actor had its turn, thus the round is completed.
+    assert_eq!(report.average_turns(), 1.0);
+    assert_eq!(report.average_rounds(), 1.0);
+    assert_eq!(report.battles_with_errors(), 0);
+    assert_eq!(report.average_metric(&METRIC_ID), 1.0);
+    assert_eq!(report.average_metric(&(METRIC_ID + 1)), 0.0);
+}
+
+#[test]
+fn parallel() {
+    let simulator = BatchSimulator::builder(setup, driver).build();
+    let sequential = simulator.run(0..BATTLES);
+    let parallel = simulator.run_parallel(BATTLES, 3);
+    assert_eq!(parallel.battles().len(), BATTLES as usize);
+    for (a, b) in sequential.battles().iter().zip(parallel.battles()) {
+        assert_eq!(a.seed(), b.seed());
+        assert_eq!(a.conclusion(&TEAM_1_ID), b.conclusion(&TEAM_1_ID));
+    }
+}
+
+#[test]
+fn max_turns() {
+    let simulator = BatchSimulator::builder(setup_endless, driver)
+        .max_turns(MAX_TURNS)
+        .build();
+    let report = simulator.run(0..1);
+    let outcome = &report.battles()[0];
+    // Creatures without a controller pass their turns.
+    assert_eq!(outcome.completed_turns(), MAX_TURNS);
+    // Each round is made of the turns of both creatures.
+    assert_eq!(outcome.completed_rounds(), MAX_TURNS / 2);
+    assert_eq!(outcome.conclusion(&TEAM_1_ID), None);
+    assert_eq!(report.win_rate(&TEAM_1_ID), 0.0);
+}
+
+#[test]
+fn seed_generator() {
+    static SEEDS: AtomicU32 = AtomicU32::new(0);
+
+    fn seeds(_: u64) {
+        SEEDS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let simulator = BatchSimulator::builder(setup, driver)
+        .seed_generator(seeds)
+        .build();
+    simulator.run_parallel(BATTLES, 2);
+    // The entropy is reset once for each battle.
+    assert_eq!(SEEDS.load(Ordering::SeqCst), BATTLES as u32);
+}
+
+#[test]
+fn errors() {
+    struct FaultyController {}
+
+    impl Controller<CustomRules> for FaultyController {
+        fn play(
+            &mut self,
+            _battle: &Battle<CustomRules>,
+            _team_id: &TeamId<CustomRules>,
+            _actions: &[LegalAction<CustomRules>],
+        ) -> Vec<EventPrototype<CustomRules>> {
+            // Rounds can't end during a turn.
+            vec![EndRound::trigger(&mut ()).prototype()]
+        }
+    }
+
+    fn faulty_driver(_: u64) -> AiDriver<CustomRules> {
+        let mut ai = AiDriver::new(Box::new(CustomAiRules {}));
+        ai.set_controller(TEAM_1_ID, Box::new(FaultyController {}));
+        ai
+    }
+
+    let simulator = BatchSimulator::builder(setup_endless, faulty_driver)
+        .max_turns(MAX_TURNS)
+        .build();
+    let report = simulator.run(0..1);
+    let outcome = &report.battles()[0];
+    // Rejected events don't stop the battle, but they are counted.
+    assert_eq!(outcome.completed_turns(), MAX_TURNS);
+    assert_eq!(outcome.errors(), (MAX_TURNS / 2) as usize);
+    assert_eq!(report.battles_with_errors(), 1);
+}
@@ -1,15 +1,16 @@
 use weasel::ability::ActivateAbility;
-use weasel::actor::{Action, ActorRules};
+use weasel::actor::{Action, Actor, ActorRules};
 use weasel::battle::{BattleController, BattlePhase, BattleRules, BattleState, EndBattle};
 use weasel::battle_rules_with_actor;
-use weasel::entity::EntityId;
+use weasel::entity::{Entity, EntityId};
 use weasel::entropy::Entropy;
-use weasel::event::{DummyEvent, EventQueue, EventTrigger};
+use weasel::event::{DummyEvent, EventKind, EventQueue, EventTrigger};
 use weasel::metric::WriteMetrics;
 use weasel::round::{EndTurn, StartTurn};
 use weasel::rules::empty::EmptyAbility;
-use weasel::WeaselError;
+use weasel::space::{MoveEntity, PositionClaim, SpaceRules};
 use weasel::{battle_rules, rules::empty::*};
+use weasel::{Server, WeaselError, WeaselResult};
 
 const TEAM_1_ID: u32 = 1;
 const CREATURE_1_ID: u32 = 1;
@@ -89,3 +90,218 @@ fn end_battle_during_events() {
     );
     assert_eq!(server.battle().phase(), BattlePhase::Ended);
 }
+
+#[test]
+fn fork() {
+    #[derive(Clone)]
+    struct ForkRules {
+        team_rules: EmptyTeamRules,
+        character_rules: EmptyCharacterRules,
+        actor_rules: EmptyActorRules,
+        fight_rules: EmptyFightRules,
+        user_rules: EmptyUserRules,
+    }
+
+    impl BattleRules for ForkRules {
+        type TR = EmptyTeamRules;
+        type CR = EmptyCharacterRules;
+        type AR = EmptyActorRules;
+        type FR = EmptyFightRules;
+        type UR = EmptyUserRules;
+        type SR = EmptySpaceRules;
+        type RR = EmptyRoundsRules;
+        type ER = EmptyEntropyRules;
+        type Version = u32;
+
+        fn team_rules(&self) -> &Self::TR {
+            &self.team_rules
+        }
+        fn character_rules(&self) -> &Self::CR {
+            &self.character_rules
+        }
+        fn actor_rules(&self) -> &Self::AR {
+            &self.actor_rules
+        }
+        fn fight_rules(&self) -> &Self::FR {
+            &self.fight_rules
+        }
+        fn user_rules(&self) -> &Self::UR {
+            &self.user_rules
+        }
+        fn space_rules(&mut self) -> Self::SR {
+            EmptySpaceRules::default()
+        }
+        fn rounds_rules(&mut self) -> Self::RR {
+            EmptyRoundsRules::default()
+        }
+        fn entropy_rules(&mut self) -> Self::ER {
+            EmptyEntropyRules::default()
+        }
+        fn version(&self) -> &Self::Version {
+            &0
+        }
+    }
+
+    let rules = ForkRules {
+        team_rules: EmptyTeamRules::default(),
+        character_rules: EmptyCharacterRules::default(),
+        actor_rules: EmptyActorRules::default(),
+        fight_rules: EmptyFightRules::default(),
+        user_rules: EmptyUserRules::default(),
+    };
+    let entity_id = EntityId::<ForkRules>::Creature(CREATURE_1_ID);
+    let mut server = util::server(rules);
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    // Evolve the fork independently from the original battle.
+    let mut fork = Server::builder(server.battle().fork()).build();
+    // The fork's history starts where the original one ends.
+    assert_eq!(fork.battle().history().len(), 2);
+    assert_eq!(fork.battle().history().first_id(), 2);
+    assert!(fork.battle().history().events().is_empty());
+    util::start_turn(&mut fork, &entity_id);
+    assert!(fork.battle().rounds().is_acting(&entity_id));
+    assert_eq!(fork.battle().history().len(), 3);
+    assert!(!server.battle().rounds().is_acting(&entity_id));
+    assert_eq!(server.battle().history().len(), 2);
+    // The original battle can evolve in a different way.
+    util::dummy(&mut server);
+    assert_eq!(
+        server.battle().history().events()[2].kind(),
+        EventKind::DummyEvent
+    );
+    assert_eq!(
+        fork.battle().history().events()[0].kind(),
+        EventKind::StartTurn
+    );
+}
+
+#[test]
+fn legal_actions() {
+    #[derive(Default)]
+    struct LegalActorRules {}
+
+    impl ActorRules<CustomRules> for LegalActorRules {
+        type Ability = EmptyAbility;
+        type AbilitiesSeed = ();
+        type Activation = u32;
+        type AbilitiesAlteration = ();
+
+        fn generate_abilities(
+            &self,
+            _: &Option<Self::AbilitiesSeed>,
+            _entropy: &mut Entropy<CustomRules>,
+            _metrics: &mut WriteMetrics<CustomRules>,
+        ) -> Box<dyn Iterator<Item = Self::Ability>> {
+            let v = vec![EmptyAbility { id: ABILITY_ID }];
+            Box::new(v.into_iter())
+        }
+
+        fn activable(
+            &self,
+            _state: &BattleState<CustomRules>,
+            action: Action<CustomRules>,
+        ) -> WeaselResult<(), CustomRules> {
+            if *action.activation == Some(ACTIVATION_OK) {
+                Ok(())
+            } else {
+                Err(WeaselError::GenericError)
+            }
+        }
+
+        fn activations(
+            &self,
+            _state: &BattleState<CustomRules>,
+            _actor: &dyn Actor<CustomRules>,
+            _ability: &Self::Ability,
+        ) -> Vec<Option<Self::Activation>> {
+            vec![Some(ACTIVATION_OK), Some(ACTIVATION_ERR)]
+        }
+    }
+
+    #[derive(Default)]
+    struct LegalSpaceRules {}
+
+    impl SpaceRules<CustomRules> for LegalSpaceRules {
+        type Position = u32;
+        type SpaceSeed = ();
+        type SpaceModel = ();
+        type SpaceAlteration = ();
+
+        fn generate_model(&self, _: &Option<Self::SpaceSeed>) -> Self::SpaceModel {}
+
+        fn check_move<'a>(
+            &self,
+            _model: &Self::SpaceModel,
+            _claim: PositionClaim<'a, CustomRules>,
+            position: &Self::Position,
+        ) -> WeaselResult<(), CustomRules> {
+            if *position != POSITION_ERR {
+                Ok(())
+            } else {
+                Err(WeaselError::GenericError)
+            }
+        }
+
+        fn reachable_positions(
+            &self,
+            _model: &Self::SpaceModel,
+            _entity: &dyn Entity<CustomRules>,
+        ) -> Vec<Self::Position> {
+            vec![POSITION_OK, POSITION_ERR]
+        }
+    }
+
+    battle_rules! {
+        EmptyTeamRules,
+        EmptyCharacterRules,
+        LegalActorRules,
+        EmptyFightRules,
+        EmptyUserRules,
+        LegalSpaceRules,
+        EmptyRoundsRules,
+        EmptyEntropyRules
+    }
+
+    const ACTIVATION_OK: u32 = 1;
+    const ACTIVATION_ERR: u32 = 2;
+    const POSITION_OK: u32 = 1;
+    const POSITION_ERR: u32 = 2;
+
+    let entity_id = EntityId::<CustomRules>::Creature(CREATURE_1_ID);
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, 0);
+    // No actions for entities that aren't acting.
+    assert!(server.battle().legal_actions(&entity_id).is_empty());
+    assert!(server
+        .battle()
+        .legal_actions(&EntityId::Creature(CREATURE_1_ID + 1))
+        .is_empty());
+    // Invalid candidates are discarded.
+    util::start_turn(&mut server, &entity_id);
+    let actions = server.battle().legal_actions(&entity_id);
+    let kinds: Vec<_> = actions.iter().map(|action| action.kind()).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            EventKind::ActivateAbility,
+            EventKind::MoveEntity,
+            EventKind::EndTurn
+        ]
+    );
+    let activation = actions[0]
+        .event()
+        .as_any()
+        .downcast_ref::<ActivateAbility<CustomRules>>()
+        .unwrap()
+        .activation();
+    assert_eq!(*activation, Some(ACTIVATION_OK));
+    let position = actions[1]
+        .event()
+        .as_any()
+        .downcast_ref::<MoveEntity<CustomRules>>()
+        .unwrap()
+        .position();
+    assert_eq!(*position, POSITION_OK);
+}
@@ -1,22 +1,25 @@
 use std::ops::Range;
 use std::sync::{Arc, Mutex};
-use weasel::battle::{Battle, BattleController, BattleRules};
+use weasel::battle::{Battle, BattleController, BattleRules, BattleState};
+use weasel::creature::CreateCreature;
 use weasel::entity::EntityId;
 use weasel::event::{
-    ClientEventPrototype, ClientSink, DummyEvent, EventKind, EventReceiver, EventServer, EventSink,
-    EventSinkId, EventTrigger, ServerSink, VersionedEventWrapper,
+    ClientEventPrototype, ClientSink, DummyEvent, EventId, EventKind, EventReceiver, EventServer,
+    EventSink, EventSinkId, EventTrigger, EventWrapper, Rejection, ServerSink,
+    VersionedEventWrapper, Visibility,
 };
-use weasel::player::PlayerId;
-use weasel::round::StartTurn;
-use weasel::team::CreateTeam;
-use weasel::{battle_rules, rules::empty::*};
-use weasel::{Client, Server};
+use weasel::player::{PlayerId, RightsHandle};
+use weasel::round::{EndTurn, StartTurn};
+use weasel::team::{CreateTeam, TeamRules};
+use weasel::{battle_rules, battle_rules_with_team, rules::empty::*};
+use weasel::{BattleSnapshot, Client, Resync, ResyncRequest, Server};
 use weasel::{WeaselError, WeaselResult};
 
 #[cfg(feature = "serialization")]
 mod helper;
 
 const TEAM_1_ID: u32 = 1;
+const TEAM_2_ID: u32 = 2;
 const CREATURE_1_ID: u32 = 1;
 const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
 const SERVER_1_ID: EventSinkId = 1;
@@ -171,7 +174,11 @@ impl<R: BattleRules> EventSink for TestClientSink<R> {
     }
 }
 
-impl<R: BattleRules> ClientSink<R> for TestClientSink<R> {
+impl<R> ClientSink<R> for TestClientSink<R>
+where
+    R: BattleRules + 'static,
+    BattleSnapshot<R>: Clone,
+{
     fn send(&mut self, event: &VersionedEventWrapper<R>) -> WeaselResult<(), R> {
         if self.sink.lock().unwrap().broken {
             Err(WeaselError::EventSinkError("broken".to_string()))
@@ -180,6 +187,41 @@ impl<R: BattleRules> ClientSink<R> for TestClientSink<R> {
             Ok(())
         }
     }
+
+    fn rewind(&mut self, event_id: EventId) -> WeaselResult<(), R> {
+        // Deliver pending events before rewinding.
+        self.receive()?;
+        self.client.lock().unwrap().rewind_to(event_id)
+    }
+
+    fn send_snapshot(&mut self, snapshot: &BattleSnapshot<R>) -> WeaselResult<(), R> {
+        // Events preceding the snapshot are superseded.
+        self.buffer.lock().unwrap().clear();
+        self.client.lock().unwrap().restore(snapshot.clone());
+        Ok(())
+    }
+
+    fn reject(&mut self, rejection: &Rejection) -> WeaselResult<(), R> {
+        self.client.lock().unwrap().receive_rejection(rejection);
+        Ok(())
+    }
+}
+
+/// A `ClientSink` that discards all events and ignores rewinds.
+struct DiscardSink {
+    id: EventSinkId,
+}
+
+impl EventSink for DiscardSink {
+    fn id(&self) -> EventSinkId {
+        self.id
+    }
+}
+
+impl<R: BattleRules> ClientSink<R> for DiscardSink {
+    fn send(&mut self, _: &VersionedEventWrapper<R>) -> WeaselResult<(), R> {
+        Ok(())
+    }
 }
 
 #[test]
@@ -438,7 +480,7 @@ fn rights() {
     .enable_authentication(PLAYER_1_ID)
     .build();
     let client = Arc::new(Mutex::new(client));
-    assert_eq!(client.lock().unwrap().authentication(), true);
+    assert!(client.lock().unwrap().authentication());
     // Connect the client to the server.
     let mut client_sink = TestClientSink::new(CLIENT_1_ID, client.clone());
     add_sink!(server, client_sink);
@@ -634,3 +676,933 @@ fn client_server_serde() {
         ]
     );
 }
+
+#[test]
+fn rewind() {
+    // Create a server and a client connected to it.
+    let server = Arc::new(Mutex::new(util::server(CustomRules::new())));
+    let server_sink = TestServerSink::new(SERVER_1_ID, server.clone());
+    let client = Arc::new(Mutex::new(util::client(CustomRules::new(), server_sink)));
+    let mut client_sink = TestClientSink::new(CLIENT_1_ID, client.clone());
+    add_sink!(server, client_sink);
+    // Add a sink that ignores rewinds.
+    assert_eq!(
+        server
+            .lock()
+            .unwrap()
+            .client_sinks_mut()
+            .add_sink(Box::new(DiscardSink { id: CLIENT_2_ID }))
+            .err(),
+        None
+    );
+    // Create two teams.
+    util::team(&mut *server.lock().unwrap(), TEAM_1_ID);
+    util::team(&mut *server.lock().unwrap(), TEAM_2_ID);
+    assert_eq!(client_sink.receive().err(), None);
+    assert_eq!(events!(client).len(), 2);
+    // Rewind the server. The client must roll back as well.
+    assert_eq!(server.lock().unwrap().rewind_to(1).err(), None);
+    assert_eq!(events!(client).len(), 1);
+    assert_eq!(
+        client.lock().unwrap().battle().entities().teams().count(),
+        1
+    );
+    // The sink ignoring rewinds is still connected.
+    assert_eq!(server.lock().unwrap().client_sinks().sinks().count(), 2);
+    // Redo the event. The client receives it again.
+    assert_eq!(server.lock().unwrap().redo_to(2).err(), None);
+    assert_eq!(client_sink.receive().err(), None);
+    assert_eq!(events!(client).len(), 2);
+    assert_eq!(
+        client.lock().unwrap().battle().entities().teams().count(),
+        2
+    );
+}
+
+#[test]
+fn prediction() {
+    // Create a server and a client with prediction connected to it.
+    let server = Arc::new(Mutex::new(util::server(CustomRules::new())));
+    let server_sink = TestServerSink::new(SERVER_1_ID, server.clone());
+    let battle = Battle::builder(CustomRules::new()).build();
+    let client = Arc::new(Mutex::new(
+        Client::builder(battle, Box::new(server_sink))
+            .enable_prediction()
+            .build(),
+    ));
+    assert!(client.lock().unwrap().prediction());
+    let mut client_sink = TestClientSink::new(CLIENT_1_ID, client.clone());
+    add_sink!(server, client_sink);
+    // Attach an event recorder to the client.
+    let event_recorder = TestClientSink::new(CLIENT_1_ID, client.clone());
+    add_sink!(client, event_recorder);
+    // Create a team and a creature.
+    util::team(&mut *server.lock().unwrap(), TEAM_1_ID);
+    util::creature(&mut *server.lock().unwrap(), CREATURE_1_ID, TEAM_1_ID, ());
+    assert_eq!(client_sink.receive().err(), None);
+    // The server fires an event, but the client doesn't know it yet.
+    util::dummy(&mut *server.lock().unwrap());
+    // Fire an event from the client. It must be applied immediately.
+    util::start_turn(&mut *client.lock().unwrap(), &ENTITY_1_ID);
+    assert_eq!(client.lock().unwrap().pending_predictions().len(), 1);
+    assert_eq!(
+        client.lock().unwrap().pending_predictions()[0].kind(),
+        EventKind::StartTurn
+    );
+    assert!(client
+        .lock()
+        .unwrap()
+        .battle()
+        .rounds()
+        .state()
+        .has_actor(&ENTITY_1_ID));
+    assert_eq!(event_recorder.buffer.lock().unwrap().len(), 2);
+    // Resync requests don't include predictions.
+    assert_eq!(events!(client).len(), 3);
+    assert_eq!(
+        client.lock().unwrap().resync_request(),
+        ResyncRequest::new(Some(1), None)
+    );
+    // Receive the server's events one by one. The prediction is kept on top of the
+    // authoritative state until it's confirmed.
+    let events: Vec<_> = client_sink.buffer.lock().unwrap().drain(..).collect();
+    let mut events = events.into_iter();
+    assert_eq!(
+        client.lock().unwrap().receive(events.next().unwrap()).err(),
+        None
+    );
+    assert_eq!(client.lock().unwrap().pending_predictions().len(), 1);
+    assert_eq!(events!(client)[2].kind(), EventKind::DummyEvent);
+    assert!(client
+        .lock()
+        .unwrap()
+        .battle()
+        .rounds()
+        .state()
+        .has_actor(&ENTITY_1_ID));
+    assert_eq!(
+        client.lock().unwrap().receive(events.next().unwrap()).err(),
+        None
+    );
+    assert!(client.lock().unwrap().pending_predictions().is_empty());
+    assert_eq!(events!(client).len(), 4);
+    assert!(client
+        .lock()
+        .unwrap()
+        .battle()
+        .rounds()
+        .state()
+        .has_actor(&ENTITY_1_ID));
+    assert_eq!(event_recorder.buffer.lock().unwrap().len(), 4);
+    // Events rejected by the server are not predicted.
+    util::end_turn(&mut *server.lock().unwrap());
+    assert!(EndTurn::trigger(&mut *client.lock().unwrap())
+        .fire()
+        .is_err());
+    assert!(client.lock().unwrap().pending_predictions().is_empty());
+    assert_eq!(client_sink.receive().err(), None);
+    assert_eq!(events!(client).len(), 5);
+    // Predictions can be discarded.
+    util::dummy(&mut *client.lock().unwrap());
+    assert_eq!(events!(client).len(), 6);
+    client.lock().unwrap().discard_predictions();
+    assert!(client.lock().unwrap().pending_predictions().is_empty());
+    assert_eq!(events!(client).len(), 5);
+    assert_eq!(client_sink.receive().err(), None);
+    assert_eq!(events!(client).len(), 6);
+}
+
+#[test]
+fn visibility() {
+    #[derive(Default)]
+    struct CustomTeamRules {}
+
+    impl TeamRules<CustomRules> for CustomTeamRules {
+        type Id = u32;
+        type ObjectivesSeed = ();
+        type Objectives = ();
+
+        fn event_visibility(
+            &self,
+            _state: &BattleState<CustomRules>,
+            event: &EventWrapper<CustomRules>,
+            player: Option<PlayerId>,
+            rights: &RightsHandle<'_, CustomRules>,
+        ) -> Visibility<CustomRules> {
+            if let Some(creation) = event
+                .event()
+                .as_any()
+                .downcast_ref::<CreateCreature<CustomRules>>()
+            {
+                match player {
+                    // Players can't see the creatures of other teams.
+                    Some(player) if !rights.check(player, creation.team_id()) => {
+                        return Visibility::Hidden
+                    }
+                    // Spectators only know that something happened.
+                    None => {
+                        return Visibility::Redacted(DummyEvent::trigger(&mut ()).event());
+                    }
+                    _ => {}
+                }
+            }
+            Visibility::Full
+        }
+    }
+
+    battle_rules_with_team! { CustomTeamRules }
+
+    /// A sink storing all events received by a player.
+    struct PlayerSink {
+        id: EventSinkId,
+        player: Option<PlayerId>,
+        events: Arc<Mutex<Vec<VersionedEventWrapper<CustomRules>>>>,
+    }
+
+    impl EventSink for PlayerSink {
+        fn id(&self) -> EventSinkId {
+            self.id
+        }
+    }
+
+    impl ClientSink<CustomRules> for PlayerSink {
+        fn send(
+            &mut self,
+            event: &VersionedEventWrapper<CustomRules>,
+        ) -> WeaselResult<(), CustomRules> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+
+        fn player(&self) -> Option<PlayerId> {
+            self.player
+        }
+    }
+
+    // Create a server filtering events by visibility.
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut server = Server::builder(battle).enable_visibility().build();
+    // Connect the two players and a spectator.
+    let players = [Some(PLAYER_1_ID), Some(PLAYER_2_ID), None];
+    let mut all_events = Vec::new();
+    for (i, player) in players.iter().enumerate() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = PlayerSink {
+            id: i as EventSinkId,
+            player: *player,
+            events: events.clone(),
+        };
+        assert_eq!(
+            server
+                .client_sinks_mut()
+                .add_sink_from(Box::new(sink), 0)
+                .err(),
+            None
+        );
+        all_events.push(events);
+    }
+    // Create two teams, each one controlled by a different player.
+    util::team(&mut server, TEAM_1_ID);
+    util::team(&mut server, TEAM_2_ID);
+    assert_eq!(server.rights_mut().add(PLAYER_1_ID, &TEAM_1_ID).err(), None);
+    assert_eq!(server.rights_mut().add(PLAYER_2_ID, &TEAM_2_ID).err(), None);
+    // Create a creature in the first team.
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    // Everyone receives contiguous events, but only the first player sees the creature.
+    let kinds = |events: &Arc<Mutex<Vec<VersionedEventWrapper<CustomRules>>>>| {
+        let events = events.lock().unwrap();
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(event.id(), i as EventId);
+        }
+        events.iter().map(|e| e.kind()).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        kinds(&all_events[0]),
+        vec![
+            EventKind::CreateTeam,
+            EventKind::CreateTeam,
+            EventKind::CreateCreature
+        ]
+    );
+    assert_eq!(
+        kinds(&all_events[1]),
+        vec![
+            EventKind::CreateTeam,
+            EventKind::CreateTeam,
+            EventKind::DummyEvent
+        ]
+    );
+    assert_eq!(kinds(&all_events[2]), kinds(&all_events[1]));
+    // The history of the server is not affected.
+    assert_eq!(
+        server.battle().history().events()[2].kind(),
+        EventKind::CreateCreature
+    );
+    // Past events can't be filtered again.
+    let sink = PlayerSink {
+        id: CLIENT_ERR_ID,
+        player: None,
+        events: Arc::new(Mutex::new(Vec::new())),
+    };
+    assert_eq!(
+        server
+            .client_sinks_mut()
+            .add_sink_from(Box::new(sink), 2)
+            .err(),
+        Some(WeaselError::FilteredEventRange(2..3))
+    );
+    assert_eq!(
+        server.client_sinks_mut().send_range(0, 0..1).err(),
+        Some(WeaselError::FilteredEventRange(0..1))
+    );
+    assert_eq!(server.client_sinks().sinks().count(), 3);
+    assert_eq!(
+        server
+            .resync(
+                Box::new(DiscardSink { id: CLIENT_ERR_ID }),
+                &ResyncRequest::new(Some(1), None)
+            )
+            .err(),
+        Some(WeaselError::FilteredEventRange(2..3))
+    );
+}
+
+#[test]
+fn visibility_divergence() {
+    use weasel::Actor;
+
+    #[derive(Default)]
+    struct CustomTeamRules {}
+
+    impl CustomTeamRules {
+        /// Returns true if the creature with the given id is hidden to `player`.
+        fn hidden(
+            state: &BattleState<CustomRules>,
+            creature_id: &u32,
+            player: PlayerId,
+            rights: &RightsHandle<'_, CustomRules>,
+        ) -> bool {
+            match state.entities().creature(creature_id) {
+                Some(creature) => !rights.check(player, creature.team_id()),
+                None => false,
+            }
+        }
+    }
+
+    impl TeamRules<CustomRules> for CustomTeamRules {
+        type Id = u32;
+        type ObjectivesSeed = ();
+        type Objectives = ();
+
+        fn event_visibility(
+            &self,
+            state: &BattleState<CustomRules>,
+            event: &EventWrapper<CustomRules>,
+            player: Option<PlayerId>,
+            rights: &RightsHandle<'_, CustomRules>,
+        ) -> Visibility<CustomRules> {
+            let player = match player {
+                Some(player) => player,
+                None => return Visibility::Full,
+            };
+            let event = event.event().as_any();
+            // Hide the creation of creatures of other teams and the turns they start.
+            // Ends of turns are left visible on purpose.
+            let hidden = if let Some(creation) = event.downcast_ref::<CreateCreature<CustomRules>>()
+            {
+                !rights.check(player, creation.team_id())
+            } else if let Some(start) = event.downcast_ref::<StartTurn<CustomRules>>() {
+                start.ids().iter().any(|id| match id {
+                    EntityId::Creature(id) => Self::hidden(state, id, player, rights),
+                    _ => false,
+                })
+            } else {
+                false
+            };
+            if hidden {
+                Visibility::Hidden
+            } else {
+                Visibility::Full
+            }
+        }
+    }
+
+    battle_rules_with_team! { CustomTeamRules }
+
+    /// A sink storing all events received by a player.
+    struct PlayerSink {
+        player: PlayerId,
+        events: Arc<Mutex<Vec<VersionedEventWrapper<CustomRules>>>>,
+    }
+
+    impl EventSink for PlayerSink {
+        fn id(&self) -> EventSinkId {
+            CLIENT_2_ID
+        }
+    }
+
+    impl ClientSink<CustomRules> for PlayerSink {
+        fn send(
+            &mut self,
+            event: &VersionedEventWrapper<CustomRules>,
+        ) -> WeaselResult<(), CustomRules> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+
+        fn player(&self) -> Option<PlayerId> {
+            Some(self.player)
+        }
+    }
+
+    // Create a server with two teams, and a client for the player controlling the second one.
+    let battle = Battle::builder(CustomRules::new()).build();
+    let server = Arc::new(Mutex::new(
+        Server::builder(battle).enable_visibility().build(),
+    ));
+    let server_sink = TestServerSink::new(SERVER_1_ID, server.clone());
+    let mut client = util::client(CustomRules::new(), server_sink);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    {
+        let mut server = server.lock().unwrap();
+        let sink = PlayerSink {
+            player: PLAYER_2_ID,
+            events: events.clone(),
+        };
+        assert_eq!(
+            server.client_sinks_mut().add_sink(Box::new(sink)).err(),
+            None
+        );
+        util::team(&mut *server, TEAM_1_ID);
+        util::team(&mut *server, TEAM_2_ID);
+        assert_eq!(server.rights_mut().add(PLAYER_2_ID, &TEAM_2_ID).err(), None);
+        // Create a creature in the first team and start its turn.
+        util::creature(&mut *server, CREATURE_1_ID, TEAM_1_ID, ());
+        util::start_turn(&mut *server, &EntityId::Creature(CREATURE_1_ID));
+    }
+    // The client doesn't know the creature, but it can apply all events.
+    let received: Vec<_> = events.lock().unwrap().drain(..).collect();
+    for event in received {
+        assert_eq!(client.receive(event).err(), None);
+    }
+    assert_eq!(client.battle().history().len(), 4);
+    assert_eq!(client.battle().entities().creatures().count(), 0);
+    // An event depending on a hidden one must be hidden as well,
+    // otherwise the client fails to verify it.
+    util::end_turn(&mut *server.lock().unwrap());
+    let end_turn = events.lock().unwrap().pop().unwrap();
+    assert_eq!(end_turn.kind(), EventKind::EndTurn);
+    assert_eq!(
+        client.receive(end_turn).err(),
+        Some(WeaselError::NoTurnInProgress)
+    );
+    assert_eq!(client.battle().history().len(), 4);
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn checksum() {
+    use weasel::space::{PositionClaim, SpaceRules};
+    use weasel::{battle_rules_with_space, WriteMetrics};
+
+    /// Space rules counting the number of moves. The count is wrong when `bias` is not zero.
+    #[derive(Default)]
+    struct CustomSpaceRules {
+        bias: u32,
+    }
+
+    impl SpaceRules<CustomRules> for CustomSpaceRules {
+        type Position = ();
+        type SpaceSeed = ();
+        type SpaceModel = u32;
+        type SpaceAlteration = ();
+
+        fn generate_model(&self, _: &Option<Self::SpaceSeed>) -> Self::SpaceModel {
+            0
+        }
+
+        fn move_entity<'a>(
+            &self,
+            model: &mut Self::SpaceModel,
+            _claim: PositionClaim<'a, CustomRules>,
+            _position: Option<&Self::Position>,
+            _metrics: &mut WriteMetrics<CustomRules>,
+        ) {
+            *model += 1 + self.bias;
+        }
+    }
+
+    battle_rules_with_space! { CustomSpaceRules }
+
+    // Create a server and a client, both verifying checksums.
+    let battle = Battle::builder(CustomRules::new()).build();
+    let server = Arc::new(Mutex::new(
+        Server::builder(battle).enable_checksum().build(),
+    ));
+    let server_sink = TestServerSink::new(SERVER_1_ID, server.clone());
+    let mut rules = CustomRules::new();
+    rules.space_rules = Some(CustomSpaceRules { bias: 1 });
+    let battle = Battle::builder(rules).build();
+    let client = Arc::new(Mutex::new(
+        Client::builder(battle, Box::new(server_sink))
+            .enable_checksum()
+            .build(),
+    ));
+    let mut client_sink = TestClientSink::new(CLIENT_1_ID, client.clone());
+    add_sink!(server, client_sink);
+    // Both battles have the same state after creating a team.
+    util::team(&mut *server.lock().unwrap(), TEAM_1_ID);
+    assert_eq!(events!(server).len(), 1);
+    let checksum = server.lock().unwrap().battle().checksum();
+    assert_eq!(
+        client_sink.buffer.lock().unwrap()[0].checksum(),
+        Some(checksum)
+    );
+    assert_eq!(client_sink.receive().err(), None);
+    assert_eq!(client.lock().unwrap().battle().checksum(), checksum);
+    // Spawning a creature uses the nondeterministic rules.
+    util::creature(&mut *server.lock().unwrap(), CREATURE_1_ID, TEAM_1_ID, ());
+    let expected = server.lock().unwrap().battle().checksum();
+    let actual = {
+        let events: Vec<_> = client_sink.buffer.lock().unwrap().drain(..).collect();
+        let mut client = client.lock().unwrap();
+        let result = client.receive(events[0].clone());
+        let actual = client.battle().checksum();
+        assert_eq!(
+            result.err(),
+            Some(WeaselError::ChecksumMismatch(1, expected, actual))
+        );
+        actual
+    };
+    assert_ne!(actual, expected);
+    // The event is applied anyway.
+    assert_eq!(events!(client).len(), 2);
+    // The server refuses to resynchronize the client with a replay.
+    let request = client.lock().unwrap().resync_request();
+    assert_eq!(request.checksum(), Some(actual));
+    assert_eq!(
+        server
+            .lock()
+            .unwrap()
+            .resync(Box::new(client_sink), &request)
+            .err(),
+        Some(WeaselError::ChecksumMismatch(1, expected, actual))
+    );
+    // The sink already connected is kept.
+    assert_eq!(server.lock().unwrap().client_sinks().sinks().count(), 1);
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn checksum_visibility() {
+    #[derive(Default)]
+    struct CustomTeamRules {}
+
+    impl TeamRules<CustomRules> for CustomTeamRules {
+        type Id = u32;
+        type ObjectivesSeed = ();
+        type Objectives = ();
+
+        fn event_visibility(
+            &self,
+            _state: &BattleState<CustomRules>,
+            event: &EventWrapper<CustomRules>,
+            player: Option<PlayerId>,
+            rights: &RightsHandle<'_, CustomRules>,
+        ) -> Visibility<CustomRules> {
+            // Players can't see the creatures of other teams.
+            match (
+                event
+                    .event()
+                    .as_any()
+                    .downcast_ref::<CreateCreature<CustomRules>>(),
+                player,
+            ) {
+                (Some(creation), Some(player)) if !rights.check(player, creation.team_id()) => {
+                    Visibility::Hidden
+                }
+                _ => Visibility::Full,
+            }
+        }
+    }
+
+    battle_rules_with_team! { CustomTeamRules }
+
+    /// A sink storing all events received by a player.
+    struct PlayerSink {
+        id: EventSinkId,
+        player: PlayerId,
+        events: Arc<Mutex<Vec<VersionedEventWrapper<CustomRules>>>>,
+    }
+
+    impl EventSink for PlayerSink {
+        fn id(&self) -> EventSinkId {
+            self.id
+        }
+    }
+
+    impl ClientSink<CustomRules> for PlayerSink {
+        fn send(
+            &mut self,
+            event: &VersionedEventWrapper<CustomRules>,
+        ) -> WeaselResult<(), CustomRules> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+
+        fn player(&self) -> Option<PlayerId> {
+            Some(self.player)
+        }
+    }
+
+    // Create a server with visibility and checksums, and connect two players.
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut server = Server::builder(battle)
+        .enable_visibility()
+        .enable_checksum()
+        .build();
+    let mut all_events = Vec::new();
+    for (i, player) in [PLAYER_1_ID, PLAYER_2_ID].iter().enumerate() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = PlayerSink {
+            id: i as EventSinkId,
+            player: *player,
+            events: events.clone(),
+        };
+        assert_eq!(
+            server.client_sinks_mut().add_sink(Box::new(sink)).err(),
+            None
+        );
+        all_events.push(events);
+    }
+    util::team(&mut server, TEAM_1_ID);
+    assert_eq!(server.rights_mut().add(PLAYER_1_ID, &TEAM_1_ID).err(), None);
+    // Create a creature hidden to the second player, then fire another event.
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    util::dummy(&mut server);
+    let checksums = |events: &Arc<Mutex<Vec<VersionedEventWrapper<CustomRules>>>>| {
+        events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| e.checksum().is_some())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(checksums(&all_events[0]), vec![true, true, true]);
+    // The second player's state diverged, thus it doesn't receive checksums anymore.
+    assert_eq!(checksums(&all_events[1]), vec![true, false, false]);
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn checksum_scheduler() {
+    use weasel::schedule::{ScheduleEvent, Timing};
+
+    // Scheduled events are part of the checksum.
+    let mut server = util::server(CustomRules::new());
+    let checksum = server.battle().checksum();
+    let prototype = DummyEvent::trigger(&mut ()).prototype();
+    assert_eq!(
+        ScheduleEvent::trigger(&mut server, prototype, Timing::Rounds(1))
+            .fire()
+            .err(),
+        None
+    );
+    assert_ne!(server.battle().checksum(), checksum);
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn checksum_compaction() {
+    // Create a server with checksums and compaction, recording the offloaded events.
+    let offloaded = Arc::new(Mutex::new(Vec::new()));
+    let offloaded_clone = offloaded.clone();
+    let battle = Battle::builder(CustomRules::new()).build();
+    let server = Arc::new(Mutex::new(
+        Server::builder(battle)
+            .enable_checksum()
+            .compact_history(2)
+            .offload_history(Box::new(move |events| {
+                offloaded_clone.lock().unwrap().extend(events)
+            }))
+            .build(),
+    ));
+    let server_sink = TestServerSink::new(SERVER_1_ID, server.clone());
+    let client = Arc::new(Mutex::new(util::client(CustomRules::new(), server_sink)));
+    let client_sink = TestClientSink::new(CLIENT_1_ID, client.clone());
+    add_sink!(server, client_sink);
+    util::team(&mut *server.lock().unwrap(), TEAM_1_ID);
+    for _ in 0..6 {
+        util::dummy(&mut *server.lock().unwrap());
+    }
+    // Offloaded events carry the same checksums sent to the clients.
+    let sent = client_sink.buffer.lock().unwrap().clone();
+    let offloaded = offloaded.lock().unwrap();
+    assert!(!offloaded.is_empty());
+    for event in offloaded.iter() {
+        assert_eq!(event.checksum(), sent[event.id() as usize].checksum());
+    }
+    // Checksums of the events still in the history are verified.
+    let mut server = server.lock().unwrap();
+    let first_id = server.battle().history().first_id();
+    assert!(first_id > 0);
+    server.client_sinks_mut().remove_sink(CLIENT_1_ID);
+    let last = sent.last().unwrap();
+    let request = ResyncRequest::new(Some(last.id()), Some(last.checksum().unwrap() + 1));
+    assert_eq!(
+        server.resync(Box::new(client_sink.clone()), &request).err(),
+        Some(WeaselError::ChecksumMismatch(
+            last.id(),
+            last.checksum().unwrap(),
+            last.checksum().unwrap() + 1
+        ))
+    );
+    let request = ResyncRequest::new(Some(last.id()), last.checksum());
+    assert_eq!(
+        server.resync(Box::new(client_sink), &request).ok(),
+        Some(Resync::Replay(7..7))
+    );
+}
+
+#[test]
+fn resync() {
+    // Create a server that sends snapshots to clients missing more than two events.
+    let battle = Battle::builder(CustomRules::new()).build();
+    let server = Arc::new(Mutex::new(
+        Server::builder(battle).enable_snapshot_resync(2).build(),
+    ));
+    let server_sink = TestServerSink::new(SERVER_1_ID, server.clone());
+    let client = Arc::new(Mutex::new(util::client(CustomRules::new(), server_sink)));
+    let mut client_sink = TestClientSink::new(CLIENT_1_ID, client.clone());
+    // A new client receives the whole history.
+    util::team(&mut *server.lock().unwrap(), TEAM_1_ID);
+    let request = client.lock().unwrap().resync_request();
+    assert_eq!(request, ResyncRequest::new(None, None));
+    assert_eq!(
+        server
+            .lock()
+            .unwrap()
+            .resync(Box::new(client_sink.clone()), &request)
+            .unwrap(),
+        Resync::Replay(0..1)
+    );
+    assert_eq!(client_sink.receive().err(), None);
+    assert_eq!(events!(client).len(), 1);
+    // The client loses one event.
+    server
+        .lock()
+        .unwrap()
+        .client_sinks_mut()
+        .remove_sink(CLIENT_1_ID);
+    util::team(&mut *server.lock().unwrap(), TEAM_2_ID);
+    // Only the missing event is replayed.
+    let request = client.lock().unwrap().resync_request();
+    assert_eq!(request.last_event(), Some(0));
+    assert_eq!(
+        server
+            .lock()
+            .unwrap()
+            .resync(Box::new(client_sink.clone()), &request)
+            .unwrap(),
+        Resync::Replay(1..2)
+    );
+    assert_eq!(client_sink.receive().err(), None);
+    assert_eq!(events!(client).len(), 2);
+    // The client loses too many events.
+    server
+        .lock()
+        .unwrap()
+        .client_sinks_mut()
+        .remove_sink(CLIENT_1_ID);
+    for _ in 0..3 {
+        util::dummy(&mut *server.lock().unwrap());
+    }
+    // The client receives a snapshot.
+    let request = client.lock().unwrap().resync_request();
+    assert_eq!(
+        server
+            .lock()
+            .unwrap()
+            .resync(Box::new(client_sink.clone()), &request)
+            .unwrap(),
+        Resync::Snapshot(5)
+    );
+    assert_eq!(client.lock().unwrap().battle().history().len(), 5);
+    assert_eq!(client.lock().unwrap().battle().history().first_id(), 5);
+    assert_eq!(
+        client.lock().unwrap().battle().entities().teams().count(),
+        2
+    );
+    // New events are received as usual.
+    util::dummy(&mut *server.lock().unwrap());
+    assert_eq!(client_sink.receive().err(), None);
+    assert_eq!(client.lock().unwrap().battle().history().len(), 6);
+    // Without snapshots, clients can't be resynchronized when events are not in the history.
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    assert!(matches!(
+        server
+            .resync(
+                Box::new(client_sink.clone()),
+                &ResyncRequest::new(Some(5), None)
+            )
+            .err(),
+        Some(WeaselError::InvalidEventRange(range, 1)) if range.start == 6
+    ));
+    assert_eq!(server.client_sinks().sinks().count(), 0);
+}
+
+#[test]
+fn rejection() {
+    // Create a server and a client connected to it.
+    let server = Arc::new(Mutex::new(util::server(CustomRules::new())));
+    let server_sink = TestServerSink::new(SERVER_1_ID, server.clone());
+    let client = Arc::new(Mutex::new(util::client(CustomRules::new(), server_sink)));
+    let client_sink = TestClientSink::new(CLIENT_1_ID, client.clone());
+    add_sink!(server, client_sink);
+    // Record all rejections received by the client.
+    let rejections = Arc::new(Mutex::new(Vec::new()));
+    let rejections_clone = rejections.clone();
+    client
+        .lock()
+        .unwrap()
+        .set_rejection_callback(Some(Box::new(move |rejection: &Rejection| {
+            rejections_clone.lock().unwrap().push(rejection.clone())
+        })));
+    // Each event fired by the client has a new correlation id.
+    assert_eq!(client.lock().unwrap().last_correlation_id(), None);
+    util::dummy(&mut *client.lock().unwrap());
+    assert_eq!(client.lock().unwrap().last_correlation_id(), Some(0));
+    util::dummy(&mut *client.lock().unwrap());
+    assert_eq!(client.lock().unwrap().last_correlation_id(), Some(1));
+    // Rejected events are notified to the client.
+    let mut prototype = StartTurn::trigger(&mut (), ENTITY_1_ID)
+        .prototype()
+        .client_prototype(0, None);
+    prototype.set_correlation_id(Some(2));
+    let error = server
+        .lock()
+        .unwrap()
+        .process_client_from(CLIENT_1_ID, prototype.clone())
+        .unwrap_err();
+    assert_eq!(
+        *rejections.lock().unwrap(),
+        vec![Rejection::new(2, error.code(), error.to_string())]
+    );
+    // Events without a correlation id don't produce rejections.
+    prototype.set_correlation_id(None);
+    assert!(server
+        .lock()
+        .unwrap()
+        .process_client_from(CLIENT_1_ID, prototype.clone())
+        .is_err());
+    assert_eq!(rejections.lock().unwrap().len(), 1);
+    // Rejections to unknown sinks don't hide the original error.
+    prototype.set_correlation_id(Some(3));
+    assert_eq!(
+        server
+            .lock()
+            .unwrap()
+            .process_client_from(CLIENT_ERR_ID, prototype)
+            .err(),
+        Some(error)
+    );
+    assert_eq!(rejections.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn rejection_after_apply() {
+    use weasel::event::{EventPrototype, EventQueue};
+    use weasel::fight::{FightRules, Reaction};
+
+    #[derive(Default)]
+    struct CustomFightRules {}
+
+    impl FightRules<CustomRules> for CustomFightRules {
+        type Impact = ();
+        type Potency = ();
+
+        fn react(
+            &self,
+            _state: &BattleState<CustomRules>,
+            event: &EventPrototype<CustomRules>,
+            mut reactions: &mut Option<EventQueue<CustomRules>>,
+        ) -> Reaction<CustomRules> {
+            // React to dummy events with an invalid event.
+            if event.kind() == EventKind::DummyEvent {
+                EndTurn::trigger(&mut reactions).fire();
+            }
+            Reaction::Proceed
+        }
+    }
+
+    weasel::battle_rules_with_fight! { CustomFightRules }
+
+    // Create a server and a client recording its rejections.
+    let server = Arc::new(Mutex::new(util::server(CustomRules::new())));
+    let server_sink = TestServerSink::new(SERVER_1_ID, server.clone());
+    let client = Arc::new(Mutex::new(util::client(CustomRules::new(), server_sink)));
+    let client_sink = TestClientSink::new(CLIENT_1_ID, client.clone());
+    add_sink!(server, client_sink);
+    let rejections = Arc::new(Mutex::new(0));
+    let rejections_clone = rejections.clone();
+    client
+        .lock()
+        .unwrap()
+        .set_rejection_callback(Some(Box::new(move |_: &Rejection| {
+            *rejections_clone.lock().unwrap() += 1
+        })));
+    // The event is applied, thus the failure of its reaction doesn't reject it.
+    let mut prototype = DummyEvent::trigger(&mut ())
+        .prototype()
+        .client_prototype(0, None);
+    prototype.set_correlation_id(Some(0));
+    assert!(server
+        .lock()
+        .unwrap()
+        .process_client_from(CLIENT_1_ID, prototype)
+        .is_err());
+    assert_eq!(events!(server).len(), 1);
+    assert_eq!(*rejections.lock().unwrap(), 0);
+}
+
+#[test]
+fn client_simulation() {
+    // Create a server and a client connected to it.
+    let server = Arc::new(Mutex::new(util::server(CustomRules::new())));
+    let server_sink = TestServerSink::new(SERVER_1_ID, server.clone());
+    let client = Arc::new(Mutex::new(util::client(CustomRules::new(), server_sink)));
+    let mut client_sink = TestClientSink::new(CLIENT_1_ID, client.clone());
+    add_sink!(server, client_sink);
+    util::team(&mut *server.lock().unwrap(), TEAM_1_ID);
+    util::creature(&mut *server.lock().unwrap(), CREATURE_1_ID, TEAM_1_ID, ());
+    assert_eq!(client_sink.receive().err(), None);
+    // Invalid events can't be simulated.
+    let prototype = EndTurn::trigger(&mut ()).prototype();
+    assert_eq!(
+        client
+            .lock()
+            .unwrap()
+            .simulate(prototype)
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::NoTurnInProgress)
+    );
+    // Simulations are neither sent to the server nor kept by the client.
+    let prototype = StartTurn::trigger(&mut (), ENTITY_1_ID).prototype();
+    let simulation = client.lock().unwrap().simulate(prototype).unwrap();
+    assert_eq!(simulation.events().len(), 1);
+    assert_eq!(simulation.events()[0].kind(), EventKind::StartTurn);
+    assert!(simulation.outcome().rounds().is_acting(&ENTITY_1_ID));
+    assert_eq!(simulation.characters().len(), 1);
+    assert_eq!(events!(client).len(), 2);
+    assert_eq!(events!(server).len(), 2);
+    assert_eq!(client.lock().unwrap().last_correlation_id(), None);
+    assert!(!client
+        .lock()
+        .unwrap()
+        .battle()
+        .rounds()
+        .state()
+        .has_actor(&ENTITY_1_ID));
+}
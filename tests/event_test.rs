@@ -17,6 +17,7 @@ use weasel::metric::WriteMetrics;
 use weasel::object::{CreateObject, RemoveObject};
 use weasel::round::{EndRound, EndTurn, EnvironmentTurn, ResetRounds, RoundsModel, StartTurn};
 use weasel::rules::ability::SimpleAbility;
+use weasel::schedule::{ScheduleEvent, Timing};
 #[cfg(feature = "serialization")]
 use weasel::serde::FlatEvent;
 use weasel::space::{AlterSpace, MoveEntity, ResetSpace, SpaceModel};
@@ -24,6 +25,7 @@ use weasel::status::{AlterStatuses, ClearStatus, InflictStatus};
 use weasel::team::{
     ConcludeObjectives, Conclusion, CreateTeam, Relation, RemoveTeam, ResetObjectives, SetRelations,
 };
+use weasel::transaction::Transaction;
 #[cfg(feature = "serialization")]
 use weasel::user::UserEventPacker;
 use weasel::user::{UserMetricId, UserRules};
@@ -46,7 +48,10 @@ pub struct MyEvent<R> {
 
 impl<R: BattleRules> MyEvent<R> {
     /// Returns a trigger for this event.
-    pub fn trigger<P: EventProcessor<R>>(processor: &mut P, data: String) -> MyEventTrigger<R, P> {
+    pub fn trigger<P: EventProcessor<R>>(
+        processor: &mut P,
+        data: String,
+    ) -> MyEventTrigger<'_, R, P> {
         MyEventTrigger {
             processor,
             data,
@@ -186,7 +191,7 @@ fn conditional() {
                 .fire();
             Conditional::new(
                 DummyEvent::trigger(&mut event_queue),
-                std::rc::Rc::new(|state: &BattleState<CustomRules>| {
+                std::sync::Arc::new(|state: &BattleState<CustomRules>| {
                     state
                         .entities()
                         .actor(&ENTITY_1_ID)
@@ -304,7 +309,7 @@ fn user_event_serde() {
     impl UserEventPacker<CustomRules> for Package {
         fn boxed(self) -> WeaselResult<Box<dyn Event<CustomRules> + Send>, CustomRules> {
             let event = match self {
-                Package::MyEvent(event) => (Box::new(event) as Box<dyn Event<CustomRules> + Send>),
+                Package::MyEvent(event) => Box::new(event) as Box<dyn Event<CustomRules> + Send>,
             };
             Ok(event)
         }
@@ -389,11 +394,30 @@ macro_rules! events_vec {
         events.push(ResetRounds::trigger(&mut ()).event());
         events.push(ResetSpace::trigger(&mut ()).event());
         events.push(EndBattle::trigger(&mut ()).event());
+        events.push(
+            ScheduleEvent::trigger(
+                &mut (),
+                DummyEvent::trigger(&mut ()).prototype(),
+                Timing::TurnStart(ENTITY_1_ID),
+            )
+            .event(),
+        );
+        events.push(
+            Transaction::trigger(
+                &mut (),
+                vec![
+                    DummyEvent::trigger(&mut ()).event(),
+                    EndTurn::trigger(&mut ()).event(),
+                ],
+            )
+            .event(),
+        );
         events
     }};
 }
 
 #[test]
+#[allow(clippy::vec_init_then_push)]
 fn events_debug() {
     let events = events_vec!();
     for event in events {
@@ -408,14 +432,11 @@ fn events_debug() {
 
 #[cfg(feature = "serialization")]
 #[test]
+#[allow(clippy::vec_init_then_push)]
 fn events_serde() {
     let events = events_vec!();
     // Serialize all events.
-    let flat_events: Vec<_> = events
-        .iter()
-        .cloned()
-        .map(|e| FlatEvent::flattened(e))
-        .collect();
+    let flat_events: Vec<_> = events.iter().cloned().map(FlatEvent::flattened).collect();
     let json = serde_json::to_string(&flat_events).unwrap();
     // Deserialize all events.
     let deserialized_flat_events: Vec<FlatEvent<_>> = serde_json::from_str(&json).unwrap();
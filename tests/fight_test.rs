@@ -4,8 +4,11 @@ use weasel::battle::{BattleController, BattleRules, BattleState};
 use weasel::character::{AlterStatistics, Character, CharacterRules};
 use weasel::entity::{EntityId, Transmutation};
 use weasel::entropy::Entropy;
-use weasel::event::{EventKind, EventQueue, EventTrigger};
-use weasel::fight::{ApplyImpact, FightRules};
+use weasel::error::WeaselError;
+use weasel::event::{
+    DummyEvent, EventKind, EventProcessor, EventPrototype, EventQueue, EventTrigger,
+};
+use weasel::fight::{ApplyImpact, FightRules, Reaction};
 use weasel::metric::WriteMetrics;
 use weasel::rules::ability::SimpleAbility;
 use weasel::rules::statistic::SimpleStatistic;
@@ -179,3 +182,145 @@ fn default_works() {
     // ApplyImpact with default rules does not return an error.
     assert_eq!(ApplyImpact::trigger(&mut server, ()).fire().err(), None);
 }
+
+#[test]
+fn reactions() {
+    #[derive(Default)]
+    pub struct ReactiveFightRules {}
+
+    impl FightRules<CustomRules> for ReactiveFightRules {
+        type Impact = i32;
+        type Potency = ();
+
+        fn react(
+            &self,
+            _state: &BattleState<CustomRules>,
+            event: &EventPrototype<CustomRules>,
+            mut reactions: &mut Option<EventQueue<CustomRules>>,
+        ) -> Reaction<CustomRules> {
+            let impact = match event.as_any().downcast_ref::<ApplyImpact<CustomRules>>() {
+                Some(event) => *event.impact(),
+                None => return Reaction::Proceed,
+            };
+            match impact {
+                // Resolve a dummy event before the impact.
+                1 => {
+                    DummyEvent::trigger(&mut reactions).fire();
+                    Reaction::Proceed
+                }
+                // Counter the impact.
+                2 => Reaction::Cancel,
+                // Weaken the impact.
+                3 => Reaction::Replace(ApplyImpact::trigger(&mut (), 1).event()),
+                _ => Reaction::Proceed,
+            }
+        }
+    }
+
+    battle_rules! {
+        EmptyTeamRules,
+        EmptyCharacterRules,
+        EmptyActorRules,
+        ReactiveFightRules,
+        EmptyUserRules,
+        EmptySpaceRules,
+        EmptyRoundsRules,
+        EmptyEntropyRules
+    }
+
+    let mut server = util::server(CustomRules::new());
+    util::dummy(&mut server);
+    // Reactions are applied before the event and they inherit its origin.
+    let mut prototype = ApplyImpact::trigger(&mut (), 1).prototype();
+    prototype.set_origin(Some(0));
+    assert_eq!(server.process(prototype).err(), None);
+    let events = server.battle().history().events();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[1].kind(), EventKind::DummyEvent);
+    assert_eq!(events[1].origin(), Some(0));
+    assert_eq!(events[2].kind(), EventKind::ApplyImpact);
+    assert_eq!(events[2].origin(), Some(0));
+    // Cancelled events are not applied.
+    assert_eq!(
+        ApplyImpact::trigger(&mut server, 2)
+            .fire()
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::EventCancelled)
+    );
+    assert_eq!(server.battle().history().len(), 3);
+    // Replaced events are substituted by the replacement, which isn't reacted to.
+    assert_eq!(ApplyImpact::trigger(&mut server, 3).fire().err(), None);
+    let events = server.battle().history().events();
+    assert_eq!(events.len(), 4);
+    let event: &ApplyImpact<CustomRules> = events[3].as_any().downcast_ref().unwrap();
+    assert_eq!(*event.impact(), 1);
+}
+
+#[test]
+fn actor_reactions() {
+    #[derive(Default)]
+    pub struct ReactiveActorRules {}
+
+    impl ActorRules<CustomRules> for ReactiveActorRules {
+        type Ability = EmptyAbility;
+        type AbilitiesSeed = ();
+        type Activation = ();
+        type AbilitiesAlteration = ();
+
+        fn react(
+            &self,
+            _state: &BattleState<CustomRules>,
+            event: &EventPrototype<CustomRules>,
+            _reactions: &mut Option<EventQueue<CustomRules>>,
+        ) -> Reaction<CustomRules> {
+            match event.as_any().downcast_ref::<ApplyImpact<CustomRules>>() {
+                // Weaken strong impacts.
+                Some(event) if *event.impact() > 1 => {
+                    Reaction::Replace(ApplyImpact::trigger(&mut (), 1).event())
+                }
+                _ => Reaction::Proceed,
+            }
+        }
+    }
+
+    #[derive(Default)]
+    pub struct CancelFightRules {}
+
+    impl FightRules<CustomRules> for CancelFightRules {
+        type Impact = i32;
+        type Potency = ();
+
+        fn react(
+            &self,
+            _state: &BattleState<CustomRules>,
+            event: &EventPrototype<CustomRules>,
+            _reactions: &mut Option<EventQueue<CustomRules>>,
+        ) -> Reaction<CustomRules> {
+            match event.as_any().downcast_ref::<ApplyImpact<CustomRules>>() {
+                // Counter strong impacts.
+                Some(event) if *event.impact() > 2 => Reaction::Cancel,
+                _ => Reaction::Proceed,
+            }
+        }
+    }
+
+    battle_rules! {
+        EmptyTeamRules,
+        EmptyCharacterRules,
+        ReactiveActorRules,
+        CancelFightRules,
+        EmptyUserRules,
+        EmptySpaceRules,
+        EmptyRoundsRules,
+        EmptyEntropyRules
+    }
+
+    let mut server = util::server(CustomRules::new());
+    // The actor rules react first, so the fight rules never see the strong impact.
+    assert_eq!(ApplyImpact::trigger(&mut server, 3).fire().err(), None);
+    let events = server.battle().history().events();
+    assert_eq!(events.len(), 1);
+    let event: &ApplyImpact<CustomRules> = events[0].as_any().downcast_ref().unwrap();
+    assert_eq!(*event.impact(), 1);
+}
@@ -1,15 +1,46 @@
 use std::convert::TryInto;
-use weasel::battle::{BattleController, BattleRules};
+use std::sync::{Arc, Mutex};
+use weasel::battle::{Battle, BattleController, BattleRules};
+use weasel::entity::EntityId;
 use weasel::entropy::ResetEntropy;
-use weasel::event::{EventId, EventKind, EventTrigger};
-use weasel::round::EndTurn;
+use weasel::error::WeaselResult;
+use weasel::event::{
+    ClientSink, DummyEvent, EventId, EventKind, EventReceiver, EventSink, EventSinkId,
+    EventTrigger, EventWrapper, Originated, VersionedEventWrapper,
+};
+use weasel::metric::system::*;
+use weasel::player::PlayerId;
+use weasel::resync::ResyncRequest;
+use weasel::round::{EndRound, EndTurn};
 use weasel::{battle_rules, rules::empty::*};
+use weasel::{Server, WeaselError};
 
 const TEAM_1_ID: u32 = 1;
+const PLAYER_1_ID: PlayerId = 1;
 const CREATURE_1_ID: u32 = 1;
+const CREATURE_2_ID: u32 = 2;
+const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
+const ENTITY_2_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_2_ID);
 
 battle_rules! {}
 
+/// A `ClientSink` that discards all events.
+struct DiscardSink {
+    id: EventSinkId,
+}
+
+impl EventSink for DiscardSink {
+    fn id(&self) -> EventSinkId {
+        self.id
+    }
+}
+
+impl ClientSink<CustomRules> for DiscardSink {
+    fn send(&mut self, _: &VersionedEventWrapper<CustomRules>) -> WeaselResult<(), CustomRules> {
+        Ok(())
+    }
+}
+
 #[test]
 fn timeline_populated() {
     // Create a server with a creature.
@@ -30,3 +61,402 @@ fn timeline_populated() {
     assert_eq!(events[2].kind(), EventKind::ResetEntropy);
     assert_eq!(events[2].id(), len - 1);
 }
+
+#[test]
+fn rewind_and_redo() {
+    // Create a server with a creature in its turn.
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    util::end_turn(&mut server);
+    assert_eq!(server.battle().rounds().completed_turns(), 1);
+    // Rewind to before the creature's creation.
+    assert_eq!(server.rewind_to(1).err(), None);
+    assert_eq!(server.battle().history().len(), 1);
+    assert_eq!(server.battle().entities().creatures().count(), 0);
+    assert_eq!(server.battle().rounds().completed_turns(), 0);
+    assert_eq!(
+        server.battle().metrics().system_u64(CREATURES_CREATED),
+        None
+    );
+    assert_eq!(server.redo_buffer().len(), 3);
+    // Rewinding further keeps the events in order.
+    assert_eq!(server.rewind_to(0).err(), None);
+    assert_eq!(server.redo_buffer().len(), 4);
+    assert_eq!(server.redo_buffer()[0].kind(), EventKind::CreateTeam);
+    // Redo part of the events.
+    assert_eq!(server.redo_to(3).err(), None);
+    assert_eq!(server.battle().entities().creatures().count(), 1);
+    assert!(server.battle().rounds().is_acting(&ENTITY_1_ID));
+    assert_eq!(server.redo_buffer().len(), 1);
+    // A new event clears the redo buffer.
+    util::end_turn(&mut server);
+    assert!(server.redo_buffer().is_empty());
+    assert_eq!(server.battle().history().len(), 4);
+    assert_eq!(server.battle().rounds().completed_turns(), 1);
+}
+
+#[test]
+fn redo_rights() {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    assert_eq!(server.rights_mut().add(PLAYER_1_ID, &TEAM_1_ID).err(), None);
+    // Rights towards discarded teams are removed.
+    assert_eq!(server.rewind_to(0).err(), None);
+    assert!(!server.rights().check(PLAYER_1_ID, &TEAM_1_ID));
+    // They are given back once the team is redone.
+    assert_eq!(server.redo_to(1).err(), None);
+    assert!(server.rights().check(PLAYER_1_ID, &TEAM_1_ID));
+    // Rights are not given back if the redo buffer is cleared.
+    assert_eq!(server.rewind_to(0).err(), None);
+    util::team(&mut server, TEAM_1_ID);
+    assert!(!server.rights().check(PLAYER_1_ID, &TEAM_1_ID));
+}
+
+#[test]
+fn rewind_errors() {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    // Can't rewind to the future.
+    assert_eq!(
+        server.rewind_to(2).err(),
+        Some(WeaselError::InvalidEventRange(0..2, 1))
+    );
+    // Can't redo events that are not in the buffer.
+    assert_eq!(server.rewind_to(0).err(), None);
+    assert_eq!(
+        server.redo_to(2).err(),
+        Some(WeaselError::InvalidEventRange(0..2, 1))
+    );
+    // Battles restored from a snapshot can't be rewound before it.
+    assert_eq!(server.redo_to(1).err(), None);
+    let snapshot = server.battle().snapshot();
+    let battle = Battle::builder(CustomRules::new())
+        .snapshot(snapshot)
+        .build();
+    let mut server = Server::builder(battle).build();
+    assert_eq!(
+        server.rewind_to(0).err(),
+        Some(WeaselError::InvalidEventRange(0..1, 1))
+    );
+    assert_eq!(server.rewind_to(1).err(), None);
+}
+
+#[test]
+fn query() {
+    // Create a server with two creatures taking three turns.
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    util::creature(&mut server, CREATURE_2_ID, TEAM_1_ID, ());
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    util::end_turn(&mut server);
+    util::start_turn(&mut server, &ENTITY_2_ID);
+    util::end_turn(&mut server);
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    assert_eq!(
+        Originated::new(DummyEvent::trigger(&mut server), 3)
+            .fire()
+            .err(),
+        None
+    );
+    let ids = |events: Vec<&EventWrapper<_>>| -> Vec<EventId> {
+        events.iter().map(|event| event.id()).collect()
+    };
+    let history = server.battle().history();
+    // Filter by a single property.
+    assert_eq!(history.query().count(), 9);
+    assert_eq!(history.query().kind(EventKind::StartTurn).count(), 3);
+    assert_eq!(
+        ids(history.query().entity(ENTITY_1_ID).events()),
+        vec![1, 3, 7]
+    );
+    assert_eq!(ids(history.query().team(TEAM_1_ID).events()), vec![0, 1, 2]);
+    assert_eq!(ids(history.query().turn(1).events()), vec![5, 6]);
+    assert_eq!(history.query().round(0).count(), 9);
+    assert_eq!(history.query().round(1).count(), 0);
+    assert_eq!(ids(history.query().origin(3).events()), vec![8]);
+    // Combine filters.
+    let query = history
+        .query()
+        .kind(EventKind::StartTurn)
+        .entity(ENTITY_1_ID);
+    assert_eq!(query.first().map(|event| event.id()), Some(3));
+    assert_eq!(query.last().map(|event| event.id()), Some(7));
+    assert_eq!(
+        ids(history.query().entity(ENTITY_1_ID).turn(2).events()),
+        vec![7]
+    );
+    assert_eq!(
+        history
+            .query()
+            .kind(EventKind::EndTurn)
+            .entity(ENTITY_1_ID)
+            .count(),
+        0
+    );
+    assert!(history.query().turn(3).last().is_none());
+    // Turns of single events.
+    assert_eq!(history.turn(4), Some(0));
+    assert_eq!(history.turn(5), Some(1));
+    assert_eq!(history.round(8), Some(0));
+    assert_eq!(history.turn(9), None);
+    // Indexes are updated after a rewind.
+    assert_eq!(server.rewind_to(5).err(), None);
+    let history = server.battle().history();
+    assert_eq!(
+        ids(history.query().entity(ENTITY_1_ID).events()),
+        vec![1, 3]
+    );
+    assert_eq!(history.query().turn(1).count(), 0);
+    assert_eq!(history.query().origin(3).count(), 0);
+    assert_eq!(history.query().kind(EventKind::StartTurn).count(), 1);
+}
+
+#[test]
+fn turns_and_rounds() {
+    // Create a server with a creature taking three turns, in three different rounds.
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    for _ in 0..2 {
+        util::start_turn(&mut server, &ENTITY_1_ID);
+        util::end_turn(&mut server);
+        assert_eq!(EndRound::trigger(&mut server).fire().err(), None);
+    }
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    let ids = |events: &[EventWrapper<_>]| -> Vec<EventId> {
+        events.iter().map(|event| event.id()).collect()
+    };
+    let history = server.battle().history();
+    // Check the events of each turn.
+    assert_eq!(ids(history.events_in_turn(0)), vec![0, 1, 2, 3]);
+    assert_eq!(ids(history.events_in_turn(1)), vec![4, 5, 6]);
+    assert_eq!(ids(history.events_in_turn(2)), vec![7, 8]);
+    assert!(history.events_in_turn(3).is_empty());
+    // Check the events of each round.
+    assert_eq!(ids(history.events_in_round(0)), vec![0, 1, 2, 3, 4]);
+    assert_eq!(ids(history.events_in_round(1)), vec![5, 6, 7]);
+    assert_eq!(ids(history.events_in_round(2)), vec![8]);
+    assert!(history.events_in_round(3).is_empty());
+    assert_eq!(history.turn(7), Some(2));
+    assert_eq!(history.round(7), Some(1));
+    // Battles restored from a snapshot continue the count.
+    let battle = Battle::builder(CustomRules::new())
+        .snapshot(server.battle().snapshot())
+        .build();
+    let mut server = Server::builder(battle).build();
+    util::end_turn(&mut server);
+    let history = server.battle().history();
+    assert!(history.events_in_turn(0).is_empty());
+    assert_eq!(ids(history.events_in_turn(2)), vec![9]);
+    assert_eq!(ids(history.events_in_round(2)), vec![9]);
+}
+
+#[test]
+fn compaction() {
+    // Create a server compacting its history every four events.
+    let offloaded = Arc::new(Mutex::new(Vec::new()));
+    let offloaded_events = offloaded.clone();
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut server = Server::builder(battle)
+        .compact_history(4)
+        .offload_history(Box::new(move |events| {
+            let mut offloaded = offloaded_events.lock().unwrap();
+            offloaded.extend(events.iter().map(|event| event.id()));
+        }))
+        .build();
+    util::team(&mut server, TEAM_1_ID);
+    for _ in 0..10 {
+        util::dummy(&mut server);
+    }
+    // Events preceding the first checkpoint are evicted.
+    let history = server.battle().history();
+    assert_eq!(history.len(), 11);
+    assert_eq!(history.first_id(), 4);
+    assert_eq!(history.evicted(), 0..4);
+    assert_eq!(history.events()[0].id(), 4);
+    assert_eq!(history.query().team(TEAM_1_ID).count(), 0);
+    assert_eq!(history.turn(3), None);
+    assert_eq!(*offloaded.lock().unwrap(), vec![0, 1, 2, 3]);
+    assert_eq!(server.checkpoint().map(|c| c.next_id()), Some(4));
+    // Recent events can still be sent to sinks.
+    let mut sinks = server.client_sinks_mut();
+    assert_eq!(
+        sinks
+            .add_sink_from(Box::new(DiscardSink { id: 1 }), 4)
+            .err(),
+        None
+    );
+    assert_eq!(
+        sinks
+            .add_sink_from(Box::new(DiscardSink { id: 2 }), 2)
+            .err(),
+        Some(WeaselError::EvictedEvents(2..11, 4))
+    );
+    assert_eq!(
+        server
+            .resync(
+                Box::new(DiscardSink { id: 2 }),
+                &ResyncRequest::new(Some(1), None)
+            )
+            .err(),
+        Some(WeaselError::EvictedEvents(2..11, 4))
+    );
+    assert_eq!(
+        server.rewind_to(2).err(),
+        Some(WeaselError::EvictedEvents(2..4, 4))
+    );
+    // Simulations don't trigger a compaction.
+    assert_eq!(
+        server
+            .simulate(DummyEvent::trigger(&mut ()).prototype())
+            .err(),
+        None
+    );
+    assert_eq!(server.battle().history().first_id(), 4);
+    util::dummy(&mut server);
+    assert_eq!(server.battle().history().first_id(), 8);
+    assert_eq!(offloaded.lock().unwrap().len(), 8);
+    // The checkpoint and the history are enough to rebuild the battle.
+    let checkpoint = server.checkpoint().unwrap().clone();
+    let battle = Battle::builder(CustomRules::new())
+        .snapshot(checkpoint)
+        .build();
+    let mut restored = Server::builder(battle).build();
+    for event in server.battle().versioned_events(8..12) {
+        assert_eq!(restored.receive(event).err(), None);
+    }
+    assert_eq!(restored.battle().history().len(), 12);
+    assert_eq!(restored.battle().entities().teams().count(), 1);
+}
+
+#[test]
+fn rewind_from_snapshots() {
+    // Battles restored from a snapshot are rewound starting from it.
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    let battle = Battle::builder(CustomRules::new())
+        .snapshot(server.battle().snapshot())
+        .build();
+    let mut server = Server::builder(battle).build();
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    util::creature(&mut server, CREATURE_2_ID, TEAM_1_ID, ());
+    assert_eq!(server.rewind_to(2).err(), None);
+    assert_eq!(server.battle().history().len(), 2);
+    assert_eq!(server.battle().entities().teams().count(), 1);
+    assert_eq!(server.battle().entities().creatures().count(), 1);
+    assert_eq!(server.rewind_to(1).err(), None);
+    assert_eq!(server.battle().entities().creatures().count(), 0);
+    assert_eq!(server.redo_to(3).err(), None);
+    assert_eq!(server.battle().entities().creatures().count(), 2);
+    // Compacted battles are rewound from the closest checkpoint.
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut server = Server::builder(battle).compact_history(4).build();
+    util::team(&mut server, TEAM_1_ID);
+    for _ in 0..9 {
+        util::dummy(&mut server);
+    }
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    assert_eq!(server.battle().history().first_id(), 4);
+    assert_eq!(server.rewind_to(10).err(), None);
+    assert_eq!(server.battle().history().len(), 10);
+    assert_eq!(server.battle().entities().teams().count(), 1);
+    assert_eq!(server.battle().entities().creatures().count(), 0);
+    assert_eq!(server.rewind_to(5).err(), None);
+    assert_eq!(server.battle().history().len(), 5);
+    assert_eq!(server.battle().history().first_id(), 4);
+    assert_eq!(server.redo_to(11).err(), None);
+    assert_eq!(server.battle().entities().creatures().count(), 1);
+    assert_eq!(
+        server.rewind_to(3).err(),
+        Some(WeaselError::EvictedEvents(3..4, 4))
+    );
+}
+
+/// Creates a server whose history contains two chains of events originated from
+/// the creature's creation.
+fn causality_server() -> Server<CustomRules> {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    for origin in &[Some(1), None, Some(2), Some(1)] {
+        let result = match origin {
+            Some(origin) => Originated::new(DummyEvent::trigger(&mut server), *origin).fire(),
+            None => DummyEvent::trigger(&mut server).fire(),
+        };
+        assert_eq!(result.err(), None);
+    }
+    server
+}
+
+#[test]
+fn causality() {
+    let server = causality_server();
+    let history = server.battle().history();
+    let ids = |events: Vec<&EventWrapper<_>>| -> Vec<EventId> {
+        events.iter().map(|event| event.id()).collect()
+    };
+    // Walk the chain of origins.
+    assert_eq!(ids(history.ancestors(4)), vec![2, 1]);
+    assert!(history.ancestors(1).is_empty());
+    assert!(history.ancestors(10).is_empty());
+    // Walk the tree of derived events.
+    assert_eq!(ids(history.descendants(1)), vec![2, 4, 5]);
+    assert_eq!(ids(history.descendants(2)), vec![4]);
+    assert!(history.descendants(3).is_empty());
+    assert!(history.descendants(10).is_empty());
+    // Build the graph of a single event.
+    let graph = history.causality(2);
+    let nodes: Vec<_> = graph.nodes().iter().map(|node| node.id).collect();
+    assert_eq!(nodes, vec![1, 2, 4]);
+    assert_eq!(graph.nodes()[0].kind, EventKind::CreateCreature);
+    assert_eq!(graph.edges().collect::<Vec<_>>(), vec![(1, 2), (2, 4)]);
+    assert!(history.causality(10).nodes().is_empty());
+    // Build the graph of the whole history.
+    let graph = history.causality_graph();
+    assert_eq!(graph.nodes().len(), 6);
+    assert_eq!(
+        graph.edges().collect::<Vec<_>>(),
+        vec![(1, 2), (2, 4), (1, 5)]
+    );
+}
+
+#[test]
+fn invalid_origin() {
+    let mut server = causality_server();
+    // Events can't originate from themselves or from later events.
+    for origin in &[6, 7] {
+        let result = Originated::new(DummyEvent::trigger(&mut server), *origin).fire();
+        assert_eq!(
+            result.err().map(|e| e.unfold()),
+            Some(WeaselError::InvalidOrigin(*origin, 6))
+        );
+    }
+    assert_eq!(server.battle().history().len(), 6);
+}
+
+#[test]
+fn causality_dot() {
+    let server = causality_server();
+    let dot = server.battle().history().causality(4).to_dot();
+    assert!(dot.starts_with("digraph causality {\n"));
+    assert!(dot.contains("    1 [label=\"1: CreateCreature\", tooltip=\"CreateCreature {"));
+    assert!(dot.contains("    4 [label=\"4: DummyEvent\""));
+    assert!(dot.contains("    1 -> 2;\n    2 -> 4;\n}"));
+    assert!(!dot.contains("5 [label"));
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn causality_json() {
+    use weasel::history::CausalityGraph;
+
+    let server = causality_server();
+    let graph = server.battle().history().causality(5);
+    let json = serde_json::to_string(&graph).unwrap();
+    assert!(json.contains("\"kind\":\"CreateCreature\""));
+    let deserialized: CausalityGraph = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, graph);
+}
@@ -0,0 +1,251 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use weasel::actor::{Action, ActorRules};
+use weasel::ai::{AiDriver, AiRules};
+use weasel::battle::{Battle, BattleController, BattleRules, BattleState};
+use weasel::creature::CreateCreature;
+use weasel::entity::EntityId;
+use weasel::entropy::Entropy;
+use weasel::event::{EventKind, EventProcessor, EventQueue, EventTrigger};
+use weasel::mcts::MctsController;
+use weasel::metric::WriteMetrics;
+use weasel::rules::empty::*;
+use weasel::server::Server;
+use weasel::team::{ConcludeObjectives, Conclusion, CreateTeam, TeamId};
+use weasel::{WeaselError, WeaselResult};
+
+const TEAM_1_ID: u32 = 1;
+const TEAM_NEW_ID: u32 = 99;
+const CREATURE_1_ID: u32 = 1;
+const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
+const ABILITY_WIN: u32 = 1;
+const ABILITY_LOSE: u32 = 2;
+const ABILITY_TEAM: u32 = 3;
+const ABILITY_FLAKY: u32 = 4;
+const SEED: u64 = 1_204_678_643_940_597_513;
+
+/// Entropy seeds of the empty rules.
+fn seed(_: u64) {}
+
+/// Number of times the entropy was rerolled by the search in the `failed_expansion` test.
+static REROLLS: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Default, Clone)]
+pub struct CustomActorRules {}
+
+impl ActorRules<CustomRules> for CustomActorRules {
+    type Ability = EmptyAbility;
+    type AbilitiesSeed = Vec<u32>;
+    type Activation = ();
+    type AbilitiesAlteration = ();
+
+    fn activable(
+        &self,
+        _state: &BattleState<CustomRules>,
+        action: Action<CustomRules>,
+    ) -> WeaselResult<(), CustomRules> {
+        // The flaky ability can't be activated during the first iterations of a search.
+        let rerolls = REROLLS.load(Ordering::SeqCst);
+        if action.ability.id == ABILITY_FLAKY && (1..=3).contains(&rerolls) {
+            Err(WeaselError::GenericError)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn generate_abilities(
+        &self,
+        seed: &Option<Self::AbilitiesSeed>,
+        _entropy: &mut Entropy<CustomRules>,
+        _metrics: &mut WriteMetrics<CustomRules>,
+    ) -> Box<dyn Iterator<Item = Self::Ability>> {
+        let v: Vec<_> = seed
+            .iter()
+            .flatten()
+            .map(|id| EmptyAbility { id: *id })
+            .collect();
+        Box::new(v.into_iter())
+    }
+
+    fn activate(
+        &self,
+        _state: &BattleState<CustomRules>,
+        action: Action<CustomRules>,
+        mut event_queue: &mut Option<EventQueue<CustomRules>>,
+        _entropy: &mut Entropy<CustomRules>,
+        _metrics: &mut WriteMetrics<CustomRules>,
+    ) {
+        match action.ability.id {
+            ABILITY_WIN | ABILITY_FLAKY => {
+                ConcludeObjectives::trigger(&mut event_queue, TEAM_1_ID, Conclusion::Victory)
+                    .fire();
+            }
+            ABILITY_LOSE => {
+                ConcludeObjectives::trigger(&mut event_queue, TEAM_1_ID, Conclusion::Defeat).fire();
+            }
+            _ => {
+                CreateTeam::trigger(&mut event_queue, TEAM_NEW_ID).fire();
+            }
+        }
+    }
+}
+
+/// Cloneable rules, required by the search.
+#[derive(Default, Clone)]
+pub struct CustomRules {
+    team_rules: EmptyTeamRules,
+    character_rules: EmptyCharacterRules,
+    actor_rules: CustomActorRules,
+    fight_rules: EmptyFightRules,
+    user_rules: EmptyUserRules,
+}
+
+impl BattleRules for CustomRules {
+    type TR = EmptyTeamRules;
+    type CR = EmptyCharacterRules;
+    type AR = CustomActorRules;
+    type FR = EmptyFightRules;
+    type UR = EmptyUserRules;
+    type SR = EmptySpaceRules;
+    type RR = EmptyRoundsRules;
+    type ER = EmptyEntropyRules;
+    type Version = u32;
+
+    fn team_rules(&self) -> &Self::TR {
+        &self.team_rules
+    }
+    fn character_rules(&self) -> &Self::CR {
+        &self.character_rules
+    }
+    fn actor_rules(&self) -> &Self::AR {
+        &self.actor_rules
+    }
+    fn fight_rules(&self) -> &Self::FR {
+        &self.fight_rules
+    }
+    fn user_rules(&self) -> &Self::UR {
+        &self.user_rules
+    }
+    fn space_rules(&mut self) -> Self::SR {
+        EmptySpaceRules::default()
+    }
+    fn rounds_rules(&mut self) -> Self::RR {
+        EmptyRoundsRules::default()
+    }
+    fn entropy_rules(&mut self) -> Self::ER {
+        EmptyEntropyRules::default()
+    }
+    fn version(&self) -> &Self::Version {
+        &0
+    }
+}
+
+/// Creates a server with a creature knowing the given abilities.
+fn init(abilities: Vec<u32>) -> Server<CustomRules> {
+    let mut server = util::server(CustomRules::default());
+    util::team(&mut server, TEAM_1_ID);
+    assert_eq!(
+        CreateCreature::trigger(&mut server, CREATURE_1_ID, TEAM_1_ID, ())
+            .abilities_seed(abilities)
+            .fire()
+            .err(),
+        None
+    );
+    server
+}
+
+#[test]
+fn victory() {
+    let mut server = init(vec![ABILITY_LOSE, ABILITY_WIN]);
+    let mut mcts = MctsController::builder(SEED, seed).iterations(100).build();
+    // Without a turn in progress, the only action is to start one.
+    let action = mcts.search(server.battle(), &TEAM_1_ID).unwrap();
+    assert_eq!(action.kind(), EventKind::StartTurn);
+    assert!(mcts.search(server.battle(), &TEAM_NEW_ID).is_none());
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    // The search finds the winning ability.
+    let action = mcts.search(server.battle(), &TEAM_1_ID).unwrap();
+    assert_eq!(server.process(action).err(), None);
+    let team = server.battle().entities().team(&TEAM_1_ID).unwrap();
+    assert_eq!(team.conclusion(), Some(Conclusion::Victory));
+    // The battle is untouched by the search.
+    assert_eq!(server.battle().history().len(), 5);
+}
+
+#[test]
+fn heuristic() {
+    fn teams(battle: &Battle<CustomRules>, _: &TeamId<CustomRules>) -> f64 {
+        battle.entities().teams().count() as f64 / 10.0
+    }
+
+    let mut server = init(vec![ABILITY_TEAM]);
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    let mut mcts = MctsController::builder(SEED, seed)
+        .iterations(100)
+        .max_depth(0)
+        .heuristic(teams)
+        .build();
+    // Creating a team scores better than ending the turn.
+    let action = mcts.search(server.battle(), &TEAM_1_ID).unwrap();
+    assert_eq!(action.kind(), EventKind::ActivateAbility);
+}
+
+#[test]
+fn reroll_entropy() {
+    static SEEDS: AtomicU32 = AtomicU32::new(0);
+
+    fn seeds(_: u64) {
+        SEEDS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let mut server = init(vec![ABILITY_LOSE, ABILITY_WIN]);
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    let mut mcts = MctsController::builder(SEED, seeds).iterations(10).build();
+    assert!(mcts.search(server.battle(), &TEAM_1_ID).is_some());
+    // A new seed is generated for every iteration.
+    assert_eq!(SEEDS.load(Ordering::SeqCst), 10);
+    // No seeds are generated with a fixed entropy.
+    let mut mcts = MctsController::builder(SEED, seeds)
+        .iterations(10)
+        .fixed_entropy()
+        .build();
+    assert!(mcts.search(server.battle(), &TEAM_1_ID).is_some());
+    assert_eq!(SEEDS.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+fn failed_expansion() {
+    fn seeds(_: u64) {
+        REROLLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let mut server = init(vec![ABILITY_LOSE, ABILITY_FLAKY]);
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    // All three actions are expanded during the first three iterations, when the flaky
+    // ability fails. It's tried again in the following iterations.
+    let mut mcts = MctsController::builder(SEED, seeds).iterations(100).build();
+    let action = mcts.search(server.battle(), &TEAM_1_ID).unwrap();
+    assert_eq!(server.process(action).err(), None);
+    let team = server.battle().entities().team(&TEAM_1_ID).unwrap();
+    assert_eq!(team.conclusion(), Some(Conclusion::Victory));
+}
+
+#[test]
+fn controller() {
+    struct CustomAiRules {}
+
+    impl AiRules<CustomRules> for CustomAiRules {}
+
+    let mut server = init(vec![ABILITY_LOSE, ABILITY_WIN]);
+    let mut ai = AiDriver::new(Box::new(CustomAiRules {}));
+    ai.set_controller(
+        TEAM_1_ID,
+        Box::new(MctsController::builder(SEED, seed).iterations(100).build()),
+    );
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    assert_eq!(ai.play(&mut server).ok(), Some(true));
+    let events = server.battle().history().events();
+    assert_eq!(events[3].kind(), EventKind::ActivateAbility);
+    assert_eq!(events[4].kind(), EventKind::ConcludeObjectives);
+    let team = server.battle().entities().team(&TEAM_1_ID).unwrap();
+    assert_eq!(team.conclusion(), Some(Conclusion::Victory));
+}
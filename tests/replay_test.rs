@@ -0,0 +1,183 @@
+use std::env;
+use std::fs;
+use weasel::battle::{Battle, BattleController, BattleRules, BattleState};
+use weasel::binary;
+use weasel::entity::EntityId;
+use weasel::event::{
+    DummyEvent, EventKind, EventProcessor, EventQueue, EventTrigger, EventWrapper,
+    VersionedEventWrapper,
+};
+use weasel::replay::{Replay, CHECKPOINT_INTERVAL};
+use weasel::serde::FlatVersionedEvent;
+use weasel::server::Server;
+use weasel::{battle_rules, rules::empty::*};
+
+const TEAM_1_ID: u32 = 1;
+const CREATURE_1_ID: u32 = 1;
+const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
+
+battle_rules! {}
+
+/// Records a history in which the creation of a creature derives a dummy event,
+/// which in turn derives another one.
+fn history() -> Vec<VersionedEventWrapper<CustomRules>> {
+    fn callback(
+        event: &EventWrapper<CustomRules>,
+        _: &BattleState<CustomRules>,
+        event_queue: &mut Option<EventQueue<CustomRules>>,
+    ) {
+        if event.kind() == EventKind::CreateCreature || event.origin() == Some(1) {
+            DummyEvent::trigger(event_queue).fire();
+        }
+    }
+
+    let battle = Battle::builder(CustomRules::new())
+        .event_callback(Box::new(callback))
+        .build();
+    let mut server = Server::builder(battle).build();
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    let len = server.battle().history().len() as usize;
+    server.battle().versioned_events(0..len).collect()
+}
+
+#[test]
+fn stepping() {
+    let mut replay = Replay::new(CustomRules::new, history());
+    assert_eq!(replay.events().len(), 5);
+    assert_eq!(replay.position(), 0);
+    assert!(replay.current().is_none());
+    assert_eq!(replay.backward().ok(), Some(false));
+    // Step forward.
+    assert_eq!(replay.forward().ok(), Some(true));
+    assert_eq!(replay.current().unwrap().kind(), EventKind::CreateTeam);
+    assert_eq!(replay.battle().entities().teams().count(), 1);
+    // Seek to the last derived event and follow its origins.
+    assert_eq!(replay.seek(4).err(), None);
+    assert_eq!(replay.current().unwrap().id(), 3);
+    let origins: Vec<_> = replay.origins().iter().map(|event| event.id()).collect();
+    assert_eq!(origins, vec![2, 1]);
+    // Step backward.
+    assert_eq!(replay.backward().ok(), Some(true));
+    assert_eq!(replay.position(), 3);
+    assert_eq!(replay.current().unwrap().origin(), Some(1));
+    assert!(replay
+        .battle()
+        .entities()
+        .creature(&CREATURE_1_ID)
+        .is_some());
+    assert_eq!(replay.seek(1).err(), None);
+    assert!(replay
+        .battle()
+        .entities()
+        .creature(&CREATURE_1_ID)
+        .is_none());
+    // Seeking past the end stops at the last event.
+    assert_eq!(replay.seek(100).err(), None);
+    assert_eq!(replay.position(), 5);
+    assert!(replay.battle().rounds().is_acting(&ENTITY_1_ID));
+    assert_eq!(replay.forward().ok(), Some(false));
+}
+
+#[test]
+fn checkpoints() {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    // Each dummy event originates from the team's creation.
+    for _ in 1..CHECKPOINT_INTERVAL * 2 {
+        let mut prototype = DummyEvent::trigger(&mut ()).prototype();
+        prototype.set_origin(Some(0));
+        assert_eq!(server.process(prototype).err(), None);
+    }
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    let len = server.battle().history().len() as usize;
+    let events = server.battle().versioned_events(0..len).collect();
+    let mut replay = Replay::new(CustomRules::new, events);
+    assert_eq!(replay.seek(len).err(), None);
+    assert!(replay
+        .battle()
+        .entities()
+        .creature(&CREATURE_1_ID)
+        .is_some());
+    // Stepping back restores the last checkpoint.
+    assert_eq!(replay.backward().ok(), Some(true));
+    assert_eq!(replay.position(), CHECKPOINT_INTERVAL * 2);
+    assert!(replay
+        .battle()
+        .entities()
+        .creature(&CREATURE_1_ID)
+        .is_none());
+    assert_eq!(replay.battle().entities().teams().count(), 1);
+    // Events and origins preceding the checkpoint are still available.
+    let current = replay.current().unwrap();
+    assert_eq!(current.id() as usize, CHECKPOINT_INTERVAL * 2 - 1);
+    assert_eq!(current.kind(), EventKind::DummyEvent);
+    let origins: Vec<_> = replay.origins().iter().map(|event| event.id()).collect();
+    assert_eq!(origins, vec![0]);
+    // Seek back and forth across checkpoints.
+    assert_eq!(replay.seek(CHECKPOINT_INTERVAL - 1).err(), None);
+    assert_eq!(replay.position(), CHECKPOINT_INTERVAL - 1);
+    assert_eq!(replay.seek(len).err(), None);
+    assert_eq!(replay.position(), len);
+    assert_eq!(replay.seek(0).err(), None);
+    assert_eq!(replay.battle().entities().teams().count(), 0);
+    assert!(replay.current().is_none());
+}
+
+#[test]
+fn json() {
+    let events: Vec<FlatVersionedEvent<CustomRules>> =
+        history().into_iter().map(|event| event.into()).collect();
+    let array = serde_json::to_string(&events).unwrap();
+    let replay = Replay::from_json(CustomRules::new, &array).unwrap();
+    assert_eq!(replay.events().len(), 5);
+    // Invalid json.
+    assert!(Replay::<CustomRules>::from_json(CustomRules::new, "[{").is_err());
+    // Load from a file.
+    let mut path = env::temp_dir();
+    path.push("weasel_replay_test");
+    fs::write(&path, array).unwrap();
+    let replay = Replay::load(CustomRules::new, &path).unwrap();
+    assert_eq!(replay.events().len(), 5);
+    fs::remove_file(&path).unwrap();
+    assert!(Replay::load(CustomRules::new, &path).is_err());
+}
+
+#[test]
+fn binary() {
+    let mut bytes = Vec::new();
+    for event in history() {
+        let event: FlatVersionedEvent<CustomRules> = event.into();
+        binary::write_frame(&mut bytes, &event).unwrap();
+    }
+    let replay = Replay::from_binary(CustomRules::new, &bytes).unwrap();
+    assert_eq!(replay.events().len(), 5);
+    // Incomplete frame.
+    assert!(Replay::from_binary(CustomRules::new, &bytes[..bytes.len() - 1]).is_err());
+    // Load from a file, as the one written by a file history store.
+    let mut path = env::temp_dir();
+    path.push("weasel_replay_binary_test");
+    fs::write(&path, bytes).unwrap();
+    let replay = Replay::load(CustomRules::new, &path).unwrap();
+    assert_eq!(replay.events().len(), 5);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn viewer() {
+    let mut replay = Replay::new(CustomRules::new, history());
+    let mut output = Vec::new();
+    let input = "n\n\ng 4\np\nx\nq\nn\n";
+    assert!(replay.run(input.as_bytes(), &mut output).is_ok());
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("5 events loaded"));
+    // The empty line repeats the previous command.
+    assert!(output.contains("position 2/5"));
+    assert!(output.contains("position 4/5\nevent 3: DummyEvent"));
+    assert!(output.contains("  from event 2: DummyEvent"));
+    assert!(output.contains("  from event 1: CreateCreature"));
+    assert!(output.contains("team 1: conclusion None, creatures [1]"));
+    // The viewer stops at the quit command.
+    assert_eq!(replay.position(), 3);
+}
@@ -0,0 +1,225 @@
+use std::sync::Arc;
+use weasel::battle::{Battle, BattleController, BattleRules, BattleState};
+use weasel::creature::RemoveCreature;
+use weasel::entity::EntityId;
+use weasel::event::{Conditional, DummyEvent, EventKind, EventPrototype, EventTrigger, Originated};
+use weasel::round::{EndRound, EnvironmentTurn};
+use weasel::schedule::{ScheduleEvent, Timing};
+use weasel::{battle_rules, rules::empty::*};
+use weasel::{Server, WeaselError};
+
+#[cfg(feature = "serialization")]
+mod helper;
+
+const TEAM_1_ID: u32 = 1;
+const CREATURE_1_ID: u32 = 1;
+const CREATURE_2_ID: u32 = 2;
+const CREATURE_ERR_ID: u32 = 99;
+const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
+const ENTITY_2_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_2_ID);
+const ENTITY_ERR_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_ERR_ID);
+
+battle_rules! {}
+
+/// Schedules a dummy event.
+macro_rules! schedule {
+    ($server: expr, $timing: expr) => {{
+        let prototype = DummyEvent::trigger(&mut ()).prototype();
+        ScheduleEvent::trigger(&mut $server, prototype, $timing).fire()
+    }};
+}
+
+/// Returns the kind of the last event in the server's history.
+macro_rules! last_kind {
+    ($server: expr) => {{
+        $server
+            .battle()
+            .history()
+            .events()
+            .iter()
+            .last()
+            .unwrap()
+            .kind()
+    }};
+}
+
+/// Creates a server with a team and two creatures.
+fn init() -> Server<CustomRules> {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    util::creature(&mut server, CREATURE_2_ID, TEAM_1_ID, ());
+    server
+}
+
+#[test]
+fn schedule_verification() {
+    let mut server = init();
+    assert_eq!(
+        schedule!(server, Timing::TurnStart(ENTITY_ERR_ID))
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::EntityNotFound(ENTITY_ERR_ID))
+    );
+    assert_eq!(
+        schedule!(server, Timing::TurnEnd(EntityId::Object(1)))
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::NotAnActor(EntityId::Object(1)))
+    );
+    assert!(server.battle().rounds().scheduler().is_empty());
+    assert_eq!(
+        schedule!(server, Timing::TurnStart(ENTITY_1_ID)).err(),
+        None
+    );
+    assert_eq!(server.battle().rounds().scheduler().len(), 1);
+}
+
+#[test]
+fn turn_timings() {
+    let mut server = init();
+    assert_eq!(
+        schedule!(server, Timing::TurnStart(ENTITY_1_ID)).err(),
+        None
+    );
+    assert_eq!(schedule!(server, Timing::TurnEnd(ENTITY_1_ID)).err(), None);
+    // Other actors' turns don't fire the events.
+    util::start_turn(&mut server, &ENTITY_2_ID);
+    util::end_turn(&mut server);
+    assert_eq!(server.battle().rounds().scheduler().len(), 2);
+    // The events fire at the start and at the end of the actor's turn.
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    assert_eq!(last_kind!(server), EventKind::DummyEvent);
+    // Fired events are derived from the event that started the turn.
+    let events = server.battle().history().events();
+    assert_eq!(
+        events[events.len() - 1].origin(),
+        Some(events[events.len() - 2].id())
+    );
+    assert_eq!(server.battle().rounds().scheduler().len(), 1);
+    util::end_turn(&mut server);
+    assert_eq!(last_kind!(server), EventKind::DummyEvent);
+    assert!(server.battle().rounds().scheduler().is_empty());
+}
+
+#[test]
+fn prototype_preserved() {
+    let mut server = init();
+    // Explicit origins are kept.
+    let prototype = Originated::new(DummyEvent::trigger(&mut ()), 0).prototype();
+    ScheduleEvent::trigger(&mut server, prototype, Timing::Turns(1))
+        .fire()
+        .unwrap();
+    let len = server.battle().history().len();
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    util::end_turn(&mut server);
+    // Start turn, end turn and the dummy event.
+    assert_eq!(server.battle().history().len(), len + 3);
+    let events = server.battle().history().events();
+    assert_eq!(last_kind!(server), EventKind::DummyEvent);
+    assert_eq!(events[events.len() - 1].origin(), Some(0));
+    assert!(server.battle().rounds().scheduler().is_empty());
+}
+
+/// Returns a prototype of a dummy event whose condition is never satisfied.
+fn conditional_prototype() -> EventPrototype<CustomRules> {
+    Conditional::new(
+        DummyEvent::trigger(&mut ()),
+        Arc::new(|state: &BattleState<CustomRules>| state.entities().creatures().count() > 2),
+    )
+    .prototype()
+}
+
+#[test]
+fn conditions() {
+    let mut server = init();
+    // Conditions can't be serialized, thus conditional prototypes are rejected.
+    assert_eq!(
+        ScheduleEvent::trigger(&mut server, conditional_prototype(), Timing::Turns(1))
+            .fire()
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::ConditionNotSerializable)
+    );
+    assert!(server.battle().rounds().scheduler().is_empty());
+}
+
+#[test]
+fn removed_actor_timings() {
+    let mut server = init();
+    assert_eq!(
+        schedule!(server, Timing::TurnStart(ENTITY_1_ID)).err(),
+        None
+    );
+    assert_eq!(schedule!(server, Timing::TurnEnd(ENTITY_1_ID)).err(), None);
+    assert_eq!(
+        schedule!(server, Timing::TurnStart(ENTITY_2_ID)).err(),
+        None
+    );
+    assert_eq!(schedule!(server, Timing::Rounds(1)).err(), None);
+    // Timings of the removed actor are discarded.
+    RemoveCreature::trigger(&mut server, CREATURE_1_ID)
+        .fire()
+        .unwrap();
+    let scheduler = server.battle().rounds().scheduler();
+    assert_eq!(scheduler.len(), 2);
+    assert!(scheduler
+        .events()
+        .all(|event| *event.timing() != Timing::TurnStart(ENTITY_1_ID)
+            && *event.timing() != Timing::TurnEnd(ENTITY_1_ID)));
+}
+
+#[test]
+fn counted_timings() {
+    let mut server = init();
+    assert_eq!(schedule!(server, Timing::Turns(2)).err(), None);
+    assert_eq!(schedule!(server, Timing::Rounds(2)).err(), None);
+    // Turns are counted from the moment the event was scheduled.
+    EnvironmentTurn::trigger(&mut server).fire().unwrap();
+    assert_eq!(last_kind!(server), EventKind::EnvironmentTurn);
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    util::end_turn(&mut server);
+    assert_eq!(last_kind!(server), EventKind::DummyEvent);
+    assert_eq!(server.battle().rounds().scheduler().len(), 1);
+    // Same for rounds.
+    EndRound::trigger(&mut server).fire().unwrap();
+    assert_eq!(last_kind!(server), EventKind::EndRound);
+    EndRound::trigger(&mut server).fire().unwrap();
+    assert_eq!(last_kind!(server), EventKind::DummyEvent);
+    assert!(server.battle().rounds().scheduler().is_empty());
+}
+
+#[test]
+fn scheduler_in_snapshot() {
+    let mut server = init();
+    assert_eq!(schedule!(server, Timing::Rounds(1)).err(), None);
+    let snapshot = server.battle().snapshot();
+    let battle = Battle::builder(CustomRules::new())
+        .snapshot(snapshot)
+        .build();
+    let mut server = Server::builder(battle).build();
+    assert_eq!(server.battle().rounds().scheduler().len(), 1);
+    EndRound::trigger(&mut server).fire().unwrap();
+    assert_eq!(last_kind!(server), EventKind::DummyEvent);
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn scheduled_events_serde() {
+    let mut server = init();
+    assert_eq!(
+        schedule!(server, Timing::TurnStart(ENTITY_1_ID)).err(),
+        None
+    );
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    // Replaying the history gives the same result, without firing the event twice.
+    let history_json = helper::history_as_json(server.battle());
+    let mut restored = util::server(CustomRules::new());
+    helper::load_json_history(&mut restored, history_json);
+    assert_eq!(
+        restored.battle().history().len(),
+        server.battle().history().len()
+    );
+    assert!(restored.battle().rounds().scheduler().is_empty());
+    assert_eq!(last_kind!(restored), EventKind::DummyEvent);
+}
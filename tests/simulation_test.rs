@@ -0,0 +1,171 @@
+use std::sync::{Arc, Mutex};
+use weasel::battle::{Battle, BattleController, BattleRules, BattleState};
+use weasel::creature::RemoveCreature;
+use weasel::entity::EntityId;
+use weasel::event::{
+    DummyEvent, EventKind, EventProcessor, EventPrototype, EventQueue, EventTrigger, EventWrapper,
+};
+use weasel::fight::{ApplyImpact, FightRules, Reaction};
+use weasel::round::StartTurn;
+use weasel::team::CreateTeam;
+use weasel::{battle_rules, battle_rules_with_fight, rules::empty::*};
+use weasel::{Server, WeaselError};
+
+const TEAM_1_ID: u32 = 1;
+const CREATURE_1_ID: u32 = 1;
+const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
+
+battle_rules! {}
+
+#[test]
+fn invalid_event() {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    let prototype = CreateTeam::trigger(&mut (), TEAM_1_ID).prototype();
+    assert_eq!(
+        server.simulate(prototype).err().map(|e| e.unfold()),
+        Some(WeaselError::DuplicatedTeam(TEAM_1_ID))
+    );
+    assert_eq!(server.battle().history().len(), 1);
+}
+
+#[test]
+fn battle_untouched() {
+    fn callback(
+        event: &EventWrapper<CustomRules>,
+        _: &BattleState<CustomRules>,
+        event_queue: &mut Option<EventQueue<CustomRules>>,
+    ) {
+        if event.kind() == EventKind::StartTurn {
+            DummyEvent::trigger(event_queue).fire();
+        }
+    }
+
+    let battle = Battle::builder(CustomRules::new())
+        .event_callback(Box::new(callback))
+        .build();
+    let mut server = Server::builder(battle).build();
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    // Simulate the start of a turn.
+    let prototype = StartTurn::trigger(&mut (), ENTITY_1_ID).prototype();
+    let simulation = server.simulate(prototype.clone()).unwrap();
+    // The simulation contains the event and its derived events.
+    let events = simulation.events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].id(), 2);
+    assert_eq!(events[0].kind(), EventKind::StartTurn);
+    assert_eq!(events[1].kind(), EventKind::DummyEvent);
+    assert_eq!(events[1].origin(), Some(2));
+    assert_eq!(simulation.outcome().history().len(), 4);
+    // The battle didn't change.
+    assert_eq!(server.battle().history().len(), 2);
+    assert!(!server.battle().rounds().is_acting(&ENTITY_1_ID));
+    // Processing the event gives the same outcome.
+    assert_eq!(server.process(prototype).err(), None);
+    let history = server.battle().history().events();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[2].kind(), events[0].kind());
+    assert_eq!(history[3].kind(), events[1].kind());
+    assert!(server.battle().rounds().is_acting(&ENTITY_1_ID));
+}
+
+#[test]
+fn callback_disabled() {
+    // Count the invocations of the event callback.
+    let invocations = Arc::new(Mutex::new(0));
+    let invocations_clone = invocations.clone();
+    let callback = move |_: &EventWrapper<CustomRules>,
+                         _: &BattleState<CustomRules>,
+                         _: &mut Option<EventQueue<CustomRules>>| {
+        *invocations_clone.lock().unwrap() += 1;
+    };
+    let battle = Battle::builder(CustomRules::new())
+        .event_callback(Box::new(callback))
+        .build();
+    let mut server = Server::builder(battle)
+        .disable_simulation_callback()
+        .build();
+    util::team(&mut server, TEAM_1_ID);
+    assert_eq!(*invocations.lock().unwrap(), 1);
+    // Simulations don't invoke the callback.
+    let prototype = DummyEvent::trigger(&mut ()).prototype();
+    let simulation = server.simulate(prototype.clone()).unwrap();
+    assert_eq!(simulation.events().len(), 1);
+    assert_eq!(*invocations.lock().unwrap(), 1);
+    // The callback is still attached to the battle.
+    assert_eq!(server.process(prototype).err(), None);
+    assert_eq!(*invocations.lock().unwrap(), 2);
+}
+
+#[test]
+fn redo_buffer_preserved() {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::dummy(&mut server);
+    assert_eq!(server.rewind_to(1).err(), None);
+    let prototype = DummyEvent::trigger(&mut ()).prototype();
+    let simulation = server.simulate(prototype).unwrap();
+    assert_eq!(simulation.events()[0].id(), 1);
+    assert_eq!(server.redo_buffer().len(), 1);
+    assert_eq!(server.redo_to(2).err(), None);
+    assert_eq!(server.battle().history().len(), 2);
+}
+
+#[test]
+fn main_event_error() {
+    #[derive(Default, Clone)]
+    pub struct CancelFightRules {}
+
+    impl FightRules<CustomRules> for CancelFightRules {
+        type Impact = ();
+        type Potency = ();
+
+        fn react(
+            &self,
+            _state: &BattleState<CustomRules>,
+            event: &EventPrototype<CustomRules>,
+            mut reactions: &mut Option<EventQueue<CustomRules>>,
+        ) -> Reaction<CustomRules> {
+            if event.kind() == EventKind::ApplyImpact {
+                // Counter the impact after a dummy reaction.
+                DummyEvent::trigger(&mut reactions).fire();
+                Reaction::Cancel
+            } else {
+                Reaction::Proceed
+            }
+        }
+    }
+
+    battle_rules_with_fight! { CancelFightRules }
+
+    let mut server = util::server(CustomRules::new());
+    // The simulation fails even if the reaction was applied.
+    let prototype = ApplyImpact::trigger(&mut (), ()).prototype();
+    assert_eq!(
+        server.simulate(prototype).err().map(|e| e.unfold()),
+        Some(WeaselError::EventCancelled)
+    );
+    assert_eq!(server.battle().history().len(), 0);
+}
+
+#[test]
+fn changes() {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    // Only the involved characters are reported.
+    let prototype = RemoveCreature::trigger(&mut (), CREATURE_1_ID).prototype();
+    let simulation = server.simulate(prototype).unwrap();
+    let characters = simulation.characters();
+    assert_eq!(characters.len(), 1);
+    assert_eq!(
+        characters[0].before().map(|c| *c.entity_id()),
+        Some(ENTITY_1_ID)
+    );
+    assert!(characters[0].after().is_none());
+    assert!(simulation.teams().is_empty());
+    // The outcome holds the whole state of the battle.
+    assert_eq!(simulation.outcome().entities().creatures().count(), 0);
+    assert_eq!(server.battle().entities().creatures().count(), 1);
+}
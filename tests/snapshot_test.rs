@@ -0,0 +1,139 @@
+use weasel::battle::{Battle, BattleController, BattleRules};
+use weasel::entity::EntityId;
+use weasel::event::{EventReceiver, EventTrigger};
+use weasel::metric::system::*;
+use weasel::player::PlayerId;
+use weasel::round::TurnState;
+use weasel::team::CreateTeam;
+use weasel::{battle_rules, rules::empty::*};
+use weasel::{Server, WeaselError};
+
+const TEAM_1_ID: u32 = 1;
+const TEAM_2_ID: u32 = 2;
+const CREATURE_1_ID: u32 = 1;
+const OBJECT_1_ID: u32 = 1;
+const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
+const PLAYER_1_ID: PlayerId = 1;
+
+battle_rules! {}
+
+/// Creates a server with a team, a creature, an object and a started turn.
+fn populated_server() -> Server<CustomRules> {
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    util::object(&mut server, OBJECT_1_ID, ());
+    util::start_turn(&mut server, &ENTITY_1_ID);
+    assert_eq!(server.rights_mut().add(PLAYER_1_ID, &TEAM_1_ID).err(), None);
+    server
+}
+
+#[test]
+fn restore_without_replay() {
+    let server = populated_server();
+    let snapshot = server.battle().snapshot();
+    assert_eq!(snapshot.next_id(), 4);
+    // Restore the snapshot.
+    let battle = Battle::builder(CustomRules::new())
+        .snapshot(snapshot)
+        .build();
+    let mut restored = Server::builder(battle).build();
+    // Check that the state is the same.
+    let battle = restored.battle();
+    assert_eq!(battle.history().len(), 4);
+    assert_eq!(battle.history().first_id(), 4);
+    assert!(battle.history().events().is_empty());
+    assert!(battle.entities().team(&TEAM_1_ID).is_some());
+    assert!(battle.entities().creature(&CREATURE_1_ID).is_some());
+    assert!(battle.entities().object(&OBJECT_1_ID).is_some());
+    assert_eq!(
+        *battle.rounds().state(),
+        TurnState::Started(vec![ENTITY_1_ID].into_iter().collect())
+    );
+    assert_eq!(battle.metrics().system_u64(CREATURES_CREATED), Some(1));
+    assert!(restored.rights().check(PLAYER_1_ID, &TEAM_1_ID));
+    // The restored battle continues the original timeline.
+    util::end_turn(&mut restored);
+    assert_eq!(restored.battle().history().len(), 5);
+    assert_eq!(restored.battle().history().events()[0].id(), 4);
+    assert_eq!(restored.battle().rounds().completed_turns(), 1);
+}
+
+#[test]
+fn replay_tail() {
+    let mut server = populated_server();
+    let snapshot = server.battle().snapshot();
+    // Fire more events after the snapshot.
+    util::end_turn(&mut server);
+    util::team(&mut server, TEAM_2_ID);
+    // Restore the snapshot and replay only the missing events.
+    let battle = Battle::builder(CustomRules::new())
+        .snapshot(snapshot)
+        .build();
+    let mut restored = Server::builder(battle).build();
+    // Events preceding the snapshot are rejected.
+    let old_event = server.battle().versioned_events(0..1).next().unwrap();
+    assert!(matches!(
+        restored.receive(old_event).err().unwrap().unfold(),
+        WeaselError::NonContiguousEventId(0, 4)
+    ));
+    for event in server.battle().versioned_events(4..6) {
+        assert_eq!(restored.receive(event).err(), None);
+    }
+    assert_eq!(restored.battle().history().len(), 6);
+    assert_eq!(restored.battle().entities().teams().count(), 2);
+    assert_eq!(*restored.battle().rounds().state(), TurnState::Ready);
+    // Ranges preceding the snapshot can't be sent to sinks.
+    assert!(matches!(
+        restored.client_sinks_mut().send_range(1, 0..5).err(),
+        Some(WeaselError::InvalidEventRange(_, 6))
+    ));
+}
+
+#[test]
+fn restored_battle_is_independent() {
+    let mut server = populated_server();
+    let snapshot = server.battle().snapshot();
+    assert_eq!(
+        CreateTeam::trigger(&mut server, TEAM_2_ID).fire().err(),
+        None
+    );
+    // The snapshot is not affected by changes to the original battle.
+    assert_eq!(snapshot.entities().teams().count(), 1);
+    let battle = Battle::builder(CustomRules::new())
+        .snapshot(snapshot.clone())
+        .build();
+    assert_eq!(battle.entities().teams().count(), 1);
+    assert_eq!(snapshot.next_id(), 4);
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn snapshot_serde() {
+    use weasel::BattleSnapshot;
+    let mut server = populated_server();
+    let snapshot = server.battle().snapshot();
+    util::end_turn(&mut server);
+    // Serialize and deserialize the snapshot.
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let snapshot: BattleSnapshot<CustomRules> = serde_json::from_str(&json).unwrap();
+    // Restore it and replay the tail.
+    let battle = Battle::builder(CustomRules::new())
+        .snapshot(snapshot)
+        .build();
+    let mut restored = Server::builder(battle).build();
+    for event in server.battle().versioned_events(4..5) {
+        assert_eq!(restored.receive(event).err(), None);
+    }
+    assert_eq!(restored.battle().history().len(), 5);
+    assert!(restored
+        .battle()
+        .entities()
+        .creature(&CREATURE_1_ID)
+        .is_some());
+    assert_eq!(
+        restored.battle().metrics().system_u64(OBJECTS_CREATED),
+        Some(1)
+    );
+    assert!(restored.rights().check(PLAYER_1_ID, &TEAM_1_ID));
+}
@@ -0,0 +1,327 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+use weasel::battle::{Battle, BattleController, BattleRules};
+use weasel::error::WeaselResult;
+use weasel::event::{
+    ClientEventPrototype, ClientSink, DummyEvent, EventId, EventKind, EventReceiver, EventSink,
+    EventSinkId, EventTrigger, ServerSink, VersionedEventWrapper,
+};
+use weasel::resync::{Resync, ResyncRequest};
+use weasel::store::{FileHistoryStore, HistoryStore, MemoryHistoryStore};
+use weasel::{battle_rules, rules::empty::*};
+use weasel::{Client, Server, WeaselError};
+
+const TEAM_1_ID: u32 = 1;
+
+battle_rules! {}
+
+/// A `ClientSink` that discards all events.
+struct DiscardSink {
+    id: EventSinkId,
+}
+
+impl EventSink for DiscardSink {
+    fn id(&self) -> EventSinkId {
+        self.id
+    }
+}
+
+impl ClientSink<CustomRules> for DiscardSink {
+    fn send(&mut self, _: &VersionedEventWrapper<CustomRules>) -> WeaselResult<(), CustomRules> {
+        Ok(())
+    }
+}
+
+/// Returns the path of a new, empty file in the temporary directory.
+fn temp_path(name: &str) -> PathBuf {
+    let mut path = env::temp_dir();
+    path.push(name);
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// Creates a server whose battle saves events into `store`.
+fn store_server(store: Box<dyn HistoryStore<CustomRules> + Send>) -> Server<CustomRules> {
+    let battle = Battle::builder(CustomRules::new())
+        .history_store(store)
+        .build();
+    Server::builder(battle).build()
+}
+
+#[test]
+fn memory() {
+    let mut server = store_server(Box::new(MemoryHistoryStore::new()));
+    util::team(&mut server, TEAM_1_ID);
+    util::dummy(&mut server);
+    util::dummy(&mut server);
+    let store = server.battle().history().store().unwrap();
+    assert_eq!(store.first_id(), 0);
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.range(1..3).unwrap()[0].kind(), EventKind::DummyEvent);
+    assert_eq!(
+        store.range(2..4).err(),
+        Some(WeaselError::InvalidEventRange(2..4, 3))
+    );
+    // Simulations don't reach the store.
+    assert_eq!(
+        server
+            .simulate(DummyEvent::trigger(&mut ()).prototype())
+            .err(),
+        None
+    );
+    assert_eq!(server.battle().history().store().unwrap().len(), 3);
+    // Rewinding discards events from the store.
+    assert_eq!(server.rewind_to(1).err(), None);
+    assert_eq!(server.battle().history().store().unwrap().len(), 1);
+    assert_eq!(server.redo_to(3).err(), None);
+    let store = server.battle().history().store().unwrap();
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.range(2..3).unwrap()[0].id(), 2);
+    // Copies of the history don't share the store.
+    assert!(server.battle().history().clone().store().is_none());
+}
+
+#[test]
+fn evicted_events() {
+    let battle = Battle::builder(CustomRules::new())
+        .history_store(Box::new(MemoryHistoryStore::new()))
+        .build();
+    let mut server = Server::builder(battle).compact_history(4).build();
+    util::team(&mut server, TEAM_1_ID);
+    for _ in 0..10 {
+        util::dummy(&mut server);
+    }
+    assert_eq!(server.battle().history().first_id(), 4);
+    // Evicted events are read from the store.
+    let events = server.battle().read_events(2..11).unwrap();
+    assert_eq!(events.len(), 9);
+    assert_eq!(events[0].id(), 2);
+    assert_eq!(events[8].id(), 10);
+    // Versioned events are limited to those kept in memory.
+    let events: Vec<_> = server.battle().versioned_events(2..11).collect();
+    assert_eq!(events.len(), 7);
+    assert_eq!(events[0].id(), 4);
+    assert_eq!(
+        server.battle().read_events(0..12).err(),
+        Some(WeaselError::InvalidEventRange(0..12, 11))
+    );
+    let mut sinks = server.client_sinks_mut();
+    assert_eq!(
+        sinks
+            .add_sink_from(Box::new(DiscardSink { id: 1 }), 0)
+            .err(),
+        None
+    );
+    assert_eq!(
+        server
+            .resync(
+                Box::new(DiscardSink { id: 2 }),
+                &ResyncRequest::new(Some(1), None)
+            )
+            .ok(),
+        Some(Resync::Replay(2..11))
+    );
+}
+
+#[test]
+fn file() {
+    let path = temp_path("weasel_store_test_file");
+    let mut server = store_server(Box::new(FileHistoryStore::open(&path).unwrap()));
+    util::team(&mut server, TEAM_1_ID);
+    util::dummy(&mut server);
+    util::dummy(&mut server);
+    assert_eq!(server.rewind_to(2).err(), None);
+    drop(server);
+    // Reopen the file and recover the battle.
+    let store = FileHistoryStore::<CustomRules>::open(&path).unwrap();
+    assert_eq!(store.len(), 2);
+    let events = store.range(0..2).unwrap();
+    assert_eq!(events[0].kind(), EventKind::CreateTeam);
+    assert_eq!(events[1].kind(), EventKind::DummyEvent);
+    let mut server = store_server(Box::new(store));
+    for event in events {
+        assert_eq!(server.receive(event).err(), None);
+    }
+    assert_eq!(server.battle().entities().teams().count(), 1);
+    // Replayed events are not stored twice.
+    util::dummy(&mut server);
+    let store = server.battle().history().store().unwrap();
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.range(2..3).unwrap()[0].id(), 2);
+    drop(server);
+    assert_eq!(
+        FileHistoryStore::<CustomRules>::open(&path).unwrap().len(),
+        3
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn file_recovery() {
+    let path = temp_path("weasel_store_test_file_recovery");
+    let mut server = store_server(Box::new(FileHistoryStore::open(&path).unwrap()));
+    util::team(&mut server, TEAM_1_ID);
+    util::dummy(&mut server);
+    drop(server);
+    let len = fs::metadata(&path).unwrap().len();
+    // Simulate a crash in the middle of a write.
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[16, 0, 0, 0, 1, 2]).unwrap();
+    drop(file);
+    // The incomplete event is discarded.
+    let mut store = FileHistoryStore::<CustomRules>::open(&path).unwrap();
+    assert_eq!(store.len(), 2);
+    assert_eq!(fs::metadata(&path).unwrap().len(), len);
+    // New events are appended after the last valid one.
+    let event = store.range(1..2).unwrap().remove(0);
+    assert_eq!(
+        store.append(&event).err(),
+        Some(WeaselError::NonContiguousEventId(1, 2))
+    );
+    assert_eq!(store.truncate(1).err(), None);
+    assert_eq!(store.append(&event).err(), None);
+    drop(store);
+    let store = FileHistoryStore::<CustomRules>::open(&path).unwrap();
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.range(0..2).unwrap().len(), 2);
+    drop(store);
+    // A complete but corrupted event is an error.
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[1, 0, 0, 0, 255]).unwrap();
+    drop(file);
+    assert!(matches!(
+        FileHistoryStore::<CustomRules>::open(&path).err(),
+        Some(WeaselError::HistoryStoreError(_))
+    ));
+    fs::remove_file(&path).unwrap();
+}
+
+/// A store that fails to save events.
+struct FailingStore {}
+
+impl HistoryStore<CustomRules> for FailingStore {
+    fn append(&mut self, _: &VersionedEventWrapper<CustomRules>) -> WeaselResult<(), CustomRules> {
+        Err(WeaselError::HistoryStoreError("disk full".to_string()))
+    }
+
+    fn range(
+        &self,
+        _: Range<EventId>,
+    ) -> WeaselResult<Vec<VersionedEventWrapper<CustomRules>>, CustomRules> {
+        Ok(Vec::new())
+    }
+
+    fn first_id(&self) -> EventId {
+        0
+    }
+
+    fn len(&self) -> EventId {
+        0
+    }
+
+    fn truncate(&mut self, _: EventId) -> WeaselResult<(), CustomRules> {
+        Ok(())
+    }
+}
+
+#[test]
+fn store_failure() {
+    let mut server = store_server(Box::new(FailingStore {}));
+    // Events are applied even if they can't be stored.
+    util::team(&mut server, TEAM_1_ID);
+    assert_eq!(server.battle().history().len(), 1);
+    // The failure is reported when reading events.
+    assert_eq!(
+        server.battle().read_events(0..1).err(),
+        Some(WeaselError::HistoryStoreError("disk full".to_string()))
+    );
+    // Sinks are kept if the events to resync them can't be read.
+    assert_eq!(
+        server
+            .client_sinks_mut()
+            .add_sink(Box::new(DiscardSink { id: 1 }))
+            .err(),
+        None
+    );
+    assert_eq!(
+        server
+            .resync(
+                Box::new(DiscardSink { id: 1 }),
+                &ResyncRequest::new(None, None)
+            )
+            .err(),
+        Some(WeaselError::HistoryStoreError("disk full".to_string()))
+    );
+    assert_eq!(server.client_sinks().sinks().count(), 1);
+}
+
+/// A `ServerSink` that discards all events.
+struct DiscardServerSink {}
+
+impl EventSink for DiscardServerSink {
+    fn id(&self) -> EventSinkId {
+        0
+    }
+}
+
+impl ServerSink<CustomRules> for DiscardServerSink {
+    fn send(&mut self, _: &ClientEventPrototype<CustomRules>) -> WeaselResult<(), CustomRules> {
+        Ok(())
+    }
+}
+
+/// Creates a client whose battle saves events into a memory store.
+fn store_client() -> Client<CustomRules> {
+    let battle = Battle::builder(CustomRules::new())
+        .history_store(Box::new(MemoryHistoryStore::new()))
+        .build();
+    Client::builder(battle, Box::new(DiscardServerSink {}))
+        .enable_prediction()
+        .build()
+}
+
+/// Returns the event with id `id` from the history of `server`.
+fn event(server: &Server<CustomRules>, id: EventId) -> VersionedEventWrapper<CustomRules> {
+    server.battle().read_events(id..id + 1).unwrap().remove(0)
+}
+
+#[test]
+fn predictions() {
+    let mut server = util::server(CustomRules::new());
+    let mut client = store_client();
+    util::team(&mut server, TEAM_1_ID);
+    assert_eq!(client.receive(event(&server, 0)).err(), None);
+    // Predictions don't reach the store.
+    util::dummy(&mut client);
+    assert_eq!(client.battle().history().len(), 2);
+    assert_eq!(client.battle().history().store().unwrap().len(), 1);
+    // Events of the server do.
+    util::dummy(&mut server);
+    assert_eq!(client.receive(event(&server, 1)).err(), None);
+    assert_eq!(client.battle().history().len(), 3);
+    let store = client.battle().history().store().unwrap();
+    assert_eq!(store.len(), 2);
+    assert_eq!(store.range(1..2).unwrap()[0].id(), 1);
+}
+
+#[test]
+fn restore_snapshot() {
+    let mut server = util::server(CustomRules::new());
+    let mut client = store_client();
+    util::team(&mut server, TEAM_1_ID);
+    assert_eq!(client.receive(event(&server, 0)).err(), None);
+    // Restore a snapshot ahead of the store.
+    util::dummy(&mut server);
+    util::dummy(&mut server);
+    client.restore(server.battle().snapshot());
+    assert!(client.battle().history().store().unwrap().is_empty());
+    // The store continues from the snapshot.
+    util::dummy(&mut server);
+    assert_eq!(client.receive(event(&server, 3)).err(), None);
+    let store = client.battle().history().store().unwrap();
+    assert_eq!(store.first_id(), 3);
+    assert_eq!(store.len(), 4);
+}
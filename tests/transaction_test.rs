@@ -0,0 +1,349 @@
+use std::sync::{Arc, Mutex};
+use weasel::battle::{Battle, BattleController, BattleRules, BattleState};
+use weasel::entity::EntityId;
+use weasel::event::{
+    DummyEvent, EventKind, EventPrototype, EventQueue, EventServer, EventTrigger, EventWrapper,
+};
+use weasel::fight::{FightRules, Reaction};
+use weasel::player::PlayerId;
+use weasel::round::{EndTurn, StartTurn};
+use weasel::space::MoveEntity;
+use weasel::store::MemoryHistoryStore;
+use weasel::team::{CreateTeam, TeamId};
+use weasel::transaction::Transaction;
+use weasel::{battle_rules, rules::empty::*};
+use weasel::{Server, WeaselError};
+
+const TEAM_1_ID: u32 = 1;
+const TEAM_2_ID: u32 = 2;
+const CREATURE_1_ID: u32 = 1;
+const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
+const PLAYER_1_ID: PlayerId = 1;
+
+battle_rules! {}
+
+/// Creates a server with transactions enabled.
+fn server() -> Server<CustomRules> {
+    let battle = Battle::builder(CustomRules::new()).build();
+    Server::builder(battle).enable_transactions().build()
+}
+
+#[test]
+fn disabled() {
+    let mut server = util::server(CustomRules::new());
+    let events = vec![DummyEvent::trigger(&mut ()).event()];
+    assert_eq!(
+        Transaction::trigger(&mut server, events).fire().err(),
+        Some(WeaselError::TransactionsDisabled)
+    );
+    assert_eq!(server.battle().history().len(), 0);
+}
+
+#[test]
+fn all_or_nothing() {
+    let mut server = server();
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    // Each member is verified against the state left by the previous ones.
+    let events = vec![
+        StartTurn::trigger(&mut (), ENTITY_1_ID).event(),
+        MoveEntity::trigger(&mut (), ENTITY_1_ID, ()).event(),
+        EndTurn::trigger(&mut ()).event(),
+    ];
+    assert_eq!(Transaction::trigger(&mut server, events).fire().err(), None);
+    let events = server.battle().history().events();
+    assert_eq!(events.len(), 5);
+    assert_eq!(events[2].kind(), EventKind::StartTurn);
+    assert_eq!(events[3].kind(), EventKind::MoveEntity);
+    assert_eq!(events[4].kind(), EventKind::EndTurn);
+    assert_eq!(server.battle().rounds().completed_turns(), 1);
+    // A single invalid member rolls back the whole transaction.
+    let events = vec![
+        StartTurn::trigger(&mut (), ENTITY_1_ID).event(),
+        StartTurn::trigger(&mut (), ENTITY_1_ID).event(),
+    ];
+    assert_eq!(
+        Transaction::trigger(&mut server, events)
+            .fire()
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::TurnInProgress)
+    );
+    assert_eq!(server.battle().history().len(), 5);
+    assert!(!server.battle().rounds().is_acting(&ENTITY_1_ID));
+}
+
+#[test]
+fn derived_events() {
+    fn callback(
+        event: &EventWrapper<CustomRules>,
+        _: &BattleState<CustomRules>,
+        event_queue: &mut Option<EventQueue<CustomRules>>,
+    ) {
+        if event.kind() == EventKind::CreateTeam {
+            DummyEvent::trigger(event_queue).fire();
+        }
+    }
+
+    let battle = Battle::builder(CustomRules::new())
+        .event_callback(Box::new(callback))
+        .build();
+    let mut server = Server::builder(battle).enable_transactions().build();
+    let events = vec![
+        CreateTeam::trigger(&mut (), TEAM_1_ID).event(),
+        CreateTeam::trigger(&mut (), TEAM_2_ID).event(),
+    ];
+    assert_eq!(Transaction::trigger(&mut server, events).fire().err(), None);
+    // Derived events come after the last member.
+    let events = server.battle().history().events();
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[0].kind(), EventKind::CreateTeam);
+    assert_eq!(events[1].kind(), EventKind::CreateTeam);
+    assert_eq!(events[2].kind(), EventKind::DummyEvent);
+    assert_eq!(events[2].origin(), Some(0));
+    assert_eq!(events[3].kind(), EventKind::DummyEvent);
+    assert_eq!(events[3].origin(), Some(1));
+}
+
+#[test]
+fn client_rights() {
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut server = Server::builder(battle)
+        .enable_transactions()
+        .enforce_authentication()
+        .build();
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    let transaction = |player| {
+        let events = vec![
+            StartTurn::trigger(&mut (), ENTITY_1_ID).event(),
+            EndTurn::trigger(&mut ()).event(),
+        ];
+        Transaction::trigger(&mut (), events)
+            .prototype()
+            .client_prototype(0, player)
+    };
+    // Members are checked against the player's rights.
+    assert_eq!(
+        server
+            .process_client(transaction(Some(PLAYER_1_ID)))
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::AuthenticationError(
+            Some(PLAYER_1_ID),
+            TEAM_1_ID
+        ))
+    );
+    assert_eq!(server.battle().history().len(), 2);
+    // Server only events can't be part of a client's transaction.
+    let events = vec![CreateTeam::trigger(&mut (), TEAM_2_ID).event()];
+    let prototype = Transaction::trigger(&mut (), events)
+        .prototype()
+        .client_prototype(0, Some(PLAYER_1_ID));
+    assert_eq!(
+        server.process_client(prototype).err().map(|e| e.unfold()),
+        Some(WeaselError::ServerOnlyEvent)
+    );
+    // Transactions succeed once the player has the rights.
+    assert_eq!(server.rights_mut().add(PLAYER_1_ID, &TEAM_1_ID).err(), None);
+    assert_eq!(
+        server.process_client(transaction(Some(PLAYER_1_ID))).err(),
+        None
+    );
+    assert_eq!(server.battle().history().len(), 4);
+}
+
+#[test]
+fn rollback_side_effects() {
+    let invocations = Arc::new(Mutex::new(0));
+    let counter = invocations.clone();
+    let callback = move |_: &EventWrapper<CustomRules>,
+                         _: &BattleState<CustomRules>,
+                         _: &mut Option<EventQueue<CustomRules>>| {
+        *counter.lock().unwrap() += 1;
+    };
+    let battle = Battle::builder(CustomRules::new())
+        .event_callback(Box::new(callback))
+        .build();
+    let mut server = Server::builder(battle).enable_transactions().build();
+    // The callback doesn't observe members of a rolled back transaction.
+    let events = vec![
+        CreateTeam::trigger(&mut (), TEAM_1_ID).event(),
+        CreateTeam::trigger(&mut (), TEAM_1_ID).event(),
+    ];
+    assert!(Transaction::trigger(&mut server, events).fire().is_err());
+    assert_eq!(*invocations.lock().unwrap(), 0);
+    // It's invoked once for each member of a committed transaction.
+    let events = vec![
+        CreateTeam::trigger(&mut (), TEAM_1_ID).event(),
+        CreateTeam::trigger(&mut (), TEAM_2_ID).event(),
+    ];
+    assert_eq!(Transaction::trigger(&mut server, events).fire().err(), None);
+    assert_eq!(*invocations.lock().unwrap(), 2);
+}
+
+#[test]
+fn nested() {
+    let mut server = server();
+    let inner = vec![DummyEvent::trigger(&mut ()).event()];
+    let events = vec![
+        DummyEvent::trigger(&mut ()).event(),
+        Transaction::trigger(&mut (), inner).event(),
+    ];
+    assert_eq!(
+        Transaction::trigger(&mut server, events)
+            .fire()
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::NestedTransaction)
+    );
+    assert_eq!(server.battle().history().len(), 0);
+}
+
+#[test]
+fn reactions() {
+    #[derive(Default)]
+    pub struct ReactiveFightRules {}
+
+    impl FightRules<CustomRules> for ReactiveFightRules {
+        type Impact = ();
+        type Potency = ();
+
+        fn react(
+            &self,
+            _state: &BattleState<CustomRules>,
+            event: &EventPrototype<CustomRules>,
+            mut reactions: &mut Option<EventQueue<CustomRules>>,
+        ) -> Reaction<CustomRules> {
+            let team: TeamId<CustomRules> =
+                match event.as_any().downcast_ref::<CreateTeam<CustomRules>>() {
+                    Some(event) => *event.id(),
+                    None => return Reaction::Proceed,
+                };
+            match team {
+                // Resolve a dummy event before the first team.
+                TEAM_1_ID => {
+                    DummyEvent::trigger(&mut reactions).fire();
+                    Reaction::Proceed
+                }
+                // Forbid the creation of the second team.
+                _ => Reaction::Cancel,
+            }
+        }
+    }
+
+    battle_rules! {
+        EmptyTeamRules,
+        EmptyCharacterRules,
+        EmptyActorRules,
+        ReactiveFightRules,
+        EmptyUserRules,
+        EmptySpaceRules,
+        EmptyRoundsRules,
+        EmptyEntropyRules
+    }
+
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut server = Server::builder(battle).enable_transactions().build();
+    // A cancelled member rolls back the transaction, reactions included.
+    let events = vec![
+        CreateTeam::trigger(&mut (), TEAM_1_ID).event(),
+        CreateTeam::trigger(&mut (), TEAM_2_ID).event(),
+    ];
+    assert_eq!(
+        Transaction::trigger(&mut server, events)
+            .fire()
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::EventCancelled)
+    );
+    assert_eq!(server.battle().history().len(), 0);
+    assert_eq!(server.battle().entities().teams().count(), 0);
+    // Reactions are stored right before the member they react to.
+    let events = vec![
+        DummyEvent::trigger(&mut ()).event(),
+        CreateTeam::trigger(&mut (), TEAM_1_ID).event(),
+    ];
+    assert_eq!(Transaction::trigger(&mut server, events).fire().err(), None);
+    let events = server.battle().history().events();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].kind(), EventKind::DummyEvent);
+    assert_eq!(events[1].kind(), EventKind::DummyEvent);
+    assert_eq!(events[2].kind(), EventKind::CreateTeam);
+}
+
+#[test]
+fn rollback_after_trial() {
+    #[derive(Default)]
+    pub struct ReactiveFightRules {}
+
+    impl FightRules<CustomRules> for ReactiveFightRules {
+        type Impact = ();
+        type Potency = ();
+
+        fn react(
+            &self,
+            _state: &BattleState<CustomRules>,
+            event: &EventPrototype<CustomRules>,
+            mut reactions: &mut Option<EventQueue<CustomRules>>,
+        ) -> Reaction<CustomRules> {
+            // Resolve a dummy event before the first team.
+            if let Some(event) = event.as_any().downcast_ref::<CreateTeam<CustomRules>>() {
+                if *event.id() == TEAM_1_ID {
+                    DummyEvent::trigger(&mut reactions).fire();
+                }
+            }
+            Reaction::Proceed
+        }
+    }
+
+    battle_rules! {
+        EmptyTeamRules,
+        EmptyCharacterRules,
+        EmptyActorRules,
+        ReactiveFightRules,
+        EmptyUserRules,
+        EmptySpaceRules,
+        EmptyRoundsRules,
+        EmptyEntropyRules
+    }
+
+    // The callback isn't attached during the trial, so the second team is created
+    // only when the members are applied for real.
+    fn callback(
+        event: &EventWrapper<CustomRules>,
+        _: &BattleState<CustomRules>,
+        event_queue: &mut Option<EventQueue<CustomRules>>,
+    ) {
+        if event.kind() == EventKind::DummyEvent {
+            CreateTeam::trigger(event_queue, TEAM_2_ID).fire();
+        }
+    }
+
+    let battle = Battle::builder(CustomRules::new())
+        .event_callback(Box::new(callback))
+        .history_store(Box::new(MemoryHistoryStore::new()))
+        .build();
+    let mut server = Server::builder(battle).enable_transactions().build();
+    let events = vec![
+        CreateTeam::trigger(&mut (), TEAM_1_ID).event(),
+        CreateTeam::trigger(&mut (), TEAM_2_ID).event(),
+    ];
+    assert_eq!(
+        Transaction::trigger(&mut server, events)
+            .fire()
+            .err()
+            .map(|e| e.unfold()),
+        Some(WeaselError::DuplicatedTeam(TEAM_2_ID))
+    );
+    // The members applied before the failure are rolled back and never stored.
+    assert_eq!(server.battle().history().len(), 0);
+    assert_eq!(server.battle().entities().teams().count(), 0);
+    assert_eq!(server.battle().history().store().unwrap().len(), 0);
+    // Members applied for real are stored once the transaction is committed.
+    let events = vec![CreateTeam::trigger(&mut (), TEAM_1_ID).event()];
+    assert_eq!(Transaction::trigger(&mut server, events).fire().err(), None);
+    assert_eq!(server.battle().history().len(), 3);
+    assert_eq!(server.battle().entities().teams().count(), 2);
+    assert_eq!(server.battle().history().store().unwrap().len(), 3);
+}
@@ -0,0 +1,318 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use weasel::battle::{Battle, BattleController, BattleRules};
+use weasel::binary::decode;
+use weasel::entity::EntityId;
+use weasel::event::{ClientSink, EventKind, EventSinkId, EventTrigger, ServerSink};
+use weasel::player::PlayerId;
+use weasel::round::StartTurn;
+use weasel::transport::{
+    write_message, Handshake, Message, Pump, Pumped, StreamClientSink, StreamServerSink,
+};
+use weasel::{battle_rules, rules::empty::*};
+use weasel::{BattleSnapshot, Client, Resync, Server, WeaselError};
+
+const TEAM_1_ID: u32 = 1;
+const CREATURE_1_ID: u32 = 1;
+const ENTITY_1_ID: EntityId<CustomRules> = EntityId::Creature(CREATURE_1_ID);
+const SERVER_1_ID: EventSinkId = 1;
+const CLIENT_1_ID: EventSinkId = 1;
+const PLAYER_1_ID: PlayerId = 1;
+
+battle_rules! {}
+
+/// An in-memory pipe. Reading from an empty pipe is like reaching the end of a stream.
+#[derive(Clone, Default)]
+struct Pipe {
+    buffer: Arc<Mutex<VecDeque<u8>>>,
+}
+
+impl Pipe {
+    /// Returns a server sink writing into this pipe.
+    fn sink(self) -> StreamServerSink<CustomRules, Pipe> {
+        StreamServerSink::new(SERVER_1_ID, self)
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.buffer.lock().unwrap().read(buf)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Feeds all client events in the pipe into the server.
+macro_rules! pump_server {
+    ($pipe: expr, $server: expr) => {{
+        let mut pump = Pump::new($pipe.clone());
+        while let Pumped::Processed(result) = pump.pump_server(&mut $server).unwrap() {
+            assert_eq!(result.err(), None);
+        }
+    }};
+}
+
+/// Feeds all events in the pipe into the client.
+macro_rules! pump_client {
+    ($pipe: expr, $client: expr) => {{
+        let mut pump = Pump::new($pipe.clone());
+        while let Pumped::Processed(result) = pump.pump_client(&mut $client).unwrap() {
+            assert_eq!(result.err(), None);
+        }
+    }};
+}
+
+#[test]
+fn events_over_stream() {
+    let to_server = Pipe::default();
+    let to_client = Pipe::default();
+    // Create a server and a client connected through the pipes.
+    let mut server = util::server(CustomRules::new());
+    let mut client = util::client(CustomRules::new(), to_server.clone().sink());
+    assert_eq!(
+        server
+            .client_sinks_mut()
+            .add_sink(Box::new(StreamClientSink::new(
+                CLIENT_1_ID,
+                None,
+                to_client.clone()
+            )))
+            .err(),
+        None
+    );
+    // Events fired on the server reach the client.
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    pump_client!(to_client, client);
+    assert_eq!(client.battle().history().len(), 2);
+    // Events fired on the client go through the server.
+    util::start_turn(&mut client, &ENTITY_1_ID);
+    assert_eq!(client.battle().history().len(), 2);
+    pump_server!(to_server, server);
+    assert_eq!(server.battle().history().len(), 3);
+    pump_client!(to_client, client);
+    assert_eq!(client.battle().history().len(), 3);
+    assert_eq!(
+        client.battle().history().events()[2].kind(),
+        EventKind::StartTurn
+    );
+    // Invalid events are rejected by the server.
+    let mut pump = Pump::new(to_server.clone());
+    let mut sink = to_server.clone().sink();
+    ServerSink::send(
+        &mut sink,
+        &StartTurn::trigger(&mut (), ENTITY_1_ID)
+            .prototype()
+            .client_prototype(0, None),
+    )
+    .unwrap();
+    match pump.pump_server(&mut server).unwrap() {
+        Pumped::Processed(Err(WeaselError::TurnInProgress)) => {}
+        _ => panic!("wrong outcome"),
+    }
+    // The stream is closed after a bye.
+    write_message(&mut to_client.clone(), &Message::<CustomRules>::Bye).unwrap();
+    let mut pump = Pump::new(to_client.clone());
+    assert!(matches!(
+        pump.pump_client(&mut client).unwrap(),
+        Pumped::Closed
+    ));
+}
+
+#[test]
+fn handshake_and_resync() {
+    let to_server = Pipe::default();
+    let to_client = Pipe::default();
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    // A client connects and sends a handshake.
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut client = Client::builder(battle, Box::new(Pipe::default().sink()))
+        .enable_authentication(PLAYER_1_ID)
+        .build();
+    let mut server_sink = to_server.clone().sink();
+    server_sink
+        .handshake(Handshake::new(*client.player(), client.resync_request()))
+        .unwrap();
+    client.set_server_sink(Box::new(server_sink));
+    let mut pump = Pump::new(to_server.clone());
+    let handshake = match pump.pump_server(&mut server).unwrap() {
+        Pumped::Unhandled(Message::Handshake(handshake)) => handshake,
+        _ => panic!("expected a handshake"),
+    };
+    assert_eq!(handshake.player(), Some(PLAYER_1_ID));
+    // The server brings the client up to date.
+    let sink = StreamClientSink::new(CLIENT_1_ID, handshake.player(), to_client.clone());
+    assert_eq!(
+        server.resync(Box::new(sink), handshake.resync()).unwrap(),
+        Resync::Replay(0..2)
+    );
+    pump_client!(to_client, client);
+    assert_eq!(client.battle().history().len(), 2);
+    // Rewinds are left to the caller.
+    server.rewind_to(1).unwrap();
+    let mut pump = Pump::new(to_client.clone());
+    match pump.pump_client(&mut client).unwrap() {
+        Pumped::Unhandled(Message::Rewind(id)) => client.rewind_to(id).unwrap(),
+        _ => panic!("expected a rewind"),
+    }
+    assert_eq!(client.battle().history().len(), 1);
+}
+
+#[test]
+fn snapshots() {
+    let to_client = Pipe::default();
+    let mut server = util::server(CustomRules::new());
+    util::team(&mut server, TEAM_1_ID);
+    let snapshot = server.battle().snapshot();
+    // Sinks can't send snapshots unless enabled.
+    let mut sink = StreamClientSink::new(CLIENT_1_ID, None, to_client.clone());
+    assert!(ClientSink::send_snapshot(&mut sink, &snapshot).is_err());
+    let mut sink = sink.enable_snapshots();
+    ClientSink::send_snapshot(&mut sink, &snapshot).unwrap();
+    let mut pump = Pump::<CustomRules, _>::new(to_client.clone());
+    let bytes = match pump.next_message().unwrap() {
+        Some(Message::Snapshot(bytes)) => bytes,
+        _ => panic!("expected a snapshot"),
+    };
+    let snapshot: BattleSnapshot<CustomRules> = decode(&bytes).unwrap();
+    let mut client = util::client(CustomRules::new(), Pipe::default().sink());
+    client.restore(snapshot);
+    assert_eq!(client.battle().entities().teams().count(), 1);
+}
+
+#[test]
+fn rejection() {
+    let to_server = Pipe::default();
+    let to_client = Pipe::default();
+    // Create a server with authentication and a client with prediction.
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut server = Server::builder(battle).enforce_authentication().build();
+    assert_eq!(
+        server
+            .client_sinks_mut()
+            .add_sink(Box::new(StreamClientSink::new(
+                CLIENT_1_ID,
+                None,
+                to_client.clone()
+            )))
+            .err(),
+        None
+    );
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut client = Client::builder(battle, Box::new(to_server.clone().sink()))
+        .enable_prediction()
+        .build();
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    pump_client!(to_client, client);
+    // The client predicts an event that the server will reject.
+    util::start_turn(&mut client, &ENTITY_1_ID);
+    assert_eq!(client.pending_predictions().len(), 1);
+    let mut pump = Pump::new(to_server.clone());
+    match pump.pump_server_from(CLIENT_1_ID, &mut server).unwrap() {
+        Pumped::Processed(Err(WeaselError::MissingAuthentication)) => {}
+        _ => panic!("wrong outcome"),
+    }
+    // The client receives the rejection and discards the prediction.
+    let mut pump = Pump::new(to_client.clone());
+    let rejection = match pump.pump_client(&mut client).unwrap() {
+        Pumped::Unhandled(Message::Rejection(rejection)) => rejection,
+        _ => panic!("expected a rejection"),
+    };
+    assert_eq!(
+        Some(rejection.correlation_id()),
+        client.last_correlation_id()
+    );
+    client.receive_rejection(&rejection);
+    assert!(client.pending_predictions().is_empty());
+    assert!(!client.battle().rounds().state().has_actor(&ENTITY_1_ID));
+}
+
+#[test]
+fn prediction_correlation() {
+    let to_server = Pipe::default();
+    let to_client = Pipe::default();
+    // Create a server with authentication and a client with prediction.
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut server = Server::builder(battle).enforce_authentication().build();
+    assert_eq!(
+        server
+            .client_sinks_mut()
+            .add_sink(Box::new(StreamClientSink::new(
+                CLIENT_1_ID,
+                None,
+                to_client.clone()
+            )))
+            .err(),
+        None
+    );
+    let battle = Battle::builder(CustomRules::new()).build();
+    let mut client = Client::builder(battle, Box::new(to_server.clone().sink()))
+        .enable_prediction()
+        .build();
+    util::team(&mut server, TEAM_1_ID);
+    util::creature(&mut server, CREATURE_1_ID, TEAM_1_ID, ());
+    pump_client!(to_client, client);
+    // An identical event fired by the server doesn't confirm the client's prediction.
+    util::dummy(&mut server);
+    util::dummy(&mut client);
+    let mut server_pump = Pump::new(to_server.clone());
+    assert!(matches!(
+        server_pump
+            .pump_server_from(CLIENT_1_ID, &mut server)
+            .unwrap(),
+        Pumped::Processed(Ok(()))
+    ));
+    let mut client_pump = Pump::new(to_client.clone());
+    assert!(matches!(
+        client_pump.pump_client(&mut client).unwrap(),
+        Pumped::Processed(Ok(()))
+    ));
+    assert_eq!(client.pending_predictions().len(), 1);
+    assert!(matches!(
+        client_pump.pump_client(&mut client).unwrap(),
+        Pumped::Processed(Ok(()))
+    ));
+    assert!(client.pending_predictions().is_empty());
+    // The server rejects the first of two predicted events.
+    util::start_turn(&mut client, &ENTITY_1_ID);
+    util::dummy(&mut client);
+    assert_eq!(client.pending_predictions().len(), 2);
+    match server_pump
+        .pump_server_from(CLIENT_1_ID, &mut server)
+        .unwrap()
+    {
+        Pumped::Processed(Err(WeaselError::MissingAuthentication)) => {}
+        _ => panic!("wrong outcome"),
+    }
+    assert!(matches!(
+        server_pump
+            .pump_server_from(CLIENT_1_ID, &mut server)
+            .unwrap(),
+        Pumped::Processed(Ok(()))
+    ));
+    // Without handling the rejection, the confirmation of the second event
+    // discards both predictions.
+    assert!(matches!(
+        client_pump.pump_client(&mut client).unwrap(),
+        Pumped::Unhandled(Message::Rejection(_))
+    ));
+    assert!(matches!(
+        client_pump.pump_client(&mut client).unwrap(),
+        Pumped::Processed(Ok(()))
+    ));
+    assert!(client.pending_predictions().is_empty());
+    assert!(!client.battle().rounds().state().has_actor(&ENTITY_1_ID));
+    assert_eq!(client.battle().history().len(), 5);
+}